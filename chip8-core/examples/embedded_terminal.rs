@@ -0,0 +1,92 @@
+//! Runs a ROM through the embedded-graphics adapter onto a stand-in panel that prints itself to the
+//! terminal, so the adapter can be tried on a desktop without hardware:
+//!
+//! ```bash
+//! cargo run --example embedded_terminal --features embedded-graphics -- roms/IBM.ch8
+//! ```
+//!
+//! The panel is a plain `DrawTarget`, the same interface a real display driver implements.
+
+use std::convert::Infallible;
+use std::io::Write;
+use std::time::Duration;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Pixel, Point, Size};
+
+use chip8_core::cpu::CPU;
+use chip8_core::embedded::DisplayAdapter;
+
+const INSTRUCTIONS_PER_FRAME: u32 = 10;
+const FRAMES: u32 = 600;
+
+/// A 64x32 monochrome panel drawn with half-block characters, two pixel rows per line
+struct TerminalPanel {
+    pixels: [[bool; 64]; 32],
+}
+
+impl OriginDimensions for TerminalPanel {
+    fn size(&self) -> Size {
+        Size::new(64, 32)
+    }
+}
+
+impl DrawTarget for TerminalPanel {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some(row) = self.pixels.get_mut(point.y as usize)
+                && let Some(pixel) = row.get_mut(point.x as usize)
+            {
+                *pixel = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TerminalPanel {
+    fn show(&self) -> std::io::Result<()> {
+        let mut out = String::from("\x1b[H");
+        for rows in self.pixels.chunks(2) {
+            for (top, bottom) in rows[0].iter().zip(&rows[1]) {
+                out.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(out.as_bytes())?;
+        stdout.flush()
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::args().nth(1).ok_or("usage: embedded_terminal <ROM>")?;
+    let mut cpu = CPU::new();
+    cpu.load_rom(&path)?;
+
+    let mut adapter = DisplayAdapter::new(Point::zero(), 1);
+    let mut panel = TerminalPanel { pixels: [[false; 64]; 32] };
+    print!("\x1b[2J");
+
+    for _ in 0..FRAMES {
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            cpu.tick()?;
+        }
+        cpu.update_timers();
+
+        adapter.draw(&cpu.display, &mut panel)?;
+        panel.show()?;
+        std::thread::sleep(Duration::from_millis(16));
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+use crate::constants::{MEMORY_SIZE, STARTING_MEMORY_ADDRESS};
+use crate::rom::ROM_EXTENSIONS;
+
+/// Largest ROM that can be unpacked, which is all the memory above the interpreter.
+/// Entries are never decompressed past it, whatever size the archive claims
+pub const MAX_ENTRY_BYTES: u64 = (MEMORY_SIZE - STARTING_MEMORY_ADDRESS) as u64;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+pub fn is_zip(name: &str, bytes: &[u8]) -> bool {
+    name.to_ascii_lowercase().ends_with(".zip") || bytes.starts_with(ZIP_MAGIC)
+}
+
+/// Splits `pack.zip/GAME.ch8` into the archive and the entry inside it
+pub fn split_entry_path(path: &str) -> Option<(&str, &str)> {
+    let end = path.to_ascii_lowercase().find(".zip/")? + ".zip".len();
+    Some((&path[..end], &path[end + 1..]))
+}
+
+/// Names of the ROMs inside an archive, in archive order
+pub fn list_roms(archive: &[u8]) -> Result<Vec<String>, std::io::Error> {
+    let archive = open(archive)?;
+    let names = archive.file_names()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| corrupt(e.into()))?;
+
+    let roms = names.into_iter()
+        .filter(|name| !name.ends_with('/'))
+        .filter(|name| {
+            let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+            extension.is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.as_str()))
+        })
+        .map(|name| name.into_owned())
+        .collect();
+    Ok(roms)
+}
+
+/// Unpacks one entry
+pub fn read_entry(archive: &[u8], name: &str) -> Result<Vec<u8>, std::io::Error> {
+    let mut archive = open(archive)?;
+    let entry = archive.by_name(name).map_err(|e| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, format!("{} is not in the archive: {}", name, e))
+    })?;
+
+    let mut rom = Vec::new();
+    entry.take(MAX_ENTRY_BYTES + 1).read_to_end(&mut rom).map_err(corrupt)?;
+    if rom.len() as u64 > MAX_ENTRY_BYTES {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} is larger than the {} bytes a ROM can be", name, MAX_ENTRY_BYTES),
+        ));
+    }
+    Ok(rom)
+}
+
+/// The archive's only ROM. Archives with several need the entry named, as `pack.zip/GAME.ch8`
+pub fn read_single(archive: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+    match list_roms(archive)?.as_slice() {
+        [name] => read_entry(archive, name),
+        [] => Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No ROMs in the archive")),
+        names => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("The archive holds {} ROMs; choose one of {}", names.len(), names.join(", ")),
+        )),
+    }
+}
+
+fn open(archive: &[u8]) -> Result<ZipArchive<Cursor<&[u8]>>, std::io::Error> {
+    ZipArchive::new(Cursor::new(archive)).map_err(|e| corrupt(e.into()))
+}
+
+fn corrupt(e: std::io::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Corrupt archive: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn zip_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_single_entry_loads() {
+        let archive = zip_of(&[("README.txt", b"not a rom"), ("PONG.ch8", &[0x12, 0x00])]);
+        assert!(is_zip("pack.bin", &archive));
+        assert_eq!(read_single(&archive).unwrap(), [0x12, 0x00]);
+    }
+
+    #[test]
+    fn test_lists_multiple_entries() {
+        let archive = zip_of(&[("games/PONG.ch8", &[1]), ("notes.txt", &[2]), ("TETRIS.C8", &[3]), ("games/", &[])]);
+        assert_eq!(list_roms(&archive).unwrap(), vec!["games/PONG.ch8", "TETRIS.C8"]);
+
+        assert_eq!(read_single(&archive).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(read_entry(&archive, "TETRIS.C8").unwrap(), [3]);
+        assert_eq!(read_entry(&archive, "MISSING.ch8").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_size_cap() {
+        let fits = vec![0; MAX_ENTRY_BYTES as usize];
+        let too_big = vec![0; MAX_ENTRY_BYTES as usize + 1];
+        let archive = zip_of(&[("fits.ch8", &fits), ("big.ch8", &too_big)]);
+
+        assert_eq!(read_entry(&archive, "fits.ch8").unwrap().len(), fits.len());
+        assert_eq!(read_entry(&archive, "big.ch8").unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_corrupt_archive_is_error() {
+        let mut archive = zip_of(&[("PONG.ch8", &[0x12, 0x00])]);
+        archive.truncate(archive.len() / 2);
+        assert_eq!(list_roms(&archive).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert!(read_single(b"PK\x03\x04garbage").is_err());
+    }
+
+    #[test]
+    fn test_split_entry_path() {
+        assert_eq!(split_entry_path("roms/pack.zip/GAME.ch8"), Some(("roms/pack.zip", "GAME.ch8")));
+        assert_eq!(split_entry_path("Pack.ZIP/dir/GAME.ch8"), Some(("Pack.ZIP", "dir/GAME.ch8")));
+        assert_eq!(split_entry_path("roms/pack.zip"), None);
+        assert_eq!(split_entry_path("roms/pong.ch8"), None);
+    }
+}
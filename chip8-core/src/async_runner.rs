@@ -0,0 +1,120 @@
+//! Drives an [`Emulator`] from a tokio runtime instead of a blocking loop, for embedding in async
+//! GUI applications. Input arrives on a `Stream` and finished frames leave through a `Sink`.
+
+use std::pin::pin;
+use std::time::Duration;
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use tokio::time::{self, Instant, MissedTickBehavior};
+use crate::emulator::{Emulator, EmulatorEvent, FrameBuffer, InputEvent};
+
+/// Time between frames: 60Hz
+pub const FRAME_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// One finished frame, as sent out by [`run`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub display: FrameBuffer,
+    /// Whether the buzzer should be sounding
+    pub sound: bool,
+    /// What happened while the frame ran, as returned by Emulator::update
+    pub events: Vec<EmulatorEvent>,
+}
+
+/// Runs the emulator at 60Hz, applying input events as they arrive between frames and
+/// sending every frame to the sink. Ends once the input stream closes, closing the sink and
+/// handing the emulator back; a sink error ends it early.
+pub async fn run<E>(
+    mut emulator: Emulator,
+    frames: impl Sink<Frame, Error = E>,
+    inputs: impl Stream<Item = InputEvent>,
+) -> Result<Emulator, E> {
+    let mut frames = pin!(frames);
+    let mut inputs = pin!(inputs);
+
+    let mut ticks = time::interval(FRAME_INTERVAL);
+    // A host that falls behind runs late frames, rather than a burst to catch up
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // The first tick comes at once, and runs a whole frame
+    let mut last_frame = Instant::now() - FRAME_INTERVAL;
+
+    loop {
+        tokio::select! {
+            // Input that is already waiting goes in before the frame it arrived for
+            biased;
+            input = inputs.next() => match input {
+                Some(event) => emulator.handle_event(event),
+                None => break,
+            },
+            now = ticks.tick() => {
+                let events = emulator.update(now - last_frame);
+                last_frame = now;
+                let frame = Frame { display: *emulator.frame(), sound: emulator.sound(), events };
+                frames.send(frame).await?;
+            }
+        }
+    }
+
+    frames.close().await?;
+    Ok(emulator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use crate::config::Config;
+    use crate::emulator::MachineSetup;
+    use crate::rom_builder::RomBuilder;
+    use crate::timing::TimingMode;
+
+    fn emulator() -> Emulator {
+        let setup = MachineSetup { seed: Some(1), ..Default::default() };
+        let mut emulator = Emulator::new(Config::default(), setup, TimingMode::Simple, 10);
+        let rom = RomBuilder::new().ld(1, 2).op(0xF118).label("loop").jp("loop").build().unwrap(); // Beep briefly
+        emulator.load_bytes(rom).unwrap();
+        emulator
+    }
+
+    /// Input events, each sent after the delay before it
+    fn script(events: Vec<(u64, InputEvent)>) -> impl Stream<Item = InputEvent> {
+        stream::iter(events).then(|(delay_ms, event)| async move {
+            time::sleep(Duration::from_millis(delay_ms)).await;
+            event
+        })
+    }
+
+    fn frame_keys(frame: &Frame) -> Vec<u16> {
+        frame.events.iter()
+            .filter_map(|event| match event {
+                EmulatorEvent::Frame { keys, .. } => Some(*keys),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_runs_frames_in_order_until_inputs_close() {
+        // Frames at 0, 16.7, 33.3 and 50ms; the inputs land between them and close at 55ms
+        let inputs = script(vec![
+            (25, InputEvent::Keys { down: 1 << 5, latched: 0 }),
+            (20, InputEvent::Reset),
+            (10, InputEvent::Keys { down: 0, latched: 0 }),
+        ]);
+        let mut frames = Vec::new();
+        let emulator = run(emulator(), &mut frames, inputs).await.unwrap();
+
+        let keys: Vec<Vec<u16>> = frames.iter().map(frame_keys).collect();
+        assert_eq!(keys, [vec![0], vec![0], vec![1 << 5], vec![1 << 5]]);
+        assert!(frames[0].sound);
+        assert_eq!(frames[3].events[0], EmulatorEvent::Reset { error: None });
+        assert!(frames[3].sound); // Beeping again since the reset
+        assert_eq!(emulator.cpu.sound_timer, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_closed_inputs_end_before_the_next_frame() {
+        let mut frames = Vec::new();
+        run(emulator(), &mut frames, stream::empty()).await.unwrap();
+        assert!(frames.is_empty());
+    }
+}
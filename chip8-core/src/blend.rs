@@ -0,0 +1,218 @@
+//! Blending the last few frames together, the classic fix for XOR sprite flicker: a sprite a game
+//! erases and redraws every other frame shows steadily at part brightness instead of blinking.
+//! Frontends push each frame they present and draw the per-pixel levels with shade()
+use crate::config::Rgb;
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+/// The widest blend window; each extra frame is one more shade between background and foreground
+pub const MAX_BLEND_FRAMES: u8 = 3;
+
+/// A ring of the last `window` frames and the rows each changed
+#[derive(Debug, Clone)]
+pub struct FrameBlend {
+    window: u8,
+    frames: Vec<Vec<u8>>, // Empty until the first push, which fills the ring
+    dirty_rows: Vec<u64>,
+    next: usize,
+}
+
+impl FrameBlend {
+    /// Blends `window` frames, clamped to 1 (no blending) through MAX_BLEND_FRAMES
+    pub fn new(window: u8) -> Self {
+        let window = window.clamp(1, MAX_BLEND_FRAMES);
+        Self { window, frames: Vec::new(), dirty_rows: vec![0; window as usize], next: 0 }
+    }
+
+    pub fn window(&self) -> u8 {
+        self.window
+    }
+
+    /// Changes the window, starting it over from the newest frame
+    pub fn set_window(&mut self, window: u8) {
+        let newest = self.frames.len().checked_sub(1).map(|last| (self.next + last) % self.frames.len());
+        let frame = newest.map(|newest| std::mem::take(&mut self.frames[newest]));
+        *self = FrameBlend::new(window);
+        if let Some(frame) = frame {
+            self.push(&frame, u64::MAX);
+        }
+    }
+
+    /// Adds the newest frame, dropping the oldest, and returns the rows whose levels may have
+    /// changed: any row that changed in a frame still in the window. The first frame fills the
+    /// whole ring, so there is no fade in from blank, and so does one at a new resolution
+    pub fn push(&mut self, frame: &[u8], dirty_rows: u64) -> u64 {
+        if self.frames.first().is_none_or(|oldest| oldest.len() != frame.len()) {
+            self.frames = vec![frame.to_vec(); self.window as usize];
+            return u64::MAX;
+        }
+
+        self.frames[self.next].clear();
+        self.frames[self.next].extend_from_slice(frame);
+        self.dirty_rows[self.next] = dirty_rows;
+        self.next = (self.next + 1) % self.frames.len();
+        self.dirty_rows.iter().fold(0, |rows, dirty| rows | dirty)
+    }
+
+    /// How many frames in the window had each pixel lit, from 0 to window(), into levels
+    pub fn levels(&self, levels: &mut Vec<u8>) {
+        levels.clear();
+        levels.resize(self.frames.first().map_or(DISPLAY_WIDTH * DISPLAY_HEIGHT, Vec::len), 0);
+        for frame in &self.frames {
+            for (level, pixel) in levels.iter_mut().zip(frame) {
+                *level += u8::from(*pixel != 0);
+            }
+        }
+    }
+}
+
+/// The colour of a pixel lit in `level` of `window` frames, evenly spaced from background (0)
+/// to foreground (window)
+pub fn shade(background: Rgb, foreground: Rgb, level: u8, window: u8) -> Rgb {
+    let (level, window) = (i32::from(level.min(window)), i32::from(window.max(1)));
+    let mix = |bg: u8, fg: u8| (i32::from(bg) + (i32::from(fg) - i32::from(bg)) * level / window) as u8;
+    Rgb(
+        mix(background.0, foreground.0),
+        mix(background.1, foreground.1),
+        mix(background.2, foreground.2),
+    )
+}
+
+/// Splits a row of levels into runs of equal, nonzero level, as (start, length, level)
+pub fn level_runs(row: &[u8]) -> impl Iterator<Item = (usize, usize, u8)> + '_ {
+    let mut x = 0;
+    std::iter::from_fn(move || {
+        while x < row.len() && row[x] == 0 {
+            x += 1;
+        }
+        let level = *row.get(x)?;
+
+        let start = x;
+        while x < row.len() && row[x] == level {
+            x += 1;
+        }
+        Some((start, x - start, level))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_with(lit: &[usize]) -> Vec<u8> {
+        let mut frame = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        for pixel in lit {
+            frame[*pixel] = 1;
+        }
+        frame
+    }
+
+    fn levels(blend: &FrameBlend) -> Vec<u8> {
+        let mut levels = Vec::new();
+        blend.levels(&mut levels);
+        levels
+    }
+
+    #[test]
+    fn test_window_is_clamped() {
+        assert_eq!(FrameBlend::new(0).window(), 1);
+        assert_eq!(FrameBlend::new(2).window(), 2);
+        assert_eq!(FrameBlend::new(9).window(), MAX_BLEND_FRAMES);
+    }
+
+    #[test]
+    fn test_flickering_sprite_is_half_lit() {
+        let mut blend = FrameBlend::new(2);
+        let (on, off) = (frame_with(&[0, 1]), frame_with(&[1]));
+        blend.push(&on, u64::MAX);
+        assert_eq!(&levels(&blend)[..3], [2, 2, 0]);
+
+        // Every other frame erased: pixel 0 settles at half, pixel 1 stays full
+        for _ in 0..3 {
+            blend.push(&off, 1);
+            assert_eq!(&levels(&blend)[..3], [1, 2, 0]);
+            blend.push(&on, 1);
+            assert_eq!(&levels(&blend)[..3], [1, 2, 0]);
+        }
+    }
+
+    #[test]
+    fn test_one_frame_window_is_the_frame() {
+        let mut blend = FrameBlend::new(1);
+        blend.push(&frame_with(&[5]), u64::MAX);
+        blend.push(&frame_with(&[7]), 1);
+        let levels = levels(&blend);
+        assert_eq!((levels[5], levels[7]), (0, 1));
+        assert_eq!(levels.iter().filter(|level| **level != 0).count(), 1);
+    }
+
+    #[test]
+    fn test_ghost_fades_over_the_window() {
+        let mut blend = FrameBlend::new(3);
+        blend.push(&frame_with(&[0]), u64::MAX);
+        let blank = frame_with(&[]);
+        assert_eq!(levels(&blend)[0], 3);
+        blend.push(&blank, 1);
+        assert_eq!(levels(&blend)[0], 2);
+        blend.push(&blank, 0);
+        assert_eq!(levels(&blend)[0], 1);
+        blend.push(&blank, 0);
+        assert_eq!(levels(&blend)[0], 0);
+    }
+
+    #[test]
+    fn test_set_window_keeps_the_newest_frame() {
+        let mut blend = FrameBlend::new(3);
+        blend.set_window(2);
+        assert_eq!(levels(&blend), frame_with(&[]));
+
+        blend.push(&frame_with(&[0]), u64::MAX);
+        blend.push(&frame_with(&[1]), 1);
+        blend.set_window(3);
+        assert_eq!(blend.window(), 3);
+        assert_eq!(&levels(&blend)[..2], [0, 3]);
+    }
+
+    #[test]
+    fn test_dirty_rows_last_the_window() {
+        let mut blend = FrameBlend::new(3);
+        let frame = frame_with(&[]);
+        assert_eq!(blend.push(&frame, 0), u64::MAX);
+        assert_eq!(blend.push(&frame, 0b01), 0b01);
+        assert_eq!(blend.push(&frame, 0b10), 0b11);
+        assert_eq!(blend.push(&frame, 0), 0b11);
+        assert_eq!(blend.push(&frame, 0), 0b10);
+        assert_eq!(blend.push(&frame, 0), 0);
+    }
+
+    #[test]
+    fn test_new_resolution_starts_the_ring_over() {
+        let mut blend = FrameBlend::new(2);
+        blend.push(&frame_with(&[0]), u64::MAX);
+        let mut hires = vec![0; 4 * DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        hires[1] = 1;
+        assert_eq!(blend.push(&hires, 0), u64::MAX);
+        let levels = levels(&blend);
+        assert_eq!((levels.len(), levels[0], levels[1]), (hires.len(), 0, 2));
+    }
+
+    #[test]
+    fn test_shades_between_background_and_foreground() {
+        let (bg, fg) = (Rgb(0, 30, 0), Rgb(90, 255, 30));
+        assert_eq!(shade(bg, fg, 0, 3), bg);
+        assert_eq!(shade(bg, fg, 1, 3), Rgb(30, 105, 10));
+        assert_eq!(shade(bg, fg, 2, 3), Rgb(60, 180, 20));
+        assert_eq!(shade(bg, fg, 3, 3), fg);
+        assert_eq!(shade(bg, fg, 1, 1), fg);
+
+        // Lighter backgrounds shade down towards a darker foreground
+        assert_eq!(shade(Rgb(200, 200, 200), Rgb(0, 0, 0), 1, 2), Rgb(100, 100, 100));
+    }
+
+    #[test]
+    fn test_level_runs() {
+        let runs = |row: &[u8]| level_runs(row).collect::<Vec<_>>();
+        assert_eq!(runs(&[0, 0]), []);
+        assert_eq!(runs(&[2, 2, 1, 0, 1, 3]), [(0, 2, 2), (2, 1, 1), (4, 1, 1), (5, 1, 3)]);
+        assert_eq!(runs(&[]), []);
+    }
+}
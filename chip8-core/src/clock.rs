@@ -0,0 +1,224 @@
+//! Wall time into emulated work: how many instructions a target clock rate owes for the time
+//! that passed, and how many 60Hz timer ticks. Remainders carry over to the next advance, so
+//! neither rate drifts however the host's frames happen to line up
+use std::fmt;
+use std::time::Duration;
+
+/// A clock rate most ROMs play well at
+pub const DEFAULT_HZ: u32 = 700;
+/// The delay and sound timers' rate
+pub const TIMER_HZ: u32 = 60;
+/// The longest gap caught up in one advance. Anything past it (a stall, a dragged window) is dropped
+pub const MAX_CATCH_UP: Duration = Duration::from_millis(100);
+
+// Emulated time is counted in hundredths of a nanosecond, so half speed divides evenly
+const TICK: u128 = 100 * 1_000_000_000;
+
+/// How fast emulated time runs against wall time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Speed {
+    Half,
+    #[default]
+    Normal,
+    Double,
+}
+
+impl Speed {
+    pub const ALL: [Speed; 3] = [Speed::Half, Speed::Normal, Speed::Double];
+
+    /// The next speed up, staying at the fastest
+    pub fn faster(self) -> Self {
+        Speed::ALL.into_iter().find(|speed| speed.percent() > self.percent()).unwrap_or(self)
+    }
+
+    /// The next speed down, staying at the slowest
+    pub fn slower(self) -> Self {
+        Speed::ALL.into_iter().rev().find(|speed| speed.percent() < self.percent()).unwrap_or(self)
+    }
+
+    fn percent(self) -> u64 {
+        match self {
+            Speed::Half => 50,
+            Speed::Normal => 100,
+            Speed::Double => 200,
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Speed::Half => write!(f, "0.5x"),
+            Speed::Normal => write!(f, "1x"),
+            Speed::Double => write!(f, "2x"),
+        }
+    }
+}
+
+/// What one advance owes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Ticks {
+    /// Instructions at the clock rate, or 0 without one
+    pub instructions: u32,
+    /// 60Hz timer ticks
+    pub timers: u32,
+    /// Frames of a per-frame budget: one per 1/60s of emulated time, in step with the timers, so
+    /// a fast monitor doesn't run the game fast
+    pub frames: u32,
+}
+
+/// Turns the time each host frame took into the ticks it owes
+#[derive(Debug, Clone)]
+pub struct Clock {
+    hz: Option<u32>,
+    speed: Speed,
+    // Each in emulated time times its rate, short of a whole tick
+    instructions: u128,
+    timers: u128,
+}
+
+impl Clock {
+    /// A clock owing instructions at hz, or none when a per-frame budget decides how many run
+    pub fn new(hz: Option<u32>) -> Self {
+        Self { hz, speed: Speed::Normal, instructions: 0, timers: 0 }
+    }
+
+    pub fn hz(&self) -> Option<u32> {
+        self.hz
+    }
+
+    pub fn set_hz(&mut self, hz: Option<u32>) {
+        self.hz = hz;
+        self.instructions = 0;
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+    }
+
+    /// Drops the time carried over, for a fresh start after a reset or a pause
+    pub fn reset(&mut self) {
+        self.instructions = 0;
+        self.timers = 0;
+    }
+
+    /// Advances by dt of wall time, scaled by the speed
+    pub fn advance(&mut self, dt: Duration) -> Ticks {
+        let percent = self.speed.percent();
+        let elapsed = dt.min(MAX_CATCH_UP).as_nanos() * u128::from(percent);
+        let owe = |carried: &mut u128, rate: u32| {
+            *carried += elapsed * u128::from(rate);
+            let owed = *carried / TICK;
+            *carried %= TICK;
+            owed as u32
+        };
+
+        let instructions = self.hz.map_or(0, |hz| owe(&mut self.instructions, hz));
+        let timers = owe(&mut self.timers, TIMER_HZ);
+        Ticks { instructions, timers, frames: timers }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Advances by each dt, totalling what was owed
+    fn total(clock: &mut Clock, dts: impl IntoIterator<Item = Duration>) -> Ticks {
+        dts.into_iter().fold(Ticks::default(), |total, dt| {
+            let ticks = clock.advance(dt);
+            Ticks {
+                instructions: total.instructions + ticks.instructions,
+                timers: total.timers + ticks.timers,
+                frames: total.frames + ticks.frames,
+            }
+        })
+    }
+
+    #[test]
+    fn test_a_second_owes_the_clock_rate() {
+        let mut clock = Clock::new(Some(DEFAULT_HZ));
+        let ticks = total(&mut clock, std::iter::repeat_n(Duration::from_millis(10), 100));
+        assert_eq!((ticks.instructions, ticks.timers, ticks.frames), (700, 60, 60));
+    }
+
+    #[test]
+    fn test_remainders_carry_over() {
+        // 7ms at 700Hz is 4.9 instructions and at 60Hz 0.42 timer ticks: neither rounds away
+        let mut clock = Clock::new(Some(700));
+        assert_eq!(clock.advance(Duration::from_millis(7)), Ticks { instructions: 4, timers: 0, frames: 0 });
+        assert_eq!(clock.advance(Duration::from_millis(7)), Ticks { instructions: 5, timers: 0, frames: 0 });
+        assert_eq!(clock.advance(Duration::from_millis(7)).timers, 1);
+
+        // Uneven frames still come to exactly 60 ticks a second
+        let mut clock = Clock::new(None);
+        let uneven = [3, 17, 25, 1, 14].map(Duration::from_millis);
+        let ticks = total(&mut clock, uneven.into_iter().cycle().take(100));
+        assert_eq!((ticks.instructions, ticks.timers), (0, 60 * 1200 / 1000));
+    }
+
+    #[test]
+    fn test_timer_ticks_at_sixty_hz_not_every_16ms() {
+        // Starting over on each 16ms tick loses 0.67ms a tick: 62 ticks a second rather than 60
+        let mut clock = Clock::new(None);
+        let ticks = total(&mut clock, std::iter::repeat_n(Duration::from_millis(1), 1000));
+        assert_eq!(ticks.timers, TIMER_HZ);
+    }
+
+    #[test]
+    fn test_budget_frames_keep_to_sixty_hz_on_a_fast_monitor() {
+        // A 144Hz vsync loop doesn't sleep, so its dt is all that keeps the budget at 60 frames a second
+        let mut clock = Clock::new(None);
+        let ticks = total(&mut clock, std::iter::repeat_n(Duration::from_nanos(1_000_000_000_u64.div_ceil(144)), 144));
+        assert_eq!(ticks.frames, 60);
+    }
+
+    #[test]
+    fn test_long_gaps_are_not_caught_up() {
+        let mut clock = Clock::new(Some(1000));
+        let ticks = clock.advance(Duration::from_secs(5));
+        assert_eq!((ticks.instructions, ticks.timers), (100, 6));
+    }
+
+    #[test]
+    fn test_speed_scales_every_rate() {
+        let mut clock = Clock::new(Some(600));
+        clock.set_speed(Speed::Double);
+        assert_eq!(total(&mut clock, std::iter::repeat_n(Duration::from_millis(50), 10)), Ticks { instructions: 600, timers: 60, frames: 60 });
+
+        clock.set_speed(Speed::Half);
+        assert_eq!(total(&mut clock, std::iter::repeat_n(Duration::from_millis(50), 10)), Ticks { instructions: 150, timers: 15, frames: 15 });
+    }
+
+    #[test]
+    fn test_reset_and_new_rate_drop_the_remainder() {
+        let mut clock = Clock::new(Some(700));
+        clock.advance(Duration::from_millis(16));
+        clock.reset();
+        assert_eq!(clock.advance(Duration::from_millis(1)), Ticks::default());
+
+        clock.set_hz(None);
+        assert_eq!(clock.advance(Duration::from_secs(1)).instructions, 0);
+        clock.set_hz(Some(2000));
+        assert_eq!(clock.advance(Duration::from_millis(1)).instructions, 2);
+    }
+
+    #[test]
+    fn test_speed_steps() {
+        assert_eq!(Speed::Normal.faster(), Speed::Double);
+        assert_eq!(Speed::Double.faster(), Speed::Double);
+        assert_eq!(Speed::Normal.slower(), Speed::Half);
+        assert_eq!(Speed::Half.slower(), Speed::Half);
+        assert_eq!(Speed::ALL.map(|speed| speed.to_string()), ["0.5x", "1x", "2x"]);
+    }
+}
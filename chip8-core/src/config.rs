@@ -0,0 +1,622 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use crate::blend::MAX_BLEND_FRAMES;
+use crate::constants::INPUTS_COUNT;
+use crate::pacing::VsyncMode;
+use crate::quirks::{Preset, Quirk, Quirks};
+
+/// Optional configuration file, looked up in the working directory
+pub const CONFIG_PATH: &str = "./chip8.toml";
+
+/// Default QWERTY layout as SDL key names mapped to CHIP-8 keys
+pub const DEFAULT_KEYS: [(&str, usize); INPUTS_COUNT] = [
+    ("1", 0x1), ("2", 0x2), ("3", 0x3), ("4", 0xC),
+    ("Q", 0x4), ("W", 0x5), ("E", 0x6), ("R", 0xD),
+    ("A", 0x7), ("S", 0x8), ("D", 0x9), ("F", 0xE),
+    ("Z", 0xA), ("X", 0x0), ("C", 0xB), ("V", 0xF),
+];
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Config {
+    pub keys: KeyBindings,
+    pub gamepad: GamepadBindings,
+    pub input: InputConfig,
+    pub session: SessionConfig,
+    pub emulation: EmulationConfig,
+    pub audio: AudioConfig,
+    pub display: DisplayConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default)]
+pub struct InputConfig {
+    /// Tapping a key toggles it instead of requiring it to be held
+    pub sticky_keys: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    /// Save the game when quitting and offer to resume it on the next launch of the same ROM
+    pub auto_save: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(default)]
+pub struct EmulationConfig {
+    /// How the opcodes interpreters disagree on behave, unless --quirks says otherwise
+    pub quirks: Preset,
+    /// Single quirks set apart from the preset, as `[emulation.overrides]`
+    pub overrides: QuirkOverrides,
+}
+
+impl EmulationConfig {
+    /// preset, or the file's own without one, with the overrides on top
+    pub fn resolve(&self, preset: Option<Preset>) -> Quirks {
+        self.overrides.apply(preset.unwrap_or(self.quirks).quirks())
+    }
+}
+
+/// Quirks that replace the preset's choice, named as in Quirks (`wrap_sprites = false`). Unset
+/// ones follow the preset, whichever it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuirkOverrides {
+    pub shift_uses_vy: Option<bool>,
+    pub load_store_increments_i: Option<bool>,
+    pub jump_with_vx: Option<bool>,
+    pub vf_reset_on_logic: Option<bool>,
+    pub wrap_sprites: Option<bool>,
+}
+
+impl QuirkOverrides {
+    pub fn get(&self, quirk: Quirk) -> Option<bool> {
+        *self.field(quirk)
+    }
+
+    pub fn set(&mut self, quirk: Quirk, on: Option<bool>) {
+        *self.field_mut(quirk) = on;
+    }
+
+    pub fn apply(&self, mut quirks: Quirks) -> Quirks {
+        for quirk in Quirk::ALL {
+            if let Some(on) = self.get(quirk) {
+                quirks.set(quirk, on);
+            }
+        }
+        quirks
+    }
+
+    fn field(&self, quirk: Quirk) -> &Option<bool> {
+        match quirk {
+            Quirk::ShiftUsesVy => &self.shift_uses_vy,
+            Quirk::LoadStoreIncrementsI => &self.load_store_increments_i,
+            Quirk::JumpWithVx => &self.jump_with_vx,
+            Quirk::VfResetOnLogic => &self.vf_reset_on_logic,
+            Quirk::WrapSprites => &self.wrap_sprites,
+        }
+    }
+
+    fn field_mut(&mut self, quirk: Quirk) -> &mut Option<bool> {
+        match quirk {
+            Quirk::ShiftUsesVy => &mut self.shift_uses_vy,
+            Quirk::LoadStoreIncrementsI => &mut self.load_store_increments_i,
+            Quirk::JumpWithVx => &mut self.jump_with_vx,
+            Quirk::VfResetOnLogic => &mut self.vf_reset_on_logic,
+            Quirk::WrapSprites => &mut self.wrap_sprites,
+        }
+    }
+}
+
+/// Physical key names (as understood by SDL) mapped to CHIP-8 key numbers
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    pub primary: HashMap<String, usize>,
+    pub player2: HashMap<String, usize>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            primary: DEFAULT_KEYS.iter().map(|(name, key)| (name.to_string(), *key)).collect(),
+            player2: HashMap::new(),
+        }
+    }
+}
+
+/// CHIP-8 keys driven by a game controller. Directions apply to both the D-pad and the left stick
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadBindings {
+    pub up: usize,
+    pub down: usize,
+    pub left: usize,
+    pub right: usize,
+    pub a: Option<usize>,
+    pub b: Option<usize>,
+}
+
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        Self { up: 0x2, down: 0x8, left: 0x4, right: 0x6, a: Some(0x5), b: None }
+    }
+}
+
+/// The beeper's tone, its envelope ramps in milliseconds, and the shortest beep frontends sound
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Loudness from 0 (silent) to 1
+    pub volume: f32,
+    pub tone_hz: f32,
+    /// Silences the beeper, as F6 does; the window's border doesn't flash for beeps either
+    pub muted: bool,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+    /// Shorter beeps are held on until they have sounded this many frames; 0 sounds them as they are
+    pub min_beep_frames: u8,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self { volume: 0.25, tone_hz: 440.0, muted: false, attack_ms: 2.0, release_ms: 2.0, min_beep_frames: 2 }
+    }
+}
+
+/// An RGB color, written as "RRGGBB" (optionally prefixed with '#') in the config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl std::str::FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let value = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)
+            .ok_or_else(|| format!("Invalid color '{}', expected RRGGBB", s))?;
+
+        Ok(Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8))
+    }
+}
+
+impl std::fmt::Display for Rgb {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct Palette {
+    pub foreground: Rgb,
+    pub background: Rgb,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self { foreground: Rgb(57, 255, 20), background: Rgb(0, 26, 0) }
+    }
+}
+
+/// Everything needed to build the window. Palette, grid, blending and title can also change at runtime
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub title: String,
+    pub scale: u32,
+    pub palette: Palette,
+    pub vsync: VsyncMode,
+    pub fullscreen: bool,
+    pub resizable: bool,
+    /// Draws faint lines between CHIP-8 pixels
+    pub grid: bool,
+    /// Frames blended together against sprite flicker, 1 (off) to 3
+    pub blend_frames: u8,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            title: "CHIP-8".to_string(),
+            scale: 20,
+            palette: Palette::default(),
+            vsync: VsyncMode::On,
+            fullscreen: false,
+            resizable: false,
+            grid: false,
+            blend_frames: 1,
+        }
+    }
+}
+
+/// Which runtime-changeable display settings differ between two configs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DisplayChanges {
+    pub palette: bool,
+    pub grid: bool,
+    pub blend_frames: bool,
+    pub title: bool,
+}
+
+impl DisplayChanges {
+    /// True if the picture has to be drawn again for the changes to show
+    pub fn needs_redraw(&self) -> bool {
+        self.palette || self.grid || self.blend_frames
+    }
+}
+
+impl DisplayConfig {
+    /// Compares the settings that can be applied to an open window
+    pub fn runtime_changes(&self, other: &DisplayConfig) -> DisplayChanges {
+        DisplayChanges {
+            palette: self.palette != other.palette,
+            grid: self.grid != other.grid,
+            blend_frames: self.blend_frames != other.blend_frames,
+            title: self.title != other.title,
+        }
+    }
+
+    fn validate(display: DisplayConfig) -> Result<Self, std::io::Error> {
+        if display.scale == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "display.scale must be at least 1"));
+        }
+        if !(1..=MAX_BLEND_FRAMES).contains(&display.blend_frames) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("display.blend_frames must be between 1 and {}, got {}", MAX_BLEND_FRAMES, display.blend_frames)
+            ));
+        }
+        Ok(display)
+    }
+}
+
+/// Config file layout before validation
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawConfig {
+    keys: RawKeys,
+    gamepad: RawGamepad,
+    input: InputConfig,
+    session: SessionConfig,
+    emulation: EmulationConfig,
+    audio: AudioConfig,
+    display: DisplayConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawGamepad {
+    up: Option<i64>,
+    down: Option<i64>,
+    left: Option<i64>,
+    right: Option<i64>,
+    a: Option<i64>,
+    b: Option<i64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawKeys {
+    player2: HashMap<String, i64>,
+    #[serde(flatten)]
+    primary: HashMap<String, i64>,
+}
+
+impl Config {
+    /// Loads the config file at path, falling back to the defaults if it does not exist
+    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Parses and validates the contents of a config file
+    pub fn parse(text: &str) -> Result<Self, std::io::Error> {
+        let raw: RawConfig = toml::from_str(text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+        Ok(Config {
+            keys: KeyBindings::from_raw(raw.keys)?,
+            gamepad: GamepadBindings::from_raw(raw.gamepad)?,
+            input: raw.input,
+            session: raw.session,
+            emulation: raw.emulation,
+            audio: AudioConfig::validate(raw.audio)?,
+            display: DisplayConfig::validate(raw.display)?,
+        })
+    }
+}
+
+/// Checks that a binding targets a key on the 16-key keypad
+fn check_key(name: &str, key: i64) -> Result<usize, std::io::Error> {
+    if (0..INPUTS_COUNT as i64).contains(&key) {
+        Ok(key as usize)
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Key '{}' is bound to {}, which is not a CHIP-8 key (0x0-0xF)", name, key)
+        ))
+    }
+}
+
+impl KeyBindings {
+    fn from_raw(raw: RawKeys) -> Result<Self, std::io::Error> {
+        let mut bindings = KeyBindings::default();
+
+        if !raw.primary.is_empty() {
+            bindings.primary = KeyBindings::validate(raw.primary)?;
+        }
+        bindings.player2 = KeyBindings::validate(raw.player2)?;
+
+        // The same physical key can't drive two different CHIP-8 keys
+        for (name, key) in &bindings.player2 {
+            let clash = bindings.primary.iter()
+                .find(|(other, other_key)| other.eq_ignore_ascii_case(name) && *other_key != key);
+
+            if let Some((_, other_key)) = clash {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Key '{}' is bound to {:X} for player 1 and {:X} for player 2", name, other_key, key)
+                ));
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    fn validate(raw: HashMap<String, i64>) -> Result<HashMap<String, usize>, std::io::Error> {
+        raw.into_iter()
+            .map(|(name, key)| check_key(&name, key).map(|key| (name, key)))
+            .collect()
+    }
+}
+
+impl GamepadBindings {
+    fn from_raw(raw: RawGamepad) -> Result<Self, std::io::Error> {
+        let defaults = GamepadBindings::default();
+        let pick = |name: &str, value: Option<i64>, default: usize| match value {
+            Some(key) => check_key(name, key),
+            None => Ok(default),
+        };
+        let pick_button = |name: &str, value: Option<i64>, default: Option<usize>| match value {
+            Some(key) => check_key(name, key).map(Some),
+            None => Ok(default),
+        };
+
+        Ok(Self {
+            up: pick("up", raw.up, defaults.up)?,
+            down: pick("down", raw.down, defaults.down)?,
+            left: pick("left", raw.left, defaults.left)?,
+            right: pick("right", raw.right, defaults.right)?,
+            a: pick_button("a", raw.a, defaults.a)?,
+            b: pick_button("b", raw.b, defaults.b)?,
+        })
+    }
+}
+
+impl AudioConfig {
+    pub const VOLUME_RANGE: (f32, f32) = (0.0, 1.0);
+    pub const TONE_RANGE: (f32, f32) = (100.0, 2000.0);
+
+    fn validate(audio: AudioConfig) -> Result<Self, std::io::Error> {
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+        for (name, ms) in [("attack_ms", audio.attack_ms), ("release_ms", audio.release_ms)] {
+            if !(1.0..=5.0).contains(&ms) {
+                return Err(invalid(format!("audio.{} must be between 1 and 5 ms, got {}", name, ms)));
+            }
+        }
+        let (min, max) = AudioConfig::VOLUME_RANGE;
+        if !(min..=max).contains(&audio.volume) {
+            return Err(invalid(format!("audio.volume must be between {} and {}, got {}", min, max, audio.volume)));
+        }
+        let (min, max) = AudioConfig::TONE_RANGE;
+        if !(min..=max).contains(&audio.tone_hz) {
+            return Err(invalid(format!("audio.tone_hz must be between {} and {} Hz, got {}", min, max, audio.tone_hz)));
+        }
+        Ok(audio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_without_keys_section() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.keys.primary.len(), INPUTS_COUNT);
+        assert!(config.keys.player2.is_empty());
+    }
+
+    #[test]
+    fn test_player2_keeps_default_primary() {
+        let config = Config::parse("[keys.player2]\nUp = 0x5\n\"Keypad 8\" = 0x8\n").unwrap();
+        assert_eq!(config.keys.primary["W"], 0x5);
+        assert_eq!(config.keys.player2["Up"], 0x5);
+        assert_eq!(config.keys.player2["Keypad 8"], 0x8);
+    }
+
+    #[test]
+    fn test_primary_replaces_defaults() {
+        let config = Config::parse("[keys]\nLeft = 4\nRight = 6\n").unwrap();
+        assert_eq!(config.keys.primary.len(), 2);
+        assert_eq!(config.keys.primary["Left"], 0x4);
+    }
+
+    #[test]
+    fn test_conflicting_player_bindings_rejected() {
+        // W is 5 in the default layout
+        assert!(Config::parse("[keys.player2]\nW = 0x8\n").is_err());
+        assert!(Config::parse("[keys]\nUp = 2\n[keys.player2]\nup = 8\n").is_err());
+    }
+
+    #[test]
+    fn test_shared_binding_to_same_key_allowed() {
+        assert!(Config::parse("[keys.player2]\nW = 0x5\n").is_ok());
+    }
+
+    #[test]
+    fn test_out_of_range_key_rejected() {
+        assert!(Config::parse("[keys]\nUp = 0x10\n").is_err());
+        assert!(Config::parse("[keys.player2]\nUp = -1\n").is_err());
+    }
+
+    #[test]
+    fn test_gamepad_bindings() {
+        let config = Config::parse("[gamepad]\nup = 0x5\nb = 0xF\n").unwrap();
+        assert_eq!(config.gamepad.up, 0x5);
+        assert_eq!(config.gamepad.down, 0x8);
+        assert_eq!(config.gamepad.a, Some(0x5));
+        assert_eq!(config.gamepad.b, Some(0xF));
+
+        assert!(Config::parse("[gamepad]\nleft = 16\n").is_err());
+    }
+
+    #[test]
+    fn test_sticky_keys_flag() {
+        assert!(!Config::parse("").unwrap().input.sticky_keys);
+        assert!(Config::parse("[input]\nsticky_keys = true\n").unwrap().input.sticky_keys);
+    }
+
+    #[test]
+    fn test_auto_save_opt_in() {
+        assert!(!Config::parse("").unwrap().session.auto_save);
+        assert!(Config::parse("[session]\nauto_save = true\n").unwrap().session.auto_save);
+    }
+
+    #[test]
+    fn test_quirks_preset() {
+        assert_eq!(Config::parse("").unwrap().emulation.quirks, Preset::Modern);
+        assert_eq!(Config::parse("[emulation]\nquirks = \"vip\"\n").unwrap().emulation.quirks, Preset::Vip);
+        assert!(Config::parse("[emulation]\nquirks = \"schip\"\n").is_err());
+    }
+
+    #[test]
+    fn test_quirk_overrides() {
+        let emulation = Config::parse("[emulation]\nquirks = \"vip\"\n[emulation.overrides]\nwrap_sprites = true\n").unwrap().emulation;
+        assert_eq!(emulation.overrides, QuirkOverrides { wrap_sprites: Some(true), ..Default::default() });
+        assert_eq!(emulation.resolve(None), Quirks { wrap_sprites: true, ..Quirks::vip() });
+        assert_eq!(emulation.resolve(Some(Preset::Modern)), Quirks::modern());
+        assert!(Config::parse("[emulation.overrides]\nwrap = true\n").is_err());
+    }
+
+    #[test]
+    fn test_audio_envelope_range() {
+        let config = Config::parse("[audio]\nattack_ms = 1\nrelease_ms = 4.5\n").unwrap();
+        assert_eq!(config.audio, AudioConfig { attack_ms: 1.0, release_ms: 4.5, ..Default::default() });
+
+        assert!(Config::parse("[audio]\nattack_ms = 0.5\n").is_err());
+        assert!(Config::parse("[audio]\nrelease_ms = 6\n").is_err());
+    }
+
+    #[test]
+    fn test_audio_tone() {
+        let audio = Config::parse("[audio]\nvolume = 0.5\ntone_hz = 880\nmuted = true\n").unwrap().audio;
+        assert_eq!((audio.volume, audio.tone_hz, audio.muted), (0.5, 880.0, true));
+
+        assert!(Config::parse("[audio]\nvolume = 1.5\n").is_err());
+        assert!(Config::parse("[audio]\ntone_hz = 20\n").is_err());
+    }
+
+    #[test]
+    fn test_default_display_config() {
+        let display = Config::parse("").unwrap().display;
+        assert_eq!(display, DisplayConfig::default());
+        assert_eq!(display.title, "CHIP-8");
+        assert_eq!(display.scale, 20);
+        assert_eq!(display.palette.foreground, Rgb(57, 255, 20));
+        assert_eq!(display.palette.background, Rgb(0, 26, 0));
+        assert_eq!(display.vsync, VsyncMode::On);
+        assert!(!display.fullscreen && !display.resizable && !display.grid);
+        assert_eq!(display.blend_frames, 1);
+    }
+
+    #[test]
+    fn test_display_config_from_file() {
+        let text = "[display]\n\
+            title = \"Pong\"\n\
+            scale = 10\n\
+            vsync = \"adaptive\"\n\
+            resizable = true\n\
+            grid = true\n\
+            blend_frames = 2\n\
+            [display.palette]\n\
+            foreground = \"#FFFFFF\"\n\
+            background = \"102030\"\n";
+        let display = Config::parse(text).unwrap().display;
+
+        assert_eq!(display.title, "Pong");
+        assert_eq!(display.scale, 10);
+        assert_eq!(display.vsync, VsyncMode::Adaptive);
+        assert!(display.resizable && display.grid && !display.fullscreen);
+        assert_eq!(display.blend_frames, 2);
+        assert_eq!(display.palette.foreground, Rgb(255, 255, 255));
+        assert_eq!(display.palette.background, Rgb(0x10, 0x20, 0x30));
+    }
+
+    #[test]
+    fn test_rgb_round_trips_through_text() {
+        let color = Rgb(0x39, 0xFF, 0x04);
+        assert_eq!(color.to_string(), "39FF04");
+        assert_eq!(color.to_string().parse::<Rgb>(), Ok(color));
+    }
+
+    #[test]
+    fn test_invalid_display_config_rejected() {
+        assert!(Config::parse("[display]\nscale = 0\n").is_err());
+        assert!(Config::parse("[display]\nblend_frames = 0\n").is_err());
+        assert!(Config::parse("[display]\nblend_frames = 4\n").is_err());
+        assert!(Config::parse("[display]\nvsync = \"sometimes\"\n").is_err());
+        assert!(Config::parse("[display.palette]\nforeground = \"12345\"\n").is_err());
+        assert!(Config::parse("[display.palette]\nforeground = \"GGGGGG\"\n").is_err());
+    }
+
+    #[test]
+    fn test_runtime_changes() {
+        let before = DisplayConfig::default();
+        assert_eq!(before.runtime_changes(&before), DisplayChanges::default());
+
+        let mut after = before.clone();
+        after.palette.foreground = Rgb(255, 0, 0);
+        let changes = before.runtime_changes(&after);
+        assert!(changes.palette && changes.needs_redraw());
+
+        let mut after = before.clone();
+        after.title = "Tetris".to_string();
+        let changes = before.runtime_changes(&after);
+        assert!(changes.title && !changes.needs_redraw());
+
+        let mut after = before.clone();
+        after.grid = true;
+        assert!(before.runtime_changes(&after).needs_redraw());
+
+        let mut after = before.clone();
+        after.blend_frames = 3;
+        assert!(before.runtime_changes(&after).needs_redraw());
+
+        // Construction-only settings aren't runtime changes
+        let mut after = before.clone();
+        after.scale = 5;
+        after.fullscreen = true;
+        assert_eq!(before.runtime_changes(&after), DisplayChanges::default());
+    }
+
+    #[test]
+    fn test_missing_file_uses_defaults() {
+        let config = Config::load("./does_not_exist.toml").unwrap();
+        assert_eq!(config, Config::default());
+    }
+}
@@ -0,0 +1,225 @@
+//! Picking up edits to the config file while a ROM runs. ConfigWatcher notices the file changing
+//! and re-parses it; changes() says which settings moved and whether a live window can take them,
+//! and in_effect() builds the config the window then runs with
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use crate::config::Config;
+
+/// How often ConfigWatcher looks at the file's modification time
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When a changed setting takes effect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applies {
+    Now,
+    /// Each fresh machine is set up with it, so it waits for the next reset or ROM
+    OnReset,
+    /// The window or session is built with it, so it waits for the next launch
+    OnRestart,
+}
+
+/// A setting that differs between two configs, named as in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub setting: &'static str,
+    pub applies: Applies,
+}
+
+type Differs = fn(&Config, &Config) -> bool;
+
+/// Every setting in the file and when a change to it applies. Speed and timing are left out because
+/// the file doesn't carry them: they belong to the running game (keys, menu, `--hz`, playlists)
+const SETTINGS: [(&str, Applies, Differs); 21] = [
+    ("keys", Applies::Now, |a, b| a.keys.primary != b.keys.primary),
+    ("keys.player2", Applies::Now, |a, b| a.keys.player2 != b.keys.player2),
+    ("gamepad", Applies::Now, |a, b| a.gamepad != b.gamepad),
+    ("input.sticky_keys", Applies::Now, |a, b| a.input != b.input),
+    ("emulation.quirks", Applies::OnReset, |a, b| a.emulation.quirks != b.emulation.quirks),
+    ("emulation.overrides", Applies::OnReset, |a, b| a.emulation.overrides != b.emulation.overrides),
+    ("audio.volume", Applies::Now, |a, b| a.audio.volume != b.audio.volume),
+    ("audio.tone_hz", Applies::Now, |a, b| a.audio.tone_hz != b.audio.tone_hz),
+    ("audio.muted", Applies::Now, |a, b| a.audio.muted != b.audio.muted),
+    ("audio.attack_ms", Applies::Now, |a, b| a.audio.attack_ms != b.audio.attack_ms),
+    ("audio.release_ms", Applies::Now, |a, b| a.audio.release_ms != b.audio.release_ms),
+    ("audio.min_beep_frames", Applies::Now, |a, b| a.audio.min_beep_frames != b.audio.min_beep_frames),
+    ("display.title", Applies::Now, |a, b| a.display.title != b.display.title),
+    ("display.palette", Applies::Now, |a, b| a.display.palette != b.display.palette),
+    ("display.grid", Applies::Now, |a, b| a.display.grid != b.display.grid),
+    ("display.blend_frames", Applies::Now, |a, b| a.display.blend_frames != b.display.blend_frames),
+    ("display.scale", Applies::OnRestart, |a, b| a.display.scale != b.display.scale),
+    ("display.vsync", Applies::OnRestart, |a, b| a.display.vsync != b.display.vsync),
+    ("display.fullscreen", Applies::OnRestart, |a, b| a.display.fullscreen != b.display.fullscreen),
+    ("display.resizable", Applies::OnRestart, |a, b| a.display.resizable != b.display.resizable),
+    ("session.auto_save", Applies::OnRestart, |a, b| a.session != b.session),
+];
+
+/// The settings that differ from old in new, in file order
+pub fn changes(old: &Config, new: &Config) -> Vec<Change> {
+    SETTINGS.iter()
+        .filter(|(_, _, differs)| differs(old, new))
+        .map(|(setting, applies, _)| Change { setting, applies: *applies })
+        .collect()
+}
+
+/// new, except for the settings that only apply on restart, which keep their values from old.
+/// Those that apply on reset come from new: the frontend hands them to the next machine
+pub fn in_effect(old: &Config, new: &Config) -> Config {
+    let mut config = new.clone();
+    config.display.scale = old.display.scale;
+    config.display.vsync = old.display.vsync;
+    config.display.fullscreen = old.display.fullscreen;
+    config.display.resizable = old.display.resizable;
+    config.session = old.session.clone();
+    config
+}
+
+/// Watches a config file by polling its modification time
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    next_check: Instant,
+}
+
+impl ConfigWatcher {
+    /// Starts from the file as it is now, so only later edits count
+    pub fn new(path: impl Into<PathBuf>, now: Instant) -> Self {
+        let path = path.into();
+        let modified = modified(&path);
+        Self { path, modified, next_check: now + POLL_INTERVAL }
+    }
+
+    /// At most once per POLL_INTERVAL: the file parsed again if it changed since the last look.
+    /// A file that was removed is left alone, and a bad edit is reported once rather than every poll
+    pub fn poll(&mut self, now: Instant) -> Option<Result<Config, Error>> {
+        if now < self.next_check {
+            return None;
+        }
+        self.next_check = now + POLL_INTERVAL;
+
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        modified?;
+        Some(std::fs::read_to_string(&self.path).and_then(|text| Config::parse(&text)))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Rgb;
+    use crate::pacing::VsyncMode;
+    use crate::quirks::Preset;
+
+    fn settings(changes: &[Change]) -> Vec<(&'static str, Applies)> {
+        changes.iter().map(|change| (change.setting, change.applies)).collect()
+    }
+
+    #[test]
+    fn test_no_changes() {
+        assert_eq!(changes(&Config::default(), &Config::default()), []);
+    }
+
+    #[test]
+    fn test_classifies_changes() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.display.palette.foreground = Rgb(255, 255, 255);
+        new.display.grid = true;
+        new.keys.primary.insert("P".to_string(), 0x1);
+        new.audio.min_beep_frames = 4;
+        new.audio.volume = 0.5;
+        new.display.scale = 10;
+        new.session.auto_save = true;
+        new.emulation.quirks = Preset::Vip;
+
+        assert_eq!(settings(&changes(&old, &new)), [
+            ("keys", Applies::Now),
+            ("emulation.quirks", Applies::OnReset),
+            ("audio.volume", Applies::Now),
+            ("audio.min_beep_frames", Applies::Now),
+            ("display.palette", Applies::Now),
+            ("display.grid", Applies::Now),
+            ("display.scale", Applies::OnRestart),
+            ("session.auto_save", Applies::OnRestart),
+        ]);
+    }
+
+    #[test]
+    fn test_in_effect_keeps_restart_settings() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.display.palette.background = Rgb(1, 2, 3);
+        new.input.sticky_keys = true;
+        new.display.scale = 5;
+        new.display.vsync = VsyncMode::Off;
+        new.display.fullscreen = true;
+        new.display.resizable = true;
+        new.session.auto_save = true;
+        new.emulation.quirks = Preset::Vip;
+
+        let config = in_effect(&old, &new);
+        assert_eq!(config.display.palette.background, Rgb(1, 2, 3));
+        assert!(config.input.sticky_keys);
+        assert_eq!(config.display.scale, old.display.scale);
+        assert_eq!(config.emulation.quirks, Preset::Vip);
+
+        // Whatever changed now can be handed on now, and the rest is still waiting
+        assert!(changes(&old, &config).iter().all(|change| change.applies != Applies::OnRestart));
+        assert!(changes(&config, &new).iter().all(|change| change.applies == Applies::OnRestart));
+        assert_eq!(changes(&config, &new).len(), 5);
+    }
+
+    fn temp_config(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("chip8-reload-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    /// Rewrites the file with a distinct modification time, as filesystem timestamps can be coarse
+    fn edit(path: &Path, text: &str, seconds: u64) {
+        std::fs::write(path, text).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + seconds)).unwrap();
+    }
+
+    #[test]
+    fn test_watcher_polls_once_an_interval() {
+        let path = temp_config("interval", "[display]\ngrid = false\n");
+        let start = Instant::now();
+        let mut watcher = ConfigWatcher::new(&path, start);
+
+        edit(&path, "[display]\ngrid = true\n", 1);
+        assert!(watcher.poll(start).is_none());
+        let config = watcher.poll(start + POLL_INTERVAL).unwrap().unwrap();
+        assert!(config.display.grid);
+
+        // Nothing new since
+        assert!(watcher.poll(start + POLL_INTERVAL * 2).is_none());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_watcher_reports_a_bad_edit_once() {
+        let path = temp_config("bad", "");
+        let start = Instant::now();
+        let mut watcher = ConfigWatcher::new(&path, start);
+
+        edit(&path, "[display]\nscale = 0\n", 1);
+        assert!(watcher.poll(start + POLL_INTERVAL).unwrap().is_err());
+        assert!(watcher.poll(start + POLL_INTERVAL * 2).is_none());
+
+        // Fixing it is picked up, and removing the file changes nothing
+        edit(&path, "[display]\nscale = 4\n", 2);
+        assert_eq!(watcher.poll(start + POLL_INTERVAL * 3).unwrap().unwrap().display.scale, 4);
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.poll(start + POLL_INTERVAL * 4).is_none());
+    }
+}
@@ -0,0 +1,183 @@
+//! Writing settings changed in a frontend's menus back to the config file, editing it in place so
+//! the player's comments, layout and key bindings survive
+use std::io::{Error, ErrorKind};
+use toml_edit::{value, DocumentMut, Item};
+use crate::config::Config;
+use crate::config_reload;
+use crate::quirks::Quirk;
+
+/// Writes the settings where config differs from the file at path into it, creating the file if
+/// there is none. Returns the settings written. Key and gamepad bindings are never written, and a
+/// file that doesn't parse is left alone rather than overwritten
+pub fn save(path: &str, config: &Config) -> Result<Vec<&'static str>, Error> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let on_file = Config::parse(&text)?;
+    let mut document: DocumentMut = text.parse()
+        .map_err(|e: toml_edit::TomlError| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+
+    let mut written = Vec::new();
+    for change in config_reload::changes(&on_file, config) {
+        let entries = entries(config, change.setting);
+        for (keys, item) in &entries {
+            set(&mut document, keys, item.clone());
+        }
+        if !entries.is_empty() {
+            written.push(change.setting);
+        }
+    }
+
+    if !written.is_empty() {
+        std::fs::write(path, document.to_string())?;
+    }
+    Ok(written)
+}
+
+/// The keys a setting is stored under and their values; none for the bindings
+fn entries(config: &Config, setting: &str) -> Vec<(&'static [&'static str], Item)> {
+    let (display, audio) = (&config.display, &config.audio);
+    match setting {
+        "input.sticky_keys" => vec![(&["input", "sticky_keys"], value(config.input.sticky_keys))],
+        "session.auto_save" => vec![(&["session", "auto_save"], value(config.session.auto_save))],
+        "emulation.quirks" => vec![(&["emulation", "quirks"], value(config.emulation.quirks.name()))],
+        "emulation.overrides" => Quirk::ALL.iter()
+            .map(|quirk| {
+                let keys: &'static [&'static str] = match quirk {
+                    Quirk::ShiftUsesVy => &["emulation", "overrides", "shift_uses_vy"],
+                    Quirk::LoadStoreIncrementsI => &["emulation", "overrides", "load_store_increments_i"],
+                    Quirk::JumpWithVx => &["emulation", "overrides", "jump_with_vx"],
+                    Quirk::VfResetOnLogic => &["emulation", "overrides", "vf_reset_on_logic"],
+                    Quirk::WrapSprites => &["emulation", "overrides", "wrap_sprites"],
+                };
+                (keys, config.emulation.overrides.get(*quirk).map_or(Item::None, value))
+            })
+            .collect(),
+        "audio.volume" => vec![(&["audio", "volume"], value(f64::from(audio.volume)))],
+        "audio.tone_hz" => vec![(&["audio", "tone_hz"], value(f64::from(audio.tone_hz)))],
+        "audio.muted" => vec![(&["audio", "muted"], value(audio.muted))],
+        "audio.attack_ms" => vec![(&["audio", "attack_ms"], value(f64::from(audio.attack_ms)))],
+        "audio.release_ms" => vec![(&["audio", "release_ms"], value(f64::from(audio.release_ms)))],
+        "audio.min_beep_frames" => vec![(&["audio", "min_beep_frames"], value(i64::from(audio.min_beep_frames)))],
+        "display.title" => vec![(&["display", "title"], value(display.title.as_str()))],
+        "display.scale" => vec![(&["display", "scale"], value(i64::from(display.scale)))],
+        "display.vsync" => vec![(&["display", "vsync"], value(display.vsync.to_string()))],
+        "display.fullscreen" => vec![(&["display", "fullscreen"], value(display.fullscreen))],
+        "display.resizable" => vec![(&["display", "resizable"], value(display.resizable))],
+        "display.grid" => vec![(&["display", "grid"], value(display.grid))],
+        "display.blend_frames" => vec![(&["display", "blend_frames"], value(i64::from(display.blend_frames)))],
+        "display.palette" => vec![
+            (&["display", "palette", "foreground"], value(display.palette.foreground.to_string())),
+            (&["display", "palette", "background"], value(display.palette.background.to_string())),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Sets a value by its table path, making the tables that aren't there yet. Item::None removes
+/// the key instead, and makes no tables
+fn set(document: &mut DocumentMut, keys: &[&str], item: Item) {
+    let (key, tables) = keys.split_last().expect("a setting has a key");
+    let mut table = document.as_table_mut();
+    for name in tables {
+        if item.is_none() && !table.contains_table(name) {
+            return;
+        }
+        table = table.entry(name)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .expect("the file parsed as a config, so its sections are tables");
+    }
+    if item.is_none() {
+        table.remove(key);
+    } else {
+        table[key] = item;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Rgb;
+    use crate::quirks::Preset;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("chip8-save-{}-{}.toml", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_keeps_comments_and_bindings() {
+        let path = temp_path("comments");
+        std::fs::write(&path, "# My settings\n[keys]\nW = 0x5 # Up\n\n[display]\ngrid = false # Off for now\nscale = 10\n").unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        config.display.grid = true;
+        config.audio.min_beep_frames = 0;
+        config.keys.primary.insert("P".to_string(), 0x1);
+        assert_eq!(save(&path, &config).unwrap(), ["audio.min_beep_frames", "display.grid"]);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# My settings\n[keys]\nW = 0x5 # Up\n"), "{}", text);
+        assert!(text.contains("grid = true"), "{}", text);
+        assert!(text.contains("scale = 10\n"), "{}", text);
+        assert!(!text.contains("P ="), "{}", text);
+
+        let saved = Config::load(&path).unwrap();
+        assert_eq!((saved.display.grid, saved.audio.min_beep_frames), (true, 0));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_creates_the_file_and_tables() {
+        let path = temp_path("create");
+        let mut config = Config::default();
+        config.display.palette.foreground = Rgb(0xFF, 0xB0, 0x00);
+        config.audio.attack_ms = 1.5;
+        config.input.sticky_keys = true;
+        config.emulation.quirks = Preset::Vip;
+        assert_eq!(save(&path, &config).unwrap(), ["input.sticky_keys", "emulation.quirks", "audio.attack_ms", "display.palette"]);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("[emulation]\nquirks = \"vip\"\n"));
+
+        assert_eq!(Config::load(&path).unwrap(), config);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_quirk_overrides_are_written_and_cleared() {
+        let path = temp_path("overrides");
+        let mut config = Config::default();
+        config.emulation.overrides.set(Quirk::WrapSprites, Some(false));
+        assert_eq!(save(&path, &config).unwrap(), ["emulation.overrides"]);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("[emulation.overrides]\nwrap_sprites = false\n"));
+        assert_eq!(Config::load(&path).unwrap(), config);
+
+        config.emulation.overrides.set(Quirk::WrapSprites, None);
+        save(&path, &config).unwrap();
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("wrap_sprites"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_nothing_changed_writes_nothing() {
+        let path = temp_path("unchanged");
+        assert_eq!(save(&path, &Config::default()).unwrap(), Vec::<&str>::new());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn test_broken_file_is_left_alone() {
+        let path = temp_path("broken");
+        std::fs::write(&path, "[display]\nscale = 0\n").unwrap();
+        let mut config = Config::default();
+        config.display.grid = true;
+
+        assert!(save(&path, &config).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[display]\nscale = 0\n");
+        std::fs::remove_file(&path).ok();
+    }
+}
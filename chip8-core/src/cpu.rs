@@ -0,0 +1,2135 @@
+use std::ops::Range;
+use crate::hooks::{MemoryHook, MemoryHooks};
+use crate::error::EmulatorError;
+use crate::quirks::Quirks;
+use crate::rng::Rng;
+use crate::rom::RomInfo;
+use crate::savestate::{self, Header, Reader, StateError, StateWarning};
+use crate::timing;
+use crate::trace::{Trace, TraceEntry};
+use crate::uninit::{UninitRead, UninitTracker};
+use crate::constants::{
+    DISPLAY_WIDTH,
+    DISPLAY_HEIGHT,
+    HIRES_WIDTH,
+    HIRES_HEIGHT,
+    INPUTS_COUNT,
+    REGISTERS_COUNT,
+    MEMORY_SIZE,
+    STACK_SIZE,
+    MAX_STACK_DEPTH,
+    STARTING_MEMORY_ADDRESS,
+    FONTSET_START_ADDRESS,
+    FONTSET,
+    BYTES_PER_FONT,
+    BIG_FONTSET_START_ADDRESS,
+    BIG_FONTSET,
+    BYTES_PER_BIG_FONT,
+    RPL_FLAGS_COUNT,
+    VIP_DISPLAY_ADDRESS
+};
+
+/// Dirty-row mask with every display row set
+pub const ALL_ROWS: u64 = u64::MAX >> (64 - DISPLAY_HEIGHT);
+/// Dirty-row mask with every row of the high-resolution screen set
+pub const ALL_HIRES_ROWS: u64 = u64::MAX >> (64 - HIRES_HEIGHT);
+
+/// What the sound timer has the beeper doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BeeperState {
+    pub on: bool,
+    /// Timer ticks (60Hz frames) until it stops; 0 when off
+    pub remaining_frames: u8,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+pub struct CPU {
+    pub v: [u8; REGISTERS_COUNT], // 16 8-bit general purpose registers named V0 to VF
+    pub i: u16, // Address register
+    pub pc: u16,
+    pub memory: [u8; MEMORY_SIZE],
+    pub stack: Vec<u16>, // Return addresses; the length is the stack pointer
+    stack_depth: usize, // Calls nested deeper than this overflow
+    quirks: Quirks,
+    pub delay_timer: u8, // Both timer counts down from 60hz to 0
+    pub sound_timer: u8,
+    pub display: [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT], // With a mapped display, decoded from memory after every change
+    mapped_display: bool, // Memory from VIP_DISPLAY_ADDRESS is the display's backing store
+    hires: bool, // SUPER-CHIP's 128x64 mode, from 00FF until 00FE
+    hires_display: Box<[u8; HIRES_WIDTH * HIRES_HEIGHT]>, // The picture in hires mode; display then holds it at half size
+    pub rpl: [u8; RPL_FLAGS_COUNT], // SUPER-CHIP's flag registers for FX75/FX85
+    pub input: [bool; INPUTS_COUNT], // Prefer press_key/release_key/set_keys, which also record edges
+    pressed_edges: u16, // Bit N set if key N went down since the last instruction
+    released_edges: u16, // Bit N set if key N went up since the last instruction
+    key_wait: Option<usize>, // Key FX0A saw go down, accepted once it comes back up
+    awaited_key: Option<usize>, // Key accepted by the last FX0A, until the frontend takes it
+    dirty_rows: u64, // Bit N set if row N of screen() changed since the frontend last took the mask
+    rom_hash: u64, // Identifies the loaded ROM in save states
+    rom_info: Option<RomInfo>,
+    hooks: MemoryHooks,
+    rng: Rng,
+    uninit: Option<Box<UninitTracker>>, // Only with --detect-uninit, so it costs nothing otherwise
+    trace: Option<Box<Trace>>, // Likewise only with --trace
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CPU {
+
+    pub fn new() -> Self {
+        let mut cpu = CPU {
+            v: [0; REGISTERS_COUNT],
+            i: 0,
+            pc: STARTING_MEMORY_ADDRESS as u16,
+            memory: [0; MEMORY_SIZE],
+            stack: Vec::with_capacity(STACK_SIZE),
+            stack_depth: STACK_SIZE,
+            quirks: Quirks::default(),
+            delay_timer: 0,
+            sound_timer: 0,
+            display: [0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            mapped_display: false,
+            hires: false,
+            hires_display: Box::new([0; HIRES_WIDTH * HIRES_HEIGHT]),
+            rpl: [0; RPL_FLAGS_COUNT],
+            input: [false; INPUTS_COUNT],
+            pressed_edges: 0,
+            released_edges: 0,
+            key_wait: None,
+            awaited_key: None,
+            dirty_rows: ALL_ROWS,
+            rom_hash: savestate::rom_hash(&[]),
+            hooks: MemoryHooks::default(),
+            rng: Rng::from_entropy(),
+            uninit: None,
+            trace: None,
+            rom_info: None,
+        };
+
+        cpu.memory[FONTSET_START_ADDRESS..FONTSET_START_ADDRESS + FONTSET.len()].copy_from_slice(&FONTSET);
+        cpu.memory[BIG_FONTSET_START_ADDRESS..BIG_FONTSET_START_ADDRESS + BIG_FONTSET.len()].copy_from_slice(&BIG_FONTSET);
+
+        cpu
+    }
+
+    /// A CPU whose call stack holds up to depth return addresses (clamped to 1..=MAX_STACK_DEPTH)
+    pub fn with_stack_depth(depth: usize) -> Self {
+        let mut cpu = CPU::new();
+        cpu.stack_depth = depth.clamp(1, MAX_STACK_DEPTH);
+        cpu
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack_depth
+    }
+
+    /// A CPU running the ambiguous opcodes as quirks says
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        let mut cpu = CPU::new();
+        cpu.quirks = quirks;
+        cpu
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Makes memory from 0xF00 the display, as on the COSMAC VIP, so ROMs can read and write pixels directly.
+    /// The current picture is copied into that memory when enabling
+    pub fn set_mapped_display(&mut self, enabled: bool) {
+        self.mapped_display = enabled;
+        if enabled {
+            self.encode_mapped_display();
+        }
+    }
+
+    pub fn mapped_display(&self) -> bool {
+        self.mapped_display
+    }
+
+    /// Redraws the display from memory after writing the mapped display region directly
+    pub fn decode_mapped_display(&mut self) {
+        for addr in VIP_DISPLAY_ADDRESS..MEMORY_SIZE {
+            self.decode_display_byte(addr);
+        }
+    }
+
+    /// Copies the eight pixels stored at addr into the display
+    fn decode_display_byte(&mut self, addr: usize) {
+        let offset = (addr - VIP_DISPLAY_ADDRESS) * 8;
+        let byte = self.memory[addr];
+        for bit in 0..8 {
+            self.display[offset + bit] = (byte >> (7 - bit)) & 0x1;
+        }
+        self.dirty_rows |= 1 << (offset / DISPLAY_WIDTH);
+    }
+
+    fn encode_mapped_display(&mut self) {
+        for (byte, pixels) in self.memory[VIP_DISPLAY_ADDRESS..].iter_mut().zip(self.display.chunks_exact(8)) {
+            *byte = pixels.iter().fold(0, |byte, pixel| (byte << 1) | (pixel & 0x1));
+        }
+    }
+
+    /// Reports reads of memory and registers the ROM never set (see uninit_reads)
+    pub fn set_detect_uninit(&mut self, enabled: bool) {
+        let rom_size = self.rom_info.as_ref().map_or(0, |info| info.size);
+        self.uninit = enabled.then(|| Box::new(UninitTracker::new(rom_size)));
+    }
+
+    /// Uninitialized reads found so far, each location once. Always empty without set_detect_uninit
+    pub fn uninit_reads(&self) -> &[UninitRead] {
+        self.uninit.as_ref().map_or(&[], |tracker| tracker.found())
+    }
+
+    /// Records the last TRACE_CAPACITY instructions run (see trace), starting from none
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled.then(|| Box::new(Trace::new()));
+    }
+
+    /// The traced instructions, oldest first. Always empty without set_trace
+    pub fn trace(&self) -> impl DoubleEndedIterator<Item = &TraceEntry> {
+        self.trace.iter().flat_map(|trace| trace.iter())
+    }
+
+    /// Loads ROM into memory
+    pub fn load_rom(&mut self, path: &str) -> Result<(), EmulatorError> {
+        self.load_rom_bytes(&crate::rom::read(path)?)
+    }
+
+    /// Loads a ROM already in memory, e.g. one embedded in a test or a replay
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), EmulatorError> {
+        if STARTING_MEMORY_ADDRESS + rom.len() > MEMORY_SIZE {
+            return Err(EmulatorError::RomTooLarge { size: rom.len() });
+        }
+
+        self.memory[STARTING_MEMORY_ADDRESS..(STARTING_MEMORY_ADDRESS + rom.len())].copy_from_slice(rom);
+        self.rom_hash = savestate::rom_hash(rom);
+        self.rom_info = Some(RomInfo::new(rom));
+        if self.uninit.is_some() {
+            self.set_detect_uninit(true);
+        }
+        Ok(())
+    }
+
+    /// Executes one CPU cycle, returning the COSMAC VIP machine cycles the instruction took
+    pub fn tick(&mut self) -> Result<u32, EmulatorError> {
+        let opcode: u16 = self.fetch()?;
+        // println!("Running opcode: {:x}", opcode);
+        let cycles = timing::vip_cycles(opcode, self);
+        let (pc, i, quirks) = (self.pc, self.i, self.quirks);
+        if let Some(tracker) = self.uninit.as_mut() {
+            tracker.before(pc, opcode, i, quirks);
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry { pc, opcode, i });
+        }
+        let result = self.decode_and_execute(opcode);
+        if let Some(tracker) = self.uninit.as_mut() && result.is_ok() {
+            tracker.after(opcode, i, quirks);
+        }
+        self.pressed_edges = 0;
+        self.released_edges = 0;
+        result.map(|()| cycles)
+    }
+
+    /// Fetches the next 2-byte opcode from memory at the current program counter
+    pub fn fetch(&mut self) -> Result<u16, EmulatorError> {
+        self.check_bounds(self.pc, 2)?;
+
+        let opcode_high = self.mem_read(self.pc);
+        let opcode_low = self.mem_read(self.pc + 1);
+        Ok((opcode_high as u16) << 8 | (opcode_low as u16))
+    }
+
+    /// Decodes the opcode and executes the corresponding instruction
+    pub fn decode_and_execute(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        match opcode & 0xF000 {
+            0x0000 => self.dispatch_0xxx(opcode),
+            0x1000 => self.op_1nnn(opcode),
+            0x2000 => self.op_2nnn(opcode),
+            0x3000 => self.op_3xnn(opcode),
+            0x4000 => self.op_4xnn(opcode),
+            0x5000 => self.op_5xy0(opcode),
+            0x6000 => self.op_6xnn(opcode),
+            0x7000 => self.op_7xnn(opcode),
+            0x8000 => self.dispatch_8xxx(opcode),
+            0x9000 => self.op_9xy0(opcode),
+            0xA000 => self.op_annn(opcode),
+            0xB000 => self.op_bnnn(opcode),
+            0xC000 => self.op_cxnn(opcode),
+            0xD000 => self.op_dxyn(opcode),
+            0xE000 => self.dispatch_exxx(opcode),
+            0xF000 => self.dispatch_fxxx(opcode),
+            _ => Err(EmulatorError::UnknownOpcode { opcode, pc: self.pc })
+        }
+    }
+
+    /// Reads a byte of memory, through any hook mapped at addr
+    pub fn mem_read(&mut self, addr: u16) -> u8 {
+        if !self.hooks.is_empty() && let Some(value) = self.hooks.read(addr) {
+            return value;
+        }
+        self.memory[addr as usize]
+    }
+
+    /// Writes a byte of memory unless a hook mapped at addr vetoes it
+    pub fn mem_write(&mut self, addr: u16, value: u8) {
+        if self.hooks.is_empty() || self.hooks.write(addr, value) {
+            self.memory[addr as usize] = value;
+            if self.mapped_display && addr as usize >= VIP_DISPLAY_ADDRESS {
+                self.decode_display_byte(addr as usize);
+            }
+        }
+    }
+
+    /// Maps a peripheral over range. Any hooks already mapped over part of the range are removed
+    pub fn set_memory_hook(&mut self, range: Range<u16>, hook: Box<dyn MemoryHook>) {
+        self.hooks.insert(range, hook);
+    }
+
+    pub fn clear_memory_hooks(&mut self) {
+        self.hooks.clear();
+    }
+
+    /// Presses a keypad key. Keys above 0xF are ignored
+    pub fn press_key(&mut self, key: u8) {
+        let key = key as usize;
+        if key < INPUTS_COUNT && !self.input[key] {
+            self.input[key] = true;
+            self.pressed_edges |= 1 << key;
+        }
+    }
+
+    /// Releases a keypad key. Keys above 0xF are ignored
+    pub fn release_key(&mut self, key: u8) {
+        let key = key as usize;
+        if key < INPUTS_COUNT && self.input[key] {
+            self.input[key] = false;
+            self.released_edges |= 1 << key;
+        }
+    }
+
+    /// Sets the whole keypad at once (bit N = key N), recording edges for every key that changed
+    pub fn set_keys(&mut self, mask: u16) {
+        for key in 0..INPUTS_COUNT as u8 {
+            if mask & (1 << key) != 0 {
+                self.press_key(key);
+            } else {
+                self.release_key(key);
+            }
+        }
+    }
+
+    /// The key FX0A has seen pressed and is waiting to see released, if any
+    pub fn key_wait(&self) -> Option<usize> {
+        self.key_wait
+    }
+
+    /// Returns the key accepted by FX0A since the last call, if any
+    pub fn take_awaited_key(&mut self) -> Option<usize> {
+        self.awaited_key.take()
+    }
+
+    /// Whether the program has switched to SUPER-CHIP's 128x64 mode
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    /// The picture at its current resolution: display in lo-res, 128x64 pixels in hires mode
+    pub fn screen(&self) -> &[u8] {
+        if self.hires { &self.hires_display[..] } else { &self.display }
+    }
+
+    /// Width and height of screen()
+    pub fn screen_size(&self) -> (usize, usize) {
+        if self.hires { (HIRES_WIDTH, HIRES_HEIGHT) } else { (DISPLAY_WIDTH, DISPLAY_HEIGHT) }
+    }
+
+    /// Returns the mask of screen() rows changed since the last call (bit N = row N) and clears it.
+    /// This is how a frontend tells what to redraw: 0 means the picture hasn't changed
+    pub fn take_dirty_rows(&mut self) -> u64 {
+        std::mem::take(&mut self.dirty_rows)
+    }
+
+    /// Serializes the whole machine into a versioned save state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(MEMORY_SIZE + DISPLAY_WIDTH * DISPLAY_HEIGHT + 64);
+        payload.extend_from_slice(&self.v);
+        payload.extend_from_slice(&self.i.to_be_bytes());
+        payload.extend_from_slice(&self.pc.to_be_bytes());
+        payload.push(self.stack_depth as u8);
+        payload.push(self.stack.len() as u8);
+        for frame in &self.stack {
+            payload.extend_from_slice(&frame.to_be_bytes());
+        }
+        payload.push(self.delay_timer);
+        payload.push(self.sound_timer);
+        payload.extend_from_slice(&self.display);
+        payload.extend(self.input.map(u8::from));
+        payload.extend_from_slice(&self.memory);
+        payload.extend_from_slice(&self.rng.seed().to_be_bytes());
+        payload.extend_from_slice(&self.rng.state().to_be_bytes());
+        payload.push(u8::from(self.hires));
+        payload.extend_from_slice(&self.rpl);
+        payload.extend_from_slice(&self.hires_display[..]);
+
+        savestate::write(&Header::new(self.rom_hash, self.quirks_name(), MEMORY_SIZE), &payload)
+    }
+
+    /// Restores a state written by save_state. Nothing is changed unless the whole state is valid;
+    /// differences that don't prevent loading are returned as warnings
+    pub fn load_state(&mut self, state: &[u8]) -> Result<Vec<StateWarning>, StateError> {
+        let (header, payload) = savestate::read(state)?;
+        let mut warnings = savestate::check(&header, self.rom_hash, self.quirks_name(), MEMORY_SIZE)?;
+
+        let mut reader = Reader { bytes: &payload };
+        let mut restored = CPU::with_stack_depth(self.stack_depth);
+        restored.v.copy_from_slice(reader.take(REGISTERS_COUNT)?);
+        restored.i = reader.u16()?;
+        restored.pc = reader.u16()?;
+
+        let saved_depth = reader.u8()? as usize;
+        let frames = reader.u8()? as usize;
+        if frames > self.stack_depth {
+            return Err(StateError::StackTooDeep { frames, limit: self.stack_depth });
+        }
+        if saved_depth != self.stack_depth {
+            warnings.push(StateWarning::StackDepth { saved: saved_depth, current: self.stack_depth });
+        }
+        for _ in 0..frames {
+            restored.stack.push(reader.u16()?);
+        }
+        restored.delay_timer = reader.u8()?;
+        restored.sound_timer = reader.u8()?;
+        restored.display.copy_from_slice(reader.take(DISPLAY_WIDTH * DISPLAY_HEIGHT)?);
+        for (key, byte) in restored.input.iter_mut().zip(reader.take(INPUTS_COUNT)?) {
+            *key = *byte != 0;
+        }
+        restored.memory.copy_from_slice(reader.take(MEMORY_SIZE)?);
+        // States from before minor 1 don't have it, so keep the current sequence going
+        restored.rng = match header.minor {
+            0 => self.rng.clone(),
+            _ => Rng::resume(reader.u64()?, reader.u64()?),
+        };
+        // Minor 3 added SUPER-CHIP's state; older ones were saved in lo-res with the flags clear
+        if header.minor >= 3 {
+            restored.hires = reader.u8()? != 0;
+            restored.rpl.copy_from_slice(reader.take(RPL_FLAGS_COUNT)?);
+            restored.hires_display.copy_from_slice(reader.take(HIRES_WIDTH * HIRES_HEIGHT)?);
+        }
+        restored.dirty_rows = restored.all_rows();
+
+        restored.rom_hash = self.rom_hash;
+        restored.quirks = self.quirks;
+        restored.rom_info = self.rom_info.take();
+        restored.hooks = std::mem::take(&mut self.hooks);
+        restored.uninit = self.uninit.take();
+        let rom_size = restored.rom_info.as_ref().map_or(0, |info| info.size);
+        if let Some(tracker) = restored.uninit.as_mut() {
+            tracker.restore(rom_size, &restored.memory);
+        }
+        restored.trace = self.trace.take();
+        // The saved picture wins over whatever the state holds at 0xF00 if it was saved unmapped
+        restored.set_mapped_display(self.mapped_display);
+        *self = restored;
+        Ok(warnings)
+    }
+
+    /// Size and checksums of the loaded ROM
+    pub fn rom_info(&self) -> Option<&RomInfo> {
+        self.rom_info.as_ref()
+    }
+
+    /// Hash of the loaded ROM, as recorded in save states
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// The seed CXNN's random numbers come from
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    /// Restarts CXNN's random numbers from seed, so a run can be repeated exactly
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::from_seed(seed);
+    }
+
+    /// Identifies the opcode behavior in save states
+    pub fn quirks_name(&self) -> &'static str {
+        self.quirks.name()
+    }
+
+    /// The beeper as the sound timer drives it, for frontends to sound
+    pub fn beeper(&self) -> BeeperState {
+        BeeperState { on: self.sound_timer > 0, remaining_frames: self.sound_timer }
+    }
+
+    /// Update the delay and sound timer. Frontends sound the beeper from beeper()
+    pub fn update_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+    }
+
+    /// Dispatcher for 0-prefixed opcodes (e.g. 0XXX)
+    fn dispatch_0xxx(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        match opcode {
+            0x00E0 => self.op_00e0(),
+            0x00EE => self.op_00ee(),
+            0x00FB => self.op_00fb(),
+            0x00FC => self.op_00fc(),
+            0x00FD => self.op_00fd(),
+            0x00FE => self.op_00fe(),
+            0x00FF => self.op_00ff(),
+            _ if opcode & 0xFFF0 == 0x00C0 => self.op_00cn(opcode),
+            _ => Err(EmulatorError::UnknownOpcode { opcode, pc: self.pc })
+        }
+    }
+
+    /// Dispatcher for 8-prefixed opcodes (e.g. 8XXX)
+    fn dispatch_8xxx(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        match opcode & 0xF00F {
+            0x8000 => self.op_8xy0(opcode),
+            0x8001 => self.op_8xy1(opcode),
+            0x8002 => self.op_8xy2(opcode),
+            0x8003 => self.op_8xy3(opcode),
+            0x8004 => self.op_8xy4(opcode),
+            0x8005 => self.op_8xy5(opcode),
+            0x8006 => self.op_8xy6(opcode),
+            0x8007 => self.op_8xy7(opcode),
+            0x800E => self.op_8xye(opcode),
+            _ => Err(EmulatorError::UnknownOpcode { opcode, pc: self.pc })
+        }
+    }
+
+    /// Dispatcher for E-prefixed opcodes (e.g. EXXX)
+    fn dispatch_exxx(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        match opcode & 0xF0FF{
+            0xE09E => self.op_ex9e(opcode),
+            0xE0A1 => self.op_exa1(opcode),
+            _ => Err(EmulatorError::UnknownOpcode { opcode, pc: self.pc })
+        }
+    }
+
+    /// Dispatcher for F-prefixed opcodes (e.g. FXXX)
+    fn dispatch_fxxx(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        match opcode & 0xF0FF{
+            0xF007 => self.op_fx07(opcode),
+            0xF00A => self.op_fx0a(opcode),
+            0xF015 => self.op_fx15(opcode),
+            0xF018 => self.op_fx18(opcode),
+            0xF01E => self.op_fx1e(opcode),
+            0xF029 => self.op_fx29(opcode),
+            0xF030 => self.op_fx30(opcode),
+            0xF033 => self.op_fx33(opcode),
+            0xF055 => self.op_fx55(opcode),
+            0xF065 => self.op_fx65(opcode),
+            0xF075 => self.op_fx75(opcode),
+            0xF085 => self.op_fx85(opcode),
+            _ => Err(EmulatorError::UnknownOpcode { opcode, pc: self.pc })
+        }
+    }
+
+    /// 00E0: Clears the screen
+    fn op_00e0(&mut self) -> Result<(), EmulatorError> {
+        self.clear_screen();
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 00CN: Scrolls the screen down N rows (SUPER-CHIP)
+    fn op_00cn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        self.scroll(0, CPU::get_n(opcode) as isize);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 00FB: Scrolls the screen right 4 pixels (SUPER-CHIP)
+    fn op_00fb(&mut self) -> Result<(), EmulatorError> {
+        self.scroll(4, 0);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 00FC: Scrolls the screen left 4 pixels (SUPER-CHIP)
+    fn op_00fc(&mut self) -> Result<(), EmulatorError> {
+        self.scroll(-4, 0);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 00FD: Exits the interpreter (SUPER-CHIP). The PC stays on it, so the program sits finished
+    /// with its last picture showing
+    fn op_00fd(&mut self) -> Result<(), EmulatorError> {
+        Ok(())
+    }
+
+    /// 00FE: Leaves the 128x64 mode (SUPER-CHIP), clearing the screen
+    fn op_00fe(&mut self) -> Result<(), EmulatorError> {
+        self.hires = false;
+        self.clear_screen();
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 00FF: Switches to the 128x64 mode (SUPER-CHIP), clearing the screen
+    fn op_00ff(&mut self) -> Result<(), EmulatorError> {
+        self.hires = true;
+        self.clear_screen();
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 00EE: Returns from a subroutine
+    fn op_00ee(&mut self) -> Result<(), EmulatorError> {
+        let Some(return_addr) = self.stack.pop() else {
+            return Err(EmulatorError::StackUnderflow { pc: self.pc });
+        };
+
+        self.pc = return_addr;
+        Ok(())
+    }
+
+    /// 1NNN: Jumps to address NNN
+    fn op_1nnn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let nnn = CPU::get_nnn(opcode);
+        self.pc = nnn;
+        Ok(())
+    }
+
+    /// 2NNN: Calls subroutine at NNN
+    fn op_2nnn(&mut self, opcode: u16) -> Result<(), EmulatorError> {        
+        if self.stack.len() >= self.stack_depth {
+            return Err(EmulatorError::StackOverflow { pc: self.pc });
+        }
+
+        let nnn = CPU::get_nnn(opcode);
+        self.stack.push(self.pc + 2); // Save address of the next instruction
+        self.pc = nnn;
+        Ok(())
+    }
+
+    /// 3XNN: Skips the next instruction if VX equals NN
+    /// Usually the next instruction is a jump to skip a code block
+    fn op_3xnn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let nn = CPU::get_nn(opcode);
+        let vx = self.v[x];
+
+        if vx == nn {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+
+        Ok(())
+    }
+
+    /// 4XNN: Skips the next instruction if VX does not equal NN
+    /// Usually the next instruction is a jump to skip a code block
+    fn op_4xnn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let nn = CPU::get_nn(opcode);
+        let vx = self.v[x];
+
+        if vx != nn {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+
+        Ok(())
+    }
+
+    /// 5XY0: Skips the next instruction if VX equals VY
+    /// Usually the next instruction is a jump to skip a code block
+    fn op_5xy0(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+        let vx = self.v[x];
+        let vy = self.v[y];
+
+        if vx == vy {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+
+        Ok(())
+    }
+
+    /// 6XNN: Sets VX to NN
+    fn op_6xnn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let nn = CPU::get_nn(opcode);
+        self.v[x] = nn;
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 7XNN: Adds NN to VX (carry flag is not changed)
+    fn op_7xnn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let nn = CPU::get_nn(opcode);
+        self.v[x] = self.v[x].wrapping_add(nn);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XY0: Sets VX to the value of VY
+    fn op_8xy0(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+
+        let vy = self.v[y];
+        self.v[x] = vy;
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XY1: Sets VX to VX or VY (bitwise OR operation). VF is cleared with the vf_reset_on_logic quirk
+    fn op_8xy1(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+
+        let vx = self.v[x];
+        let vy = self.v[y];
+        self.v[x] = vx | vy;
+        self.reset_vf_on_logic();
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XY2: Sets VX to VX and VY (bitwise AND operation). VF is cleared with the vf_reset_on_logic quirk
+    fn op_8xy2(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+
+        let vx = self.v[x];
+        let vy = self.v[y];
+        self.v[x] = vx & vy;
+        self.reset_vf_on_logic();
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XY3: Sets VX to VX xor VY. VF is cleared with the vf_reset_on_logic quirk
+    fn op_8xy3(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+
+        let vx = self.v[x];
+        let vy = self.v[y];
+        self.v[x] = vx ^ vy;
+        self.reset_vf_on_logic();
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XY4: Adds VY to VX. VF is set to 1 when there's an overflow, and to 0 when there is not
+    fn op_8xy4(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+
+        let vx = self.v[x];
+        let vy = self.v[y];
+        
+        let (sum, did_overflow) = vx.overflowing_add(vy);
+        self.v[x] = sum;
+        self.v[0xF] = if did_overflow { 1 } else { 0 };
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XY5: VY is subtracted from VX. VF is set to 0 when there's an underflow, and 1 when there is not
+    /// (i.e. VF set to 1 if VX >= VY and 0 if not)
+    fn op_8xy5(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+
+        let vx = self.v[x];
+        let vy = self.v[y];
+        
+        let (result, did_underflow) = vx.overflowing_sub(vy);
+        self.v[x] = result;
+        self.v[0xF] = if did_underflow { 0 } else { 1 };
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XY6: Shifts VX to the right by 1, then stores the least significant bit of VX prior to the shift into VF.
+    /// With the shift_uses_vy quirk VY is shifted into VX instead
+    fn op_8xy6(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+
+        let vx = self.shift_source(opcode);
+        let vx_lsb = vx & 0x01;
+
+        self.v[x] = vx >> 1;
+        self.v[0xF] = vx_lsb;
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XY7: Sets VX to VY minus VX. VF is set to 0 when there's an underflow, and 1 when there is not
+    /// (i.e. VF set to 1 if VY >= VX)
+    fn op_8xy7(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+
+        let vx = self.v[x];
+        let vy = self.v[y];
+        
+        let (result, did_underflow) = vy.overflowing_sub(vx);
+        self.v[x] = result;
+        self.v[0xF] = if did_underflow { 0 } else { 1 };
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 8XYE: Shifts VX to the left by 1, then sets VF to 1 if the most significant bit 
+    /// of VX prior to that shift was set, or to 0 if it was unset. With the shift_uses_vy quirk
+    /// VY is shifted into VX instead
+    fn op_8xye(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+
+        let vx = self.shift_source(opcode);
+        let vx_msb = (vx >> 7) & 0x01;
+
+        self.v[x] = vx << 1;
+        self.v[0xF] = vx_msb;
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// 9XY0: Skips the next instruction if VX does not equal VY
+    /// Usually the next instruction is a jump to skip a code block
+    fn op_9xy0(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+        let vx = self.v[x];
+        let vy = self.v[y];
+
+        if vx != vy {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+
+        Ok(())
+    }
+
+    /// ANNN: Sets I to the address NNN
+    fn op_annn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let nnn = CPU::get_nnn(opcode);
+        self.i = nnn;
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// BNNN: Jumps to the address NNN plus V0. With the jump_with_vx quirk it is BXNN, adding VX instead
+    fn op_bnnn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let nnn = CPU::get_nnn(opcode);
+        let register = if self.quirks.jump_with_vx { CPU::get_x(opcode) } else { 0x0 };
+        let target = nnn + self.v[register] as u16;
+        self.check_bounds(target, 2)?; // Caught here, rather than at the fetch, so the error points at the jump
+        self.pc = target;
+        Ok(())
+    }
+
+    /// CXNN: Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN
+    fn op_cxnn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let nn = CPU::get_nn(opcode);
+        self.v[x] = nn & self.rng.byte();
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// DXYN: Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N pixels
+    /// Each row of 8 pixels is read as bit-coded starting from memory location I
+    /// I value does not change after the execution of this instruction
+    /// VF is set to 1 if any screen pixels are flipped from set to unset when the sprite is drawn, and to 0 if that does not happen.
+    /// In hi-res mode VF counts, as on SUPER-CHIP: the sprite rows that collided plus those clipped off the bottom
+    /// Sprites wrap around the screen edges with the wrap_sprites quirk; otherwise only their start wraps and the rest is clipped
+    /// DXY0 draws SUPER-CHIP's 16x16 sprite instead, two bytes per row
+    fn op_dxyn(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+        let n = CPU::get_n(opcode) as usize;
+
+        let (width, height) = self.screen_size();
+        let (sprite_width, sprite_height) = if n == 0 { (16, 16) } else { (8, n) };
+        let bytes_per_row = sprite_width / 8;
+
+        let wrap = self.quirks.wrap_sprites;
+        let row_offset = self.v[y] as usize % height;
+        let col_offset = self.v[x] as usize % width;
+        let rows = if wrap { sprite_height } else { sprite_height.min(height - row_offset) };
+        self.check_bounds(self.i, rows * bytes_per_row)?;
+
+        let mut collided_rows = 0;
+        for row in 0..rows {
+            let mut collided = false;
+            let mut sprite_row = 0u16;
+            for byte in 0..bytes_per_row {
+                sprite_row = (sprite_row << 8) | self.mem_read(self.i + (row * bytes_per_row + byte) as u16) as u16;
+            }
+            let display_y = (row + row_offset) % height;
+            self.dirty_rows |= 1 << display_y;
+
+            let screen = if self.hires { &mut self.hires_display[..] } else { &mut self.display[..] };
+            for col in 0..sprite_width {
+                if !wrap && col + col_offset >= width {
+                    break;
+                }
+                let display_x = (col + col_offset) % width;
+
+                let current_pixel = screen[display_y * width + display_x];
+                let pixel = ((sprite_row >> (sprite_width - 1 - col)) & 0x1) as u8;
+                screen[display_y * width + display_x] = current_pixel ^ pixel;
+
+                if current_pixel == 1 && pixel == 1 {
+                    collided = true;
+                }
+            }
+            collided_rows += usize::from(collided);
+        }
+        self.v[0xF] = if self.hires {
+            (collided_rows + sprite_height - rows) as u8
+        } else {
+            u8::from(collided_rows > 0)
+        };
+
+        self.screen_changed();
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// EX9E: Skips the next instruction if the key stored in VX (only consider the lowest nibble) is pressed
+    /// Usually the next instruction is a jump to skip a code block
+    fn op_ex9e(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let key = (self.v[x] & 0x0F) as usize;
+        if self.input[key] {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    /// EXA1: Skips the next instruction if the key stored in VX (only consider the lowest nibble) is not pressed
+    /// Usually the next instruction is a jump to skip a code block
+    fn op_exa1(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let key = (self.v[x] & 0x0F) as usize;
+        if !self.input[key] {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    /// FX07: Sets VX to the value of the delay timer
+    fn op_fx07(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        self.v[x] = self.delay_timer;
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX0A: A key press is awaited, and then stored in VX
+    /// Blocking operation, all instruction halted until next key event, delay and sound timers should continue processing.
+    /// As on the COSMAC VIP the key counts once it is released, so one already held when the wait
+    /// starts doesn't end it until it comes back up
+    fn op_fx0a(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        if self.key_wait.is_none() {
+            // A tap that was pressed and released between instructions still counts
+            self.key_wait = (0..INPUTS_COUNT).find(|key| self.input[*key] || self.pressed_edges & (1 << key) != 0);
+        }
+        if let Some(key) = self.key_wait
+            && (!self.input[key] || self.released_edges & (1 << key) != 0)
+        {
+            self.v[x] = key as u8;
+            self.key_wait = None;
+            self.awaited_key = Some(key);
+            self.pc += 2;
+        }
+        // Otherwise the PC is not updated and the instruction is repeated
+        Ok(())
+    }
+
+    /// FX15: Sets the delay timer to VX
+    fn op_fx15(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        self.delay_timer = self.v[x];
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX18: Sets the sound timer to VX
+    fn op_fx18(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        self.sound_timer = self.v[x];
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX1E: Adds VX to I. VF is not affected
+    fn op_fx1e(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        // I may end up past the end of memory; the instructions that use it check
+        self.i = self.i.wrapping_add(self.v[x] as u16);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX29: Sets I to the location of the sprite for the character in VX (only consider the lowest nibble).
+    /// Characters 0-F (in hexadecimal) are represented by a 4x5 font
+    fn op_fx29(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let character = self.v[x] as usize;
+
+        if character > 0x0F {
+            return Err(EmulatorError::InvalidDigit { digit: self.v[x], pc: self.pc });
+        }
+
+        self.i = (FONTSET_START_ADDRESS + (character * BYTES_PER_FONT)) as u16;
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX30: Sets I to the location of SUPER-CHIP's 8x10 sprite for the digit in VX
+    fn op_fx30(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let digit = self.v[x] as usize;
+
+        if digit > 0x0F {
+            return Err(EmulatorError::InvalidDigit { digit: self.v[x], pc: self.pc });
+        }
+
+        self.i = (BIG_FONTSET_START_ADDRESS + (digit * BYTES_PER_BIG_FONT)) as u16;
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX33: Stores the binary-coded decimal representation of VX, with the hundreds digit in memory
+    /// at location in I, the tens digit at location I+1, and the ones digit at location I+2.
+    fn op_fx33(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        let value = self.v[x];
+
+        let hundreds = value / 100;
+        let tens = (value / 10) % 10;
+        let ones = value % 10;
+
+        self.check_bounds(self.i, 3)?;
+        self.mem_write(self.i, hundreds);
+        self.mem_write(self.i + 1, tens);
+        self.mem_write(self.i + 2, ones);
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX55: Stores from V0 to VX (including VX) in memory, starting at address I
+    /// The offset from I is increased by 1 for each value written, but I itself is left unmodified
+    /// unless the load_store_increments_i quirk is set
+    fn op_fx55(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        self.check_bounds(self.i, x + 1)?;
+
+        for i in 0..=x {
+            self.mem_write(self.i + i as u16, self.v[i]);
+        }
+        self.step_i_past(x);
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX65: Fills from V0 to VX (including VX) with values from memory, starting at address I.
+    /// The offset from I is increased by 1 for each value read, but I itself is left unmodified
+    /// unless the load_store_increments_i quirk is set.
+    fn op_fx65(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        self.check_bounds(self.i, x + 1)?;
+
+        for i in 0..=x {
+            self.v[i] = self.mem_read(self.i + i as u16);
+        }
+        self.step_i_past(x);
+
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX75: Stores V0 to VX (including VX) in the flag registers (SUPER-CHIP)
+    fn op_fx75(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// FX85: Fills V0 to VX (including VX) from the flag registers (SUPER-CHIP)
+    fn op_fx85(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let x = CPU::get_x(opcode);
+        self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+        self.pc += 2;
+        Ok(())
+    }
+
+    /// Dirty-row mask with every row of screen() set
+    fn all_rows(&self) -> u64 {
+        if self.hires { ALL_HIRES_ROWS } else { ALL_ROWS }
+    }
+
+    /// Blanks both resolutions' pictures, as 00E0 and the mode switches do
+    fn clear_screen(&mut self) {
+        self.display.fill(0);
+        self.hires_display.fill(0);
+        if self.mapped_display {
+            self.memory[VIP_DISPLAY_ADDRESS..].fill(0);
+        }
+        self.dirty_rows = self.all_rows();
+    }
+
+    /// Moves the picture dx pixels right and dy down at the current resolution, blanking the pixels
+    /// scrolled in. Nothing wraps: what goes past an edge is gone
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.screen_size();
+        let screen = if self.hires { &mut self.hires_display[..] } else { &mut self.display[..] };
+        let before = screen.to_vec();
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x as isize - dx, y as isize - dy);
+                let inside = (0..width as isize).contains(&from_x) && (0..height as isize).contains(&from_y);
+                screen[y * width + x] = if inside { before[from_y as usize * width + from_x as usize] } else { 0 };
+            }
+        }
+        self.dirty_rows = self.all_rows();
+        self.screen_changed();
+    }
+
+    /// Brings display up to date after screen() changed: in hires mode each lo-res pixel is lit if
+    /// any of the four it covers is, so frontends that only show 64x32 still follow the picture
+    fn screen_changed(&mut self) {
+        if self.hires {
+            for y in 0..DISPLAY_HEIGHT {
+                for x in 0..DISPLAY_WIDTH {
+                    let top = 2 * y * HIRES_WIDTH + 2 * x;
+                    let bottom = top + HIRES_WIDTH;
+                    self.display[y * DISPLAY_WIDTH + x] = self.hires_display[top] | self.hires_display[top + 1]
+                        | self.hires_display[bottom] | self.hires_display[bottom + 1];
+                }
+            }
+        }
+        if self.mapped_display {
+            self.encode_mapped_display();
+        }
+    }
+
+    /// Errors with the first address past the end of memory unless the len bytes from addr are
+    /// all inside it, so an instruction fails before it changes anything
+    fn check_bounds(&self, addr: u16, len: usize) -> Result<(), EmulatorError> {
+        if len > 0 && addr as usize + len > MEMORY_SIZE {
+            let addr = (addr as usize).max(MEMORY_SIZE) as u16;
+            return Err(EmulatorError::MemoryOutOfBounds { addr, pc: self.pc });
+        }
+        Ok(())
+    }
+
+    /// The register 8XY6 and 8XYE shift: VY with the shift_uses_vy quirk, otherwise VX
+    fn shift_source(&self, opcode: u16) -> u8 {
+        if self.quirks.shift_uses_vy { self.v[CPU::get_y(opcode)] } else { self.v[CPU::get_x(opcode)] }
+    }
+
+    fn reset_vf_on_logic(&mut self) {
+        if self.quirks.vf_reset_on_logic {
+            self.v[0xF] = 0;
+        }
+    }
+
+    /// With the load_store_increments_i quirk, moves I past the registers FX55/FX65 just used
+    fn step_i_past(&mut self, x: usize) {
+        if self.quirks.load_store_increments_i {
+            self.i += x as u16 + 1;
+        }
+    }
+
+    /// Helper function to extract x from the opcode
+    pub(crate) fn get_x(opcode: u16) -> usize {
+        ((opcode & 0x0F00) >> 8) as usize
+    }
+
+    /// Helper function to extract y from the opcode
+    pub(crate) fn get_y(opcode: u16) -> usize {
+        ((opcode & 0x00F0) >> 4) as usize
+    }
+
+    /// Helper function to extract n from the opcode
+    pub(crate) fn get_n(opcode: u16) -> u8 {
+        (opcode & 0x000F) as u8
+    }
+
+    /// Helper function to extract nn from the opcode
+    pub(crate) fn get_nn(opcode: u16) -> u8 {
+        (opcode & 0x00FF) as u8
+    }
+
+    /// Helper function to extract nnn from the opcode
+    pub(crate) fn get_nnn(opcode: u16) -> u16 {
+        opcode & 0x0FFF
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Pattern;
+    use crate::savestate::rom_hash;
+    use crate::rom_builder::RomBuilder;
+    use crate::run::{StopCondition, StopReason};
+
+    #[test]
+    fn test_update_timers() {
+        let mut cpu = CPU::new();
+        cpu.delay_timer = 3;
+        cpu.sound_timer = 2;
+
+        cpu.update_timers();
+        assert_eq!(cpu.delay_timer, 2);
+        assert_eq!(cpu.sound_timer, 1);
+
+        cpu.update_timers();
+        assert_eq!(cpu.delay_timer, 1);
+        assert_eq!(cpu.sound_timer, 0);
+
+        cpu.update_timers();
+        assert_eq!(cpu.delay_timer, 0);
+        assert_eq!(cpu.sound_timer, 0);
+
+        // Should stay at 0
+        cpu.update_timers();
+        assert_eq!(cpu.delay_timer, 0);
+        assert_eq!(cpu.sound_timer, 0);
+    }
+
+    #[test]
+    fn test_beeper_follows_the_sound_timer() {
+        let mut cpu = CPU::new();
+        assert_eq!(cpu.beeper(), BeeperState { on: false, remaining_frames: 0 });
+        cpu.sound_timer = 2;
+        assert_eq!(cpu.beeper(), BeeperState { on: true, remaining_frames: 2 });
+        cpu.update_timers();
+        cpu.update_timers();
+        assert!(!cpu.beeper().on);
+    }
+
+    /// Draws an n-row sprite at (vx, vy) from a block of 0xFF bytes and returns the dirty mask
+    fn draw_rows(cpu: &mut CPU, vx: u8, vy: u8, n: u16) -> u64 {
+        cpu.memory[0x300..0x310].fill(0xFF);
+        cpu.i = 0x300;
+        cpu.v[0] = vx;
+        cpu.v[1] = vy;
+        cpu.decode_and_execute(0xD010 | n).unwrap();
+        cpu.take_dirty_rows()
+    }
+
+    #[test]
+    fn test_everything_dirty_initially() {
+        let mut cpu = CPU::new();
+        assert_eq!(cpu.take_dirty_rows(), ALL_ROWS);
+        assert_eq!(cpu.take_dirty_rows(), 0);
+    }
+
+    #[test]
+    fn test_dxyn_marks_sprite_rows() {
+        let mut cpu = CPU::new();
+        cpu.take_dirty_rows();
+
+        assert_eq!(draw_rows(&mut cpu, 0, 0, 1), 0b1);
+        assert_eq!(draw_rows(&mut cpu, 10, 5, 3), 0b111 << 5);
+        assert_eq!(draw_rows(&mut cpu, 60, 16, 15), 0x7FFF << 16);
+        assert_eq!(draw_rows(&mut cpu, 0, 31, 1), 1 << 31);
+    }
+
+    #[test]
+    fn test_dxyn_dirty_rows_wrap_across_bottom() {
+        let mut cpu = CPU::new();
+        cpu.take_dirty_rows();
+
+        // Rows 30, 31, then 0, 1, 2
+        assert_eq!(draw_rows(&mut cpu, 0, 30, 5), (0b11 << 30) | 0b111);
+        // Y coordinates past the bottom wrap too
+        assert_eq!(draw_rows(&mut cpu, 0, 33, 2), 0b110);
+    }
+
+    #[test]
+    fn test_dirty_rows_accumulate_until_taken() {
+        let mut cpu = CPU::new();
+        cpu.take_dirty_rows();
+
+        cpu.memory[0x300] = 0x80;
+        cpu.i = 0x300;
+        cpu.v[1] = 4;
+        cpu.decode_and_execute(0xD011).unwrap();
+        cpu.v[1] = 20;
+        cpu.decode_and_execute(0xD011).unwrap();
+        assert_eq!(cpu.take_dirty_rows(), (1 << 4) | (1 << 20));
+    }
+
+    #[test]
+    fn test_clear_marks_all_rows_dirty() {
+        let mut cpu = CPU::new();
+        cpu.take_dirty_rows();
+
+        cpu.decode_and_execute(0x00E0).unwrap();
+        assert_eq!(cpu.take_dirty_rows(), ALL_ROWS);
+    }
+
+    #[test]
+    fn test_only_drawing_opcodes_dirty_the_display() {
+        let mut cpu = CPU::new();
+        assert_ne!(cpu.take_dirty_rows(), 0);
+        assert_eq!(cpu.take_dirty_rows(), 0);
+
+        // Loads, arithmetic, timers, memory and jumps leave the picture alone
+        for opcode in [0x6105, 0x7101, 0x8124, 0xA300, 0xF115, 0xF133, 0xF155, 0xF165, 0x1200] {
+            cpu.decode_and_execute(opcode).unwrap();
+            assert_eq!(cpu.take_dirty_rows(), 0, "{:04X}", opcode);
+        }
+
+        for opcode in [0x00E0, 0xD011, 0x00C1, 0x00FB, 0x00FC, 0x00FF, 0x00FE] {
+            cpu.decode_and_execute(opcode).unwrap();
+            assert_ne!(cpu.take_dirty_rows(), 0, "{:04X}", opcode);
+        }
+    }
+
+    /// A CPU with some of every kind of state set
+    fn busy_cpu() -> CPU {
+        let mut cpu = CPU::new();
+        cpu.rom_hash = rom_hash(b"game");
+        cpu.v[3] = 0x33;
+        cpu.v[0xF] = 1;
+        cpu.i = 0x345;
+        cpu.pc = 0x2A4;
+        cpu.stack = vec![0x202, 0x310];
+        cpu.delay_timer = 12;
+        cpu.sound_timer = 4;
+        cpu.display[70] = 1;
+        cpu.display[DISPLAY_WIDTH * DISPLAY_HEIGHT - 1] = 1;
+        cpu.input[0xA] = true;
+        cpu.memory[0x200] = 0x12;
+        cpu.memory[MEMORY_SIZE - 1] = 0xEE;
+        cpu
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        let cpu = busy_cpu();
+        let state = cpu.save_state();
+
+        let mut restored = CPU::new();
+        restored.rom_hash = rom_hash(b"game");
+        assert_eq!(restored.load_state(&state), Ok(vec![]));
+
+        assert_eq!(restored.v, cpu.v);
+        assert_eq!(restored.i, cpu.i);
+        assert_eq!(restored.pc, cpu.pc);
+        assert_eq!(restored.stack, cpu.stack);
+        assert_eq!(restored.delay_timer, cpu.delay_timer);
+        assert_eq!(restored.sound_timer, cpu.sound_timer);
+        assert_eq!(restored.display, cpu.display);
+        assert_eq!(restored.input, cpu.input);
+        assert_eq!(restored.memory, cpu.memory);
+        assert_eq!(restored.take_dirty_rows(), ALL_ROWS);
+        assert_eq!(restored.save_state(), state);
+    }
+
+    #[test]
+    fn test_load_state_for_other_rom_leaves_cpu_untouched() {
+        let state = busy_cpu().save_state();
+        let mut cpu = CPU::new();
+        cpu.rom_hash = rom_hash(b"other");
+        cpu.v[0] = 9;
+
+        assert!(matches!(cpu.load_state(&state), Err(StateError::RomMismatch { .. })));
+        assert_eq!(cpu.v[0], 9);
+        assert_eq!(cpu.pc, STARTING_MEMORY_ADDRESS as u16);
+    }
+
+    #[test]
+    fn test_load_truncated_state_errors() {
+        let state = busy_cpu().save_state();
+        let mut cpu = CPU::new();
+        cpu.rom_hash = rom_hash(b"game");
+
+        for len in [0, 10, state.len() / 2, state.len() - 1] {
+            assert!(cpu.load_state(&state[..len]).is_err(), "length {}", len);
+        }
+        assert_eq!(cpu.pc, STARTING_MEMORY_ADDRESS as u16);
+    }
+
+    #[test]
+    fn test_load_state_checks_stack_depth() {
+        let mut deep = CPU::with_stack_depth(32);
+        deep.rom_hash = rom_hash(b"game");
+        deep.stack = vec![0x200; 20];
+        let state = deep.save_state();
+
+        assert_eq!(busy_cpu().load_state(&state), Err(StateError::StackTooDeep { frames: 20, limit: STACK_SIZE }));
+
+        deep.stack.truncate(3);
+        let mut cpu = busy_cpu();
+        assert_eq!(cpu.load_state(&deep.save_state()), Ok(vec![StateWarning::StackDepth { saved: 32, current: STACK_SIZE }]));
+        assert_eq!(cpu.stack, vec![0x200; 3]);
+        assert_eq!(cpu.stack_depth(), STACK_SIZE);
+    }
+
+    #[test]
+    fn test_key_edges_last_until_next_instruction() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x200..0x202].copy_from_slice(&[0x12, 0x00]);
+
+        cpu.press_key(0x4);
+        cpu.press_key(0x4);
+        assert!(cpu.input[0x4]);
+        assert_eq!((cpu.pressed_edges, cpu.released_edges), (1 << 0x4, 0));
+
+        cpu.tick().unwrap();
+        assert_eq!((cpu.pressed_edges, cpu.released_edges), (0, 0));
+        assert!(cpu.input[0x4]);
+
+        cpu.release_key(0x4);
+        cpu.release_key(0x5);
+        assert!(!cpu.input[0x4]);
+        assert_eq!((cpu.pressed_edges, cpu.released_edges), (0, 1 << 0x4));
+
+        cpu.tick().unwrap();
+        assert_eq!(cpu.released_edges, 0);
+    }
+
+    #[test]
+    fn test_out_of_range_keys_ignored() {
+        let mut cpu = CPU::new();
+        cpu.press_key(0x10);
+        cpu.press_key(0xFF);
+        cpu.release_key(0x10);
+        assert_eq!(cpu.input, [false; INPUTS_COUNT]);
+        assert_eq!((cpu.pressed_edges, cpu.released_edges), (0, 0));
+    }
+
+    #[test]
+    fn test_tap_between_instructions_satisfies_key_wait() {
+        let mut cpu = CPU::new();
+        // 0x200: LD V2, K
+        cpu.memory[0x200..0x202].copy_from_slice(&[0xF2, 0x0A]);
+
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.press_key(0xB);
+        cpu.release_key(0xB);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.v[2], 0xB);
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.take_awaited_key(), Some(0xB));
+    }
+
+    #[test]
+    fn test_held_key_blocks_until_released() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x200..0x202].copy_from_slice(&[0xF2, 0x0A]);
+
+        cpu.press_key(0x7);
+        for _ in 0..5 {
+            cpu.tick().unwrap();
+            assert_eq!(cpu.pc, 0x200);
+        }
+        assert_eq!(cpu.key_wait(), Some(0x7));
+        assert_eq!(cpu.take_awaited_key(), None);
+
+        // Another key going down meanwhile doesn't take over
+        cpu.press_key(0x3);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.release_key(0x7);
+        cpu.tick().unwrap();
+        assert_eq!((cpu.v[2], cpu.pc), (0x7, 0x202));
+        assert_eq!(cpu.key_wait(), None);
+        assert_eq!(cpu.take_awaited_key(), Some(0x7));
+    }
+
+    #[test]
+    fn test_timers_run_while_waiting_for_a_key() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x200..0x202].copy_from_slice(&[0xF2, 0x0A]);
+        cpu.delay_timer = 10;
+        cpu.sound_timer = 3;
+        cpu.press_key(0x1);
+        for _ in 0..4 {
+            cpu.tick().unwrap();
+            cpu.update_timers();
+        }
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!((cpu.delay_timer, cpu.sound_timer), (6, 0));
+    }
+
+    #[test]
+    fn test_key_wait_ignores_released_key_after_its_tick() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x200..0x202].copy_from_slice(&[0xF2, 0x0A]);
+        cpu.memory[0x202..0x204].copy_from_slice(&[0x12, 0x00]);
+
+        // The tap is seen by the jump, not the key wait
+        cpu.pc = 0x202;
+        cpu.press_key(0x1);
+        cpu.release_key(0x1);
+        cpu.tick().unwrap();
+
+        cpu.pc = 0x200;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x200);
+    }
+
+    #[test]
+    fn test_set_keys_matches_individual_calls() {
+        let mut by_mask = CPU::new();
+        let mut by_key = CPU::new();
+
+        by_mask.set_keys(0b1000_0000_0010_0001);
+        by_key.press_key(0x0);
+        by_key.press_key(0x5);
+        by_key.press_key(0xF);
+        assert_eq!(by_mask.input, by_key.input);
+        assert_eq!(by_mask.pressed_edges, by_key.pressed_edges);
+
+        by_mask.pressed_edges = 0;
+        by_key.pressed_edges = 0;
+        by_mask.set_keys(0b0000_0000_0010_0010);
+        by_key.release_key(0x0);
+        by_key.release_key(0xF);
+        by_key.press_key(0x1);
+        assert_eq!(by_mask.input, by_key.input);
+        assert_eq!(
+            (by_mask.pressed_edges, by_mask.released_edges),
+            (by_key.pressed_edges, by_key.released_edges)
+        );
+    }
+
+    /// Reads back an incrementing counter, like a frame counter peripheral
+    struct Counter(u8);
+
+    impl MemoryHook for Counter {
+        fn read(&mut self, _addr: u16) -> Option<u8> {
+            self.0 += 1;
+            Some(self.0)
+        }
+    }
+
+    /// Vetoes every write, like a ROM chip
+    struct ReadOnly;
+
+    impl MemoryHook for ReadOnly {
+        fn write(&mut self, _addr: u16, _value: u8) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_read_hook_seen_by_fx65() {
+        let mut cpu = CPU::new();
+        // 0x200: LD I, 0xFFE; 0x202: LD V1, [I]
+        cpu.memory[0x200..0x204].copy_from_slice(&[0xAF, 0xFE, 0xF1, 0x65]);
+        cpu.set_memory_hook(0xFFE..0xFFF, Box::new(Counter(0)));
+
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        // Only 0xFFE is hooked; 0xFFF reads plain memory
+        assert_eq!(cpu.v[0], 1);
+        assert_eq!(cpu.v[1], 0);
+
+        cpu.pc = 0x202;
+        cpu.tick().unwrap();
+        assert_eq!(cpu.v[0], 2);
+    }
+
+    #[test]
+    fn test_write_hook_vetoes_fx55_and_fx33() {
+        let mut cpu = CPU::new();
+        cpu.set_memory_hook(0x400..0x402, Box::new(ReadOnly));
+        cpu.v[0] = 0xAA;
+        cpu.v[1] = 0xBB;
+        cpu.v[2] = 0xCC;
+        cpu.i = 0x400;
+
+        cpu.decode_and_execute(0xF255).unwrap();
+        assert_eq!(&cpu.memory[0x400..0x403], &[0, 0, 0xCC]);
+
+        cpu.v[3] = 123;
+        cpu.i = 0x401;
+        cpu.decode_and_execute(0xF333).unwrap();
+        assert_eq!(&cpu.memory[0x401..0x404], &[0, 2, 3]);
+    }
+
+    #[test]
+    fn test_hooks_see_fetch_and_sprite_reads() {
+        let mut cpu = CPU::new();
+        // Every fetched byte reads back as 0x12 / 0x13..., so the opcode becomes a jump
+        cpu.set_memory_hook(0x200..0x202, Box::new(Counter(0x11)));
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x213);
+
+        cpu.set_memory_hook(0x300..0x301, Box::new(Counter(0x7F)));
+        cpu.i = 0x300;
+        cpu.decode_and_execute(0xD001).unwrap();
+        assert_eq!(&cpu.display[..8], &[1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_hooks_survive_load_state() {
+        let mut cpu = CPU::new();
+        cpu.set_memory_hook(0xFFE..0xFFF, Box::new(Counter(0)));
+        let state = cpu.save_state();
+
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.mem_read(0xFFE), 1);
+    }
+
+    /// Runs a ROM that recurses into 0x200 forever, returning how many calls succeeded before the error
+    fn calls_before_overflow(cpu: &mut CPU) -> usize {
+        RomBuilder::new().label("recurse").call("recurse").load(cpu).unwrap();
+        let outcome = cpu.run_until(StopCondition::InstructionCount(MAX_STACK_DEPTH as u64 + 1), u64::MAX);
+        assert_eq!(outcome.reason, StopReason::Error(EmulatorError::StackOverflow { pc: 0x200 }));
+        outcome.instructions as usize
+    }
+
+    #[test]
+    fn test_nesting_to_the_limit() {
+        let mut cpu = CPU::new();
+        assert_eq!(calls_before_overflow(&mut cpu), STACK_SIZE);
+        assert_eq!(cpu.stack.len(), STACK_SIZE);
+
+        // The failed call leaves the stack as it was
+        assert_eq!(cpu.tick().unwrap_err(), EmulatorError::StackOverflow { pc: 0x200 });
+        assert_eq!(cpu.stack.len(), STACK_SIZE);
+    }
+
+    #[test]
+    fn test_deeper_stack_configuration() {
+        let mut cpu = CPU::with_stack_depth(48);
+        assert_eq!(calls_before_overflow(&mut cpu), 48);
+
+        // Unwinds all the way back
+        for _ in 0..48 {
+            cpu.decode_and_execute(0x00EE).unwrap();
+        }
+        assert!(matches!(cpu.decode_and_execute(0x00EE), Err(EmulatorError::StackUnderflow { .. })));
+    }
+
+    #[test]
+    fn test_errors_name_the_failure() {
+        let mut cpu = CPU::new();
+        cpu.pc = 0x2A4;
+        for opcode in [0x0123, 0x8008, 0xE0FF, 0xF0FF] {
+            assert_eq!(cpu.decode_and_execute(opcode), Err(EmulatorError::UnknownOpcode { opcode, pc: 0x2A4 }));
+        }
+
+        cpu.v[3] = 0x10;
+        assert_eq!(cpu.decode_and_execute(0xF329), Err(EmulatorError::InvalidDigit { digit: 0x10, pc: 0x2A4 }));
+
+        cpu.pc = 0xFFF;
+        assert_eq!(cpu.fetch(), Err(EmulatorError::MemoryOutOfBounds { addr: 0x1000, pc: 0xFFF }));
+
+        let rom = vec![0; MEMORY_SIZE];
+        assert_eq!(cpu.load_rom_bytes(&rom), Err(EmulatorError::RomTooLarge { size: MEMORY_SIZE }));
+        assert!(matches!(cpu.load_rom("/no/such/rom.ch8"), Err(EmulatorError::Io(_))));
+    }
+
+    /// The error running opcode on a machine with I at i, and whether it left the machine as it was
+    fn past_the_end(opcode: u16, i: u16) -> (EmulatorError, bool) {
+        let mut cpu = CPU::new();
+        cpu.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+        cpu.i = i;
+        let before = (cpu.v, cpu.i, cpu.pc, cpu.memory, cpu.display);
+        let error = cpu.decode_and_execute(opcode).unwrap_err();
+        (error, before == (cpu.v, cpu.i, cpu.pc, cpu.memory, cpu.display))
+    }
+
+    #[test]
+    fn test_memory_bounds_at_the_end_of_memory() {
+        let out_of_bounds = EmulatorError::MemoryOutOfBounds { addr: 0x1000, pc: 0x200 };
+        // The fuzzed ROM that used to panic: FX55 with X=5 and I at 0xFFE
+        assert_eq!(past_the_end(0xF555, 0xFFE), (out_of_bounds.clone(), true));
+        assert_eq!(past_the_end(0xF565, 0xFFE), (out_of_bounds.clone(), true));
+        assert_eq!(past_the_end(0xF033, 0xFFE), (out_of_bounds.clone(), true));
+        assert_eq!(past_the_end(0xD013, 0xFFE), (out_of_bounds.clone(), true));
+        assert_eq!(past_the_end(0xF055, 0x1000), (out_of_bounds, true));
+        assert_eq!(past_the_end(0xF055, 0xFFFF).0, EmulatorError::MemoryOutOfBounds { addr: 0xFFFF, pc: 0x200 });
+
+        // Right up to the last byte is fine
+        let mut cpu = CPU::new();
+        cpu.v[..3].copy_from_slice(&[1, 2, 3]);
+        for (opcode, i) in [(0xF255, 0xFFD), (0xF265, 0xFFD), (0xF233, 0xFFD), (0xD002, 0xFFE)] {
+            cpu.i = i;
+            cpu.decode_and_execute(opcode).unwrap();
+        }
+        assert_eq!(cpu.memory[0xFFD..], [0, 0, 3]);
+    }
+
+    #[test]
+    fn test_clipped_sprite_rows_are_not_read() {
+        let mut cpu = CPU::with_quirks(Quirks::vip());
+        cpu.i = 0xFFE;
+        cpu.v[1] = 30;
+        cpu.decode_and_execute(0xD01F).unwrap(); // Only two of the fifteen rows are on screen
+        cpu.v[1] = 29;
+        assert!(cpu.decode_and_execute(0xD01F).is_err());
+    }
+
+    #[test]
+    fn test_fx1e_past_the_end_of_memory() {
+        let mut cpu = CPU::new();
+        cpu.i = 0xFFF0;
+        cpu.v[1] = 0xFF;
+        cpu.decode_and_execute(0xF11E).unwrap();
+        assert_eq!(cpu.i, 0x00EF); // Wraps like the 16-bit register it is, rather than panicking
+
+        cpu.i = 0xFF0;
+        cpu.decode_and_execute(0xF11E).unwrap();
+        assert_eq!(cpu.i, 0x10EF);
+        assert!(matches!(cpu.decode_and_execute(0xF065), Err(EmulatorError::MemoryOutOfBounds { addr: 0x10EF, .. })));
+    }
+
+    #[test]
+    fn test_bnnn_past_the_end_of_memory() {
+        let mut cpu = CPU::new();
+        cpu.v[0] = 0x10;
+        assert_eq!(cpu.decode_and_execute(0xBFF0), Err(EmulatorError::MemoryOutOfBounds { addr: 0x1000, pc: 0x200 }));
+        assert_eq!(cpu.pc, 0x200);
+        cpu.decode_and_execute(0xBFEE).unwrap();
+        assert_eq!(cpu.pc, 0xFFE);
+    }
+
+    #[test]
+    fn test_stack_depth_clamped() {
+        assert_eq!(CPU::with_stack_depth(0).stack_depth(), 1);
+        assert_eq!(CPU::with_stack_depth(1000).stack_depth(), MAX_STACK_DEPTH);
+    }
+
+    #[test]
+    fn test_mapped_display_store_shows_pixels() {
+        let mut cpu = CPU::new();
+        cpu.set_mapped_display(true);
+        cpu.take_dirty_rows();
+
+        // Row 1, pixels 8-15
+        cpu.v[0] = 0b1010_0101;
+        cpu.i = (VIP_DISPLAY_ADDRESS + 9) as u16;
+        cpu.decode_and_execute(0xF055).unwrap();
+
+        assert_eq!(Pattern::parse("#.#..#.#").unwrap().find(&cpu.display), Some((8, 1)));
+        assert_eq!(cpu.display.iter().filter(|pixel| **pixel != 0).count(), 4);
+        assert_eq!(cpu.take_dirty_rows(), 1 << 1);
+    }
+
+    #[test]
+    fn test_mapped_display_draws_into_memory() {
+        let mut cpu = CPU::new();
+        cpu.set_mapped_display(true);
+        cpu.memory[0x300] = 0xF0;
+        cpu.i = 0x300;
+        cpu.v[0] = 4;
+        cpu.v[1] = 2;
+
+        cpu.decode_and_execute(0xD011).unwrap();
+        assert_eq!(cpu.v[0xF], 0);
+        // Pixels 4-7 of row 2 are the low nibble of the first byte on that row
+        assert_eq!(cpu.memory[VIP_DISPLAY_ADDRESS + 16], 0x0F);
+
+        // Reading it back sees the picture, and drawing over it still collides
+        cpu.i = (VIP_DISPLAY_ADDRESS + 16) as u16;
+        cpu.decode_and_execute(0xF065).unwrap();
+        assert_eq!(cpu.v[0], 0x0F);
+        cpu.v[0] = 4;
+        cpu.i = 0x300;
+        cpu.decode_and_execute(0xD011).unwrap();
+        assert_eq!(cpu.v[0xF], 1);
+        assert_eq!(cpu.memory[VIP_DISPLAY_ADDRESS + 16], 0x00);
+
+        cpu.memory[VIP_DISPLAY_ADDRESS + 16] = 0xFF;
+        cpu.decode_and_execute(0x00E0).unwrap();
+        assert!(cpu.memory[VIP_DISPLAY_ADDRESS..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_separate_display_by_default() {
+        let mut cpu = CPU::new();
+        cpu.v[0] = 0xFF;
+        cpu.i = VIP_DISPLAY_ADDRESS as u16;
+        cpu.decode_and_execute(0xF055).unwrap();
+        assert!(cpu.display.iter().all(|pixel| *pixel == 0));
+    }
+
+    /// Scatters random digits over the screen, faster while key 1 is held, and returns the display's hash
+    fn random_run(cpu: &mut CPU) -> u64 {
+        let rom = crate::rom_builder::RomBuilder::new()
+            .ld(3, 1)
+            .label("loop")
+            .op(0xC03F) // RND V0, 0x3F
+            .op(0xC11F) // RND V1, 0x1F
+            .op(0xC20F) // RND V2, 0x0F
+            .op(0xF229) // LD F, V2
+            .draw(0, 1, 5)
+            .op(0xE39E) // SKP V3
+            .jp("loop")
+            .op(0xC40F) // RND V4, 0x0F: an extra draw from the sequence
+            .jp("loop")
+            .build()
+            .unwrap();
+        cpu.load_rom_bytes(&rom).unwrap();
+
+        cpu.run_until(crate::run::StopCondition::InstructionCount(300), 300);
+        cpu.set_keys(1 << 1);
+        cpu.run_until(crate::run::StopCondition::InstructionCount(300), 300);
+        cpu.set_keys(0);
+        cpu.run_until(crate::run::StopCondition::InstructionCount(300), 300);
+        rom_hash(&cpu.display)
+    }
+
+    #[test]
+    fn test_same_seed_same_run() {
+        let run = |seed| {
+            let mut cpu = CPU::new();
+            cpu.set_seed(seed);
+            random_run(&mut cpu)
+        };
+        assert_eq!(run(0xDEADBEEF), run(0xDEADBEEF));
+        assert_ne!(run(0xDEADBEEF), run(0xDEADBEEE));
+    }
+
+    #[test]
+    fn test_reported_seed_reproduces_run() {
+        let mut first = CPU::new();
+        let seed = first.seed();
+        let hash = random_run(&mut first);
+
+        let mut again = CPU::new();
+        again.set_seed(seed);
+        assert_eq!(random_run(&mut again), hash);
+    }
+
+    #[test]
+    fn test_load_state_continues_random_sequence() {
+        let mut cpu = CPU::new();
+        cpu.set_seed(5);
+        cpu.decode_and_execute(0xC0FF).unwrap();
+        let state = cpu.save_state();
+        let next = (0..8).map(|_| { cpu.decode_and_execute(0xC0FF).unwrap(); cpu.v[0] }).collect::<Vec<_>>();
+
+        let mut restored = CPU::new();
+        restored.load_state(&state).unwrap();
+        assert_eq!(restored.seed(), 5);
+        let replayed = (0..8).map(|_| { restored.decode_and_execute(0xC0FF).unwrap(); restored.v[0] }).collect::<Vec<_>>();
+        assert_eq!(replayed, next);
+    }
+
+    /// Both settings of a quirk, the one given first
+    fn with_and_without(quirk: fn(&mut Quirks, bool)) -> [CPU; 2] {
+        [true, false].map(|on| {
+            let mut quirks = Quirks::modern();
+            quirk(&mut quirks, on);
+            CPU::with_quirks(quirks)
+        })
+    }
+
+    #[test]
+    fn test_shift_quirk() {
+        let [mut vy, mut vx] = with_and_without(|quirks, on| quirks.shift_uses_vy = on);
+        for cpu in [&mut vy, &mut vx] {
+            cpu.v[1] = 0x04;
+            cpu.v[2] = 0x81;
+            cpu.decode_and_execute(0x8126).unwrap();
+        }
+        assert_eq!((vy.v[1], vy.v[0xF]), (0x40, 1));
+        assert_eq!((vx.v[1], vx.v[0xF]), (0x02, 0));
+
+        for cpu in [&mut vy, &mut vx] {
+            cpu.v[1] = 0x01;
+            cpu.v[2] = 0x80;
+            cpu.decode_and_execute(0x812E).unwrap();
+        }
+        assert_eq!((vy.v[1], vy.v[0xF]), (0x00, 1));
+        assert_eq!((vx.v[1], vx.v[0xF]), (0x02, 0));
+    }
+
+    #[test]
+    fn test_load_store_quirk() {
+        let [mut stepped, mut kept] = with_and_without(|quirks, on| quirks.load_store_increments_i = on);
+        for opcode in [0xF255, 0xF265] {
+            for cpu in [&mut stepped, &mut kept] {
+                cpu.i = 0x300;
+                cpu.decode_and_execute(opcode).unwrap();
+            }
+            assert_eq!((stepped.i, kept.i), (0x303, 0x300));
+        }
+    }
+
+    #[test]
+    fn test_jump_quirk() {
+        let [mut vx, mut v0] = with_and_without(|quirks, on| quirks.jump_with_vx = on);
+        for cpu in [&mut vx, &mut v0] {
+            cpu.v[0] = 0x10;
+            cpu.v[2] = 0x20;
+            cpu.decode_and_execute(0xB240).unwrap();
+        }
+        assert_eq!((vx.pc, v0.pc), (0x260, 0x250));
+    }
+
+    #[test]
+    fn test_vf_reset_quirk() {
+        let [mut reset, mut kept] = with_and_without(|quirks, on| quirks.vf_reset_on_logic = on);
+        for opcode in [0x8121, 0x8122, 0x8123] {
+            for cpu in [&mut reset, &mut kept] {
+                cpu.v[0xF] = 0x33;
+                cpu.decode_and_execute(opcode).unwrap();
+            }
+            assert_eq!((reset.v[0xF], kept.v[0xF]), (0, 0x33), "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_sprite_wrap_quirk() {
+        let [mut wrapped, mut clipped] = with_and_without(|quirks, on| quirks.wrap_sprites = on);
+        for cpu in [&mut wrapped, &mut clipped] {
+            cpu.memory[0x300..0x302].copy_from_slice(&[0xFF, 0xFF]);
+            cpu.i = 0x300;
+            cpu.v[0] = 60;
+            cpu.v[1] = 31;
+            cpu.decode_and_execute(0xD012).unwrap();
+        }
+        let lit = |cpu: &CPU| cpu.display.iter().filter(|pixel| **pixel != 0).count();
+        assert_eq!((lit(&wrapped), lit(&clipped)), (16, 4));
+        assert_eq!(wrapped.display[0], 1); // Wrapped to the top left
+        assert_eq!(clipped.display[31 * DISPLAY_WIDTH + 63], 1);
+
+        // Only the start wraps when clipping
+        clipped.v[0] = 64 + 2;
+        clipped.v[1] = 0;
+        clipped.decode_and_execute(0xD011).unwrap();
+        assert_eq!(clipped.display[2..8], [1; 6]);
+    }
+
+    #[test]
+    fn test_quirks_survive_load_state() {
+        let mut cpu = CPU::with_quirks(Quirks::vip());
+        let state = cpu.save_state();
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.quirks(), Quirks::vip());
+    }
+
+    fn hires_cpu() -> CPU {
+        let mut cpu = CPU::new();
+        cpu.decode_and_execute(0x00FF).unwrap();
+        cpu.take_dirty_rows();
+        cpu
+    }
+
+    fn hires_pixel(cpu: &CPU, x: usize, y: usize) -> u8 {
+        cpu.screen()[y * HIRES_WIDTH + x]
+    }
+
+    #[test]
+    fn test_hires_mode_switches_clear_the_screen() {
+        let mut cpu = CPU::new();
+        cpu.display[5] = 1;
+        cpu.decode_and_execute(0x00FF).unwrap();
+        assert!(cpu.hires());
+        assert_eq!(cpu.screen_size(), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert!(cpu.screen().iter().all(|pixel| *pixel == 0));
+        assert_eq!(cpu.display[5], 0);
+        assert_eq!(cpu.take_dirty_rows(), u64::MAX);
+
+        cpu.decode_and_execute(0x00FE).unwrap();
+        assert!(!cpu.hires());
+        assert_eq!(cpu.screen().len(), DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        assert_eq!(cpu.take_dirty_rows(), ALL_ROWS);
+        assert_eq!(cpu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_16x16_sprite_collision() {
+        let mut cpu = hires_cpu();
+        cpu.memory[0x300..0x320].fill(0xFF);
+        cpu.i = 0x300;
+        cpu.decode_and_execute(0xD010).unwrap();
+        assert_eq!(cpu.v[0xF], 0);
+        assert_eq!(cpu.screen().iter().filter(|pixel| **pixel != 0).count(), 256);
+        assert_eq!((hires_pixel(&cpu, 15, 15), hires_pixel(&cpu, 16, 0), hires_pixel(&cpu, 0, 16)), (1, 0, 0));
+        assert_eq!(cpu.take_dirty_rows(), 0xFFFF);
+
+        // Overlapping only the sprite's last column, which comes from each row's second byte: all 16 rows collide
+        cpu.v[0] = 15;
+        cpu.decode_and_execute(0xD010).unwrap();
+        assert_eq!(cpu.v[0xF], 16);
+        assert_eq!((hires_pixel(&cpu, 14, 3), hires_pixel(&cpu, 15, 3), hires_pixel(&cpu, 30, 3)), (1, 0, 1));
+
+        // Clear of everything drawn so far
+        cpu.v[0] = 40;
+        cpu.decode_and_execute(0xD010).unwrap();
+        assert_eq!(cpu.v[0xF], 0);
+    }
+
+    #[test]
+    fn test_vf_counts_rows_in_hires_only() {
+        let clipping = Quirks { wrap_sprites: false, ..Quirks::modern() };
+        let mut lores = CPU::with_quirks(clipping);
+        let mut hires = CPU::with_quirks(clipping);
+        hires.decode_and_execute(0x00FF).unwrap();
+
+        for cpu in [&mut lores, &mut hires] {
+            cpu.memory[0x300..0x304].fill(0xFF);
+            cpu.i = 0x300;
+            cpu.decode_and_execute(0xD014).unwrap();
+            assert_eq!(cpu.v[0xF], 0);
+
+            // Two rows down, the sprite's first two rows overlap the last two drawn
+            cpu.v[1] = 2;
+            cpu.decode_and_execute(0xD014).unwrap();
+        }
+        assert_eq!((lores.v[0xF], hires.v[0xF]), (1, 2));
+
+        // Hanging three rows off the bottom, over nothing: only hi-res counts the clipped rows
+        lores.v[1] = (DISPLAY_HEIGHT - 1) as u8;
+        hires.v[1] = (HIRES_HEIGHT - 1) as u8;
+        for cpu in [&mut lores, &mut hires] {
+            cpu.v[0] = 20;
+            cpu.decode_and_execute(0xD014).unwrap();
+        }
+        assert_eq!((lores.v[0xF], hires.v[0xF]), (0, 3));
+
+        // Collided and clipped rows add up, the 16x16 sprite included
+        hires.memory[0x300..0x320].fill(0xFF);
+        hires.v[1] = (HIRES_HEIGHT - 4) as u8;
+        hires.decode_and_execute(0xD010).unwrap();
+        assert_eq!(hires.v[0xF], 1 + 12);
+    }
+
+    #[test]
+    fn test_hires_picture_is_downsampled_into_display() {
+        let mut cpu = hires_cpu();
+        cpu.memory[0x300] = 0x40;
+        cpu.i = 0x300;
+        cpu.v[0] = 10;
+        cpu.v[1] = 7;
+        cpu.decode_and_execute(0xD011).unwrap();
+        assert_eq!(hires_pixel(&cpu, 11, 7), 1);
+        assert_eq!(cpu.display[3 * DISPLAY_WIDTH + 5], 1);
+        assert_eq!(cpu.display.iter().filter(|pixel| **pixel != 0).count(), 1);
+    }
+
+    #[test]
+    fn test_16x16_sprites_clip_at_the_bottom_edge() {
+        let mut cpu = CPU::with_quirks(Quirks::vip());
+        cpu.decode_and_execute(0x00FF).unwrap();
+        cpu.memory[MEMORY_SIZE - 8..].fill(0xFF);
+        cpu.i = (MEMORY_SIZE - 8) as u16;
+        cpu.v[1] = 60;
+        cpu.decode_and_execute(0xD010).unwrap();
+        assert_eq!(cpu.screen().iter().filter(|pixel| **pixel != 0).count(), 4 * 16);
+        assert_eq!(hires_pixel(&cpu, 15, 63), 1);
+    }
+
+    #[test]
+    fn test_scrolling_drops_pixels_at_the_screen_edge() {
+        let mut cpu = hires_cpu();
+        cpu.hires_display[127] = 1; // Top right corner
+        cpu.hires_display[63 * HIRES_WIDTH] = 1; // Bottom left corner
+        cpu.decode_and_execute(0x00FB).unwrap();
+        assert_eq!((hires_pixel(&cpu, 127, 0), hires_pixel(&cpu, 4, 63)), (0, 1));
+        assert_eq!(cpu.screen().iter().filter(|pixel| **pixel != 0).count(), 1);
+        assert_eq!(cpu.take_dirty_rows(), u64::MAX);
+
+        cpu.decode_and_execute(0x00C1).unwrap();
+        assert!(cpu.screen().iter().all(|pixel| *pixel == 0));
+
+        cpu.hires_display[5 * HIRES_WIDTH + 6] = 1;
+        cpu.hires_display[5 * HIRES_WIDTH + 2] = 1;
+        cpu.decode_and_execute(0x00FC).unwrap();
+        assert_eq!((hires_pixel(&cpu, 2, 5), hires_pixel(&cpu, 0, 5)), (1, 0));
+        assert_eq!(cpu.screen().iter().filter(|pixel| **pixel != 0).count(), 1);
+    }
+
+    #[test]
+    fn test_lores_scrolls_in_lores_pixels() {
+        let mut cpu = CPU::new();
+        cpu.display[10] = 1;
+        cpu.display[DISPLAY_WIDTH * DISPLAY_HEIGHT - 1] = 1;
+        cpu.decode_and_execute(0x00C3).unwrap();
+        assert_eq!(cpu.display[3 * DISPLAY_WIDTH + 10], 1);
+        assert_eq!(cpu.display.iter().filter(|pixel| **pixel != 0).count(), 1);
+    }
+
+    #[test]
+    fn test_big_font_and_flag_registers() {
+        let mut cpu = CPU::new();
+        cpu.v[2] = 0xA;
+        cpu.decode_and_execute(0xF230).unwrap();
+        assert_eq!(cpu.i as usize, BIG_FONTSET_START_ADDRESS + 0xA * BYTES_PER_BIG_FONT);
+        assert_eq!(cpu.memory[cpu.i as usize..][..BYTES_PER_BIG_FONT], BIG_FONTSET[100..110]);
+        cpu.v[2] = 0x10;
+        assert_eq!(cpu.decode_and_execute(0xF230), Err(EmulatorError::InvalidDigit { digit: 0x10, pc: 0x202 }));
+
+        cpu.v[..4].copy_from_slice(&[1, 2, 3, 4]);
+        cpu.decode_and_execute(0xF275).unwrap();
+        cpu.v[..4].fill(0);
+        cpu.decode_and_execute(0xF385).unwrap();
+        assert_eq!(cpu.v[..4], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_exit_stays_put() {
+        let mut cpu = CPU::new();
+        cpu.decode_and_execute(0x00FD).unwrap();
+        assert_eq!(cpu.pc, 0x200);
+    }
+
+    #[test]
+    fn test_hires_state_round_trip() {
+        let mut cpu = hires_cpu();
+        cpu.hires_display[HIRES_WIDTH * HIRES_HEIGHT - 1] = 1;
+        cpu.rpl[5] = 9;
+        let state = cpu.save_state();
+
+        let mut restored = CPU::new();
+        restored.load_state(&state).unwrap();
+        assert!(restored.hires());
+        assert_eq!(restored.screen(), cpu.screen());
+        assert_eq!(restored.rpl, cpu.rpl);
+        assert_eq!(restored.take_dirty_rows(), u64::MAX);
+    }
+}
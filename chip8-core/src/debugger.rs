@@ -0,0 +1,354 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use crate::constants::{MEMORY_SIZE, REGISTERS_COUNT};
+use crate::cpu::CPU;
+use crate::diagnostics;
+use crate::disassembler::disassemble;
+use crate::run::{RunOutcome, StopCondition};
+
+/// Pause, single-step and breakpoint state, consulted by the main loop before each instruction
+#[derive(Debug, Default)]
+pub struct Debugger {
+    paused: bool,
+    steps: u32, // Instructions still to run while paused
+    breakpoints: BTreeSet<u16>,
+    leaving: Option<u16>, // Breakpoint address to run past after continuing or stepping from it
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.steps = 0;
+    }
+
+    pub fn resume(&mut self, pc: u16) {
+        self.paused = false;
+        self.steps = 0;
+        self.leaving = Some(pc);
+    }
+
+    /// Runs count more instructions, then stays paused
+    pub fn step(&mut self, count: u32, pc: u16) {
+        self.paused = true;
+        self.steps = self.steps.saturating_add(count);
+        self.leaving = Some(pc);
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Adds a breakpoint, or removes it if one is already set at addr. Returns whether it is now set
+    pub fn toggle_breakpoint(&mut self, addr: u16) -> bool {
+        if self.breakpoints.remove(&addr) {
+            return false;
+        }
+        self.breakpoints.insert(addr);
+        true
+    }
+
+    /// Adds (set) or removes the breakpoint at addr
+    pub fn set_breakpoint(&mut self, addr: u16, set: bool) {
+        if set {
+            self.breakpoints.insert(addr);
+        } else {
+            self.breakpoints.remove(&addr);
+        }
+    }
+
+    /// Whether the instruction at pc may run now. Reaching a breakpoint pauses, even mid-step
+    pub fn allows(&mut self, pc: u16) -> bool {
+        let leaving = self.leaving.take() == Some(pc);
+        if !leaving && self.breakpoints.contains(&pc) {
+            self.paused = true;
+            self.steps = 0;
+        }
+
+        if !self.paused {
+            return true;
+        }
+        if self.steps > 0 {
+            self.steps -= 1;
+            return true;
+        }
+        self.leaving = leaving.then_some(pc);
+        false
+    }
+
+    /// Where the machine stands, for printing while paused: the next instruction, the registers,
+    /// the stack pointer and the return address on top of the stack, and the timers
+    pub fn dump(&self, cpu: &CPU) -> String {
+        let pc = cpu.pc as usize;
+        let next = match cpu.memory.get(pc..pc + 2) {
+            Some(bytes) => {
+                let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+                format!("{:04X}  {}", opcode, disassemble(opcode))
+            }
+            None => "----".to_string(),
+        };
+        let mark = if self.breakpoints.contains(&cpu.pc) { " (breakpoint)" } else { "" };
+
+        let mut out = format!("PC {:#05X}{}  {}\n", cpu.pc, mark, next);
+        out.push_str(&diagnostics::registers(cpu, "  "));
+        let top = cpu.stack.last().map_or("-".to_string(), |addr| format!("{:#05X}", addr));
+        writeln!(out, "  I {:#05X}  SP {}  top {}  DT {}  ST {}", cpu.i, cpu.stack.len(), top, cpu.delay_timer, cpu.sound_timer).ok();
+        out
+    }
+
+    /// Runs cpu until stop fires or a breakpoint is reached
+    pub fn run_until(&self, cpu: &mut CPU, stop: StopCondition, max_instructions: u64) -> RunOutcome {
+        cpu.run_until_or_break(&stop, max_instructions, &self.breakpoints)
+    }
+
+    /// Runs until the instruction at addr is next
+    pub fn run_to(&self, cpu: &mut CPU, addr: u16, max_instructions: u64) -> RunOutcome {
+        self.run_until(cpu, StopCondition::PcEquals(addr), max_instructions)
+    }
+
+    /// Runs until the current subroutine returns, or None outside of a subroutine
+    pub fn step_out(&self, cpu: &mut CPU, max_instructions: u64) -> Option<RunOutcome> {
+        let depth = cpu.stack.len().checked_sub(1)?;
+        Some(self.run_until(cpu, StopCondition::StackDepth(depth as u8), max_instructions))
+    }
+}
+
+/// A register the debugger can edit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+}
+
+/// A change to the machine or the debugger. Every debugger frontend (the web page, the
+/// in-window panel) goes through these, so they all behave the same
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Pause,
+    Continue,
+    Step(u32),
+    ToggleBreakpoint(u16),
+    SetBreakpoint { addr: u16, set: bool },
+    Poke { addr: u16, value: u8 },
+    SetRegister { register: Register, value: u16 },
+}
+
+/// Carries out a command between frames, or says why it can't
+pub fn apply(command: Command, cpu: &mut CPU, debugger: &mut Debugger) -> Result<(), String> {
+    match command {
+        Command::Pause => debugger.pause(),
+        Command::Continue => debugger.resume(cpu.pc),
+        Command::Step(count) => debugger.step(count, cpu.pc),
+        Command::ToggleBreakpoint(addr) => {
+            debugger.toggle_breakpoint(addr);
+        }
+        Command::SetBreakpoint { addr, set } => debugger.set_breakpoint(addr, set),
+        Command::Poke { addr, value } => {
+            let byte = cpu.memory.get_mut(addr as usize)
+                .ok_or_else(|| format!("Address {:#05X} is outside memory", addr))?;
+            *byte = value;
+            if cpu.mapped_display() {
+                cpu.decode_mapped_display();
+            }
+        }
+        Command::SetRegister { register, value } => set_register(cpu, register, value)?,
+    }
+    Ok(())
+}
+
+fn set_register(cpu: &mut CPU, register: Register, value: u16) -> Result<(), String> {
+    let byte = || u8::try_from(value).map_err(|_| format!("{:#X} does not fit in {:?}", value, register));
+    let addr = || match value as usize {
+        addr if addr < MEMORY_SIZE => Ok(value),
+        _ => Err(format!("Address {:#05X} is outside memory", value)),
+    };
+    match register {
+        Register::V(x) if (x as usize) < REGISTERS_COUNT => cpu.v[x as usize] = byte()?,
+        Register::V(x) => return Err(format!("There is no register V{:X}", x)),
+        Register::I => cpu.i = addr()?,
+        Register::Pc => cpu.pc = addr()?,
+        Register::DelayTimer => cpu.delay_timer = byte()?,
+        Register::SoundTimer => cpu.sound_timer = byte()?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::RomBuilder;
+    use crate::run::StopReason;
+
+    #[test]
+    fn test_pause_and_step() {
+        let mut debugger = Debugger::new();
+        assert!(debugger.allows(0x200));
+
+        debugger.pause();
+        assert!(!debugger.allows(0x200));
+
+        debugger.step(2, 0x200);
+        assert!(debugger.allows(0x200));
+        assert!(debugger.allows(0x202));
+        assert!(!debugger.allows(0x204));
+        assert!(debugger.is_paused());
+
+        debugger.resume(0x204);
+        assert!(debugger.allows(0x204));
+        assert!(!debugger.is_paused());
+    }
+
+    #[test]
+    fn test_breakpoint_pauses_until_continued() {
+        let mut debugger = Debugger::new();
+        assert!(debugger.toggle_breakpoint(0x204));
+
+        assert!(debugger.allows(0x202));
+        assert!(!debugger.allows(0x204));
+        assert!(debugger.is_paused());
+        assert!(!debugger.allows(0x204));
+
+        // Continuing runs the instruction under the breakpoint instead of stopping on it again
+        debugger.resume(0x204);
+        assert!(debugger.allows(0x204));
+        assert!(debugger.allows(0x206));
+
+        // ...but it still fires the next time around
+        assert!(!debugger.allows(0x204));
+    }
+
+    #[test]
+    fn test_step_off_breakpoint() {
+        let mut debugger = Debugger::new();
+        debugger.toggle_breakpoint(0x300);
+        assert!(!debugger.allows(0x300));
+
+        debugger.step(1, 0x300);
+        assert!(debugger.allows(0x300));
+        assert!(!debugger.allows(0x302));
+    }
+
+    #[test]
+    fn test_multi_step_stops_at_breakpoint() {
+        let mut debugger = Debugger::new();
+        debugger.toggle_breakpoint(0x204);
+        debugger.step(10, 0x200);
+
+        assert!(debugger.allows(0x200));
+        assert!(debugger.allows(0x202));
+        assert!(!debugger.allows(0x204));
+        assert!(!debugger.allows(0x204));
+    }
+
+    #[test]
+    fn test_toggle_breakpoint() {
+        let mut debugger = Debugger::new();
+        assert!(debugger.toggle_breakpoint(0x210));
+        assert!(debugger.toggle_breakpoint(0x200));
+        assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), vec![0x200, 0x210]);
+
+        assert!(!debugger.toggle_breakpoint(0x210));
+        assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), vec![0x200]);
+    }
+
+    #[test]
+    fn test_set_breakpoint() {
+        let mut debugger = Debugger::new();
+        debugger.set_breakpoint(0x210, true);
+        debugger.set_breakpoint(0x210, true);
+        assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), vec![0x210]);
+
+        debugger.set_breakpoint(0x210, false);
+        debugger.set_breakpoint(0x220, false);
+        assert_eq!(debugger.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn test_edit_a_register_while_paused() {
+        let mut cpu = RomBuilder::new().ld(3, 1).add(3, 1).spin().cpu().unwrap();
+        let mut debugger = Debugger::new();
+        cpu.tick().unwrap();
+
+        apply(Command::Pause, &mut cpu, &mut debugger).unwrap();
+        assert!(!debugger.allows(cpu.pc));
+        apply(Command::SetRegister { register: Register::V(3), value: 0x40 }, &mut cpu, &mut debugger).unwrap();
+        apply(Command::Continue, &mut cpu, &mut debugger).unwrap();
+
+        assert!(debugger.allows(cpu.pc));
+        cpu.tick().unwrap();
+        assert_eq!(cpu.v[3], 0x41);
+    }
+
+    #[test]
+    fn test_set_register_checks_ranges() {
+        let mut cpu = CPU::new();
+        let mut debugger = Debugger::new();
+        let mut set = |register, value| apply(Command::SetRegister { register, value }, &mut cpu, &mut debugger);
+
+        assert!(set(Register::I, 0xFFF).is_ok());
+        assert!(set(Register::Pc, 0x300).is_ok());
+        assert!(set(Register::SoundTimer, 0xFF).is_ok());
+        assert!(set(Register::V(0xF), 0x100).is_err());
+        assert!(set(Register::V(0x10), 0).is_err());
+        assert!(set(Register::Pc, MEMORY_SIZE as u16).is_err());
+        assert_eq!((cpu.i, cpu.pc, cpu.sound_timer), (0xFFF, 0x300, 0xFF));
+    }
+
+    #[test]
+    fn test_dump() {
+        let mut cpu = RomBuilder::new().ld(0xA, 0x0F).cpu().unwrap();
+        let mut debugger = Debugger::new();
+        cpu.v[0xF] = 1;
+        cpu.i = 0x345;
+        cpu.stack = vec![0x202, 0x310];
+        cpu.delay_timer = 12;
+        cpu.sound_timer = 4;
+
+        let text = debugger.dump(&cpu);
+        assert_eq!(text, "PC 0x200  6A0F  LD VA, 0x0F\n  \
+            V0-V7  00 00 00 00 00 00 00 00\n  \
+            V8-VF  00 00 00 00 00 00 00 01\n  \
+            I 0x345  SP 2  top 0x310  DT 12  ST 4\n");
+
+        debugger.toggle_breakpoint(0xFFF);
+        cpu.pc = 0xFFF;
+        cpu.stack.clear();
+        let text = debugger.dump(&cpu);
+        assert!(text.starts_with("PC 0xFFF (breakpoint)  ----\n"), "{}", text);
+        assert!(text.contains("SP 0  top -"), "{}", text);
+    }
+
+    #[test]
+    fn test_step_out() {
+        let mut cpu = RomBuilder::new().call("sub").spin().label("sub").ld(0, 1).ld(1, 2).ret().cpu().unwrap();
+        let debugger = Debugger::new();
+        assert_eq!(debugger.step_out(&mut cpu, 100), None);
+
+        cpu.tick().unwrap();
+        let outcome = debugger.step_out(&mut cpu, 100).unwrap();
+        assert_eq!(outcome.reason, StopReason::Condition(StopCondition::StackDepth(0)));
+        assert_eq!(outcome.instructions, 3);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_run_to_stops_at_breakpoints_on_the_way() {
+        let mut cpu = RomBuilder::new().ld(0, 1).ld(1, 2).ld(2, 3).spin().cpu().unwrap();
+        let mut debugger = Debugger::new();
+        debugger.toggle_breakpoint(0x202);
+
+        assert_eq!(debugger.run_to(&mut cpu, 0x206, 100).reason, StopReason::Breakpoint(0x202));
+        assert_eq!(debugger.run_to(&mut cpu, 0x206, 100).reason, StopReason::Condition(StopCondition::PcEquals(0x206)));
+        assert_eq!(cpu.v[2], 3);
+    }
+}
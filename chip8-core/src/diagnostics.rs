@@ -0,0 +1,114 @@
+//! State dumps on request, for runs that seem hung. A frontend sets the flags from outside the
+//! loop (chip8-sdl does from SIGUSR1 and SIGUSR2 on Unix) and checks them once per frame
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use crate::cpu::CPU;
+
+/// Set from anywhere, such as a signal handler; each request is taken once
+#[derive(Debug, Clone, Default)]
+pub struct Requests {
+    pub dump: Arc<AtomicBool>,
+    pub toggle_pause: Arc<AtomicBool>,
+}
+
+impl Requests {
+    /// The dump, if one was asked for since the last call
+    pub fn take_dump(&self, cpu: &CPU, frames: u64, elapsed: Duration) -> Option<String> {
+        self.dump.swap(false, Ordering::Relaxed).then(|| dump(cpu, frames, elapsed))
+    }
+
+    /// True once for each request to pause or resume
+    pub fn take_toggle_pause(&self) -> bool {
+        self.toggle_pause.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// The registers, timers, stack and the opcode about to run, after `frames` frames over `elapsed`
+pub fn dump(cpu: &CPU, frames: u64, elapsed: Duration) -> String {
+    let pc = cpu.pc as usize;
+    let opcode = match cpu.memory.get(pc..pc + 2) {
+        Some(bytes) => format!("{:02X}{:02X}", bytes[0], bytes[1]),
+        None => "----".to_string(),
+    };
+
+    let mut out = format!("State after {} frames ({:.1}s):\n", frames, elapsed.as_secs_f64());
+    writeln!(out, "  PC {:#05X} (next {})  I {:#05X}  DT {}  ST {}", cpu.pc, opcode, cpu.i, cpu.delay_timer, cpu.sound_timer).ok();
+    out.push_str(&registers(cpu, "  "));
+    let stack: Vec<String> = cpu.stack.iter().map(|address| format!("{:#05X}", address)).collect();
+    let stack = format!("  Stack ({}/{})  {}", cpu.stack.len(), cpu.stack_depth(), stack.join(" "));
+    writeln!(out, "{}", stack.trim_end()).ok();
+    writeln!(out, "  Quirks {}  seed {:#x}  ROM {:016x}", cpu.quirks_name(), cpu.seed(), cpu.rom_hash()).ok();
+    out
+}
+
+/// V0-V7 and V8-VF in hex, a line each after indent. Every dump of the machine lists them this way
+pub fn registers(cpu: &CPU, indent: &str) -> String {
+    let mut out = String::new();
+    for (half, registers) in cpu.v.chunks(8).enumerate() {
+        let registers: Vec<String> = registers.iter().map(|v| format!("{:02X}", v)).collect();
+        writeln!(out, "{}V{:X}-V{:X}  {}", indent, half * 8, half * 8 + 7, registers.join(" ")).ok();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_is_taken_once() {
+        let requests = Requests::default();
+        let cpu = CPU::new();
+        assert_eq!(requests.take_dump(&cpu, 1, Duration::ZERO), None);
+
+        // As the signal handler would
+        requests.dump.store(true, Ordering::Relaxed);
+        assert!(requests.take_dump(&cpu, 1, Duration::ZERO).is_some());
+        assert_eq!(requests.take_dump(&cpu, 2, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut cpu = CPU::new();
+        cpu.v[0x0] = 0x01;
+        cpu.v[0xF] = 0xAB;
+        assert_eq!(registers(&cpu, "> "), "> V0-V7  01 00 00 00 00 00 00 00\n> V8-VF  00 00 00 00 00 00 00 AB\n");
+    }
+
+    #[test]
+    fn test_toggle_pause_is_taken_once() {
+        let requests = Requests::default();
+        assert!(!requests.take_toggle_pause());
+        requests.toggle_pause.store(true, Ordering::Relaxed);
+        assert!(requests.take_toggle_pause());
+        assert!(!requests.take_toggle_pause());
+    }
+
+    #[test]
+    fn test_dump_contents() {
+        let mut cpu = CPU::new();
+        cpu.pc = 0x21A;
+        cpu.memory[0x21A..0x21C].copy_from_slice(&[0x12, 0x1A]);
+        cpu.i = 0x300;
+        cpu.v[0x3] = 0x2A;
+        cpu.v[0xF] = 1;
+        cpu.delay_timer = 7;
+        cpu.stack.push(0x204);
+
+        let text = dump(&cpu, 1234, Duration::from_millis(20_560));
+        assert!(text.starts_with("State after 1234 frames (20.6s):\n"), "{}", text);
+        assert!(text.contains("PC 0x21A (next 121A)  I 0x300  DT 7  ST 0"), "{}", text);
+        assert!(text.contains("V0-V7  00 00 00 2A 00 00 00 00\n"), "{}", text);
+        assert!(text.contains("V8-VF  00 00 00 00 00 00 00 01\n"), "{}", text);
+        assert!(text.contains("Stack (1/16)  0x204\n"), "{}", text);
+
+        // A PC run off the end of memory has no next opcode, and an empty stack lists nothing
+        cpu.pc = 0xFFF;
+        cpu.stack.clear();
+        let text = dump(&cpu, 0, Duration::ZERO);
+        assert!(text.contains("(next ----)"), "{}", text);
+        assert!(text.contains("\n  Stack (0/16)\n"), "{}", text);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::cpu::CPU;
+
+/// Mnemonic for one opcode, in the style of Cowgod's technical reference
+pub fn disassemble(opcode: u16) -> String {
+    let x = CPU::get_x(opcode);
+    let y = CPU::get_y(opcode);
+    let n = CPU::get_n(opcode);
+    let nn = CPU::get_nn(opcode);
+    let nnn = CPU::get_nnn(opcode);
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            0x00FB => "SCR".to_string(),
+            0x00FC => "SCL".to_string(),
+            0x00FD => "EXIT".to_string(),
+            0x00FE => "LOW".to_string(),
+            0x00FF => "HIGH".to_string(),
+            _ if opcode & 0xFFF0 == 0x00C0 => format!("SCD {}", n),
+            _ => format!("SYS {:#05X}", nnn),
+        },
+        0x1000 => format!("JP {:#05X}", nnn),
+        0x2000 => format!("CALL {:#05X}", nnn),
+        0x3000 => format!("SE V{:X}, {:#04X}", x, nn),
+        0x4000 => format!("SNE V{:X}, {:#04X}", x, nn),
+        0x5000 if n == 0 => format!("SE V{:X}, V{:X}", x, y),
+        0x6000 => format!("LD V{:X}, {:#04X}", x, nn),
+        0x7000 => format!("ADD V{:X}, {:#04X}", x, nn),
+        0x8000 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X}, V{:X}", x, y),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}, V{:X}", x, y),
+            _ => unknown(opcode),
+        },
+        0x9000 if n == 0 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA000 => format!("LD I, {:#05X}", nnn),
+        0xB000 => format!("JP V0, {:#05X}", nnn),
+        0xC000 => format!("RND V{:X}, {:#04X}", x, nn),
+        0xD000 => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE000 => match nn {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => unknown(opcode),
+        },
+        0xF000 => match nn {
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x30 => format!("LD HF, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            0x75 => format!("LD R, V{:X}", x),
+            0x85 => format!("LD V{:X}, R", x),
+            _ => unknown(opcode),
+        },
+        _ => unknown(opcode),
+    }
+}
+
+/// One (address, opcode, mnemonic) per instruction word of rom, loaded at base. A trailing odd
+/// byte comes last as a DB of its own
+pub fn disassemble_rom(rom: &[u8], base: u16) -> Vec<(u16, u16, String)> {
+    let mut words = rom.chunks_exact(2);
+    let mut listing: Vec<_> = words.by_ref()
+        .enumerate()
+        .map(|(index, word)| {
+            let opcode = u16::from_be_bytes([word[0], word[1]]);
+            (base.wrapping_add(2 * index as u16), opcode, disassemble(opcode))
+        })
+        .collect();
+    if let [byte] = words.remainder() {
+        listing.push((base.wrapping_add(rom.len() as u16 - 1), *byte as u16, format!("DB {:#04X}", byte)));
+    }
+    listing
+}
+
+/// Data words and opcodes the interpreter doesn't know
+fn unknown(opcode: u16) -> String {
+    format!("DW {:#06X}", opcode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mnemonics() {
+        assert_eq!(disassemble(0x00E0), "CLS");
+        assert_eq!(disassemble(0x00EE), "RET");
+        assert_eq!(disassemble(0x1228), "JP 0x228");
+        assert_eq!(disassemble(0x6A0F), "LD VA, 0x0F");
+        assert_eq!(disassemble(0x8AB4), "ADD VA, VB");
+        assert_eq!(disassemble(0xD015), "DRW V0, V1, 5");
+        assert_eq!(disassemble(0xF365), "LD V3, [I]");
+    }
+
+    #[test]
+    fn test_super_chip_mnemonics() {
+        assert_eq!(disassemble(0x00C4), "SCD 4");
+        assert_eq!(disassemble(0x00FF), "HIGH");
+        assert_eq!(disassemble(0xD120), "DRW V1, V2, 0");
+        assert_eq!(disassemble(0xF530), "LD HF, V5");
+        assert_eq!(disassemble(0xF785), "LD V7, R");
+    }
+
+    #[test]
+    fn test_every_family() {
+        let table = [
+            (0x00E0, "CLS"), (0x00EE, "RET"), (0x0123, "SYS 0x123"),
+            (0x1228, "JP 0x228"), (0x2ABC, "CALL 0xABC"),
+            (0x3A12, "SE VA, 0x12"), (0x4B34, "SNE VB, 0x34"), (0x5120, "SE V1, V2"),
+            (0x631F, "LD V3, 0x1F"), (0x7401, "ADD V4, 0x01"),
+            (0x8120, "LD V1, V2"), (0x8121, "OR V1, V2"), (0x8122, "AND V1, V2"), (0x8123, "XOR V1, V2"),
+            (0x8124, "ADD V1, V2"), (0x8125, "SUB V1, V2"), (0x8126, "SHR V1, V2"), (0x8127, "SUBN V1, V2"),
+            (0x812E, "SHL V1, V2"), (0x9120, "SNE V1, V2"),
+            (0xA2F0, "LD I, 0x2F0"), (0xB300, "JP V0, 0x300"), (0xC5FF, "RND V5, 0xFF"),
+            (0xD01F, "DRW V0, V1, 15"), (0xE49E, "SKP V4"), (0xE4A1, "SKNP V4"),
+            (0xF107, "LD V1, DT"), (0xF10A, "LD V1, K"), (0xF115, "LD DT, V1"), (0xF118, "LD ST, V1"),
+            (0xF11E, "ADD I, V1"), (0xF129, "LD F, V1"), (0xF133, "LD B, V1"), (0xF155, "LD [I], V1"),
+            (0xF165, "LD V1, [I]"),
+        ];
+        for (opcode, mnemonic) in table {
+            assert_eq!(disassemble(opcode), mnemonic, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_disassemble_rom() {
+        let listing = disassemble_rom(&[0x6A, 0x0F, 0x12, 0x00, 0xAB], 0x200);
+        assert_eq!(listing, [
+            (0x200, 0x6A0F, "LD VA, 0x0F".to_string()),
+            (0x202, 0x1200, "JP 0x200".to_string()),
+            (0x204, 0xAB, "DB 0xAB".to_string()),
+        ]);
+        assert_eq!(disassemble_rom(&[], 0x200), []);
+    }
+
+    #[test]
+    fn test_unknown_opcodes_are_data() {
+        assert_eq!(disassemble(0x5121), "DW 0x5121");
+        assert_eq!(disassemble(0x800F), "DW 0x800F");
+        assert_eq!(disassemble(0xFFFF), "DW 0xFFFF");
+    }
+}
@@ -0,0 +1,150 @@
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{DrawTarget, Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+use crate::net::diff_display;
+
+/// Draws the CHIP-8 display onto an embedded-graphics target, each pixel as a scale x scale square.
+/// After the first frame only the pixels that changed are drawn again
+pub struct DisplayAdapter {
+    origin: Point, // Where the top left pixel goes on the target
+    scale: u32,
+    shown: Option<Vec<u8>>, // What the target shows, once drawn in full
+}
+
+impl DisplayAdapter {
+    pub fn new(origin: Point, scale: u32) -> Self {
+        Self { origin, scale: scale.max(1), shown: None }
+    }
+
+    /// The target area the whole display covers
+    pub fn bounds(&self) -> Rectangle {
+        Rectangle::new(self.origin, Size::new(DISPLAY_WIDTH as u32 * self.scale, DISPLAY_HEIGHT as u32 * self.scale))
+    }
+
+    /// The target area one display pixel covers
+    pub fn pixel_bounds(&self, index: usize) -> Rectangle {
+        let x = (index % DISPLAY_WIDTH) as i32 * self.scale as i32;
+        let y = (index / DISPLAY_WIDTH) as i32 * self.scale as i32;
+        Rectangle::new(self.origin + Point::new(x, y), Size::new(self.scale, self.scale))
+    }
+
+    /// Makes the next draw repaint everything, e.g. after something else drew over the target
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    pub fn draw<D>(&mut self, display: &[u8], target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        match &self.shown {
+            Some(shown) => {
+                for index in diff_display(shown, display) {
+                    self.draw_pixel(index as usize, display, target)?;
+                }
+            }
+            None => {
+                target.fill_solid(&self.bounds(), BinaryColor::Off)?;
+                for index in (0..display.len()).filter(|index| display[*index] != 0) {
+                    self.draw_pixel(index, display, target)?;
+                }
+            }
+        }
+
+        self.shown = Some(display.to_vec());
+        Ok(())
+    }
+
+    fn draw_pixel<D>(&self, index: usize, display: &[u8], target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let color = if display[index] != 0 { BinaryColor::On } else { BinaryColor::Off };
+        target.fill_solid(&self.pixel_bounds(index), color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mock_display::MockDisplay;
+
+    fn blank() -> Vec<u8> {
+        vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT]
+    }
+
+    fn mock() -> MockDisplay<BinaryColor> {
+        let mut target = MockDisplay::new();
+        target.set_allow_overdraw(true);
+        target
+    }
+
+    #[test]
+    fn test_pixel_coordinates() {
+        let adapter = DisplayAdapter::new(Point::new(10, 20), 3);
+        assert_eq!(adapter.pixel_bounds(0), Rectangle::new(Point::new(10, 20), Size::new(3, 3)));
+        assert_eq!(adapter.pixel_bounds(DISPLAY_WIDTH + 2), Rectangle::new(Point::new(16, 23), Size::new(3, 3)));
+        assert_eq!(adapter.bounds().size, Size::new(192, 96));
+
+        let last = adapter.pixel_bounds(DISPLAY_WIDTH * DISPLAY_HEIGHT - 1);
+        assert_eq!(last.bottom_right(), adapter.bounds().bottom_right());
+    }
+
+    #[test]
+    fn test_zero_scale_draws_single_pixels() {
+        assert_eq!(DisplayAdapter::new(Point::zero(), 0).pixel_bounds(1).size, Size::new(1, 1));
+    }
+
+    #[test]
+    fn test_first_draw_paints_everything() {
+        let mut display = blank();
+        display[0] = 1;
+        display[DISPLAY_WIDTH + 1] = 1;
+
+        let mut target = mock();
+        DisplayAdapter::new(Point::zero(), 1).draw(&display, &mut target).unwrap();
+
+        assert_eq!(target.affected_area(), Rectangle::new(Point::zero(), Size::new(64, 32)));
+        assert_eq!(target.get_pixel(Point::new(0, 0)), Some(BinaryColor::On));
+        assert_eq!(target.get_pixel(Point::new(1, 1)), Some(BinaryColor::On));
+        assert_eq!(target.get_pixel(Point::new(1, 0)), Some(BinaryColor::Off));
+    }
+
+    #[test]
+    fn test_later_draws_only_touch_changes() {
+        // The mock is 64x64, so only one scale fits; the coordinates test covers scaling
+        let mut adapter = DisplayAdapter::new(Point::new(0, 2), 1);
+        let mut display = blank();
+        display[5] = 1;
+        display[6] = 1;
+        adapter.draw(&display, &mut mock()).unwrap();
+
+        // Pixel 5 turns off and pixel 7 turns on; pixel 6 stays lit and isn't redrawn
+        display[5] = 0;
+        display[7] = 1;
+        let mut target = mock();
+        adapter.draw(&display, &mut target).unwrap();
+
+        let mut expected = mock();
+        expected.set_pixel(Point::new(5, 2), Some(BinaryColor::Off));
+        expected.set_pixel(Point::new(7, 2), Some(BinaryColor::On));
+        target.assert_eq(&expected);
+
+        // Nothing changed, nothing drawn
+        let mut target = mock();
+        adapter.draw(&display, &mut target).unwrap();
+        assert_eq!(target.affected_area().size, Size::zero());
+    }
+
+    #[test]
+    fn test_invalidate_repaints() {
+        let mut adapter = DisplayAdapter::new(Point::zero(), 1);
+        adapter.draw(&blank(), &mut mock()).unwrap();
+        adapter.invalidate();
+
+        let mut target = mock();
+        adapter.draw(&blank(), &mut target).unwrap();
+        assert_eq!(target.affected_area().size, Size::new(64, 32));
+    }
+}
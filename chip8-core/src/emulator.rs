@@ -0,0 +1,756 @@
+//! The running machine and everything that decides when it runs: pause, reset, speed, turbo and
+//! the FX0A key handshake, with no window involved.
+//!
+//! A frontend feeds it [`InputEvent`]s as they happen, calls [`Emulator::update`] once per host frame
+//! with the time since the last one, reacts to the [`EmulatorEvent`]s it returns, and draws
+//! [`Emulator::frame`]. Recording, replays and network play hook in at those two enums.
+
+use std::time::Duration;
+use crate::clock::{Clock, Speed, TIMER_HZ};
+use crate::config::Config;
+use crate::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH, STACK_SIZE};
+use crate::cpu::{BeeperState, CPU};
+#[cfg(feature = "debugger")]
+use crate::debugger::Debugger;
+use crate::error::EmulatorError;
+use crate::quirks::Quirks;
+use crate::sink::FrameSink;
+use crate::timing::{FrameBudget, TimingMode};
+
+/// One timer tick, rounded up so that an update this long always ticks the timers
+pub const TIMER_INTERVAL: Duration = Duration::from_nanos(1_000_000_000_u64.div_ceil(TIMER_HZ as u64));
+/// Frames run per update while turbo is on
+pub const TURBO_FRAMES: u32 = 4;
+
+pub type FrameBuffer = [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+
+/// How every fresh machine is set up, on load and on reset
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineSetup {
+    pub stack_depth: usize,
+    pub vip_display: bool,
+    pub detect_uninit: bool,
+    pub trace: bool,
+    pub quirks: Quirks,
+    pub seed: Option<u64>, // None picks a new random seed for each machine
+}
+
+impl Default for MachineSetup {
+    fn default() -> Self {
+        Self { stack_depth: STACK_SIZE, vip_display: false, detect_uninit: false, trace: false, quirks: Quirks::modern(), seed: None }
+    }
+}
+
+impl MachineSetup {
+    pub fn machine(&self) -> CPU {
+        let mut cpu = CPU::with_stack_depth(self.stack_depth);
+        cpu.set_quirks(self.quirks);
+        cpu.set_mapped_display(self.vip_display);
+        cpu.set_detect_uninit(self.detect_uninit);
+        cpu.set_trace(self.trace);
+        if let Some(seed) = self.seed {
+            cpu.set_seed(seed);
+        }
+        cpu
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunState {
+    Running,
+    Paused,
+    /// An instruction failed; only a reset, another ROM or skipping the instruction gets going again
+    Halted { pc: u16, error: EmulatorError },
+}
+
+/// Something the player or frontend did
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    /// The keypad from now on: every key down (bit N = key N), and which of those are only
+    /// latched by sticky keys, so that FX0A can take them
+    Keys { down: u16, latched: u16 },
+    Pause,
+    Resume,
+    TogglePause,
+    /// Power-on with the same ROM, read again from disk if it came from a file
+    Reset,
+    /// Power-on with this ROM instead, reported as a Reset
+    Load(Vec<u8>),
+    /// Asks for a StateSaved with the machine as it is now
+    SaveState,
+    /// Restores a state from CPU::save_state, reported as StateLoaded
+    LoadState(Vec<u8>),
+    SetSpeed { timing: TimingMode, instructions_per_frame: u32 },
+    /// Runs instructions at this clock rate rather than by the per-frame budget, or goes back to
+    /// the budget with None
+    SetClockRate(Option<u32>),
+    /// Scales emulated time against wall time: instructions, timers and frames alike
+    SetClockSpeed(Speed),
+    /// Maps the display into memory from 0xF00 (see CPU::set_mapped_display), now and after resets
+    SetVipDisplay(bool),
+    /// Runs the ambiguous opcodes this way from the next reset or ROM on. The machine running keeps
+    /// its quirks, as a ROM can break if they change under it
+    SetQuirks(Quirks),
+    Turbo(bool),
+    /// Steps past the instruction the machine halted on and runs on, for frontends that treat
+    /// some errors (say, unknown opcodes) as not fatal. Ignored unless halted
+    SkipInstruction,
+}
+
+/// Something that happened during an update, in the order it happened
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmulatorEvent {
+    /// An update started with these keys, `latched` of them by sticky keys, and ran `frames` budget
+    /// frames, or `instructions` instructions with a clock rate, then ticked the timers `timers`
+    /// times. Replays record exactly this, and release latches where FX0A took them, as it did
+    Frame { keys: u16, latched: u16, frames: u32, instructions: u32, timers: u32 },
+    /// FX0A is waiting on a latched key, which the frontend should release: one tap satisfies one wait
+    LatchConsumed(usize),
+    Paused,
+    Resumed,
+    /// The machine was reset; error is set if the ROM could not be loaded again
+    Reset { error: Option<String> },
+    Halted { pc: u16, error: EmulatorError },
+    /// The sound timer started or stopped the beeper
+    Sound(bool),
+    /// The machine as CPU::save_state wrote it, in reply to SaveState
+    StateSaved(Vec<u8>),
+    /// The warnings from loading a state, or why it couldn't be loaded
+    StateLoaded(Result<Vec<String>, String>),
+}
+
+/// Holds beeps shorter than min_frames timer ticks on until they have lasted that long, so a
+/// one-frame beep is heard rather than clicking the audio device. The sound timer is left alone
+struct BeepSmoothing {
+    min_frames: u8,
+    sounded: u8, // Timer ticks since the current beep started
+    on: bool,
+}
+
+impl BeepSmoothing {
+    fn new(min_frames: u8) -> Self {
+        Self { min_frames, sounded: 0, on: false }
+    }
+
+    /// Whether the beeper should sound, seeing the sound timer's beeper now
+    fn observe(&mut self, beeper: BeeperState) -> bool {
+        if beeper.on && !self.on {
+            self.sounded = 0;
+        }
+        self.on = beeper.on || (self.on && self.sounded < self.min_frames);
+        self.on
+    }
+
+    fn tick(&mut self) {
+        if self.on {
+            self.sounded = self.sounded.saturating_add(1);
+        }
+    }
+}
+
+enum Rom {
+    None,
+    File(String),
+    Bytes(Vec<u8>),
+}
+
+pub struct Emulator {
+    pub cpu: CPU,
+    pub config: Config,
+    #[cfg(feature = "debugger")]
+    pub debugger: Debugger,
+    state: RunState,
+    setup: MachineSetup,
+    rom: Rom,
+    budget: FrameBudget,
+    turbo: bool,
+    keys: u16,
+    latched: u16,
+    clock: Clock,
+    beeper: BeepSmoothing,
+    sound: bool,
+    pending: Vec<EmulatorEvent>, // From handle_event, returned by the next update
+}
+
+impl Emulator {
+    /// A running machine with nothing loaded
+    pub fn new(config: Config, setup: MachineSetup, timing: TimingMode, instructions_per_frame: u32) -> Self {
+        let beeper = BeepSmoothing::new(config.audio.min_beep_frames);
+        Self {
+            cpu: setup.machine(),
+            config,
+            #[cfg(feature = "debugger")]
+            debugger: Debugger::new(),
+            state: RunState::Running,
+            setup,
+            rom: Rom::None,
+            budget: FrameBudget::new(timing, instructions_per_frame),
+            turbo: false,
+            keys: 0,
+            latched: 0,
+            clock: Clock::default(),
+            beeper,
+            sound: false,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Starts the ROM at path on a fresh machine. If it can't be read the machine is left empty
+    pub fn load(&mut self, path: &str) -> Result<(), EmulatorError> {
+        self.rom = Rom::File(path.to_string());
+        self.power_on()
+    }
+
+    /// Starts a ROM already in memory on a fresh machine
+    pub fn load_bytes(&mut self, rom: Vec<u8>) -> Result<(), EmulatorError> {
+        self.rom = Rom::Bytes(rom);
+        self.power_on()
+    }
+
+    /// Swaps in an edited config between frames. Of its settings the emulator itself only reads
+    /// the beep length, which applies from the next beep; the frontend applies the rest
+    pub fn set_config(&mut self, config: Config) {
+        self.beeper.min_frames = config.audio.min_beep_frames;
+        self.config = config;
+    }
+
+    /// How the next machine will be set up, on reset or load
+    pub fn setup(&self) -> &MachineSetup {
+        &self.setup
+    }
+
+    pub fn state(&self) -> &RunState {
+        &self.state
+    }
+
+    /// The timing mode and instructions per frame in effect
+    pub fn speed(&self) -> (TimingMode, u32) {
+        (self.budget.mode(), self.budget.instructions_per_frame())
+    }
+
+    /// The clock rate and speed in effect
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        self.turbo
+    }
+
+    /// Whether the beeper should be sounding: the CPU's beeper, with short beeps held on for
+    /// config.audio.min_beep_frames
+    pub fn sound(&self) -> bool {
+        self.sound
+    }
+
+    pub fn frame(&self) -> &FrameBuffer {
+        &self.cpu.display
+    }
+
+    /// Draws the frame into sink, with the rows that changed since the last present. In hires
+    /// mode that is the 128x64 picture, where frame() stays at 64x32
+    pub fn present(&mut self, sink: &mut impl FrameSink) {
+        let dirty_rows = self.cpu.take_dirty_rows();
+        sink.draw_rows(self.cpu.screen(), dirty_rows);
+    }
+
+    pub fn handle_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Keys { down, latched } => {
+                self.keys = down;
+                self.latched = latched & down;
+            }
+            InputEvent::Pause if self.state == RunState::Running => {
+                self.state = RunState::Paused;
+                self.pending.push(EmulatorEvent::Paused);
+            }
+            InputEvent::Resume if self.state == RunState::Paused => {
+                self.state = RunState::Running;
+                self.clock.reset();
+                self.pending.push(EmulatorEvent::Resumed);
+            }
+            InputEvent::Pause | InputEvent::Resume => {}
+            InputEvent::TogglePause => {
+                let toggled = if self.state == RunState::Paused { InputEvent::Resume } else { InputEvent::Pause };
+                self.handle_event(toggled);
+            }
+            InputEvent::Reset => {
+                let error = self.power_on().err().map(|e| e.to_string());
+                self.pending.push(EmulatorEvent::Reset { error });
+            }
+            InputEvent::Load(rom) => {
+                let error = self.load_bytes(rom).err().map(|e| e.to_string());
+                self.pending.push(EmulatorEvent::Reset { error });
+            }
+            InputEvent::SaveState => self.pending.push(EmulatorEvent::StateSaved(self.cpu.save_state())),
+            InputEvent::LoadState(state) => {
+                let result = self.cpu.load_state(&state)
+                    .map(|warnings| warnings.iter().map(|warning| warning.to_string()).collect())
+                    .map_err(|e| e.to_string());
+                if result.is_ok() && matches!(self.state, RunState::Halted { .. }) {
+                    self.state = RunState::Running;
+                }
+                self.pending.push(EmulatorEvent::StateLoaded(result));
+            }
+            InputEvent::SetSpeed { timing, instructions_per_frame } => {
+                self.budget = FrameBudget::new(timing, instructions_per_frame);
+            }
+            InputEvent::SetClockRate(hz) => self.clock.set_hz(hz),
+            InputEvent::SetClockSpeed(speed) => self.clock.set_speed(speed),
+            InputEvent::SetVipDisplay(enabled) => {
+                self.setup.vip_display = enabled;
+                self.cpu.set_mapped_display(enabled);
+            }
+            InputEvent::SetQuirks(quirks) => self.setup.quirks = quirks,
+            InputEvent::Turbo(on) => self.turbo = on,
+            InputEvent::SkipInstruction => {
+                if let RunState::Halted { pc, .. } = self.state {
+                    self.cpu.pc = pc.wrapping_add(2);
+                    self.state = RunState::Running;
+                }
+            }
+        }
+    }
+
+    /// Runs one host frame (several with turbo on) that took dt, unless paused or halted
+    pub fn update(&mut self, dt: Duration) -> Vec<EmulatorEvent> {
+        let mut events = std::mem::take(&mut self.pending);
+        let frames = if self.turbo { TURBO_FRAMES } else { 1 };
+        for _ in 0..frames {
+            if self.state != RunState::Running {
+                break;
+            }
+            self.run_frame(dt, &mut events);
+        }
+
+        let sound = self.beeper.observe(self.cpu.beeper()) && self.state == RunState::Running;
+        if sound != self.sound {
+            self.sound = sound;
+            events.push(EmulatorEvent::Sound(sound));
+        }
+        events
+    }
+
+    fn run_frame(&mut self, dt: Duration, events: &mut Vec<EmulatorEvent>) {
+        let (keys, latched) = (self.keys, self.latched);
+        self.cpu.set_keys(keys);
+
+        let ticks = self.clock.advance(dt);
+        let (mut frames, mut instructions) = (0, 0);
+        if self.clock.hz().is_some() {
+            while instructions < ticks.instructions && self.step(events).is_some() {
+                instructions += 1;
+            }
+        } else {
+            frames = ticks.frames;
+            for _ in 0..ticks.frames {
+                self.budget.begin_frame();
+                while self.budget.has_time() {
+                    match self.step(events) {
+                        Some(cycles) => self.budget.spend(cycles),
+                        None => break,
+                    }
+                }
+            }
+        }
+        if self.state != RunState::Running {
+            return;
+        }
+
+        for _ in 0..ticks.timers {
+            // A beep the timers are about to end still counts as heard
+            self.beeper.observe(self.cpu.beeper());
+            self.cpu.update_timers();
+            self.beeper.tick();
+        }
+        events.push(EmulatorEvent::Frame { keys, latched, frames, instructions, timers: ticks.timers });
+    }
+
+    /// Runs one instruction and returns what it cost, or None if the machine halted on it or the
+    /// debugger holds it back
+    fn step(&mut self, events: &mut Vec<EmulatorEvent>) -> Option<u32> {
+        #[cfg(feature = "debugger")]
+        if !self.debugger.allows(self.cpu.pc) {
+            return None;
+        }
+        let cycles = match self.cpu.tick() {
+            Ok(cycles) => cycles,
+            Err(e) => {
+                let pc = e.pc().unwrap_or(self.cpu.pc);
+                self.state = RunState::Halted { pc, error: e.clone() };
+                events.push(EmulatorEvent::Halted { pc, error: e });
+                return None;
+            }
+        };
+
+        if let Some(key) = release_awaited_latch(&mut self.cpu, &mut self.keys, &mut self.latched) {
+            events.push(EmulatorEvent::LatchConsumed(key));
+        }
+        Some(cycles)
+    }
+
+    /// A fresh machine with the ROM loaded, running
+    fn power_on(&mut self) -> Result<(), EmulatorError> {
+        self.cpu = self.setup.machine();
+        self.state = RunState::Running;
+        self.clock.reset();
+        self.beeper = BeepSmoothing::new(self.beeper.min_frames);
+        match &self.rom {
+            Rom::None => Ok(()),
+            Rom::File(path) => self.cpu.load_rom(path),
+            Rom::Bytes(rom) => self.cpu.load_rom_bytes(rom),
+        }
+    }
+}
+
+/// FX0A takes a key once it's released, so a latched key it is waiting on is let go, from keys and
+/// from the machine. Returns the key released, if any
+pub(crate) fn release_awaited_latch(cpu: &mut CPU, keys: &mut u16, latched: &mut u16) -> Option<usize> {
+    let key = cpu.key_wait().filter(|key| *latched & (1 << key) != 0)?;
+    *latched &= !(1 << key);
+    *keys &= !(1 << key);
+    cpu.set_keys(*keys);
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::RomBuilder;
+
+    const FRAME: Duration = TIMER_INTERVAL;
+
+    /// Counts instructions in V0, sounding the beeper for the first few timer ticks
+    fn counter() -> RomBuilder {
+        RomBuilder::new().ld(1, 2).op(0xF118).label("loop").add(0, 1).jp("loop")
+    }
+
+    fn running(rom: RomBuilder) -> Emulator {
+        let setup = MachineSetup { seed: Some(1), ..Default::default() };
+        let mut emulator = Emulator::new(Config::default(), setup, TimingMode::Simple, 10);
+        emulator.load_bytes(rom.build().unwrap()).unwrap();
+        emulator
+    }
+
+    fn frames(events: &[EmulatorEvent]) -> usize {
+        events.iter().filter(|event| matches!(event, EmulatorEvent::Frame { .. })).count()
+    }
+
+    /// ticks budget frames with keys held, each with its timer tick
+    fn frame(keys: u16, ticks: u32) -> EmulatorEvent {
+        EmulatorEvent::Frame { keys, latched: 0, frames: ticks, instructions: 0, timers: ticks }
+    }
+
+    /// The timer ticks over every Frame event
+    fn timer_ticks(events: &[EmulatorEvent]) -> u32 {
+        events.iter()
+            .map(|event| match event {
+                EmulatorEvent::Frame { timers, .. } => *timers,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_update_runs_a_frame_and_ticks_timers_by_the_clock() {
+        let mut emulator = running(counter());
+        let events = emulator.update(FRAME);
+        assert_eq!(events, [frame(0, 1), EmulatorEvent::Sound(true)]);
+        assert_eq!(emulator.cpu.v[0], 4); // Two setup instructions, then four loops
+        assert_eq!(emulator.cpu.sound_timer, 1);
+
+        // However often the host updates, the budget runs once per 1/60s, with the rest carried over
+        assert_eq!(emulator.update(Duration::from_millis(10)), [frame(0, 0)]);
+        assert_eq!(emulator.cpu.v[0], 4);
+        assert_eq!(emulator.update(Duration::from_millis(7)), [
+            frame(0, 1),
+            EmulatorEvent::Sound(false),
+        ]);
+        assert_eq!(emulator.cpu.v[0], 9);
+    }
+
+    /// Sets the sound timer to st once, then spins
+    fn beep(st: u8) -> RomBuilder {
+        RomBuilder::new().ld(1, st).op(0xF118).spin()
+    }
+
+    fn sounds(events: &[EmulatorEvent]) -> Vec<bool> {
+        events.iter().filter_map(|event| match event {
+            EmulatorEvent::Sound(on) => Some(*on),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_short_beeps_are_held_for_the_minimum() {
+        // Set and run out within one frame, which would otherwise never sound at all
+        let mut emulator = running(beep(1));
+        assert_eq!(sounds(&emulator.update(FRAME)), [true]);
+        assert_eq!((emulator.cpu.sound_timer, emulator.sound()), (0, true));
+        assert!(sounds(&emulator.update(Duration::from_millis(10))).is_empty()); // No tick yet, still held on
+        assert_eq!(sounds(&emulator.update(Duration::from_millis(7))), [false]); // The second tick
+
+        let mut config = Config::default();
+        config.audio.min_beep_frames = 0;
+        let mut emulator = Emulator::new(config, MachineSetup::default(), TimingMode::Simple, 10);
+        emulator.load_bytes(beep(2).build().unwrap()).unwrap();
+        assert_eq!(sounds(&emulator.update(FRAME)), [true]);
+        assert_eq!(sounds(&emulator.update(FRAME)), [false]); // Stops with the timer
+    }
+
+    #[test]
+    fn test_set_config_changes_the_beep_length() {
+        let mut emulator = running(beep(2));
+        let mut config = Config::default();
+        config.audio.min_beep_frames = 0;
+        emulator.set_config(config.clone());
+        assert_eq!(emulator.config, config);
+
+        assert_eq!(sounds(&emulator.update(FRAME)), [true]);
+        assert_eq!(sounds(&emulator.update(FRAME)), [false]);
+    }
+
+    #[test]
+    fn test_long_beeps_are_not_extended() {
+        let mut emulator = running(beep(5));
+        let mut sounding = Vec::new();
+        for _ in 0..6 {
+            emulator.update(TIMER_INTERVAL);
+            sounding.push(emulator.sound());
+        }
+        // One tick per frame, the first in the frame that set the timer
+        assert_eq!(sounding, [true, true, true, true, false, false]);
+        assert_eq!(emulator.cpu.beeper(), BeeperState { on: false, remaining_frames: 0 });
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let mut emulator = running(counter());
+        emulator.update(FRAME);
+        emulator.handle_event(InputEvent::TogglePause);
+        assert_eq!(emulator.state(), &RunState::Paused);
+
+        // Nothing runs, the beeper stops, and pausing again changes nothing
+        emulator.handle_event(InputEvent::Pause);
+        assert_eq!(emulator.update(Duration::from_secs(1)), [EmulatorEvent::Paused, EmulatorEvent::Sound(false)]);
+        assert_eq!((emulator.cpu.v[0], emulator.cpu.sound_timer), (4, 1));
+
+        emulator.handle_event(InputEvent::TogglePause);
+        let events = emulator.update(FRAME);
+        assert_eq!(events[0], EmulatorEvent::Resumed);
+        assert_eq!(frames(&events), 1);
+        assert_eq!(emulator.cpu.v[0], 9);
+    }
+
+    #[test]
+    fn test_halt_and_reset() {
+        let mut emulator = running(RomBuilder::new().ld(0, 7).ret());
+        assert_eq!(emulator.update(FRAME), [EmulatorEvent::Halted { pc: 0x202, error: EmulatorError::StackUnderflow { pc: 0x202 } }]);
+        assert!(matches!(emulator.state(), RunState::Halted { pc: 0x202, .. }));
+        assert_eq!(emulator.update(FRAME), []);
+
+        emulator.handle_event(InputEvent::Reset);
+        assert_eq!((emulator.state(), emulator.cpu.v[0], emulator.cpu.pc), (&RunState::Running, 0, 0x200));
+        let events = emulator.update(FRAME);
+        assert_eq!(events[0], EmulatorEvent::Reset { error: None });
+        assert!(matches!(events[1], EmulatorEvent::Halted { .. }));
+    }
+
+    #[test]
+    fn test_skip_instruction_runs_on_past_the_error() {
+        let mut emulator = running(RomBuilder::new().op(0x0123).ld(0, 7).spin());
+        emulator.handle_event(InputEvent::SkipInstruction); // Not halted, so nothing to skip
+        let error = EmulatorError::UnknownOpcode { opcode: 0x0123, pc: 0x200 };
+        assert_eq!(emulator.update(FRAME), [EmulatorEvent::Halted { pc: 0x200, error }]);
+
+        emulator.handle_event(InputEvent::SkipInstruction);
+        assert_eq!(emulator.state(), &RunState::Running);
+        assert_eq!(frames(&emulator.update(FRAME)), 1);
+        assert_eq!(emulator.cpu.v[0], 7);
+    }
+
+    #[test]
+    fn test_reset_rereads_the_file() {
+        let path = std::env::temp_dir().join(format!("chip8-emulator-reset-{}.ch8", std::process::id()));
+        std::fs::write(&path, RomBuilder::new().ld(0, 1).spin().build().unwrap()).unwrap();
+        let mut emulator = Emulator::new(Config::default(), MachineSetup::default(), TimingMode::Simple, 10);
+        emulator.load(path.to_str().unwrap()).unwrap();
+        emulator.update(FRAME);
+        assert_eq!(emulator.cpu.v[0], 1);
+
+        std::fs::write(&path, RomBuilder::new().ld(0, 2).spin().build().unwrap()).unwrap();
+        emulator.handle_event(InputEvent::Reset);
+        emulator.update(FRAME);
+        assert_eq!(emulator.cpu.v[0], 2);
+
+        std::fs::remove_file(&path).unwrap();
+        emulator.handle_event(InputEvent::Reset);
+        assert!(matches!(&emulator.update(FRAME)[0], EmulatorEvent::Reset { error: Some(_) }));
+    }
+
+    #[test]
+    fn test_speed_change_and_turbo() {
+        let mut emulator = running(counter());
+        emulator.handle_event(InputEvent::SetSpeed { timing: TimingMode::Simple, instructions_per_frame: 20 });
+        assert_eq!(emulator.speed(), (TimingMode::Simple, 20));
+        emulator.update(FRAME);
+        assert_eq!(emulator.cpu.v[0], 9);
+
+        emulator.handle_event(InputEvent::Turbo(true));
+        let events = emulator.update(FRAME);
+        assert_eq!(frames(&events), TURBO_FRAMES as usize);
+        // Each turbo frame counts as dt, so the timers tick as often as the frames would
+        assert_eq!(timer_ticks(&events), TURBO_FRAMES);
+        assert_eq!(emulator.cpu.v[0], 49);
+
+        emulator.handle_event(InputEvent::Turbo(false));
+        assert_eq!(frames(&emulator.update(FRAME)), 1);
+    }
+
+    #[test]
+    fn test_clock_rate_runs_instructions_by_wall_time() {
+        let mut emulator = running(counter());
+        emulator.handle_event(InputEvent::SetClockRate(Some(700)));
+        assert_eq!(emulator.clock().hz(), Some(700));
+        // 7 instructions: the setup, then five loops
+        assert_eq!(emulator.update(Duration::from_millis(10))[0], EmulatorEvent::Frame { keys: 0, latched: 0, frames: 0, instructions: 7, timers: 0 });
+        assert_eq!(emulator.cpu.v[0], 3);
+        emulator.update(Duration::from_millis(30)); // 21 more
+        assert_eq!(emulator.cpu.v[0], 13);
+
+        emulator.handle_event(InputEvent::SetClockRate(None));
+        emulator.update(FRAME);
+        assert_eq!(emulator.cpu.v[0], 18);
+    }
+
+    #[test]
+    fn test_clock_speed_scales_frames_and_timers() {
+        let mut emulator = running(counter());
+        emulator.handle_event(InputEvent::SetClockSpeed(Speed::Half));
+        let ticked = |events: &[EmulatorEvent]| timer_ticks(events) > 0;
+
+        // Half speed ticks the timers, and runs the budget with them, every other interval
+        assert!(!ticked(&emulator.update(TIMER_INTERVAL)));
+        assert_eq!(emulator.cpu.v[0], 0);
+        assert!(ticked(&emulator.update(TIMER_INTERVAL)));
+        assert_eq!(emulator.cpu.v[0], 4);
+
+        emulator.handle_event(InputEvent::SetClockSpeed(Speed::Double));
+        assert_eq!(emulator.clock().speed(), Speed::Double);
+        let v0 = emulator.cpu.v[0];
+        emulator.update(FRAME);
+        assert_eq!(emulator.cpu.v[0], v0 + 10);
+    }
+
+    #[cfg(feature = "debugger")]
+    #[test]
+    fn test_timers_run_while_the_debugger_pauses() {
+        let mut emulator = running(RomBuilder::new().ld(1, 30).op(0xF115).label("loop").add(2, 1).jp("loop"));
+        emulator.update(FRAME);
+        let (counted, delay) = (emulator.cpu.v[2], emulator.cpu.delay_timer);
+
+        emulator.debugger.pause();
+        for _ in 0..3 {
+            emulator.update(TIMER_INTERVAL);
+        }
+        assert_eq!(emulator.cpu.v[2], counted);
+        assert_eq!(emulator.cpu.delay_timer, delay - 3);
+    }
+
+    #[test]
+    fn test_fx0a_takes_latched_key_once() {
+        // Waits for a key into V0, counting the waits satisfied in V1
+        let rom = RomBuilder::new().label("loop").op(0xF00A).add(1, 1).jp("loop");
+        let mut emulator = running(rom);
+        emulator.handle_event(InputEvent::Keys { down: 1 << 5, latched: 1 << 5 });
+        let events = emulator.update(FRAME);
+        assert!(events.contains(&EmulatorEvent::LatchConsumed(5)));
+        assert_eq!(events.last(), Some(&EmulatorEvent::Frame { keys: 1 << 5, latched: 1 << 5, frames: 1, instructions: 0, timers: 1 }));
+        assert_eq!((emulator.cpu.v[0], emulator.cpu.v[1]), (5, 1));
+
+        // A held key isn't consumed
+        let mut emulator = running(RomBuilder::new().label("loop").op(0xF00A).add(1, 1).jp("loop"));
+        emulator.handle_event(InputEvent::Keys { down: 1 << 5, latched: 0 });
+        assert!(!emulator.update(FRAME).iter().any(|event| matches!(event, EmulatorEvent::LatchConsumed(_))));
+    }
+
+    #[test]
+    fn test_frame_is_the_display() {
+        let mut emulator = running(RomBuilder::new().ld_i("dot").draw(0, 0, 1).spin().label("dot").data(&[0x80]));
+        emulator.update(FRAME);
+        assert_eq!(emulator.frame()[0], 1);
+        assert_eq!(emulator.frame().iter().filter(|pixel| **pixel != 0).count(), 1);
+    }
+
+    #[test]
+    fn test_vip_display_stays_on_across_resets() {
+        let mut emulator = running(counter());
+        emulator.handle_event(InputEvent::SetVipDisplay(true));
+        assert!(emulator.cpu.mapped_display());
+
+        emulator.handle_event(InputEvent::Reset);
+        assert!(emulator.cpu.mapped_display());
+    }
+
+    #[test]
+    fn test_quirks_change_on_the_next_reset() {
+        let mut emulator = running(counter());
+        emulator.handle_event(InputEvent::SetQuirks(Quirks::vip()));
+        assert_eq!((emulator.cpu.quirks(), emulator.setup().quirks), (Quirks::modern(), Quirks::vip()));
+
+        emulator.handle_event(InputEvent::Reset);
+        assert_eq!(emulator.cpu.quirks(), Quirks::vip());
+        emulator.handle_event(InputEvent::Load(RomBuilder::new().spin().build().unwrap()));
+        assert_eq!(emulator.cpu.quirks(), Quirks::vip());
+    }
+
+    #[test]
+    fn test_load_another_rom() {
+        let mut emulator = running(counter());
+        emulator.update(FRAME);
+        emulator.handle_event(InputEvent::Load(RomBuilder::new().ld(5, 7).spin().build().unwrap()));
+        assert_eq!(emulator.update(FRAME)[0], EmulatorEvent::Reset { error: None });
+        assert_eq!((emulator.cpu.v[0], emulator.cpu.v[5]), (0, 7));
+    }
+
+    #[test]
+    fn test_present_draws_the_changed_rows() {
+        struct Rows(Vec<u64>);
+        impl FrameSink for Rows {
+            fn draw(&mut self, _buffer: &[u8]) {}
+            fn draw_rows(&mut self, buffer: &[u8], dirty_rows: u64) {
+                assert_eq!(buffer.len(), DISPLAY_WIDTH * DISPLAY_HEIGHT);
+                self.0.push(dirty_rows);
+            }
+        }
+
+        let sprite = [0b1000_0000];
+        let mut emulator = running(RomBuilder::new().ld(0, 3).ld_i("sprite").draw(0, 0, 1).spin().label("sprite").data(&sprite));
+        let mut rows = Rows(Vec::new());
+        emulator.present(&mut rows);
+        emulator.update(FRAME);
+        emulator.present(&mut rows);
+        emulator.present(&mut rows);
+        assert_eq!(rows.0, [(1 << DISPLAY_HEIGHT) - 1, 1 << 3, 0]);
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        let mut emulator = running(counter());
+        emulator.update(FRAME);
+        let saved_v0 = emulator.cpu.v[0];
+        emulator.handle_event(InputEvent::SaveState);
+        let Some(EmulatorEvent::StateSaved(state)) = emulator.update(FRAME).first().cloned() else {
+            panic!("no state saved");
+        };
+        assert_ne!(emulator.cpu.v[0], saved_v0);
+
+        emulator.handle_event(InputEvent::LoadState(state));
+        emulator.handle_event(InputEvent::Pause); // Keep the loaded state still to look at it
+        assert_eq!(emulator.update(FRAME)[0], EmulatorEvent::StateLoaded(Ok(Vec::new())));
+        assert_eq!(emulator.cpu.v[0], saved_v0);
+
+        emulator.handle_event(InputEvent::LoadState(vec![1, 2, 3]));
+        assert!(matches!(emulator.update(FRAME)[0], EmulatorEvent::StateLoaded(Err(_))));
+    }
+}
@@ -0,0 +1,132 @@
+//! Why the CPU stopped: each failure as its own variant, with the opcode and the PC of the
+//! instruction that failed, so callers can tell them apart without reading the message
+use std::fmt;
+
+#[derive(Debug)]
+pub enum EmulatorError {
+    UnknownOpcode { opcode: u16, pc: u16 },
+    /// 2NNN with every stack frame in use
+    StackOverflow { pc: u16 },
+    /// 00EE with nothing to return to
+    StackUnderflow { pc: u16 },
+    MemoryOutOfBounds { addr: u16, pc: u16 },
+    /// FX29 or FX30 with VX past the last font digit
+    InvalidDigit { digit: u8, pc: u16 },
+    RomTooLarge { size: usize },
+    /// Reading the ROM failed
+    Io(std::io::Error),
+}
+
+impl EmulatorError {
+    /// The PC of the instruction that failed, for the errors an instruction causes
+    pub fn pc(&self) -> Option<u16> {
+        match self {
+            EmulatorError::UnknownOpcode { pc, .. }
+            | EmulatorError::StackOverflow { pc }
+            | EmulatorError::StackUnderflow { pc }
+            | EmulatorError::MemoryOutOfBounds { pc, .. }
+            | EmulatorError::InvalidDigit { pc, .. } => Some(*pc),
+            EmulatorError::RomTooLarge { .. } | EmulatorError::Io(_) => None,
+        }
+    }
+}
+
+// The PC is left out: the places that show these print it alongside
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EmulatorError::UnknownOpcode { opcode, .. } => write!(f, "Unknown opcode {:04X}", opcode),
+            EmulatorError::StackOverflow { .. } => write!(f, "Stack overflow"),
+            EmulatorError::StackUnderflow { .. } => write!(f, "Stack underflow"),
+            EmulatorError::MemoryOutOfBounds { addr, .. } => write!(f, "Memory access out of bounds at {:#05X}", addr),
+            EmulatorError::InvalidDigit { digit, .. } => write!(f, "Invalid character {:#04X} in VX for the font", digit),
+            EmulatorError::RomTooLarge { size } => write!(f, "ROM too large ({} bytes)", size),
+            EmulatorError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EmulatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EmulatorError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// std::io::Error is neither Clone nor PartialEq, so an Io error is copied and compared by its kind
+// and message. Halted states and events carry these, and those are both
+impl Clone for EmulatorError {
+    fn clone(&self) -> Self {
+        match self {
+            EmulatorError::UnknownOpcode { opcode, pc } => EmulatorError::UnknownOpcode { opcode: *opcode, pc: *pc },
+            EmulatorError::StackOverflow { pc } => EmulatorError::StackOverflow { pc: *pc },
+            EmulatorError::StackUnderflow { pc } => EmulatorError::StackUnderflow { pc: *pc },
+            EmulatorError::MemoryOutOfBounds { addr, pc } => EmulatorError::MemoryOutOfBounds { addr: *addr, pc: *pc },
+            EmulatorError::InvalidDigit { digit, pc } => EmulatorError::InvalidDigit { digit: *digit, pc: *pc },
+            EmulatorError::RomTooLarge { size } => EmulatorError::RomTooLarge { size: *size },
+            EmulatorError::Io(e) => EmulatorError::Io(std::io::Error::new(e.kind(), e.to_string())),
+        }
+    }
+}
+
+impl PartialEq for EmulatorError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (EmulatorError::Io(a), EmulatorError::Io(b)) => a.kind() == b.kind() && a.to_string() == b.to_string(),
+            (EmulatorError::UnknownOpcode { opcode: a, pc: pc_a }, EmulatorError::UnknownOpcode { opcode: b, pc: pc_b }) => (a, pc_a) == (b, pc_b),
+            (EmulatorError::StackOverflow { pc: a }, EmulatorError::StackOverflow { pc: b })
+            | (EmulatorError::StackUnderflow { pc: a }, EmulatorError::StackUnderflow { pc: b }) => a == b,
+            (EmulatorError::MemoryOutOfBounds { addr: a, pc: pc_a }, EmulatorError::MemoryOutOfBounds { addr: b, pc: pc_b }) => (a, pc_a) == (b, pc_b),
+            (EmulatorError::InvalidDigit { digit: a, pc: pc_a }, EmulatorError::InvalidDigit { digit: b, pc: pc_b }) => (a, pc_a) == (b, pc_b),
+            (EmulatorError::RomTooLarge { size: a }, EmulatorError::RomTooLarge { size: b }) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl From<std::io::Error> for EmulatorError {
+    fn from(e: std::io::Error) -> Self {
+        EmulatorError::Io(e)
+    }
+}
+
+/// For the file-level code (replays, headless runs) that reports everything as std::io::Error
+impl From<EmulatorError> for std::io::Error {
+    fn from(e: EmulatorError) -> Self {
+        match e {
+            EmulatorError::Io(e) => e,
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        assert_eq!(EmulatorError::UnknownOpcode { opcode: 0x0123, pc: 0x200 }.to_string(), "Unknown opcode 0123");
+        assert_eq!(EmulatorError::MemoryOutOfBounds { addr: 0x1000, pc: 0x2FE }.to_string(), "Memory access out of bounds at 0x1000");
+        assert_eq!(EmulatorError::RomTooLarge { size: 4000 }.to_string(), "ROM too large (4000 bytes)");
+    }
+
+    #[test]
+    fn test_pc() {
+        assert_eq!(EmulatorError::StackOverflow { pc: 0x2A4 }.pc(), Some(0x2A4));
+        assert_eq!(EmulatorError::RomTooLarge { size: 4000 }.pc(), None);
+    }
+
+    #[test]
+    fn test_io_errors_clone_and_convert() {
+        let missing = EmulatorError::from(std::io::Error::new(std::io::ErrorKind::NotFound, "no ROM"));
+        assert_eq!(missing.clone(), missing);
+        assert_ne!(missing, EmulatorError::Io(std::io::Error::other("no ROM")));
+        assert_eq!(std::io::Error::from(missing).kind(), std::io::ErrorKind::NotFound);
+
+        let underflow = std::io::Error::from(EmulatorError::StackUnderflow { pc: 0x200 });
+        assert_eq!((underflow.kind(), underflow.to_string()), (std::io::ErrorKind::InvalidData, "Stack underflow".to_string()));
+    }
+}
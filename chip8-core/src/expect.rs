@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use crate::constants::{MEMORY_SIZE, REGISTERS_COUNT};
+use crate::cpu::CPU;
+
+/// Final machine state a test ROM should leave behind, from a `--verify` file:
+///
+/// ```toml
+/// pc = 0x224
+/// i = 0x300
+/// v = { 0 = 0x2A, F = 1, "1..4" = 0 }     # Register numbers are hex, as in VF
+/// memory = { "0x300" = [1, 2, 3], "0x400..0x410" = 0 }
+/// ```
+///
+/// A list of bytes is expected from its address onwards; a range expects every byte in it to be one value
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expectation {
+    pub pc: Option<u16>,
+    pub i: Option<u16>,
+    pub v: BTreeMap<usize, u8>,
+    pub memory: BTreeMap<usize, u8>,
+}
+
+/// One field that didn't end up as expected, by a --verify file or a replay
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:<8} expected {:<18} got {}", self.field, self.expected, self.actual)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawExpectation {
+    pc: Option<u16>,
+    i: Option<u16>,
+    #[serde(default)]
+    v: BTreeMap<String, u8>,
+    #[serde(default)]
+    memory: BTreeMap<String, Bytes>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Bytes {
+    One(u8),
+    Many(Vec<u8>),
+}
+
+impl Expectation {
+    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+        Expectation::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, std::io::Error> {
+        let raw: RawExpectation = toml::from_str(text).map_err(|e| invalid(e.to_string()))?;
+        let mut expectation = Expectation { pc: raw.pc, i: raw.i, ..Default::default() };
+
+        for (key, value) in raw.v {
+            let registers = parse_range(&key, parse_register)
+                .filter(|registers| registers.end <= REGISTERS_COUNT)
+                .ok_or_else(|| invalid(format!("v: '{}' is not a register (0-F) or range of them", key)))?;
+            for register in registers {
+                expectation.v.insert(register, value);
+            }
+        }
+
+        for (key, bytes) in raw.memory {
+            let addresses = parse_range(&key, parse_address)
+                .ok_or_else(|| invalid(format!("memory: '{}' is not an address or range", key)))?;
+            let expected = match bytes {
+                Bytes::One(byte) if addresses.len() > 1 => vec![byte; addresses.len()],
+                Bytes::One(byte) => vec![byte],
+                Bytes::Many(_) if addresses.len() > 1 => {
+                    return Err(invalid(format!("memory: the range '{}' takes a single value", key)));
+                }
+                Bytes::Many(bytes) => bytes,
+            };
+            if addresses.start + expected.len() > MEMORY_SIZE {
+                return Err(invalid(format!("memory: '{}' runs past the end of memory", key)));
+            }
+            for (offset, byte) in expected.into_iter().enumerate() {
+                expectation.memory.insert(addresses.start + offset, byte);
+            }
+        }
+
+        Ok(expectation)
+    }
+
+    /// Everything that doesn't match, in the order pc, i, registers, memory
+    pub fn check(&self, cpu: &CPU) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let mut compare = |field: String, expected: String, actual: String| {
+            if expected != actual {
+                mismatches.push(Mismatch { field, expected, actual });
+            }
+        };
+
+        if let Some(pc) = self.pc {
+            compare("pc".to_string(), format!("{:#05X}", pc), format!("{:#05X}", cpu.pc));
+        }
+        if let Some(i) = self.i {
+            compare("i".to_string(), format!("{:#05X}", i), format!("{:#05X}", cpu.i));
+        }
+        for (register, value) in &self.v {
+            compare(format!("V{:X}", register), format!("{:#04X}", value), format!("{:#04X}", cpu.v[*register]));
+        }
+        for (addr, value) in &self.memory {
+            compare(format!("[{:#05X}]", addr), format!("{:#04X}", value), format!("{:#04X}", cpu.memory[*addr]));
+        }
+        mismatches
+    }
+}
+
+/// `N` or `N..M` (M exclusive), each side parsed by parse
+fn parse_range(key: &str, parse: fn(&str) -> Option<usize>) -> Option<std::ops::Range<usize>> {
+    match key.split_once("..") {
+        Some((start, end)) => Some(parse(start)?..parse(end)?).filter(|range| !range.is_empty()),
+        None => parse(key).map(|n| n..n + 1),
+    }
+}
+
+/// A register number: hex, with or without 0x or a V in front
+fn parse_register(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let digits = text.strip_prefix(['V', 'v']).or_else(|| text.strip_prefix("0x")).unwrap_or(text);
+    usize::from_str_radix(digits, 16).ok()
+}
+
+/// An address: hex with 0x, otherwise decimal
+fn parse_address(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let address = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    (address < MEMORY_SIZE).then_some(address)
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_example() {
+        let expectation = Expectation::parse(r#"
+            pc = 0x224
+            v = { 0 = 0x2A, 0xF = 1, VA = 3 }
+            memory = { "0x300" = [1, 2, 3], "16" = 9 }
+        "#).unwrap();
+
+        assert_eq!(expectation.pc, Some(0x224));
+        assert_eq!(expectation.i, None);
+        assert_eq!(expectation.v, BTreeMap::from([(0, 0x2A), (0xA, 3), (0xF, 1)]));
+        assert_eq!(expectation.memory, BTreeMap::from([(16, 9), (0x300, 1), (0x301, 2), (0x302, 3)]));
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        let expectation = Expectation::parse(r#"
+            v = { "1..4" = 0 }
+            memory = { "0x400..0x403" = 0xFF }
+        "#).unwrap();
+
+        assert_eq!(expectation.v.keys().copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(expectation.memory, BTreeMap::from([(0x400, 0xFF), (0x401, 0xFF), (0x402, 0xFF)]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in [
+            "v = { 10 = 1 }",                   // No V16
+            "v = { G = 1 }",
+            "v = { \"4..4\" = 1 }",             // Empty range
+            "memory = { \"0x1000\" = 1 }",      // Past the end
+            "memory = { \"0xFFF\" = [1, 2] }",
+            "memory = { \"0x300..0x302\" = [1, 2] }",
+            "memory = { \"0x300\" = 256 }",
+            "pc = \"start\"",
+            "sp = 1",
+        ] {
+            assert!(Expectation::parse(text).is_err(), "{} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let mut cpu = CPU::new();
+        cpu.pc = 0x224;
+        cpu.v[0] = 0x2A;
+        cpu.memory[0x300] = 1;
+        cpu.memory[0x301] = 5;
+
+        let expectation = Expectation::parse(r#"
+            pc = 0x224
+            i = 0x300
+            v = { 0 = 0x2A, F = 1 }
+            memory = { "0x300" = [1, 2] }
+        "#).unwrap();
+
+        let fields = expectation.check(&cpu).into_iter()
+            .map(|mismatch| (mismatch.field, mismatch.expected, mismatch.actual))
+            .collect::<Vec<_>>();
+        assert_eq!(fields, [
+            ("i".to_string(), "0x300".to_string(), "0x000".to_string()),
+            ("VF".to_string(), "0x01".to_string(), "0x00".to_string()),
+            ("[0x301]".to_string(), "0x02".to_string(), "0x05".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_empty_expectation_always_passes() {
+        assert!(Expectation::parse("").unwrap().check(&CPU::new()).is_empty());
+    }
+}
@@ -0,0 +1,287 @@
+use std::time::{Duration, Instant};
+use crate::cpu::CPU;
+use crate::error::EmulatorError;
+use crate::pattern::Pattern;
+use crate::run::stuck;
+use crate::timing::{FrameBudget, TimingMode};
+
+/// Exit codes of `chip8_emulator headless`, shown in its --help
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Ran to --max-frames, or halted with --exit-on-halt
+  1  --verify found differences, or the run could not start
+  2  --timeout-secs passed first
+  3  The ROM hit an emulation error";
+
+/// When a headless run stops on its own
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Limits {
+    pub max_frames: Option<u64>,
+    pub exit_on_halt: bool,
+    pub timeout: Option<Duration>,
+}
+
+/// How a run was going at the end of a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub frames: u64,
+    pub halted: bool, // The last frame ended stuck: spinning on a jump to itself, or waiting for a key
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunEnd {
+    FrameLimit,
+    Halted,
+    Timeout,
+    Error(EmulatorError),
+}
+
+impl RunEnd {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            RunEnd::FrameLimit | RunEnd::Halted => 0,
+            RunEnd::Timeout => 2,
+            RunEnd::Error(_) => 3,
+        }
+    }
+}
+
+impl std::fmt::Display for RunEnd {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RunEnd::FrameLimit => write!(f, "reached the frame limit"),
+            RunEnd::Halted => write!(f, "halted"),
+            RunEnd::Timeout => write!(f, "timed out"),
+            RunEnd::Error(e) => match e.pc() {
+                Some(pc) => write!(f, "emulation error at PC {:#05X}: {}", pc, e),
+                None => write!(f, "emulation error: {}", e),
+            },
+        }
+    }
+}
+
+/// Which limit, if any, ends the run. A halt wins over the others since it says the most about the ROM
+pub fn should_stop(limits: &Limits, progress: &Progress) -> Option<RunEnd> {
+    if limits.exit_on_halt && progress.halted {
+        Some(RunEnd::Halted)
+    } else if limits.max_frames.is_some_and(|max| progress.frames >= max) {
+        Some(RunEnd::FrameLimit)
+    } else if limits.timeout.is_some_and(|timeout| progress.elapsed >= timeout) {
+        Some(RunEnd::Timeout)
+    } else {
+        None
+    }
+}
+
+/// Runs whole frames without a window or pacing, each the budget's instructions then one timer tick,
+/// until a limit is reached or the ROM fails. Returns how it ended and the frames run
+pub fn run(cpu: &mut CPU, budget: &mut FrameBudget, limits: &Limits) -> (RunEnd, u64) {
+    run_with(cpu, budget, limits, |_, _| {})
+}
+
+/// As run, calling after_frame at the end of every frame, before the limits are checked
+pub fn run_with(
+    cpu: &mut CPU,
+    budget: &mut FrameBudget,
+    limits: &Limits,
+    mut after_frame: impl FnMut(&CPU, &Progress),
+) -> (RunEnd, u64) {
+    let start = Instant::now();
+    let mut progress = Progress { frames: 0, halted: false, elapsed: Duration::ZERO };
+
+    loop {
+        if let Some(end) = should_stop(limits, &progress) {
+            return (end, progress.frames);
+        }
+        match run_frame(cpu, budget) {
+            Ok(halted) => progress.halted = halted,
+            Err(e) => return (RunEnd::Error(e), progress.frames),
+        }
+        progress.frames += 1;
+        progress.elapsed = start.elapsed();
+        after_frame(cpu, &progress);
+    }
+}
+
+/// One frame and its timer tick; true if it ended stuck
+pub fn run_frame(cpu: &mut CPU, budget: &mut FrameBudget) -> Result<bool, EmulatorError> {
+    let halted = run_instructions(cpu, budget)?;
+    cpu.update_timers();
+    Ok(halted)
+}
+
+/// Runs frames of ten instructions, as the SDL window does, until the pattern (see `pattern`) shows
+/// anywhere on the display, checking after each frame. False if max_frames ran first or the ROM failed.
+/// For tests, so it panics when the pattern itself is malformed
+pub fn run_until_display_matches(cpu: &mut CPU, pattern: &str, max_frames: u32) -> bool {
+    let pattern = Pattern::parse(pattern).unwrap_or_else(|e| panic!("{}", e));
+    let mut budget = FrameBudget::new(TimingMode::Simple, 10);
+    for _ in 0..max_frames {
+        if run_frame(cpu, &mut budget).is_err() {
+            return false;
+        }
+        if pattern.find(&cpu.display).is_some() {
+            return true;
+        }
+    }
+    false
+}
+
+/// One frame's instructions, leaving the timers alone; true if the last of them went nowhere
+pub fn run_instructions(cpu: &mut CPU, budget: &mut FrameBudget) -> Result<bool, EmulatorError> {
+    let mut halted = false;
+    budget.begin_frame();
+    while budget.has_time() {
+        let (pc, depth) = (cpu.pc, cpu.stack.len());
+        let cycles = cpu.tick()?;
+        budget.spend(cycles);
+        halted = stuck(cpu, pc, depth);
+    }
+    Ok(halted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::RomBuilder;
+
+    /// V0 counts up forever, once every two instructions
+    fn counter_rom() -> RomBuilder {
+        RomBuilder::new().label("loop").add(0, 1).jp("loop")
+    }
+
+    fn frames(max: u64) -> Limits {
+        Limits { max_frames: Some(max), ..Default::default() }
+    }
+
+    fn progress(frames: u64, halted: bool, secs: u64) -> Progress {
+        Progress { frames, halted, elapsed: Duration::from_secs(secs) }
+    }
+
+    #[test]
+    fn test_should_stop() {
+        let all = Limits { max_frames: Some(10), exit_on_halt: true, timeout: Some(Duration::from_secs(5)) };
+        assert_eq!(should_stop(&all, &progress(9, false, 4)), None);
+        assert_eq!(should_stop(&all, &progress(10, false, 4)), Some(RunEnd::FrameLimit));
+        assert_eq!(should_stop(&all, &progress(9, false, 5)), Some(RunEnd::Timeout));
+        assert_eq!(should_stop(&all, &progress(10, true, 5)), Some(RunEnd::Halted));
+
+        // Halting only counts when asked for, and no limits never stop
+        assert_eq!(should_stop(&frames(10), &progress(3, true, 0)), None);
+        assert_eq!(should_stop(&Limits::default(), &progress(u64::MAX, true, u64::MAX)), None);
+    }
+
+    #[test]
+    fn test_exit_codes_are_documented() {
+        for end in [RunEnd::FrameLimit, RunEnd::Halted, RunEnd::Timeout, RunEnd::Error(EmulatorError::StackUnderflow { pc: 0x200 })] {
+            assert!(EXIT_CODES_HELP.contains(&format!("\n  {}  ", end.exit_code())), "{:?}", end);
+        }
+        assert_ne!(RunEnd::Timeout.exit_code(), RunEnd::FrameLimit.exit_code());
+    }
+
+    #[test]
+    fn test_runs_budget_per_frame() {
+        let mut cpu = RomBuilder::new().ld(1, 30).op(0xF115).label("loop").add(0, 1).jp("loop").cpu().unwrap();
+        let mut budget = FrameBudget::new(TimingMode::Simple, 10);
+
+        assert_eq!(run(&mut cpu, &mut budget, &frames(3)), (RunEnd::FrameLimit, 3));
+        // Two setup instructions, then 28 spread over the loop's two
+        assert_eq!(cpu.v[0], 14);
+        assert_eq!(cpu.delay_timer, 27);
+    }
+
+    #[test]
+    fn test_exit_on_halt() {
+        let mut cpu = RomBuilder::new().ld(0, 1).spin().cpu().unwrap();
+        let limits = Limits { max_frames: Some(100), exit_on_halt: true, ..Default::default() };
+        assert_eq!(run(&mut cpu, &mut FrameBudget::new(TimingMode::Simple, 10), &limits), (RunEnd::Halted, 1));
+
+        // A busy loop isn't a halt
+        let mut cpu = counter_rom().cpu().unwrap();
+        assert_eq!(run(&mut cpu, &mut FrameBudget::new(TimingMode::Simple, 10), &limits).0, RunEnd::FrameLimit);
+    }
+
+    #[test]
+    fn test_after_frame_sees_every_frame() {
+        let mut cpu = counter_rom().cpu().unwrap();
+        let mut seen = Vec::new();
+        let (end, ran) = run_with(&mut cpu, &mut FrameBudget::new(TimingMode::Simple, 10), &frames(3), |cpu, progress| {
+            seen.push((progress.frames, cpu.v[0]));
+        });
+        assert_eq!((end, ran), (RunEnd::FrameLimit, 3));
+        assert_eq!(seen, [(1, 5), (2, 10), (3, 15)]);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut cpu = RomBuilder::new().spin().cpu().unwrap();
+        let limits = Limits { timeout: Some(Duration::ZERO), ..Default::default() };
+        let (end, ran) = run(&mut cpu, &mut FrameBudget::new(TimingMode::Simple, 10), &limits);
+        assert_eq!((end.exit_code(), ran), (2, 0));
+    }
+
+    #[test]
+    fn test_stops_on_error() {
+        let mut cpu = RomBuilder::new().ret().cpu().unwrap();
+        let (end, ran) = run(&mut cpu, &mut FrameBudget::new(TimingMode::Simple, 10), &frames(5));
+        assert_eq!(end, RunEnd::Error(EmulatorError::StackUnderflow { pc: 0x200 }));
+        assert_eq!((end.exit_code(), ran), (3, 0));
+    }
+
+    /// Holds the delay timer at 200, loops 25 times, draws font digit 0, then spins
+    fn slow_zero() -> RomBuilder {
+        RomBuilder::new()
+            .ld(3, 200)
+            .op(0xF315) // LD DT, V3
+            .ld(0, 25)
+            .label("wait")
+            .add(0, 0xFF)
+            .op(0x3000) // SE V0, 0
+            .jp("wait")
+            .ld(1, 40)
+            .ld(2, 9)
+            .op(0xF029) // LD F, V0
+            .draw(1, 2, 5)
+            .spin()
+    }
+
+    const ZERO: &str = "
+        ####
+        #..#
+        #..#
+        #..#
+        ####
+    ";
+
+    #[test]
+    fn test_runs_until_display_matches() {
+        // The draw is the 81st instruction, in the ninth frame
+        let mut cpu = slow_zero().cpu().unwrap();
+        assert!(!run_until_display_matches(&mut cpu, ZERO, 8));
+        assert!(run_until_display_matches(&mut cpu, ZERO, 1));
+        assert_eq!(cpu.display[9 * crate::constants::DISPLAY_WIDTH + 40], 1);
+
+        // Returns on the frame it shows up, not at max_frames
+        let mut cpu = slow_zero().cpu().unwrap();
+        assert!(run_until_display_matches(&mut cpu, ZERO, 1000));
+        assert_eq!(cpu.delay_timer, 200 - 9);
+    }
+
+    #[test]
+    fn test_display_never_matching() {
+        let mut cpu = RomBuilder::new().ld(0, 8).op(0xF029).draw(0, 0, 5).spin().cpu().unwrap();
+        assert!(!run_until_display_matches(&mut cpu, ZERO, 10)); // An 8, not a 0
+        assert!(run_until_display_matches(&mut cpu, "####\n#??#\n####", 1));
+
+        // A failing ROM ends it early
+        let mut cpu = RomBuilder::new().ret().cpu().unwrap();
+        assert!(!run_until_display_matches(&mut cpu, "?", 10));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected")]
+    fn test_malformed_display_pattern_panics() {
+        run_until_display_matches(&mut CPU::new(), "#o#", 1);
+    }
+}
@@ -0,0 +1,110 @@
+use std::ops::Range;
+
+/// A pseudo-peripheral mapped over part of memory.
+/// Every CPU access to its range (fetches, sprite reads, FX33/FX55/FX65) goes through it first
+pub trait MemoryHook {
+    /// Returns a value to read instead of memory, or None to read memory as usual
+    fn read(&mut self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    /// Sees every write into the range. Returning false vetoes it, leaving memory unchanged
+    fn write(&mut self, _addr: u16, _value: u8) -> bool {
+        true
+    }
+}
+
+/// Registered hooks, at most one per address
+#[derive(Default)]
+pub struct MemoryHooks {
+    hooks: Vec<(Range<u16>, Box<dyn MemoryHook>)>,
+}
+
+impl MemoryHooks {
+    /// Maps hook over range, replacing any hooks the range overlaps
+    pub fn insert(&mut self, range: Range<u16>, hook: Box<dyn MemoryHook>) {
+        self.hooks.retain(|(existing, _)| existing.end <= range.start || range.end <= existing.start);
+        self.hooks.push((range, hook));
+    }
+
+    pub fn clear(&mut self) {
+        self.hooks.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub fn read(&mut self, addr: u16) -> Option<u8> {
+        self.find(addr).and_then(|hook| hook.read(addr))
+    }
+
+    /// True if the write should reach memory
+    pub fn write(&mut self, addr: u16, value: u8) -> bool {
+        self.find(addr).is_none_or(|hook| hook.write(addr, value))
+    }
+
+    fn find(&mut self, addr: u16) -> Option<&mut Box<dyn MemoryHook>> {
+        self.hooks.iter_mut().find(|(range, _)| range.contains(&addr)).map(|(_, hook)| hook)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Fixed(u8);
+
+    impl MemoryHook for Fixed {
+        fn read(&mut self, _addr: u16) -> Option<u8> {
+            Some(self.0)
+        }
+    }
+
+    /// Records writes and vetoes odd values
+    struct Log(Rc<RefCell<Vec<(u16, u8)>>>);
+
+    impl MemoryHook for Log {
+        fn write(&mut self, addr: u16, value: u8) -> bool {
+            self.0.borrow_mut().push((addr, value));
+            value.is_multiple_of(2)
+        }
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        let mut hooks = MemoryHooks::default();
+        hooks.insert(0x100..0x104, Box::new(Fixed(7)));
+
+        assert_eq!(hooks.read(0x0FF), None);
+        assert_eq!(hooks.read(0x100), Some(7));
+        assert_eq!(hooks.read(0x103), Some(7));
+        assert_eq!(hooks.read(0x104), None);
+    }
+
+    #[test]
+    fn test_overlapping_hook_replaces_existing() {
+        let mut hooks = MemoryHooks::default();
+        hooks.insert(0x100..0x110, Box::new(Fixed(1)));
+        hooks.insert(0x200..0x210, Box::new(Fixed(2)));
+        hooks.insert(0x10F..0x120, Box::new(Fixed(3)));
+
+        assert_eq!(hooks.read(0x100), None);
+        assert_eq!(hooks.read(0x10F), Some(3));
+        assert_eq!(hooks.read(0x200), Some(2));
+    }
+
+    #[test]
+    fn test_write_observe_and_veto() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut hooks = MemoryHooks::default();
+        hooks.insert(0x300..0x301, Box::new(Log(log.clone())));
+
+        assert!(hooks.write(0x300, 2));
+        assert!(!hooks.write(0x300, 3));
+        assert!(hooks.write(0x301, 3));
+        assert_eq!(*log.borrow(), vec![(0x300, 2), (0x300, 3)]);
+    }
+}
@@ -0,0 +1,62 @@
+//! The CHIP-8 interpreter and everything around it that needs no window: timing, save states,
+//! replays, the headless runner and the test-ROM tooling. Frontends such as `chip8-sdl` build on it.
+
+#[cfg(feature = "zip")]
+pub mod archive;
+#[cfg(feature = "async")]
+pub mod async_runner;
+pub mod blend;
+pub mod clock;
+pub mod cpu;
+pub mod constants;
+pub mod config;
+pub mod config_reload;
+pub mod config_save;
+pub mod expect;
+pub mod headless;
+pub mod hooks;
+#[cfg(feature = "debugger")]
+pub mod debugger;
+#[cfg(feature = "debugger")]
+pub mod disassembler;
+#[cfg(feature = "embedded-graphics")]
+pub mod embedded;
+pub mod diagnostics;
+pub mod emulator;
+pub mod error;
+pub mod net;
+pub mod pacing;
+pub mod panel;
+pub mod pattern;
+pub mod playlist;
+pub mod pnm;
+pub mod quirks;
+#[cfg(feature = "remote-debug")]
+pub mod remote_debug;
+#[cfg(feature = "recording")]
+pub mod replay;
+pub mod rng;
+pub mod rom;
+pub mod rom_builder;
+pub mod run;
+pub mod savestate;
+pub mod selftest;
+pub mod session;
+pub mod sink;
+#[cfg(feature = "ssd1306")]
+pub mod ssd1306;
+pub mod thumbnails;
+pub mod timing;
+pub mod trace;
+pub mod uninit;
+
+pub use config::Config;
+pub use cpu::CPU;
+pub use emulator::{Emulator, EmulatorEvent, InputEvent};
+pub use error::EmulatorError;
+pub use expect::{Expectation, Mismatch};
+pub use headless::{Limits, RunEnd};
+pub use quirks::Quirks;
+pub use rom_builder::RomBuilder;
+pub use sink::FrameSink;
+pub use timing::{FrameBudget, TimingMode};
@@ -0,0 +1,460 @@
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT, INPUTS_COUNT};
+
+const TAG_KEY: u8 = 0x01;
+const TAG_FRAME: u8 = 0x02;
+/// How long a frame may wait on a client that stopped reading before the client is dropped,
+/// so a stalled link can't freeze the host's game
+pub const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A message exchanged between host and client.
+/// On the wire each message is a big-endian u16 payload length followed by the payload,
+/// whose first byte is the message tag.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// Client -> host: a CHIP-8 key changed state on the client's keypad
+    Key { key: u8, pressed: bool, frame: u32 },
+    /// Host -> client: display pixels that flipped since the previous frame, plus the buzzer state
+    Frame { buzzer: bool, flipped: Vec<u16> },
+}
+
+impl Message {
+    /// Serializes the message, including its length prefix
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::new();
+
+        match self {
+            Message::Key { key, pressed, frame } => {
+                payload.push(TAG_KEY);
+                payload.push(*key);
+                payload.push(*pressed as u8);
+                payload.extend_from_slice(&frame.to_be_bytes());
+            }
+            Message::Frame { buzzer, flipped } => {
+                payload.push(TAG_FRAME);
+                payload.push(*buzzer as u8);
+                payload.extend_from_slice(&(flipped.len() as u16).to_be_bytes());
+                for index in flipped {
+                    payload.extend_from_slice(&index.to_be_bytes());
+                }
+            }
+        }
+
+        let mut bytes = (payload.len() as u16).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    /// Parses a message payload (without its length prefix)
+    pub fn decode(payload: &[u8]) -> Result<Message, std::io::Error> {
+        let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+
+        match payload.first() {
+            Some(&TAG_KEY) => {
+                if payload.len() != 7 {
+                    return Err(invalid("Malformed key message"));
+                }
+                let key = payload[1];
+                if key as usize >= INPUTS_COUNT {
+                    return Err(invalid("Key index out of range"));
+                }
+                Ok(Message::Key {
+                    key,
+                    pressed: payload[2] != 0,
+                    frame: u32::from_be_bytes([payload[3], payload[4], payload[5], payload[6]]),
+                })
+            }
+            Some(&TAG_FRAME) => {
+                if payload.len() < 4 {
+                    return Err(invalid("Malformed frame message"));
+                }
+                let count = u16::from_be_bytes([payload[2], payload[3]]) as usize;
+                if payload.len() != 4 + count * 2 {
+                    return Err(invalid("Frame message length does not match pixel count"));
+                }
+                let flipped = payload[4..]
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect::<Vec<u16>>();
+                if flipped.iter().any(|index| *index as usize >= DISPLAY_WIDTH * DISPLAY_HEIGHT) {
+                    return Err(invalid("Pixel index out of range"));
+                }
+                Ok(Message::Frame { buzzer: payload[1] != 0, flipped })
+            }
+            _ => Err(invalid("Unknown message tag")),
+        }
+    }
+}
+
+/// Reads one length-prefixed message, blocking until it has fully arrived
+pub fn read_message(reader: &mut impl Read) -> Result<Message, std::io::Error> {
+    let mut len = [0; 2];
+    reader.read_exact(&mut len)?;
+
+    let mut payload = vec![0; u16::from_be_bytes(len) as usize];
+    reader.read_exact(&mut payload)?;
+
+    Message::decode(&payload)
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Message) -> Result<(), std::io::Error> {
+    writer.write_all(&message.encode())
+}
+
+/// Lists the indices of pixels that differ between two display buffers
+pub fn diff_display(prev: &[u8], next: &[u8]) -> Vec<u16> {
+    prev.iter()
+        .zip(next)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(index, _)| index as u16)
+        .collect()
+}
+
+/// Flips the given pixels, turning the previous frame into the next one
+pub fn apply_display_diff(buffer: &mut [u8], flipped: &[u16]) {
+    for index in flipped {
+        if let Some(pixel) = buffer.get_mut(*index as usize) {
+            *pixel ^= 1;
+        }
+    }
+}
+
+/// What happened on the host's network side since the last poll
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostEvent {
+    Connected(SocketAddr),
+    Key { key: usize, pressed: bool },
+    Disconnected,
+}
+
+/// Events sent from the accept/reader threads, tagged with the connection they came from
+enum Incoming {
+    Connected(usize, TcpStream, SocketAddr),
+    Key(usize, usize, bool),
+    Disconnected(usize),
+}
+
+/// Host side of a two-player session: accepts one remote keypad at a time and
+/// streams the display to it
+pub struct NetHost {
+    local_addr: SocketAddr,
+    incoming: Receiver<Incoming>,
+    client: Option<(usize, TcpStream)>,
+    dropped: bool,
+    sent_display: Vec<u8>,
+    sent_buzzer: bool,
+}
+
+impl NetHost {
+    pub fn bind(port: u16) -> Result<Self, std::io::Error> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let local_addr = listener.local_addr()?;
+        let (tx, rx) = channel();
+
+        thread::spawn(move || NetHost::accept_loop(listener, tx));
+
+        Ok(Self {
+            local_addr,
+            incoming: rx,
+            client: None,
+            dropped: false,
+            sent_display: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            sent_buzzer: false,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Drains network activity without blocking
+    pub fn poll(&mut self) -> Vec<HostEvent> {
+        let mut events = Vec::new();
+
+        if self.dropped {
+            self.dropped = false;
+            events.push(HostEvent::Disconnected);
+        }
+
+        loop {
+            match self.incoming.try_recv() {
+                Ok(Incoming::Connected(id, stream, addr)) => {
+                    // Only one remote player; later connections are dropped
+                    if self.client.is_none() {
+                        self.client = Some((id, stream));
+                        self.sent_display.fill(0);
+                        self.sent_buzzer = false;
+                        events.push(HostEvent::Connected(addr));
+                    } else {
+                        stream.shutdown(Shutdown::Both).ok();
+                    }
+                }
+                Ok(Incoming::Key(id, key, pressed)) => {
+                    if self.is_current(id) {
+                        events.push(HostEvent::Key { key, pressed });
+                    }
+                }
+                Ok(Incoming::Disconnected(id)) => {
+                    if self.is_current(id) {
+                        self.client = None;
+                        events.push(HostEvent::Disconnected);
+                    }
+                }
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break,
+            }
+        }
+
+        events
+    }
+
+    /// Sends the display changes since the last frame to the connected client, if any.
+    /// A failed send, or one blocked past WRITE_TIMEOUT, drops the client, which is reported by
+    /// the next poll
+    pub fn send_frame(&mut self, display: &[u8], buzzer: bool) {
+        let Some((_, stream)) = self.client.as_mut() else {
+            return;
+        };
+
+        let flipped = diff_display(&self.sent_display, display);
+        if flipped.is_empty() && buzzer == self.sent_buzzer {
+            return;
+        }
+
+        if write_message(stream, &Message::Frame { buzzer, flipped }).is_err() {
+            stream.shutdown(Shutdown::Both).ok();
+            self.client = None;
+            self.dropped = true;
+            return;
+        }
+
+        self.sent_display.copy_from_slice(display);
+        self.sent_buzzer = buzzer;
+    }
+
+    fn is_current(&self, id: usize) -> bool {
+        matches!(self.client, Some((current, _)) if current == id)
+    }
+
+    fn accept_loop(listener: TcpListener, tx: Sender<Incoming>) {
+        for (id, stream) in listener.incoming().enumerate() {
+            let Ok(stream) = stream else { continue };
+            let Ok(addr) = stream.peer_addr() else { continue };
+            let Ok(mut reader) = stream.try_clone() else { continue };
+            stream.set_nodelay(true).ok();
+            stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok();
+
+            if tx.send(Incoming::Connected(id, stream, addr)).is_err() {
+                return;
+            }
+
+            let tx = tx.clone();
+            thread::spawn(move || {
+                while let Ok(message) = read_message(&mut reader) {
+                    if let Message::Key { key, pressed, .. } = message
+                        && tx.send(Incoming::Key(id, key as usize, pressed)).is_err()
+                    {
+                        return;
+                    }
+                }
+                tx.send(Incoming::Disconnected(id)).ok();
+            });
+        }
+    }
+}
+
+/// Client side of a two-player session: sends local key changes and mirrors the host's display
+pub struct NetClient {
+    stream: TcpStream,
+    frames: Receiver<Message>,
+    pub display: Vec<u8>,
+    pub buzzer: bool,
+    pub connected: bool,
+    frame: u32,
+}
+
+impl NetClient {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, std::io::Error> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut reader = stream.try_clone()?;
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            while let Ok(message) = read_message(&mut reader) {
+                if tx.send(message).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self {
+            stream,
+            frames: rx,
+            display: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            buzzer: false,
+            connected: true,
+            frame: 0,
+        })
+    }
+
+    /// Tells the host a key changed state
+    pub fn send_key(&mut self, key: usize, pressed: bool) {
+        let message = Message::Key { key: key as u8, pressed, frame: self.frame };
+        if write_message(&mut self.stream, &message).is_err() {
+            self.connected = false;
+        }
+    }
+
+    /// Applies every frame received since the last poll. Returns true if the display changed
+    pub fn poll(&mut self) -> bool {
+        self.frame = self.frame.wrapping_add(1);
+        let mut changed = false;
+
+        loop {
+            match self.frames.try_recv() {
+                Ok(Message::Frame { buzzer, flipped }) => {
+                    apply_display_diff(&mut self.display, &flipped);
+                    self.buzzer = buzzer;
+                    changed |= !flipped.is_empty();
+                }
+                Ok(Message::Key { .. }) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    break;
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+impl Drop for NetClient {
+    fn drop(&mut self) {
+        // Also unblocks the reader thread, which holds a clone of the socket
+        self.stream.shutdown(Shutdown::Both).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn decode_framed(bytes: &[u8]) -> Message {
+        read_message(&mut &bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn test_key_message_round_trip() {
+        let message = Message::Key { key: 0xA, pressed: true, frame: 0x01020304 };
+        let bytes = message.encode();
+        assert_eq!(bytes, [0x00, 0x07, TAG_KEY, 0x0A, 0x01, 0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(decode_framed(&bytes), message);
+    }
+
+    #[test]
+    fn test_frame_message_round_trip() {
+        let message = Message::Frame { buzzer: true, flipped: vec![0, 65, 2047] };
+        assert_eq!(decode_framed(&message.encode()), message);
+
+        let empty = Message::Frame { buzzer: false, flipped: vec![] };
+        assert_eq!(decode_framed(&empty.encode()), empty);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        assert!(Message::decode(&[]).is_err());
+        assert!(Message::decode(&[0x7F]).is_err());
+        assert!(Message::decode(&[TAG_KEY, 0x10, 1, 0, 0, 0, 0]).is_err()); // Key 16
+        assert!(Message::decode(&[TAG_KEY, 0x1, 1]).is_err());
+        assert!(Message::decode(&[TAG_FRAME, 0, 0, 2, 0, 1]).is_err()); // Says 2 pixels, has 1
+        assert!(Message::decode(&[TAG_FRAME, 0, 0, 1, 0x08, 0x00]).is_err()); // Pixel 2048
+    }
+
+    #[test]
+    fn test_truncated_stream_is_error() {
+        let bytes = Message::Key { key: 1, pressed: false, frame: 9 }.encode();
+        assert!(read_message(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_display_diff_applies() {
+        let prev = vec![0, 1, 1, 0];
+        let next = vec![1, 1, 0, 0];
+        let flipped = diff_display(&prev, &next);
+        assert_eq!(flipped, vec![0, 2]);
+
+        let mut buffer = prev.clone();
+        apply_display_diff(&mut buffer, &flipped);
+        assert_eq!(buffer, next);
+    }
+
+    /// Polls until the condition holds or a generous timeout passes
+    fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        false
+    }
+
+    #[test]
+    fn test_loopback_session() {
+        let mut host = NetHost::bind(0).unwrap();
+        let mut client = NetClient::connect(("127.0.0.1", host.local_addr().port())).unwrap();
+
+        let mut events = Vec::new();
+        assert!(wait_for(|| {
+            events.extend(host.poll());
+            events.iter().any(|e| matches!(e, HostEvent::Connected(_)))
+        }));
+
+        client.send_key(0x5, true);
+        let mut events = Vec::new();
+        assert!(wait_for(|| {
+            events.extend(host.poll());
+            events.contains(&HostEvent::Key { key: 0x5, pressed: true })
+        }));
+
+        let mut display = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        display[3] = 1;
+        display[DISPLAY_WIDTH * 2 + 10] = 1;
+        host.send_frame(&display, true);
+        assert!(wait_for(|| client.poll() || client.display == display));
+        assert_eq!(client.display, display);
+        assert!(client.buzzer);
+
+        drop(client);
+        let mut events = Vec::new();
+        assert!(wait_for(|| {
+            events.extend(host.poll());
+            events.contains(&HostEvent::Disconnected)
+        }));
+    }
+
+    #[test]
+    fn test_client_that_stops_reading_is_dropped() {
+        let mut host = NetHost::bind(0).unwrap();
+        let _stalled = TcpStream::connect(("127.0.0.1", host.local_addr().port())).unwrap();
+        assert!(wait_for(|| host.poll().iter().any(|e| matches!(e, HostEvent::Connected(_)))));
+
+        // Every pixel flips each frame until the socket buffers fill; then one send times out
+        let mut display = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        let start = Instant::now();
+        while !host.poll().contains(&HostEvent::Disconnected) {
+            assert!(start.elapsed() < Duration::from_secs(5), "still sending to a client that stopped reading");
+            display.iter_mut().for_each(|pixel| *pixel ^= 1);
+            host.send_frame(&display, false);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+
+/// Host frame duration the main loop is paced to (60Hz)
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// How presenting a frame is synchronized with the monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum VsyncMode {
+    /// Present blocks until the next vertical blank
+    On,
+    /// Present returns immediately; the loop sleeps to keep 60Hz
+    Off,
+    /// Vsync, but late frames are shown immediately (tearing) instead of waiting a whole refresh
+    Adaptive,
+}
+
+impl std::fmt::Display for VsyncMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VsyncMode::On => write!(f, "on"),
+            VsyncMode::Off => write!(f, "off"),
+            VsyncMode::Adaptive => write!(f, "adaptive"),
+        }
+    }
+}
+
+/// How long to sleep at the end of a frame that has taken `elapsed` so far.
+/// With vsync the present call already throttles the loop, so sleeping as well would halve the frame rate
+pub fn frame_sleep(mode: VsyncMode, elapsed: Duration) -> Duration {
+    match mode {
+        VsyncMode::Off => FRAME_DURATION.saturating_sub(elapsed),
+        VsyncMode::On | VsyncMode::Adaptive => Duration::ZERO,
+    }
+}
+
+/// Remaining wait below which precise_sleep stops trusting the OS scheduler and spins
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// Waits for target with sub-millisecond accuracy. OS sleeps can overshoot by a whole
+/// scheduler tick (~15ms on Windows by default), so this sleeps in coarse chunks while
+/// plenty of time is left and busy-waits the last couple of milliseconds
+pub fn precise_sleep(target: Duration) {
+    let deadline = Instant::now() + target;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining <= SPIN_THRESHOLD {
+            break;
+        }
+        std::thread::sleep(remaining - SPIN_THRESHOLD);
+    }
+
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+#[cfg(windows)]
+#[link(name = "winmm")]
+unsafe extern "system" {
+    fn timeBeginPeriod(period: u32) -> u32;
+    fn timeEndPeriod(period: u32) -> u32;
+}
+
+/// Raises the Windows timer resolution to 1ms for as long as it is alive, which makes
+/// the coarse part of precise_sleep far more accurate. Does nothing on other platforms
+pub struct TimerResolution;
+
+impl TimerResolution {
+    pub fn acquire() -> Self {
+        #[cfg(windows)]
+        unsafe {
+            timeBeginPeriod(1);
+        }
+        TimerResolution
+    }
+}
+
+impl Drop for TimerResolution {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        unsafe {
+            timeEndPeriod(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vsync_off_sleeps_remainder_of_frame() {
+        let elapsed = Duration::from_millis(5);
+        assert_eq!(frame_sleep(VsyncMode::Off, elapsed), FRAME_DURATION - elapsed);
+    }
+
+    #[test]
+    fn test_vsync_off_late_frame_does_not_sleep() {
+        assert_eq!(frame_sleep(VsyncMode::Off, Duration::from_millis(20)), Duration::ZERO);
+        assert_eq!(frame_sleep(VsyncMode::Off, FRAME_DURATION), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_vsync_modes_do_not_double_throttle() {
+        assert_eq!(frame_sleep(VsyncMode::On, Duration::ZERO), Duration::ZERO);
+        assert_eq!(frame_sleep(VsyncMode::Adaptive, Duration::from_millis(1)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_precise_sleep_accuracy() {
+        let _resolution = TimerResolution::acquire();
+
+        for target in [Duration::from_micros(500), Duration::from_millis(3), Duration::from_millis(10)] {
+            let start = Instant::now();
+            precise_sleep(target);
+            let waited = start.elapsed();
+
+            // Never early; the late bound is generous so loaded CI machines don't flake
+            assert!(waited >= target, "woke after {:?}, wanted {:?}", waited, target);
+            assert!(waited < target + Duration::from_millis(20), "woke after {:?}, wanted {:?}", waited, target);
+        }
+    }
+
+    #[test]
+    fn test_precise_sleep_zero_returns_immediately() {
+        let start = Instant::now();
+        precise_sleep(Duration::ZERO);
+        assert!(start.elapsed() < Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,41 @@
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+/// Hands every pixel of the display to set_pixel as (x, y, on), each display pixel as a
+/// scale x scale block, row by row. Nothing here waits: the caller decides when frames go out
+pub fn push_frame(display: &[u8], scale: usize, set_pixel: &mut impl FnMut(usize, usize, bool)) {
+    let scale = scale.max(1);
+    for y in 0..DISPLAY_HEIGHT * scale {
+        for x in 0..DISPLAY_WIDTH * scale {
+            set_pixel(x, y, display[(y / scale) * DISPLAY_WIDTH + x / scale] != 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visits_every_pixel_in_order() {
+        let mut display = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        display[DISPLAY_WIDTH + 3] = 1;
+
+        let mut seen = Vec::new();
+        push_frame(&display, 1, &mut |x, y, on| seen.push((x, y, on)));
+
+        assert_eq!(seen.len(), DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        assert_eq!(seen[1], (1, 0, false));
+        assert_eq!(seen[DISPLAY_WIDTH], (0, 1, false));
+        assert_eq!(seen.iter().filter(|(_, _, on)| *on).collect::<Vec<_>>(), [&(3, 1, true)]);
+    }
+
+    #[test]
+    fn test_scale_doubles_pixels() {
+        let mut display = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        display[DISPLAY_WIDTH * DISPLAY_HEIGHT - 1] = 1;
+
+        let mut lit = Vec::new();
+        push_frame(&display, 2, &mut |x, y, on| if on { lit.push((x, y)) });
+        assert_eq!(lit, [(126, 62), (127, 62), (126, 63), (127, 63)]);
+    }
+}
@@ -0,0 +1,150 @@
+//! ASCII-art sub-images of the display, for tests that look for what a ROM drew without pinning
+//! down where. `#` is a lit pixel, `.` an unlit one and `?` either. Blank lines around the art
+//! and indentation shared by every line are ignored, so a pattern can sit indented in a raw string
+use std::io::{Error, ErrorKind};
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+/// One pattern pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    On,
+    Off,
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>, // Row by row; lines shorter than the widest are padded with Any
+}
+
+impl Pattern {
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        let first = lines.iter().position(|line| !line.is_empty());
+        let last = lines.iter().rposition(|line| !line.is_empty());
+        let lines = match (first, last) {
+            (Some(first), Some(last)) => &lines[first..=last],
+            _ => return Err(Error::new(ErrorKind::InvalidData, "Empty display pattern")),
+        };
+
+        let indent = lines.iter()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines: Vec<&str> = lines.iter().map(|line| line.get(indent..).unwrap_or("")).collect();
+        let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+        let height = lines.len();
+        if width > DISPLAY_WIDTH || height > DISPLAY_HEIGHT {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Display pattern is {}x{}, larger than the display", width, height)));
+        }
+
+        let mut cells = Vec::with_capacity(width * height);
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                cells.push(match c {
+                    '#' => Cell::On,
+                    '.' => Cell::Off,
+                    '?' => Cell::Any,
+                    _ => return Err(Error::new(ErrorKind::InvalidData, format!("Unexpected {:?} in display pattern at line {}, column {}", c, row + 1, column + 1))),
+                });
+            }
+            cells.resize((row + 1) * width, Cell::Any);
+        }
+        Ok(Pattern { width, height, cells })
+    }
+
+    /// Where the pattern's top-left corner first lands on the display, scanning row by row.
+    /// The pattern must fit whole: it doesn't wrap around the edges
+    pub fn find(&self, display: &[u8]) -> Option<(usize, usize)> {
+        for y in 0..=DISPLAY_HEIGHT - self.height {
+            for x in 0..=DISPLAY_WIDTH - self.width {
+                if self.matches_at(display, x, y) {
+                    return Some((x, y));
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_at(&self, display: &[u8], x: usize, y: usize) -> bool {
+        self.cells.chunks(self.width).enumerate().all(|(row, cells)| {
+            let pixels = &display[(y + row) * DISPLAY_WIDTH + x..];
+            cells.iter().zip(pixels).all(|(cell, pixel)| match cell {
+                Cell::On => *pixel != 0,
+                Cell::Off => *pixel == 0,
+                Cell::Any => true,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display_with(lit: &[(usize, usize)]) -> Vec<u8> {
+        let mut display = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        for (x, y) in lit {
+            display[y * DISPLAY_WIDTH + x] = 1;
+        }
+        display
+    }
+
+    #[test]
+    fn test_parse_trims_blank_lines_and_indentation() {
+        let pattern = Pattern::parse("
+            #.#
+            ?#
+        ").unwrap();
+        assert_eq!((pattern.width, pattern.height), (3, 2));
+        assert_eq!(pattern.cells, [Cell::On, Cell::Off, Cell::On, Cell::Any, Cell::On, Cell::Any]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Pattern::parse("").is_err());
+        assert!(Pattern::parse("\n   \n").is_err());
+        assert!(Pattern::parse("#x#").is_err());
+        assert!(Pattern::parse("#\n #").is_err()); // Spaces aren't unlit pixels
+        assert!(Pattern::parse(&"#".repeat(DISPLAY_WIDTH + 1)).is_err());
+        assert!(Pattern::parse(&"#\n".repeat(DISPLAY_HEIGHT + 1)).is_err());
+    }
+
+    #[test]
+    fn test_finds_pattern_anywhere() {
+        let display = display_with(&[(10, 4), (12, 4), (11, 5)]);
+        let pattern = Pattern::parse("#.#\n.#.").unwrap();
+        assert_eq!(pattern.find(&display), Some((10, 4)));
+
+        // Unlit cells must be unlit
+        let display = display_with(&[(10, 4), (11, 4), (12, 4), (11, 5)]);
+        assert_eq!(pattern.find(&display), None);
+    }
+
+    #[test]
+    fn test_wildcards_match_either() {
+        let pattern = Pattern::parse("#?#").unwrap();
+        assert_eq!(pattern.find(&display_with(&[(3, 7), (5, 7)])), Some((3, 7)));
+        assert_eq!(pattern.find(&display_with(&[(3, 7), (4, 7), (5, 7)])), Some((3, 7)));
+        assert_eq!(pattern.find(&display_with(&[(3, 7), (6, 7)])), None);
+    }
+
+    #[test]
+    fn test_finds_pattern_at_the_edges() {
+        let pattern = Pattern::parse("##\n##").unwrap();
+        let corner = display_with(&[(62, 30), (63, 30), (62, 31), (63, 31)]);
+        assert_eq!(pattern.find(&corner), Some((62, 30)));
+
+        // No wrapping from the right edge onto the next row
+        let split = display_with(&[(63, 3), (0, 4), (63, 4), (0, 5)]);
+        assert_eq!(pattern.find(&split), None);
+
+        // All unlit matches the blank display straight away, whole-display patterns too
+        assert_eq!(Pattern::parse("..").unwrap().find(&display_with(&[])), Some((0, 0)));
+        let full = format!("{}\n", "?".repeat(DISPLAY_WIDTH)).repeat(DISPLAY_HEIGHT);
+        assert_eq!(Pattern::parse(&full).unwrap().find(&display_with(&[])), Some((0, 0)));
+    }
+}
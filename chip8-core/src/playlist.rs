@@ -0,0 +1,226 @@
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::quirks::Preset;
+use crate::rom;
+use crate::timing::TimingMode;
+
+/// One ROM in a playlist, with optional overrides of the command-line settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaylistEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub speed: Option<u32>, // Instructions per frame in simple timing
+    pub timing: Option<TimingMode>,
+    pub quirks: Option<Preset>,
+}
+
+/// An ordered set of ROMs to step through with wrap-around
+#[derive(Debug)]
+pub struct Playlist {
+    entries: Vec<PlaylistEntry>,
+    current: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPlaylist {
+    rom: Vec<RawEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawEntry {
+    path: PathBuf,
+    name: Option<String>,
+    speed: Option<u32>,
+    timing: Option<TimingMode>,
+    quirks: Option<Preset>,
+}
+
+impl Playlist {
+    /// Loads a playlist file. Relative ROM paths are relative to the playlist, and entries whose
+    /// file is missing are skipped, with a warning for each
+    pub fn load(path: &str) -> Result<(Self, Vec<String>), std::io::Error> {
+        let text = std::fs::read_to_string(path)?;
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut playlist = Playlist::parse(&text, base)?;
+
+        let warnings = playlist.retain_existing(|path| rom::exists(&path.to_string_lossy()))
+            .iter()
+            .map(|missing| format!("skipping missing ROM {}", missing.display()))
+            .collect();
+        if playlist.entries.is_empty() {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No ROMs in the playlist could be found"));
+        }
+
+        Ok((playlist, warnings))
+    }
+
+    /// Parses and validates the contents of a playlist file
+    pub fn parse(text: &str, base: &Path) -> Result<Self, std::io::Error> {
+        let raw: RawPlaylist = toml::from_str(text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+        if raw.rom.is_empty() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Playlist has no [[rom]] entries"));
+        }
+
+        let entries = raw.rom.into_iter().map(|entry| {
+            if entry.speed == Some(0) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{}: speed must be at least 1", entry.path.display()),
+                ));
+            }
+
+            let name = entry.name.unwrap_or_else(|| {
+                entry.path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+            });
+
+            Ok(PlaylistEntry {
+                path: base.join(&entry.path),
+                name,
+                speed: entry.speed,
+                timing: entry.timing,
+                quirks: entry.quirks,
+            })
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Playlist { entries, current: 0 })
+    }
+
+    /// Drops entries whose ROM doesn't exist, returning their paths
+    pub fn retain_existing(&mut self, exists: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+        let (kept, missing): (Vec<_>, Vec<_>) = self.entries.drain(..).partition(|entry| exists(&entry.path));
+        self.entries = kept;
+        self.current = 0;
+        missing.into_iter().map(|entry| entry.path).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn current(&self) -> &PlaylistEntry {
+        &self.entries[self.current]
+    }
+
+    /// Moves to the next entry, wrapping from the last back to the first
+    pub fn advance(&mut self) -> &PlaylistEntry {
+        self.current = (self.current + 1) % self.entries.len();
+        self.current()
+    }
+
+    /// Moves to the previous entry, wrapping from the first to the last
+    pub fn go_back(&mut self) -> &PlaylistEntry {
+        self.current = (self.current + self.entries.len() - 1) % self.entries.len();
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARTY: &str = r#"
+        [[rom]]
+        path = "pong.ch8"
+
+        [[rom]]
+        path = "games/tetris.ch8"
+        name = "Tetris"
+        speed = 20
+
+        [[rom]]
+        path = "/abs/invaders.ch8"
+        timing = "cycles"
+        quirks = "vip"
+    "#;
+
+    fn names(playlist: &Playlist) -> Vec<String> {
+        playlist.entries.iter().map(|entry| entry.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let playlist = Playlist::parse(PARTY, Path::new("lists")).unwrap();
+        assert_eq!(playlist.len(), 3);
+
+        assert_eq!(playlist.entries[0], PlaylistEntry {
+            path: PathBuf::from("lists/pong.ch8"),
+            name: "pong".to_string(),
+            speed: None,
+            timing: None,
+            quirks: None,
+        });
+        assert_eq!(playlist.entries[1].path, PathBuf::from("lists/games/tetris.ch8"));
+        assert_eq!(playlist.entries[1].name, "Tetris");
+        assert_eq!(playlist.entries[1].speed, Some(20));
+        assert_eq!(playlist.entries[2].path, PathBuf::from("/abs/invaders.ch8"));
+        assert_eq!(playlist.entries[2].timing, Some(TimingMode::Cycles));
+        assert_eq!(playlist.entries[2].quirks, Some(Preset::Vip));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_playlists() {
+        assert!(Playlist::parse("", Path::new("")).is_err());
+        assert!(Playlist::parse("rom = []", Path::new("")).is_err());
+        assert!(Playlist::parse("[[rom]]\nname = \"no path\"", Path::new("")).is_err());
+        assert!(Playlist::parse("[[rom]]\npath = \"a.ch8\"\nspeed = 0", Path::new("")).is_err());
+        assert!(Playlist::parse("[[rom]]\npath = \"a.ch8\"\ntiming = \"fast\"", Path::new("")).is_err());
+        assert!(Playlist::parse("[[rom]]\npath = \"a.ch8\"\nquirks = \"chip8\"", Path::new("")).is_err());
+        assert!(Playlist::parse("[[rom]]\npath = \"a.ch8\"\nspeeed = 5", Path::new("")).is_err());
+    }
+
+    #[test]
+    fn test_missing_entries_skipped() {
+        let mut playlist = Playlist::parse(PARTY, Path::new("")).unwrap();
+        let missing = playlist.retain_existing(|path| !path.ends_with("tetris.ch8"));
+
+        assert_eq!(missing, vec![PathBuf::from("games/tetris.ch8")]);
+        assert_eq!(names(&playlist), vec!["pong", "invaders"]);
+        assert_eq!(playlist.current().name, "pong");
+    }
+
+    #[test]
+    fn test_load_warns_of_missing_entries() {
+        let dir = std::env::temp_dir().join(format!("chip8-playlist-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("pong.ch8"), [0x12, 0x00]).unwrap();
+        let path = dir.join("party.toml");
+        std::fs::write(&path, "[[rom]]\npath = \"pong.ch8\"\n[[rom]]\npath = \"gone.ch8\"").unwrap();
+
+        let (playlist, warnings) = Playlist::load(&path.to_string_lossy()).unwrap();
+        assert_eq!(names(&playlist), vec!["pong"]);
+        assert_eq!(warnings, [format!("skipping missing ROM {}", dir.join("gone.ch8").display())]);
+
+        std::fs::write(&path, "[[rom]]\npath = \"gone.ch8\"").unwrap();
+        assert!(Playlist::load(&path.to_string_lossy()).is_err());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_navigation_wraps_both_ways() {
+        let mut playlist = Playlist::parse(PARTY, Path::new("")).unwrap();
+
+        assert_eq!(playlist.current().name, "pong");
+        assert_eq!(playlist.advance().name, "Tetris");
+        assert_eq!(playlist.advance().name, "invaders");
+        assert_eq!(playlist.advance().name, "pong");
+
+        assert_eq!(playlist.go_back().name, "invaders");
+        assert_eq!(playlist.go_back().name, "Tetris");
+        assert_eq!(playlist.go_back().name, "pong");
+    }
+
+    #[test]
+    fn test_single_entry_navigation_stays_put() {
+        let mut playlist = Playlist::parse("[[rom]]\npath = \"solo.ch8\"", Path::new("")).unwrap();
+        assert_eq!(playlist.advance().name, "solo");
+        assert_eq!(playlist.go_back().name, "solo");
+    }
+}
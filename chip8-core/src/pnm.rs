@@ -0,0 +1,160 @@
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+/// Plain PBM and PGM lines may be at most this long
+const MAX_LINE: usize = 70;
+
+/// The display as a plain-text PBM (P1), one image row per line, 1 for a lit pixel
+pub fn to_pbm(display: &[u8]) -> String {
+    let mut out = format!("P1\n{} {}\n", DISPLAY_WIDTH, DISPLAY_HEIGHT);
+    for row in display.chunks(DISPLAY_WIDTH) {
+        out.extend(row.iter().map(|pixel| if *pixel != 0 { '1' } else { '0' }));
+        out.push('\n');
+    }
+    out
+}
+
+/// Per-pixel intensities, 0 dark to 255 fully lit, as a plain-text PGM (P2).
+/// Each image row starts a new line and long rows wrap to stay within the line limit
+pub fn to_pgm(intensities: &[u8]) -> String {
+    let mut out = format!("P2\n{} {}\n255\n", DISPLAY_WIDTH, DISPLAY_HEIGHT);
+    for row in intensities.chunks(DISPLAY_WIDTH) {
+        let mut line = String::new();
+        for value in row {
+            let value = value.to_string();
+            if !line.is_empty() && line.len() + 1 + value.len() > MAX_LINE {
+                out.push_str(&line);
+                out.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&value);
+        }
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Reads a plain PBM of the display's size back into display pixels (0 or 1)
+pub fn from_pbm(text: &str) -> Option<Vec<u8>> {
+    let mut tokens = tokens(text);
+    read_header(&mut tokens, "P1")?;
+
+    // Bits need no whitespace between them
+    let pixels = tokens
+        .flat_map(|token| token.chars())
+        .map(|bit| match bit {
+            '0' => Some(0),
+            '1' => Some(1),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    (pixels.len() == DISPLAY_WIDTH * DISPLAY_HEIGHT).then_some(pixels)
+}
+
+/// Reads a plain PGM of the display's size back into intensities, rescaled to 0-255
+pub fn from_pgm(text: &str) -> Option<Vec<u8>> {
+    let mut tokens = tokens(text);
+    read_header(&mut tokens, "P2")?;
+    let max = tokens.next()?.parse::<u32>().ok().filter(|max| (1..=u16::MAX as u32).contains(max))?;
+
+    let pixels = tokens
+        .map(|token| token.parse::<u32>().ok().filter(|value| *value <= max).map(|value| (value * 255 / max) as u8))
+        .collect::<Option<Vec<u8>>>()?;
+    (pixels.len() == DISPLAY_WIDTH * DISPLAY_HEIGHT).then_some(pixels)
+}
+
+/// Whitespace-separated tokens with `#` comments removed
+fn tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(str::split_whitespace)
+}
+
+fn read_header<'a>(tokens: &mut impl Iterator<Item = &'a str>, magic: &str) -> Option<()> {
+    let size = (DISPLAY_WIDTH.to_string(), DISPLAY_HEIGHT.to_string());
+    (tokens.next()? == magic && tokens.next()? == size.0 && tokens.next()? == size.1).then_some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern() -> Vec<u8> {
+        (0..DISPLAY_WIDTH * DISPLAY_HEIGHT).map(|index| (index % 3 == 0 || index % 7 == 0) as u8).collect()
+    }
+
+    #[test]
+    fn test_pbm_layout() {
+        let mut display = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        display[1] = 1;
+        display[DISPLAY_WIDTH * DISPLAY_HEIGHT - 1] = 1;
+
+        let pbm = to_pbm(&display);
+        let lines = pbm.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..2], ["P1", "64 32"]);
+        assert_eq!(lines.len(), 2 + DISPLAY_HEIGHT);
+        assert_eq!(&lines[2][..4], "0100");
+        assert!(lines[lines.len() - 1].ends_with("01"));
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE));
+    }
+
+    #[test]
+    fn test_pbm_round_trip() {
+        let display = pattern();
+        assert_eq!(from_pbm(&to_pbm(&display)), Some(display));
+    }
+
+    #[test]
+    fn test_pbm_parsing_accepts_comments_and_spacing() {
+        let mut text = String::from("P1 # made by hand\n# size next\n64\n32\n");
+        for _ in 0..DISPLAY_WIDTH * DISPLAY_HEIGHT - 1 {
+            text.push_str("0 ");
+        }
+        text.push('1');
+
+        let display = from_pbm(&text).unwrap();
+        assert_eq!(display[DISPLAY_WIDTH * DISPLAY_HEIGHT - 1], 1);
+        assert_eq!(display.iter().filter(|pixel| **pixel != 0).count(), 1);
+    }
+
+    #[test]
+    fn test_pbm_parsing_rejects_bad_input() {
+        let pbm = to_pbm(&pattern());
+        assert_eq!(from_pbm(&pbm.replacen("P1", "P4", 1)), None);
+        assert_eq!(from_pbm(&pbm.replacen("64 32", "32 32", 1)), None);
+        assert_eq!(from_pbm(&pbm[..pbm.len() - 2]), None);
+        assert_eq!(from_pbm(&format!("{}1\n", pbm)), None);
+        assert_eq!(from_pbm(&pbm.replacen("\n1", "\n2", 1)), None);
+    }
+
+    #[test]
+    fn test_pgm_wraps_long_rows() {
+        let intensities = vec![255; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        let pgm = to_pgm(&intensities);
+        let lines = pgm.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[..3], ["P2", "64 32", "255"]);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE));
+        // 17 values of "255 " fit on a line, so each row of 64 takes four lines
+        assert_eq!(lines.len(), 3 + DISPLAY_HEIGHT * 4);
+    }
+
+    #[test]
+    fn test_pgm_round_trip() {
+        let intensities = (0..DISPLAY_WIDTH * DISPLAY_HEIGHT).map(|index| (index * 37 % 256) as u8).collect::<Vec<_>>();
+        assert_eq!(from_pgm(&to_pgm(&intensities)), Some(intensities));
+    }
+
+    #[test]
+    fn test_pgm_rescales_and_checks_values() {
+        let mut text = String::from("P2\n64 32\n1\n");
+        text.push_str(&"1 0 ".repeat(DISPLAY_WIDTH * DISPLAY_HEIGHT / 2));
+        assert_eq!(from_pgm(&text).unwrap()[..2], [255, 0]);
+
+        assert_eq!(from_pgm(&text.replacen("1 0", "2 0", 1)), None);
+        assert_eq!(from_pgm(&text.replacen("\n1\n", "\n0\n", 1)), None);
+    }
+}
@@ -0,0 +1,193 @@
+//! Opcodes whose behavior changed between the COSMAC VIP's interpreter and the ones that came
+//! after it. ROMs written for one can break on the other, so the CPU carries a choice for each
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VY into VX, rather than shifting VX in place
+    pub shift_uses_vy: bool,
+    /// FX55/FX65 leave I pointing past the last register stored or loaded
+    pub load_store_increments_i: bool,
+    /// BNNN jumps to XNN plus VX, as BXNN, rather than to NNN plus V0
+    pub jump_with_vx: bool,
+    /// 8XY1/8XY2/8XY3 clear VF
+    pub vf_reset_on_logic: bool,
+    /// DXYN wraps sprites around the edges of the screen, rather than clipping them
+    pub wrap_sprites: bool,
+}
+
+impl Quirks {
+    /// The original interpreter on the COSMAC VIP
+    pub const fn vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_with_vx: false,
+            vf_reset_on_logic: true,
+            wrap_sprites: false,
+        }
+    }
+
+    /// What most interpreters since do, and what most ROMs written today expect
+    pub const fn modern() -> Self {
+        Self {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_with_vx: false,
+            vf_reset_on_logic: false,
+            wrap_sprites: true,
+        }
+    }
+
+    /// The preset's name, or "custom" for any other mix. Save states and replays record it
+    pub fn name(&self) -> &'static str {
+        Preset::ALL.iter()
+            .find(|preset| preset.quirks() == *self)
+            .map_or("custom", |preset| preset.name())
+    }
+
+    pub fn get(&self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::ShiftUsesVy => self.shift_uses_vy,
+            Quirk::LoadStoreIncrementsI => self.load_store_increments_i,
+            Quirk::JumpWithVx => self.jump_with_vx,
+            Quirk::VfResetOnLogic => self.vf_reset_on_logic,
+            Quirk::WrapSprites => self.wrap_sprites,
+        }
+    }
+
+    pub fn set(&mut self, quirk: Quirk, on: bool) {
+        *match quirk {
+            Quirk::ShiftUsesVy => &mut self.shift_uses_vy,
+            Quirk::LoadStoreIncrementsI => &mut self.load_store_increments_i,
+            Quirk::JumpWithVx => &mut self.jump_with_vx,
+            Quirk::VfResetOnLogic => &mut self.vf_reset_on_logic,
+            Quirk::WrapSprites => &mut self.wrap_sprites,
+        } = on;
+    }
+}
+
+/// One of the choices Quirks makes, for settings that take them one at a time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    ShiftUsesVy,
+    LoadStoreIncrementsI,
+    JumpWithVx,
+    VfResetOnLogic,
+    WrapSprites,
+}
+
+impl Quirk {
+    pub const ALL: [Quirk; 5] = [Quirk::ShiftUsesVy, Quirk::LoadStoreIncrementsI, Quirk::JumpWithVx, Quirk::VfResetOnLogic, Quirk::WrapSprites];
+
+    /// How menus list it
+    pub fn label(self) -> &'static str {
+        match self {
+            Quirk::ShiftUsesVy => "Shift VY",
+            Quirk::LoadStoreIncrementsI => "Load/store move I",
+            Quirk::JumpWithVx => "Jump with VX",
+            Quirk::VfResetOnLogic => "Logic clears VF",
+            Quirk::WrapSprites => "Wrap sprites",
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::modern()
+    }
+}
+
+/// The quirk presets, by name, for the command line and the config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// The original COSMAC VIP interpreter
+    Vip,
+    /// Modern interpreters
+    #[default]
+    Modern,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 2] = [Preset::Vip, Preset::Modern];
+
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Preset::Vip => Quirks::vip(),
+            Preset::Modern => Quirks::modern(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Vip => "vip",
+            Preset::Modern => "modern",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    /// How the frontends' menus list the preset
+    pub fn label(self) -> &'static str {
+        match self {
+            Preset::Vip => "COSMAC VIP",
+            Preset::Modern => "Modern",
+        }
+    }
+}
+
+/// Save states and replays from before the presets existed name the one behavior there was, which
+/// is now the modern preset, "chip8". No preset goes by that name, so it can't be mistaken for the
+/// VIP's; readers of those older versions rename it with this
+pub fn upgrade_legacy_name(name: &str) -> String {
+    match name {
+        "chip8" => Preset::Modern.name().to_string(),
+        name => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Quirks::vip().name(), "vip");
+        assert_eq!(Quirks::modern().name(), "modern");
+        assert_eq!(Quirks::default(), Quirks::modern());
+        assert_eq!(Quirks { jump_with_vx: true, ..Quirks::modern() }.name(), "custom");
+    }
+
+    #[test]
+    fn test_presets_differ_in_every_vip_quirk() {
+        let (vip, modern) = (Quirks::vip(), Quirks::modern());
+        assert_ne!(vip.shift_uses_vy, modern.shift_uses_vy);
+        assert_ne!(vip.load_store_increments_i, modern.load_store_increments_i);
+        assert_ne!(vip.vf_reset_on_logic, modern.vf_reset_on_logic);
+        assert_ne!(vip.wrap_sprites, modern.wrap_sprites);
+        for preset in Preset::ALL {
+            assert_eq!(preset.quirks().name(), preset.name());
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+        }
+        assert_eq!(Preset::from_name("custom"), None);
+    }
+
+    #[test]
+    fn test_quirks_one_at_a_time() {
+        let mut quirks = Quirks::modern();
+        for quirk in Quirk::ALL {
+            quirks.set(quirk, !quirks.get(quirk));
+        }
+        assert_eq!(quirks, Quirks { jump_with_vx: true, ..Quirks::vip() });
+    }
+
+    #[test]
+    fn test_legacy_name_is_modern() {
+        assert_eq!(upgrade_legacy_name("chip8"), "modern");
+        assert_eq!(upgrade_legacy_name("vip"), "vip");
+    }
+}
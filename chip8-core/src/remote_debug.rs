@@ -0,0 +1,427 @@
+//! WebSocket debugger for the page in `web-debugger/`.
+//!
+//! Clients send JSON commands tagged by `cmd`:
+//!
+//! | Command                                    | Effect                                      |
+//! |--------------------------------------------|---------------------------------------------|
+//! | `{"cmd":"subscribe"}`                      | Start receiving a `state` message per frame |
+//! | `{"cmd":"pause"}`                          | Stop before the next instruction            |
+//! | `{"cmd":"continue"}`                       | Run freely again                            |
+//! | `{"cmd":"step","count":1}`                 | Run `count` instructions (default 1), then pause |
+//! | `{"cmd":"break","addr":512}`               | Toggle a breakpoint                         |
+//! | `{"cmd":"poke","addr":768,"value":255}`    | Write one byte of memory                    |
+//! | `{"cmd":"screenshot_pbm"}`                 | Reply with the display as a plain PBM image |
+//!
+//! and receive messages tagged by `type`: `state` (see [`Snapshot`]), `pbm` with the image `text`,
+//! or `error` with a `message`.
+//! Commands are applied by the main loop between frames.
+
+use std::io::ErrorKind;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tungstenite::Message;
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT, MEMORY_SIZE};
+use crate::cpu::CPU;
+use crate::debugger::{self, Debugger};
+use crate::disassembler::disassemble;
+use crate::pnm;
+
+/// Instructions shown before and after pc in a snapshot's disassembly
+const DISASSEMBLY_BEFORE: u16 = 4;
+const DISASSEMBLY_AFTER: u16 = 8;
+/// How long a client connection waits for a command before checking for a new state to send
+const CLIENT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Command {
+    Subscribe,
+    Pause,
+    Continue,
+    Step {
+        #[serde(default = "one")]
+        count: u32,
+    },
+    Break { addr: u16 },
+    Poke { addr: u16, value: u8 },
+    ScreenshotPbm,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl Command {
+    pub fn decode(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    State(Snapshot),
+    Pbm { text: String },
+    Error { message: String },
+}
+
+impl Reply {
+    pub fn encode(&self) -> String {
+        serde_json::to_string(self).expect("replies always serialize")
+    }
+}
+
+/// Machine state as sent to subscribers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snapshot {
+    pub pc: u16,
+    pub i: u16,
+    pub v: Vec<u8>,
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub paused: bool,
+    pub breakpoints: Vec<u16>,
+    pub disassembly: Vec<Line>,
+    pub display: Screen,
+}
+
+/// One disassembled instruction around pc
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Line {
+    pub addr: u16,
+    pub opcode: u16,
+    pub text: String,
+}
+
+/// The display packed eight pixels to a byte, most significant bit leftmost, then base64 encoded
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Screen {
+    pub width: usize,
+    pub height: usize,
+    pub bits: String,
+}
+
+impl Snapshot {
+    pub fn capture(cpu: &CPU, debugger: &Debugger) -> Self {
+        // Reads memory directly so memory hooks don't see the debugger
+        let start = cpu.pc.saturating_sub(DISASSEMBLY_BEFORE * 2);
+        let disassembly = (0..=DISASSEMBLY_BEFORE + DISASSEMBLY_AFTER)
+            .map(|n| start + n * 2)
+            .filter(|addr| (*addr as usize) + 1 < MEMORY_SIZE)
+            .map(|addr| {
+                let opcode = u16::from_be_bytes([cpu.memory[addr as usize], cpu.memory[addr as usize + 1]]);
+                Line { addr, opcode, text: disassemble(opcode) }
+            })
+            .collect();
+
+        Self {
+            pc: cpu.pc,
+            i: cpu.i,
+            v: cpu.v.to_vec(),
+            stack: cpu.stack.clone(),
+            delay_timer: cpu.delay_timer,
+            sound_timer: cpu.sound_timer,
+            paused: debugger.is_paused(),
+            breakpoints: debugger.breakpoints().collect(),
+            disassembly,
+            display: Screen { width: DISPLAY_WIDTH, height: DISPLAY_HEIGHT, bits: base64(&pack_display(&cpu.display)) },
+        }
+    }
+}
+
+/// Carries out a command from a client, returning the reply for commands that have one.
+/// Subscribing is handled by the connection itself, and everything else by debugger::apply
+pub fn apply(command: Command, cpu: &mut CPU, debugger: &mut Debugger) -> Result<Option<Reply>, String> {
+    let command = match command {
+        Command::Subscribe => return Ok(None),
+        Command::ScreenshotPbm => return Ok(Some(Reply::Pbm { text: pnm::to_pbm(&cpu.display) })),
+        Command::Pause => debugger::Command::Pause,
+        Command::Continue => debugger::Command::Continue,
+        Command::Step { count } => debugger::Command::Step(count),
+        Command::Break { addr } => debugger::Command::ToggleBreakpoint(addr),
+        Command::Poke { addr, value } => debugger::Command::Poke { addr, value },
+    };
+    debugger::apply(command, cpu, debugger).map(|()| None)
+}
+
+fn pack_display(display: &[u8]) -> Vec<u8> {
+    display.chunks(8)
+        .map(|pixels| pixels.iter().enumerate().fold(0, |byte, (n, pixel)| byte | (((*pixel != 0) as u8) << (7 - n))))
+        .collect()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = u32::from_be_bytes([0, chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)]);
+        for n in 0..4 {
+            if n <= chunk.len() {
+                out.push(ALPHABET[(word >> (18 - n * 6)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Accepts debugger connections on localhost and relays their commands to the main loop
+pub struct RemoteDebugServer {
+    local_addr: SocketAddr,
+    commands: Receiver<Command>,
+    clients: Arc<Mutex<Vec<Sender<Outgoing>>>>,
+}
+
+/// A reply on its way to one connection
+struct Outgoing {
+    state: bool,
+    text: String,
+}
+
+impl RemoteDebugServer {
+    pub fn bind(port: u16) -> Result<Self, std::io::Error> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let local_addr = listener.local_addr()?;
+        let (tx, rx) = channel();
+        let clients = Arc::new(Mutex::new(Vec::new()));
+
+        let accept_clients = Arc::clone(&clients);
+        thread::spawn(move || RemoteDebugServer::accept_loop(listener, tx, accept_clients));
+
+        Ok(Self { local_addr, commands: rx, clients })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn has_clients(&self) -> bool {
+        !self.clients.lock().unwrap().is_empty()
+    }
+
+    /// Commands received since the last poll, without blocking
+    pub fn poll(&self) -> Vec<Command> {
+        self.commands.try_iter().collect()
+    }
+
+    /// Sends a reply to every connection, forgetting those that have closed.
+    /// States only reach connections that subscribed
+    pub fn publish(&self, reply: &Reply) {
+        let state = matches!(reply, Reply::State(_));
+        let text = reply.encode();
+        self.clients.lock().unwrap().retain(|client| client.send(Outgoing { state, text: text.clone() }).is_ok());
+    }
+
+    fn accept_loop(listener: TcpListener, commands: Sender<Command>, clients: Arc<Mutex<Vec<Sender<Outgoing>>>>) {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let (tx, rx) = channel();
+            clients.lock().unwrap().push(tx);
+
+            let commands = commands.clone();
+            thread::spawn(move || RemoteDebugServer::serve(stream, commands, rx));
+        }
+    }
+
+    fn serve(stream: TcpStream, commands: Sender<Command>, outgoing: Receiver<Outgoing>) {
+        let Ok(mut socket) = tungstenite::accept(stream) else { return };
+        if socket.get_ref().set_read_timeout(Some(CLIENT_POLL_INTERVAL)).is_err() {
+            return;
+        }
+
+        let mut subscribed = false;
+        loop {
+            match socket.read() {
+                Ok(Message::Text(text)) => match Command::decode(&text) {
+                    Ok(Command::Subscribe) => subscribed = true,
+                    Ok(command) => {
+                        if commands.send(command).is_err() {
+                            return;
+                        }
+                    }
+                    Err(message) => {
+                        if socket.send(Message::text(Reply::Error { message }.encode())).is_err() {
+                            return;
+                        }
+                    }
+                },
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => return,
+            }
+
+            // A slow client only gets the newest state
+            let mut latest = None;
+            for reply in outgoing.try_iter() {
+                if reply.state {
+                    latest = Some(reply.text);
+                } else if socket.send(Message::text(reply.text)).is_err() {
+                    return;
+                }
+            }
+            if subscribed && let Some(text) = latest && socket.send(Message::text(text)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn test_decode_commands() {
+        assert_eq!(Command::decode(r#"{"cmd":"subscribe"}"#), Ok(Command::Subscribe));
+        assert_eq!(Command::decode(r#"{"cmd":"pause"}"#), Ok(Command::Pause));
+        assert_eq!(Command::decode(r#"{"cmd":"continue"}"#), Ok(Command::Continue));
+        assert_eq!(Command::decode(r#"{"cmd":"screenshot_pbm"}"#), Ok(Command::ScreenshotPbm));
+        assert_eq!(Command::decode(r#"{"cmd":"step"}"#), Ok(Command::Step { count: 1 }));
+        assert_eq!(Command::decode(r#"{"cmd":"step","count":30}"#), Ok(Command::Step { count: 30 }));
+        assert_eq!(Command::decode(r#"{"cmd":"break","addr":516}"#), Ok(Command::Break { addr: 0x204 }));
+        assert_eq!(
+            Command::decode(r#"{"cmd":"poke","addr":768,"value":255}"#),
+            Ok(Command::Poke { addr: 0x300, value: 0xFF })
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        assert!(Command::decode("").is_err());
+        assert!(Command::decode(r#"{"cmd":"explode"}"#).is_err());
+        assert!(Command::decode(r#"{"cmd":"break"}"#).is_err());
+        assert!(Command::decode(r#"{"cmd":"poke","addr":768,"value":256}"#).is_err());
+        assert!(Command::decode(r#"{"cmd":"step","cout":3}"#).is_err());
+    }
+
+    #[test]
+    fn test_encode_error() {
+        assert_eq!(Reply::Error { message: "nope".to_string() }.encode(), r#"{"type":"error","message":"nope"}"#);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x200..0x204].copy_from_slice(&[0x00, 0xE0, 0x12, 0x00]);
+        cpu.v[0xA] = 7;
+        cpu.display[0] = 1;
+        cpu.display[9] = 1;
+        let mut debugger = Debugger::new();
+        debugger.toggle_breakpoint(0x202);
+
+        let snapshot = Snapshot::capture(&cpu, &debugger);
+        assert_eq!(snapshot.pc, 0x200);
+        assert_eq!(snapshot.v[0xA], 7);
+        assert_eq!(snapshot.breakpoints, vec![0x202]);
+        assert!(!snapshot.paused);
+
+        let current = snapshot.disassembly.iter().position(|line| line.addr == 0x200).unwrap();
+        assert_eq!(current, DISASSEMBLY_BEFORE as usize);
+        assert_eq!(snapshot.disassembly[current].text, "CLS");
+        assert_eq!(snapshot.disassembly[current + 1], Line { addr: 0x202, opcode: 0x1200, text: "JP 0x200".to_string() });
+        assert_eq!(snapshot.disassembly.len(), (DISASSEMBLY_BEFORE + DISASSEMBLY_AFTER + 1) as usize);
+
+        // Pixels 0 and 9: 0b1000_0000, 0b0100_0000, then zeros
+        assert!(snapshot.display.bits.starts_with("gEA"));
+        assert_eq!(snapshot.display.bits.len(), 344);
+
+        let json = Reply::State(snapshot).encode();
+        assert!(json.starts_with(r#"{"type":"state","pc":512,"i":0,"#));
+    }
+
+    #[test]
+    fn test_disassembly_stays_in_memory() {
+        let mut cpu = CPU::new();
+        cpu.pc = 0;
+        assert_eq!(Snapshot::capture(&cpu, &Debugger::new()).disassembly[0].addr, 0);
+
+        cpu.pc = (MEMORY_SIZE - 2) as u16;
+        let last = Snapshot::capture(&cpu, &Debugger::new()).disassembly.pop().unwrap();
+        assert_eq!(last.addr, cpu.pc);
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_apply_commands() {
+        let mut cpu = CPU::new();
+        let mut debugger = Debugger::new();
+
+        apply(Command::Pause, &mut cpu, &mut debugger).unwrap();
+        assert!(!debugger.allows(cpu.pc));
+
+        apply(Command::Step { count: 1 }, &mut cpu, &mut debugger).unwrap();
+        assert!(debugger.allows(cpu.pc));
+        assert!(!debugger.allows(cpu.pc + 2));
+
+        apply(Command::Break { addr: 0x210 }, &mut cpu, &mut debugger).unwrap();
+        assert_eq!(debugger.breakpoints().collect::<Vec<_>>(), vec![0x210]);
+        apply(Command::Break { addr: 0x210 }, &mut cpu, &mut debugger).unwrap();
+        assert_eq!(debugger.breakpoints().count(), 0);
+
+        apply(Command::Continue, &mut cpu, &mut debugger).unwrap();
+        assert!(!debugger.is_paused());
+
+        apply(Command::Poke { addr: 0x300, value: 0xAB }, &mut cpu, &mut debugger).unwrap();
+        assert_eq!(cpu.memory[0x300], 0xAB);
+        assert!(apply(Command::Poke { addr: MEMORY_SIZE as u16, value: 1 }, &mut cpu, &mut debugger).is_err());
+
+        cpu.display[0] = 1;
+        let Ok(Some(Reply::Pbm { text })) = apply(Command::ScreenshotPbm, &mut cpu, &mut debugger) else {
+            panic!("screenshot_pbm should reply with an image");
+        };
+        assert_eq!(pnm::from_pbm(&text).as_deref(), Some(&cpu.display[..]));
+    }
+
+    /// Polls until the condition holds or a generous timeout passes
+    fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        false
+    }
+
+    #[test]
+    fn test_server_relays_commands_and_states() {
+        let server = RemoteDebugServer::bind(0).unwrap();
+        let (mut client, _) = tungstenite::connect(format!("ws://{}", server.local_addr())).unwrap();
+
+        client.send(Message::text(r#"{"cmd":"subscribe"}"#)).unwrap();
+        client.send(Message::text(r#"{"cmd":"pause"}"#)).unwrap();
+        let mut received = Vec::new();
+        assert!(wait_for(|| {
+            received.extend(server.poll());
+            !received.is_empty()
+        }));
+        assert_eq!(received, vec![Command::Pause]);
+
+        let snapshot = Snapshot::capture(&CPU::new(), &Debugger::new());
+        server.publish(&Reply::State(snapshot));
+        let Message::Text(text) = client.read().unwrap() else { panic!("expected a text message") };
+        assert!(text.starts_with(r#"{"type":"state""#));
+
+        // Malformed commands are answered on the connection that sent them
+        client.send(Message::text("{")).unwrap();
+        let Message::Text(text) = client.read().unwrap() else { panic!("expected a text message") };
+        assert!(text.starts_with(r#"{"type":"error""#));
+    }
+}
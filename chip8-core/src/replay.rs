@@ -0,0 +1,565 @@
+//! `.c8replay` files: everything needed to repeat a session exactly and check that it ends the same way.
+//!
+//! A replay is the magic `C8REPLAY`, the format version as u16 BE, then a bincode-encoded [`Replay`].
+//! Playing one starts the ROM from power-on with the recorded settings and seed, then feeds it the
+//! recorded keypad state one update at a time, running as many frames or instructions and ticking
+//! the timers as often as the window did. Sticky keys latched down are let go when FX0A takes
+//! them, in the middle of an update, just as the window let them go.
+
+use serde::{Deserialize, Serialize};
+use crate::constants::REGISTERS_COUNT;
+use crate::cpu::CPU;
+use crate::emulator;
+use crate::expect::Mismatch;
+use crate::quirks::{self, Preset};
+use crate::rom::RomInfo;
+use crate::savestate;
+use crate::timing::{FrameBudget, TimingMode};
+
+pub const REPLAY_MAGIC: &[u8; 8] = b"C8REPLAY";
+/// Version 4 keeps which keys were latched. Version 3 counts each update's frames, instructions and
+/// timer ticks, and keeps the clock rate. Version 2 names the quirk preset; version 1 replays call
+/// the modern one "chip8"
+pub const REPLAY_VERSION: u16 = 4;
+
+/// How the machine was set up for the recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub quirks: String,
+    pub timing: TimingMode,
+    pub instructions_per_frame: u32,
+    pub stack_depth: u8,
+    pub vip_display: bool,
+    /// Instructions per second with --hz, when updates run instructions rather than frames
+    pub hz: Option<u32>,
+}
+
+/// Input for one update: the keys held and which of them only latched, the budget frames run with
+/// them (or, with a clock rate, the instructions), then how many times the timers ticked. The
+/// speed and long updates change the counts, so there is no telling them from the time an update took
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct FrameInput {
+    pub keys: u16,
+    pub latched: u16,
+    pub frames: u32,
+    pub instructions: u32,
+    pub timers: u32,
+}
+
+/// What the recording ended with, compared after playing it back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FinalState {
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; REGISTERS_COUNT],
+    pub display_hash: u64, // FNV-1a of the display
+}
+
+impl FinalState {
+    pub fn of(cpu: &CPU) -> Self {
+        Self { pc: cpu.pc, i: cpu.i, v: cpu.v, display_hash: savestate::rom_hash(&cpu.display) }
+    }
+
+    /// Every field that differs, expected (self) against actual
+    pub fn diff(&self, actual: &FinalState) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let mut compare = |field: String, expected: String, got: String| {
+            if expected != got {
+                mismatches.push(Mismatch { field, expected, actual: got });
+            }
+        };
+        compare("pc".to_string(), format!("{:#05X}", self.pc), format!("{:#05X}", actual.pc));
+        compare("i".to_string(), format!("{:#05X}", self.i), format!("{:#05X}", actual.i));
+        for (register, (expected, got)) in self.v.iter().zip(actual.v).enumerate() {
+            compare(format!("V{:X}", register), format!("{:#04X}", expected), format!("{:#04X}", got));
+        }
+        compare("display".to_string(), format!("{:016x}", self.display_hash), format!("{:016x}", actual.display_hash));
+        mismatches
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u16,
+    pub rom_sha1: String,
+    pub settings: Settings,
+    pub seed: u64,
+    pub inputs: Vec<FrameInput>,
+    pub frames: u64,
+    pub expected: FinalState,
+}
+
+impl Replay {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = REPLAY_MAGIC.to_vec();
+        out.extend_from_slice(&REPLAY_VERSION.to_be_bytes());
+        out.extend(bincode::serde::encode_to_vec(self, bincode::config::standard()).expect("replays always encode"));
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, std::io::Error> {
+        let payload = bytes.strip_prefix(REPLAY_MAGIC).ok_or_else(|| invalid("Not a replay file".to_string()))?;
+        let version = payload.get(..2).map(|version| u16::from_be_bytes([version[0], version[1]]));
+        if !version.is_some_and(|version| (1..=REPLAY_VERSION).contains(&version)) {
+            return Err(invalid(format!("Unsupported replay version {:?}", version)));
+        }
+
+        let corrupt = |e: bincode::error::DecodeError| invalid(format!("Corrupt replay: {}", e));
+        let mut replay = if version == Some(REPLAY_VERSION) {
+            bincode::serde::decode_from_slice::<Replay, _>(&payload[2..], bincode::config::standard()).map_err(corrupt)?.0
+        } else if version == Some(3) {
+            bincode::serde::decode_from_slice::<Version3Replay, _>(&payload[2..], bincode::config::standard()).map_err(corrupt)?.0.into()
+        } else {
+            bincode::serde::decode_from_slice::<LegacyReplay, _>(&payload[2..], bincode::config::standard()).map_err(corrupt)?.0.into()
+        };
+        if version == Some(1) {
+            replay.settings.quirks = quirks::upgrade_legacy_name(&replay.settings.quirks);
+        }
+        if replay.frames != replay.inputs.len() as u64 {
+            return Err(invalid(format!("Replay claims {} frames but holds {}", replay.frames, replay.inputs.len())));
+        }
+        Ok(replay)
+    }
+
+    /// A machine set up as recorded with the ROM loaded, if it is the ROM that was recorded
+    pub fn prepare(&self, rom: &[u8]) -> Result<CPU, std::io::Error> {
+        let sha1 = RomInfo::new(rom).sha1_hex();
+        if sha1 != self.rom_sha1 {
+            return Err(invalid(format!("The replay was recorded with ROM {}, not {}", self.rom_sha1, sha1)));
+        }
+
+        let preset = Preset::from_name(&self.settings.quirks)
+            .ok_or_else(|| invalid(format!("The replay needs quirks '{}'", self.settings.quirks)))?;
+        let mut cpu = CPU::with_stack_depth(self.settings.stack_depth as usize);
+        cpu.set_quirks(preset.quirks());
+        cpu.set_mapped_display(self.settings.vip_display);
+        cpu.set_seed(self.seed);
+        cpu.load_rom_bytes(rom)?;
+        Ok(cpu)
+    }
+
+    /// Plays every recorded update and returns how the machine ended up
+    pub fn play(&self, cpu: &mut CPU) -> Result<FinalState, std::io::Error> {
+        let mut budget = FrameBudget::new(self.settings.timing, self.settings.instructions_per_frame);
+        for input in &self.inputs {
+            let (mut keys, mut latched) = (input.keys, input.latched);
+            cpu.set_keys(keys);
+            let mut step = |cpu: &mut CPU| {
+                let cycles = cpu.tick()?;
+                emulator::release_awaited_latch(cpu, &mut keys, &mut latched);
+                Ok::<_, std::io::Error>(cycles)
+            };
+            for _ in 0..input.frames {
+                budget.begin_frame();
+                while budget.has_time() {
+                    budget.spend(step(cpu)?);
+                }
+            }
+            for _ in 0..input.instructions {
+                step(cpu)?;
+            }
+            for _ in 0..input.timers {
+                cpu.update_timers();
+            }
+        }
+        Ok(FinalState::of(cpu))
+    }
+
+    /// Plays the replay against rom; an empty list means it ended exactly as recorded
+    pub fn verify(&self, rom: &[u8]) -> Result<Vec<Mismatch>, std::io::Error> {
+        let mut cpu = self.prepare(rom)?;
+        let actual = self.play(&mut cpu)?;
+        Ok(self.expected.diff(&actual))
+    }
+}
+
+/// Collects a session's input frame by frame
+pub struct Recorder {
+    rom_sha1: String,
+    settings: Settings,
+    seed: u64,
+    inputs: Vec<FrameInput>,
+}
+
+impl Recorder {
+    /// Starts recording cpu, which must be freshly loaded
+    pub fn new(cpu: &CPU, settings: Settings) -> Self {
+        let rom_sha1 = cpu.rom_info().map(RomInfo::sha1_hex).unwrap_or_default();
+        Self { rom_sha1, settings, seed: cpu.seed(), inputs: Vec::new() }
+    }
+
+    /// Throws away what was recorded, for when the machine is reset
+    pub fn restart(&mut self, cpu: &CPU) {
+        self.seed = cpu.seed();
+        self.inputs.clear();
+    }
+
+    pub fn record(&mut self, input: FrameInput) {
+        self.inputs.push(input);
+    }
+
+    pub fn finish(self, cpu: &CPU) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            rom_sha1: self.rom_sha1,
+            settings: self.settings,
+            seed: self.seed,
+            frames: self.inputs.len() as u64,
+            inputs: self.inputs,
+            expected: FinalState::of(cpu),
+        }
+    }
+}
+
+/// Version 3, which had no latched keys
+#[derive(Deserialize)]
+struct Version3Replay {
+    version: u16,
+    rom_sha1: String,
+    settings: Settings,
+    seed: u64,
+    inputs: Vec<Version3FrameInput>,
+    frames: u64,
+    expected: FinalState,
+}
+
+#[derive(Deserialize)]
+struct Version3FrameInput {
+    keys: u16,
+    frames: u32,
+    instructions: u32,
+    timers: u32,
+}
+
+impl From<Version3Replay> for Replay {
+    fn from(old: Version3Replay) -> Self {
+        let inputs = old.inputs.into_iter()
+            .map(|input| FrameInput { keys: input.keys, latched: 0, frames: input.frames, instructions: input.instructions, timers: input.timers })
+            .collect();
+        Self { version: old.version, rom_sha1: old.rom_sha1, settings: old.settings, seed: old.seed, inputs, frames: old.frames, expected: old.expected }
+    }
+}
+
+/// Versions 1 and 2, which ran one budget frame per input and ticked the timers at most once
+#[derive(Deserialize)]
+struct LegacyReplay {
+    version: u16,
+    rom_sha1: String,
+    settings: LegacySettings,
+    seed: u64,
+    inputs: Vec<LegacyFrameInput>,
+    frames: u64,
+    expected: FinalState,
+}
+
+#[derive(Deserialize)]
+struct LegacySettings {
+    quirks: String,
+    timing: TimingMode,
+    instructions_per_frame: u32,
+    stack_depth: u8,
+    vip_display: bool,
+}
+
+#[derive(Deserialize)]
+struct LegacyFrameInput {
+    keys: u16,
+    timers: bool,
+}
+
+impl From<LegacyReplay> for Replay {
+    fn from(old: LegacyReplay) -> Self {
+        let settings = Settings {
+            quirks: old.settings.quirks,
+            timing: old.settings.timing,
+            instructions_per_frame: old.settings.instructions_per_frame,
+            stack_depth: old.settings.stack_depth,
+            vip_display: old.settings.vip_display,
+            hz: None,
+        };
+        let inputs = old.inputs.into_iter()
+            .map(|input| FrameInput { keys: input.keys, latched: 0, frames: 1, instructions: 0, timers: u32::from(input.timers) })
+            .collect();
+        Self { version: old.version, rom_sha1: old.rom_sha1, settings, seed: old.seed, inputs, frames: old.frames, expected: old.expected }
+    }
+}
+
+fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::clock::Speed;
+    use crate::config::Config;
+    use crate::emulator::{Emulator, EmulatorEvent, InputEvent, MachineSetup, TIMER_INTERVAL};
+    use crate::headless;
+    use crate::rom_builder::RomBuilder;
+
+    fn settings() -> Settings {
+        Settings {
+            quirks: "modern".to_string(),
+            timing: TimingMode::Simple,
+            instructions_per_frame: 10,
+            stack_depth: 16,
+            vip_display: false,
+            hz: None,
+        }
+    }
+
+    /// Draws a random byte's worth of pixels wherever key 5 has been held, so the outcome
+    /// depends on both the seed and the input
+    fn rom() -> Vec<u8> {
+        RomBuilder::new()
+            .ld(1, 5)
+            .label("loop")
+            .op(0xE19E) // SKP V1
+            .jp("loop")
+            .op(0xC2FF) // RND V2, 0xFF
+            .ld_i("sprite")
+            .op(0xF233) // BCD of V2 into the sprite
+            .draw(0, 3, 3)
+            .add(0, 8)
+            .jp("loop")
+            .label("sprite")
+            .data(&[0, 0, 0])
+            .build()
+            .unwrap()
+    }
+
+    /// Records a session the way the window does
+    fn record(seed: u64, presses: &[bool]) -> Replay {
+        let rom = rom();
+        let mut cpu = CPU::new();
+        cpu.set_seed(seed);
+        cpu.load_rom_bytes(&rom).unwrap();
+
+        let mut recorder = Recorder::new(&cpu, settings());
+        let mut budget = FrameBudget::new(TimingMode::Simple, 10);
+        for (frame, pressed) in presses.iter().enumerate() {
+            let keys = if *pressed { 1 << 5 } else { 0 };
+            cpu.set_keys(keys);
+            headless::run_instructions(&mut cpu, &mut budget).unwrap();
+            let timers = u32::from(frame % 5 != 4);
+            if timers > 0 {
+                cpu.update_timers();
+            }
+            recorder.record(FrameInput { keys, latched: 0, frames: 1, instructions: 0, timers });
+        }
+        recorder.finish(&cpu)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let replay = record(7, &[false, true, true, false, true]);
+        assert_eq!(replay.frames, 5);
+        assert_eq!(Replay::decode(&replay.encode()).unwrap(), replay);
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_files() {
+        let bytes = record(7, &[true]).encode();
+        assert!(Replay::decode(b"CHIP8SAV").is_err());
+        assert!(Replay::decode(&bytes[..bytes.len() - 4]).is_err());
+
+        let mut newer = bytes.clone();
+        newer[9] = REPLAY_VERSION as u8 + 1;
+        assert!(Replay::decode(&newer).unwrap_err().to_string().contains("version"));
+    }
+
+    /// A version 1 or 2 file: what Replay held before the counts, with this replay's values
+    fn legacy(replay: &Replay, version: u16) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct Old<'a> {
+            version: u16,
+            rom_sha1: &'a str,
+            settings: (&'a str, TimingMode, u32, u8, bool),
+            seed: u64,
+            inputs: Vec<(u16, bool)>,
+            frames: u64,
+            expected: &'a FinalState,
+        }
+        let settings = &replay.settings;
+        let old = Old {
+            version,
+            rom_sha1: &replay.rom_sha1,
+            settings: (&settings.quirks, settings.timing, settings.instructions_per_frame, settings.stack_depth, settings.vip_display),
+            seed: replay.seed,
+            inputs: replay.inputs.iter().map(|input| (input.keys, input.timers > 0)).collect(),
+            frames: replay.frames,
+            expected: &replay.expected,
+        };
+        let mut out = REPLAY_MAGIC.to_vec();
+        out.extend_from_slice(&version.to_be_bytes());
+        out.extend(bincode::serde::encode_to_vec(&old, bincode::config::standard()).unwrap());
+        out
+    }
+
+    #[test]
+    fn test_version_1_names_the_modern_preset() {
+        let mut replay = record(7, &[true]);
+        replay.settings.quirks = "chip8".to_string();
+        assert_eq!(Replay::decode(&legacy(&replay, 1)).unwrap().settings.quirks, "modern");
+        assert_eq!(Replay::decode(&replay.encode()).unwrap().settings.quirks, "chip8");
+    }
+
+    #[test]
+    fn test_older_versions_still_verify() {
+        let replay = record(42, &[true, true, false, true, true, true, false, false]);
+        let old = Replay::decode(&legacy(&replay, 2)).unwrap();
+        assert_eq!((old.version, old.inputs.clone()), (2, replay.inputs.clone()));
+        assert_eq!(old.settings.hz, None);
+        assert_eq!(old.verify(&rom()).unwrap(), []);
+    }
+
+    #[test]
+    fn test_version_3_has_no_latched_keys() {
+        #[derive(Serialize)]
+        struct Old<'a> {
+            version: u16,
+            rom_sha1: &'a str,
+            settings: &'a Settings,
+            seed: u64,
+            inputs: Vec<(u16, u32, u32, u32)>,
+            frames: u64,
+            expected: &'a FinalState,
+        }
+        let replay = record(42, &[true, true, false, true]);
+        let old = Old {
+            version: 3,
+            rom_sha1: &replay.rom_sha1,
+            settings: &replay.settings,
+            seed: replay.seed,
+            inputs: replay.inputs.iter().map(|input| (input.keys, input.frames, input.instructions, input.timers)).collect(),
+            frames: replay.frames,
+            expected: &replay.expected,
+        };
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.extend_from_slice(&3u16.to_be_bytes());
+        bytes.extend(bincode::serde::encode_to_vec(&old, bincode::config::standard()).unwrap());
+
+        let decoded = Replay::decode(&bytes).unwrap();
+        assert_eq!((decoded.version, decoded.inputs.clone()), (3, replay.inputs.clone()));
+        assert_eq!(decoded.verify(&rom()).unwrap(), []);
+    }
+
+    #[test]
+    fn test_sticky_key_taken_by_fx0a_verifies() {
+        // Counts in V1 from the moment FX0A takes the key, which is partway through the first update
+        let rom = RomBuilder::new().op(0xF00A).label("count").add(1, 1).jp("count").build().unwrap();
+        let setup = MachineSetup { seed: Some(1), ..Default::default() };
+        let mut emulator = Emulator::new(Config::default(), setup, TimingMode::Simple, 10);
+        emulator.load_bytes(rom.clone()).unwrap();
+        let mut recorder = Recorder::new(&emulator.cpu, settings());
+
+        emulator.handle_event(InputEvent::Keys { down: 1 << 5, latched: 1 << 5 });
+        for _ in 0..3 {
+            for event in emulator.update(TIMER_INTERVAL) {
+                match event {
+                    EmulatorEvent::Frame { keys, latched, frames, instructions, timers } => {
+                        recorder.record(FrameInput { keys, latched, frames, instructions, timers });
+                    }
+                    // As the window does, the latch is let go from then on
+                    EmulatorEvent::LatchConsumed(_) => emulator.handle_event(InputEvent::Keys { down: 0, latched: 0 }),
+                    _ => {}
+                }
+            }
+        }
+        let replay = recorder.finish(&emulator.cpu);
+        assert_eq!(replay.inputs[0].latched, 1 << 5);
+        assert_eq!(replay.verify(&rom).unwrap(), []);
+    }
+
+    /// Records the emulator the way the window does, one input per Frame event
+    fn record_updates(emulator: &mut Emulator, updates: &[(u16, Duration)], settings: Settings) -> Replay {
+        let mut recorder = Recorder::new(&emulator.cpu, settings);
+        for (keys, dt) in updates {
+            emulator.handle_event(InputEvent::Keys { down: *keys, latched: 0 });
+            for event in emulator.update(*dt) {
+                if let EmulatorEvent::Frame { keys, latched, frames, instructions, timers } = event {
+                    recorder.record(FrameInput { keys, latched, frames, instructions, timers });
+                }
+            }
+        }
+        recorder.finish(&emulator.cpu)
+    }
+
+    #[test]
+    fn test_speed_clock_rate_and_long_updates_verify() {
+        // Pressed, released and pressed again, with a long stall and updates too short to run a frame at half speed
+        let updates = [(1 << 5, 10), (0, 4), (1 << 5, 250), (1 << 5, 16), (0, 3), (1 << 5, 40), (1 << 5, 7)]
+            .map(|(keys, ms)| (keys, Duration::from_millis(ms)));
+        let emulator = |hz: Option<u32>, speed: Speed| {
+            let setup = MachineSetup { seed: Some(42), ..Default::default() };
+            let mut emulator = Emulator::new(Config::default(), setup, TimingMode::Simple, 10);
+            emulator.handle_event(InputEvent::SetClockRate(hz));
+            emulator.handle_event(InputEvent::SetClockSpeed(speed));
+            emulator.load_bytes(rom()).unwrap();
+            emulator
+        };
+
+        for (hz, speed) in [(None, Speed::Double), (None, Speed::Half), (Some(900), Speed::Normal), (Some(900), Speed::Double)] {
+            let mut emulator = emulator(hz, speed);
+            let replay = record_updates(&mut emulator, &updates, Settings { hz, ..settings() });
+            assert!(replay.inputs.iter().any(|input| input.timers > 1), "{:?} {:?}", hz, speed);
+            let decoded = Replay::decode(&replay.encode()).unwrap();
+            assert_eq!(decoded.verify(&rom()).unwrap(), [], "{:?} {:?}", hz, speed);
+        }
+    }
+
+    #[test]
+    fn test_verify_passes() {
+        let replay = record(42, &[true, true, false, true, true, true, false, false]);
+        assert_ne!(replay.expected.display_hash, savestate::rom_hash(&[0; 64 * 32]));
+        assert_eq!(replay.verify(&rom()).unwrap(), []);
+    }
+
+    #[test]
+    fn test_verify_reports_differences() {
+        let mut replay = record(42, &[true, true, true, true]);
+        // A different seed draws different pixels
+        replay.seed = 43;
+        let mismatches = replay.verify(&rom()).unwrap();
+        assert!(mismatches.iter().any(|mismatch| mismatch.field == "display"));
+        assert!(mismatches.iter().any(|mismatch| mismatch.field == "V2"));
+
+        // Without the key held nothing is drawn
+        let mut replay = record(42, &[true, true, true, true]);
+        replay.inputs.iter_mut().for_each(|input| input.keys = 0);
+        assert!(replay.verify(&rom()).unwrap().iter().any(|mismatch| mismatch.field == "V0"));
+    }
+
+    #[test]
+    fn test_verify_checks_rom_hash() {
+        let replay = record(1, &[true]);
+        let mut other = rom();
+        other[1] = 6;
+        let error = replay.verify(&other).unwrap_err();
+        assert!(error.to_string().contains(&replay.rom_sha1));
+    }
+
+    #[test]
+    fn test_recorder_restart() {
+        let mut cpu = CPU::new();
+        cpu.load_rom_bytes(&rom()).unwrap();
+        let mut recorder = Recorder::new(&cpu, settings());
+        recorder.record(FrameInput { keys: 1, latched: 0, frames: 1, instructions: 0, timers: 1 });
+
+        cpu.set_seed(99);
+        recorder.restart(&cpu);
+        let replay = recorder.finish(&cpu);
+        assert_eq!((replay.frames, replay.seed), (0, 99));
+    }
+
+    #[test]
+    fn test_mismatch_table_row() {
+        let expected = FinalState { pc: 0x200, i: 0, v: [0; REGISTERS_COUNT], display_hash: 1 };
+        let mut actual = expected.clone();
+        actual.pc = 0x20A;
+        let mismatches = expected.diff(&actual);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].to_string(), "pc       expected 0x200              got 0x20A");
+    }
+}
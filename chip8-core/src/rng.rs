@@ -0,0 +1,68 @@
+/// SplitMix64, the generator behind CXNN. Its whole state is one u64, so a run can be
+/// reproduced from its seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    /// A generator part way through its sequence, as saved by seed() and state()
+    pub fn resume(seed: u64, state: u64) -> Self {
+        Self { seed, state }
+    }
+
+    /// A generator with a seed drawn from the OS
+    pub fn from_entropy() -> Self {
+        Self::from_seed(rand::random())
+    }
+
+    /// The seed this generator started from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Where in the sequence the generator is
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_sequence() {
+        // Reference SplitMix64 output for seed 0
+        let mut rng = Rng::from_seed(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+    }
+
+    #[test]
+    fn test_same_seed_same_bytes() {
+        let mut a = Rng::from_seed(0xDEAD_BEEF);
+        let mut b = Rng::from_seed(0xDEAD_BEEF);
+        let bytes = (0..64).map(|_| a.byte()).collect::<Vec<_>>();
+        assert_eq!(bytes, (0..64).map(|_| b.byte()).collect::<Vec<_>>());
+        assert!(bytes.iter().any(|byte| *byte != bytes[0]));
+        assert_eq!(a.seed(), 0xDEAD_BEEF);
+    }
+}
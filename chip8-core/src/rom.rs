@@ -0,0 +1,141 @@
+use sha1::{Digest, Sha1};
+
+/// Files with these extensions are taken to be ROMs, inside archives and in the file dialog
+pub const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "rom", "sc8", "xo8"];
+
+/// Identifying details of a ROM image, computed once when it is loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub size: usize,
+    pub sha1: [u8; 20],
+    pub crc32: u32,
+}
+
+impl RomInfo {
+    pub fn new(rom: &[u8]) -> Self {
+        Self {
+            size: rom.len(),
+            sha1: Sha1::digest(rom).into(),
+            crc32: crc32fast::hash(rom),
+        }
+    }
+
+    pub fn sha1_hex(&self) -> String {
+        self.sha1.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// One line for `chip8_emulator hash`, in the style of sha1sum
+    pub fn summary(&self, name: &str) -> String {
+        format!("{}  {:08x}  {:>6} bytes  {}", self.sha1_hex(), self.crc32, self.size, name)
+    }
+}
+
+/// Reads a ROM file. With the `zip` feature this also reads `pack.zip` if it holds one ROM,
+/// and `pack.zip/GAME.ch8` for a ROM inside an archive
+pub fn read(path: &str) -> Result<Vec<u8>, std::io::Error> {
+    #[cfg(feature = "zip")]
+    {
+        if let Some((archive, entry)) = crate::archive::split_entry_path(path) {
+            return crate::archive::read_entry(&std::fs::read(archive)?, entry);
+        }
+        let bytes = std::fs::read(path)?;
+        if crate::archive::is_zip(path, &bytes) {
+            return crate::archive::read_single(&bytes);
+        }
+        Ok(bytes)
+    }
+    #[cfg(not(feature = "zip"))]
+    std::fs::read(path)
+}
+
+/// Whether read could find the ROM: the file itself, or the archive a `pack.zip/GAME.ch8` path points into
+pub fn exists(path: &str) -> bool {
+    #[cfg(feature = "zip")]
+    if let Some((archive, _)) = crate::archive::split_entry_path(path) {
+        return std::path::Path::new(archive).is_file();
+    }
+    std::path::Path::new(path).is_file()
+}
+
+/// What the ROM picker lists for a file: archives holding several ROMs list each as `pack.zip/GAME.ch8`.
+/// An archive that can't be listed has no entries, and a warning saying why
+pub fn picker_entries(path: &str) -> (Vec<String>, Vec<String>) {
+    #[cfg(feature = "zip")]
+    if let Ok(bytes) = std::fs::read(path)
+        && crate::archive::is_zip(path, &bytes)
+    {
+        return match crate::archive::list_roms(&bytes) {
+            Ok(names) if names.len() > 1 => (names.iter().map(|name| format!("{}/{}", path, name)).collect(), Vec::new()),
+            Ok(_) => (vec![path.to_string()], Vec::new()),
+            Err(e) => (Vec::new(), vec![format!("{}: {}", path, e)]),
+        };
+    }
+    (vec![path.to_string()], Vec::new())
+}
+
+impl std::fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} bytes, SHA-1 {}, CRC32 {:08x}", self.size, self.sha1_hex(), self.crc32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_hashes() {
+        let info = RomInfo::new(b"abc");
+        assert_eq!(info.size, 3);
+        assert_eq!(info.sha1_hex(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(info.crc32, 0x352441c2);
+    }
+
+    #[test]
+    fn test_summary_snapshot() {
+        assert_eq!(
+            RomInfo::new(b"abc").summary("roms/abc.ch8"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d  352441c2       3 bytes  roms/abc.ch8"
+        );
+        assert_eq!(
+            RomInfo::new(b"").summary("empty.ch8"),
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709  00000000       0 bytes  empty.ch8"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            RomInfo::new(b"abc").to_string(),
+            "3 bytes, SHA-1 a9993e364706816aba3e25717850c26c9cd0d89d, CRC32 352441c2"
+        );
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_read_from_archive() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, rom) in [("A.ch8", [0xA1]), ("B.ch8", [0xB2])] {
+            writer.start_file(name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(&rom).unwrap();
+        }
+        let path = std::env::temp_dir().join(format!("chip8-pack-{}.zip", std::process::id()));
+        std::fs::write(&path, writer.finish().unwrap().into_inner()).unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        assert_eq!(read(&format!("{}/B.ch8", path)).unwrap(), [0xB2]);
+        assert!(read(&path).is_err());
+        assert!(exists(&format!("{}/B.ch8", path)));
+        assert_eq!(picker_entries(&path), (vec![format!("{}/A.ch8", path), format!("{}/B.ch8", path)], Vec::new()));
+
+        std::fs::write(&path, b"PK\x03\x04 not really").unwrap();
+        let (entries, warnings) = picker_entries(&path);
+        assert!(entries.is_empty());
+        assert_eq!(warnings.len(), 1);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use crate::constants::{MEMORY_SIZE, STARTING_MEMORY_ADDRESS};
+use crate::cpu::CPU;
+
+/// Assembles a ROM from typed instructions, for tests and generated workloads.
+/// Labels may be used before they are defined; they are resolved by `build`
+///
+/// ```
+/// use chip8_core::rom_builder::RomBuilder;
+///
+/// let rom = RomBuilder::new()
+///     .ld(0, 0)
+///     .label("loop")
+///     .add(0, 1)
+///     .jp("loop")
+///     .build()
+///     .unwrap();
+/// assert_eq!(rom, [0x60, 0x00, 0x70, 0x01, 0x12, 0x02]);
+/// ```
+#[derive(Debug, Default)]
+pub struct RomBuilder {
+    bytes: Vec<u8>,
+    labels: HashMap<String, u16>,
+    fixups: Vec<(usize, String)>, // Offsets of opcodes whose NNN is the address of a label
+    duplicate: Option<String>,
+}
+
+impl RomBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks the address of the next instruction or data
+    pub fn label(mut self, name: &str) -> Self {
+        let addr = self.here();
+        if self.labels.insert(name.to_string(), addr).is_some() {
+            self.duplicate.get_or_insert_with(|| name.to_string());
+        }
+        self
+    }
+
+    /// Any opcode, for instructions without a typed method
+    pub fn op(mut self, opcode: u16) -> Self {
+        self.bytes.extend_from_slice(&opcode.to_be_bytes());
+        self
+    }
+
+    pub fn data(mut self, bytes: &[u8]) -> Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    /// 00E0
+    pub fn cls(self) -> Self {
+        self.op(0x00E0)
+    }
+
+    /// 00EE
+    pub fn ret(self) -> Self {
+        self.op(0x00EE)
+    }
+
+    /// 1NNN
+    pub fn jp(self, label: &str) -> Self {
+        self.op_to(0x1000, label)
+    }
+
+    /// 2NNN
+    pub fn call(self, label: &str) -> Self {
+        self.op_to(0x2000, label)
+    }
+
+    /// 6XNN
+    pub fn ld(self, x: u8, nn: u8) -> Self {
+        self.op(0x6000 | register(x) | nn as u16)
+    }
+
+    /// 7XNN
+    pub fn add(self, x: u8, nn: u8) -> Self {
+        self.op(0x7000 | register(x) | nn as u16)
+    }
+
+    /// ANNN
+    pub fn ld_i(self, label: &str) -> Self {
+        self.op_to(0xA000, label)
+    }
+
+    /// DXYN
+    pub fn draw(self, x: u8, y: u8, n: u8) -> Self {
+        self.op(0xD000 | register(x) | (register(y) >> 4) | (n & 0x0F) as u16)
+    }
+
+    /// A jump to itself, the usual way for a ROM to stop
+    pub fn spin(self) -> Self {
+        let addr = self.here();
+        self.op(0x1000 | (addr & 0x0FFF))
+    }
+
+    /// The ROM image, to be loaded at 0x200
+    pub fn build(mut self) -> Result<Vec<u8>, std::io::Error> {
+        let invalid = |msg: String| std::io::Error::new(std::io::ErrorKind::InvalidData, msg);
+
+        if let Some(name) = self.duplicate {
+            return Err(invalid(format!("Label {} defined twice", name)));
+        }
+        if STARTING_MEMORY_ADDRESS + self.bytes.len() > MEMORY_SIZE {
+            return Err(invalid(format!("ROM is {} bytes, more than fits in memory", self.bytes.len())));
+        }
+
+        for (offset, label) in &self.fixups {
+            let addr = *self.labels.get(label).ok_or_else(|| invalid(format!("Undefined label {}", label)))?;
+            self.bytes[*offset] |= (addr >> 8) as u8;
+            self.bytes[*offset + 1] = addr as u8;
+        }
+
+        Ok(self.bytes)
+    }
+
+    /// Builds the ROM and loads it into `cpu` as `CPU::load_rom_bytes` would a file
+    pub fn load(self, cpu: &mut CPU) -> Result<(), std::io::Error> {
+        let rom = self.build()?;
+        // build has already refused anything too large to load
+        cpu.load_rom_bytes(&rom).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    /// A fresh CPU with the ROM loaded
+    pub fn cpu(self) -> Result<CPU, std::io::Error> {
+        let mut cpu = CPU::new();
+        self.load(&mut cpu)?;
+        Ok(cpu)
+    }
+
+    fn op_to(mut self, opcode: u16, label: &str) -> Self {
+        self.fixups.push((self.bytes.len(), label.to_string()));
+        self.op(opcode)
+    }
+
+    /// Address of the next byte once loaded. Wraps for oversized ROMs, which build rejects
+    fn here(&self) -> u16 {
+        (STARTING_MEMORY_ADDRESS + self.bytes.len()) as u16
+    }
+}
+
+/// X in the second nibble; `>> 4` moves it to Y
+fn register(x: u8) -> u16 {
+    ((x & 0x0F) as u16) << 8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emits_opcodes() {
+        let rom = RomBuilder::new()
+            .cls()
+            .ld(0xA, 0x12)
+            .add(0x3, 0xFF)
+            .draw(0x1, 0x2, 5)
+            .ret()
+            .op(0xF365)
+            .data(&[0xF0, 0x90])
+            .build()
+            .unwrap();
+
+        assert_eq!(rom, [0x00, 0xE0, 0x6A, 0x12, 0x73, 0xFF, 0xD1, 0x25, 0x00, 0xEE, 0xF3, 0x65, 0xF0, 0x90]);
+    }
+
+    #[test]
+    fn test_resolves_labels_both_directions() {
+        let rom = RomBuilder::new()
+            .call("sub")       // 0x200
+            .label("top")
+            .jp("top")         // 0x202
+            .label("sub")
+            .ld_i("sprite")    // 0x204
+            .ret()             // 0x206
+            .label("sprite")
+            .data(&[0xFF])     // 0x208
+            .build()
+            .unwrap();
+
+        assert_eq!(rom, [0x22, 0x04, 0x12, 0x02, 0xA2, 0x08, 0x00, 0xEE, 0xFF]);
+    }
+
+    #[test]
+    fn test_spin_jumps_to_itself() {
+        let rom = RomBuilder::new().data(&[0; 0x100]).spin().build().unwrap();
+        assert_eq!(rom[0x100..], [0x13, 0x00]);
+    }
+
+    #[test]
+    fn test_label_errors() {
+        assert!(RomBuilder::new().jp("nowhere").build().is_err());
+        assert!(RomBuilder::new().label("a").cls().label("a").build().is_err());
+    }
+
+    #[test]
+    fn test_oversized_rom_is_error() {
+        let fits = MEMORY_SIZE - STARTING_MEMORY_ADDRESS;
+        assert!(RomBuilder::new().data(&vec![0; fits]).build().is_ok());
+        assert!(RomBuilder::new().data(&vec![0; fits]).spin().build().is_err());
+    }
+
+    #[test]
+    fn test_built_rom_runs() {
+        let mut cpu = RomBuilder::new()
+            .ld(0, 3)
+            .call("double")
+            .spin()
+            .label("double")
+            .op(0x8004) // ADD V0, V0
+            .ret()
+            .cpu()
+            .unwrap();
+
+        for _ in 0..5 {
+            cpu.tick().unwrap();
+        }
+        assert_eq!(cpu.v[0], 6);
+        assert_eq!(cpu.pc, 0x204);
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeSet;
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+use crate::cpu::CPU;
+use crate::error::EmulatorError;
+
+/// When `CPU::run_until` should stop. Conditions are checked after each instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopCondition {
+    /// The next instruction to run is at this address
+    PcEquals(u16),
+    /// The display differs from how it was when the run started
+    DisplayChanged,
+    /// The sound timer went from zero to running
+    SoundStarted,
+    /// The call stack is exactly this deep
+    StackDepth(u8),
+    /// This many instructions have run
+    InstructionCount(u64),
+    /// Whichever of these fires first
+    Any(Vec<StopCondition>),
+}
+
+/// Why a run stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    /// The condition that fired; for `Any`, the one inside it
+    Condition(StopCondition),
+    /// A debugger breakpoint was reached
+    Breakpoint(u16),
+    /// An instruction left pc where it was: a jump to itself, or FX0A with no key pressed
+    Halted,
+    /// The instruction at pc failed
+    Error(EmulatorError),
+    /// The instruction cap was reached first
+    Limit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOutcome {
+    pub reason: StopReason,
+    pub instructions: u64,
+}
+
+/// What the conditions compare against
+struct Baseline {
+    display: [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+    sound_timer: u8, // Before the latest instruction
+}
+
+impl StopCondition {
+    fn fired(&self, cpu: &CPU, baseline: &Baseline, executed: u64) -> Option<StopCondition> {
+        let fired = match self {
+            StopCondition::PcEquals(addr) => cpu.pc == *addr,
+            StopCondition::DisplayChanged => cpu.display != baseline.display,
+            StopCondition::SoundStarted => baseline.sound_timer == 0 && cpu.sound_timer > 0,
+            StopCondition::StackDepth(depth) => cpu.stack.len() == *depth as usize,
+            StopCondition::InstructionCount(count) => executed >= *count,
+            StopCondition::Any(conditions) => {
+                return conditions.iter().find_map(|condition| condition.fired(cpu, baseline, executed));
+            }
+        };
+        fired.then(|| self.clone())
+    }
+}
+
+impl CPU {
+    /// Runs instructions until stop fires, the machine halts or errors, or max_instructions have run.
+    /// At least one instruction runs, so a condition that already holds at the start doesn't stop it
+    pub fn run_until(&mut self, stop: StopCondition, max_instructions: u64) -> RunOutcome {
+        self.run_until_or_break(&stop, max_instructions, &BTreeSet::new())
+    }
+
+    /// run_until that also stops on reaching any of the breakpoints
+    pub(crate) fn run_until_or_break(&mut self, stop: &StopCondition, max_instructions: u64, breakpoints: &BTreeSet<u16>) -> RunOutcome {
+        let mut baseline = Baseline { display: self.display, sound_timer: self.sound_timer };
+        let mut instructions = 0;
+
+        let reason = loop {
+            if instructions >= max_instructions {
+                break StopReason::Limit;
+            }
+
+            let (pc, depth) = (self.pc, self.stack.len());
+            baseline.sound_timer = self.sound_timer;
+            if let Err(e) = self.tick() {
+                break StopReason::Error(e);
+            }
+            instructions += 1;
+
+            if let Some(condition) = stop.fired(self, &baseline, instructions) {
+                break StopReason::Condition(condition);
+            }
+            if breakpoints.contains(&self.pc) {
+                break StopReason::Breakpoint(self.pc);
+            }
+            if stuck(self, pc, depth) {
+                break StopReason::Halted;
+            }
+        };
+
+        RunOutcome { reason, instructions }
+    }
+}
+
+/// True if the instruction that started at pc with the stack depth-deep left the machine where it was.
+/// A call to itself isn't stuck: it runs until the stack overflows
+pub(crate) fn stuck(cpu: &CPU, pc: u16, depth: usize) -> bool {
+    cpu.pc == pc && cpu.stack.len() == depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::RomBuilder;
+
+    /// V0 counts up forever
+    fn counter() -> RomBuilder {
+        RomBuilder::new().label("loop").add(0, 1).jp("loop")
+    }
+
+    #[test]
+    fn test_pc_equals() {
+        let mut cpu = RomBuilder::new().ld(0, 1).ld(1, 2).ld(2, 3).spin().cpu().unwrap();
+        let outcome = cpu.run_until(StopCondition::PcEquals(0x204), 100);
+
+        assert_eq!(outcome, RunOutcome { reason: StopReason::Condition(StopCondition::PcEquals(0x204)), instructions: 2 });
+        assert_eq!(cpu.v[1], 2);
+        assert_eq!(cpu.v[2], 0);
+    }
+
+    #[test]
+    fn test_display_changed() {
+        let mut cpu = RomBuilder::new().ld(0, 5).ld(1, 5).ld_i("sprite").draw(0, 1, 1).spin().label("sprite").data(&[0x80]).cpu().unwrap();
+        let outcome = cpu.run_until(StopCondition::DisplayChanged, 100);
+
+        assert_eq!(outcome.reason, StopReason::Condition(StopCondition::DisplayChanged));
+        assert_eq!(outcome.instructions, 4);
+        assert_eq!(cpu.display[5 * DISPLAY_WIDTH + 5], 1);
+    }
+
+    #[test]
+    fn test_sound_started() {
+        // The first FX18 loads zero and doesn't count
+        let mut cpu = RomBuilder::new().op(0xF018).ld(0, 30).op(0xF018).spin().cpu().unwrap();
+        let outcome = cpu.run_until(StopCondition::SoundStarted, 100);
+
+        assert_eq!(outcome.reason, StopReason::Condition(StopCondition::SoundStarted));
+        assert_eq!(outcome.instructions, 3);
+        assert_eq!(cpu.sound_timer, 30);
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        let mut cpu = RomBuilder::new().call("a").spin().label("a").call("b").ret().label("b").ld(3, 3).ret().cpu().unwrap();
+
+        let outcome = cpu.run_until(StopCondition::StackDepth(2), 100);
+        assert_eq!(outcome.instructions, 2);
+        assert_eq!(cpu.pc, 0x208);
+
+        // Step out of both calls
+        let outcome = cpu.run_until(StopCondition::StackDepth(0), 100);
+        assert_eq!(outcome.reason, StopReason::Condition(StopCondition::StackDepth(0)));
+        assert_eq!(cpu.v[3], 3);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_instruction_count() {
+        let mut cpu = counter().cpu().unwrap();
+        let outcome = cpu.run_until(StopCondition::InstructionCount(9), 100);
+
+        assert_eq!(outcome, RunOutcome { reason: StopReason::Condition(StopCondition::InstructionCount(9)), instructions: 9 });
+        assert_eq!(cpu.v[0], 5);
+    }
+
+    #[test]
+    fn test_any_reports_the_condition_that_fired() {
+        let mut cpu = counter().cpu().unwrap();
+        let stop = StopCondition::Any(vec![StopCondition::DisplayChanged, StopCondition::PcEquals(0x202)]);
+        let outcome = cpu.run_until(stop, 100);
+
+        assert_eq!(outcome.reason, StopReason::Condition(StopCondition::PcEquals(0x202)));
+        assert_eq!(outcome.instructions, 1);
+    }
+
+    #[test]
+    fn test_cap() {
+        let mut cpu = counter().cpu().unwrap();
+        assert_eq!(cpu.run_until(StopCondition::DisplayChanged, 50), RunOutcome { reason: StopReason::Limit, instructions: 50 });
+        assert_eq!(cpu.run_until(StopCondition::DisplayChanged, 0).instructions, 0);
+    }
+
+    #[test]
+    fn test_halts() {
+        let mut cpu = RomBuilder::new().ld(0, 1).spin().cpu().unwrap();
+        assert_eq!(cpu.run_until(StopCondition::DisplayChanged, 100), RunOutcome { reason: StopReason::Halted, instructions: 2 });
+
+        // Waiting for a key that never comes
+        let mut cpu = RomBuilder::new().op(0xF00A).cpu().unwrap();
+        assert_eq!(cpu.run_until(StopCondition::DisplayChanged, 100).reason, StopReason::Halted);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut cpu = RomBuilder::new().ret().cpu().unwrap();
+        let outcome = cpu.run_until(StopCondition::DisplayChanged, 100);
+        assert_eq!(outcome, RunOutcome { reason: StopReason::Error(EmulatorError::StackUnderflow { pc: 0x200 }), instructions: 0 });
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut cpu = counter().cpu().unwrap();
+        let breakpoints = BTreeSet::from([0x202]);
+        let outcome = cpu.run_until_or_break(&StopCondition::InstructionCount(10), 100, &breakpoints);
+        assert_eq!(outcome, RunOutcome { reason: StopReason::Breakpoint(0x202), instructions: 1 });
+
+        // Starting on a breakpoint runs past it
+        let outcome = cpu.run_until_or_break(&StopCondition::InstructionCount(10), 100, &breakpoints);
+        assert_eq!(outcome.instructions, 2);
+    }
+}
@@ -0,0 +1,433 @@
+//! Save-state file format.
+//!
+//! A state is an envelope followed by the machine payload:
+//!
+//! | Field         | Encoding                          |
+//! |---------------|-----------------------------------|
+//! | magic         | 8 bytes, `CHIP8SAV`               |
+//! | format major  | u16 BE                            |
+//! | format minor  | u16 BE                            |
+//! | crate version | u8 length + UTF-8                 |
+//! | ROM hash      | u64 BE (FNV-1a of the ROM bytes)  |
+//! | quirks        | u8 length + UTF-8                 |
+//! | memory size   | u32 BE                            |
+//! | payload       | rest of the file                  |
+//!
+//! A new major version means older readers cannot make sense of the payload.
+//! Minor versions only add data older readers can ignore.
+
+use std::fmt;
+use crate::constants::{REGISTERS_COUNT, STACK_SIZE};
+use crate::quirks;
+
+pub const MAGIC: &[u8; 8] = b"CHIP8SAV";
+pub const FORMAT_MAJOR: u16 = 2;
+/// Minor 1 appends the random number generator's seed and state to the payload.
+/// Minor 2 names the quirk preset; earlier states call the modern one "chip8"
+/// Minor 3 appends SUPER-CHIP's hires flag, flag registers and 128x64 picture
+pub const FORMAT_MINOR: u16 = 3;
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Everything in a save state before the payload
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub major: u16,
+    pub minor: u16,
+    pub crate_version: String,
+    pub rom_hash: u64,
+    pub quirks: String,
+    pub memory_size: u32,
+}
+
+impl Header {
+    /// A header for the current format version
+    pub fn new(rom_hash: u64, quirks: &str, memory_size: usize) -> Self {
+        Self {
+            major: FORMAT_MAJOR,
+            minor: FORMAT_MINOR,
+            crate_version: CRATE_VERSION.to_string(),
+            rom_hash,
+            quirks: quirks.to_string(),
+            memory_size: memory_size as u32,
+        }
+    }
+}
+
+/// Why a save state was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion { major: u16, minor: u16 },
+    MemorySizeMismatch { saved: u32, expected: u32 },
+    RomMismatch { saved: u64, loaded: u64 },
+    StackTooDeep { frames: usize, limit: usize },
+    Truncated,
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a CHIP-8 save state"),
+            StateError::UnsupportedVersion { major, minor } => write!(
+                f,
+                "save state format {}.{} is newer than this emulator supports ({}.x)",
+                major, minor, FORMAT_MAJOR
+            ),
+            StateError::MemorySizeMismatch { saved, expected } => write!(
+                f,
+                "save state has {} bytes of memory, expected {}",
+                saved, expected
+            ),
+            StateError::RomMismatch { saved, loaded } => write!(
+                f,
+                "save state is for a different ROM (hash {:016x}, loaded ROM is {:016x})",
+                saved, loaded
+            ),
+            StateError::StackTooDeep { frames, limit } => write!(
+                f,
+                "save state has {} nested calls but the stack depth is {}",
+                frames, limit
+            ),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Corrupt(what) => write!(f, "save state is corrupt: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Differences that don't stop a state from loading but may change how it behaves
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateWarning {
+    CrateVersion { saved: String },
+    Quirks { saved: String, current: String },
+    StackDepth { saved: usize, current: usize },
+}
+
+impl fmt::Display for StateWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateWarning::CrateVersion { saved } => write!(
+                f,
+                "save state was made by version {} (this is {})",
+                saved, CRATE_VERSION
+            ),
+            StateWarning::Quirks { saved, current } => write!(
+                f,
+                "save state was made with quirks '{}', running with '{}'",
+                saved, current
+            ),
+            StateWarning::StackDepth { saved, current } => write!(
+                f,
+                "save state was made with a stack depth of {}, running with {}",
+                saved, current
+            ),
+        }
+    }
+}
+
+/// Stable 64-bit FNV-1a hash identifying a ROM
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Wraps a payload in an envelope
+pub fn write(header: &Header, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(64 + payload.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&header.major.to_be_bytes());
+    out.extend_from_slice(&header.minor.to_be_bytes());
+    write_str(&mut out, &header.crate_version);
+    out.extend_from_slice(&header.rom_hash.to_be_bytes());
+    write_str(&mut out, &header.quirks);
+    out.extend_from_slice(&header.memory_size.to_be_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Splits a state into its header and a payload in the current format's layout,
+/// migrating older formats forward
+pub fn read(bytes: &[u8]) -> Result<(Header, Vec<u8>), StateError> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(MAGIC.len()).map_err(|_| StateError::BadMagic)? != MAGIC {
+        return Err(StateError::BadMagic);
+    }
+
+    let major = reader.u16()?;
+    let minor = reader.u16()?;
+    if major > FORMAT_MAJOR {
+        return Err(StateError::UnsupportedVersion { major, minor });
+    }
+
+    let mut header = Header {
+        major,
+        minor,
+        crate_version: reader.string()?,
+        rom_hash: reader.u64()?,
+        quirks: reader.string()?,
+        memory_size: reader.u32()?,
+    };
+    if (major, minor) < (2, 2) {
+        header.quirks = quirks::upgrade_legacy_name(&header.quirks);
+    }
+
+    let payload = migrate(header.major, reader.bytes.to_vec())?;
+    Ok((header, payload))
+}
+
+/// Checks a header against the running machine, returning anything worth warning about
+pub fn check(header: &Header, rom_hash: u64, quirks: &str, memory_size: usize) -> Result<Vec<StateWarning>, StateError> {
+    if header.memory_size as usize != memory_size {
+        return Err(StateError::MemorySizeMismatch { saved: header.memory_size, expected: memory_size as u32 });
+    }
+    if header.rom_hash != rom_hash {
+        return Err(StateError::RomMismatch { saved: header.rom_hash, loaded: rom_hash });
+    }
+
+    let mut warnings = Vec::new();
+    if header.crate_version != CRATE_VERSION {
+        warnings.push(StateWarning::CrateVersion { saved: header.crate_version.clone() });
+    }
+    if header.quirks != quirks {
+        warnings.push(StateWarning::Quirks { saved: header.quirks.clone(), current: quirks.to_string() });
+    }
+
+    Ok(warnings)
+}
+
+/// Upgrades a payload from an older major version to the current layout
+type Migration = fn(Vec<u8>) -> Result<Vec<u8>, StateError>;
+
+/// MIGRATIONS[n] turns a version n + 1 payload into a version n + 2 payload.
+/// A format bump appends its step here, so every older version keeps loading
+const MIGRATIONS: &[Migration] = &[v1_to_v2];
+const _: () = assert!(MIGRATIONS.len() == FORMAT_MAJOR as usize - 1);
+
+fn migrate(major: u16, payload: Vec<u8>) -> Result<Vec<u8>, StateError> {
+    migrate_with(MIGRATIONS, major, payload)
+}
+
+fn migrate_with(steps: &[Migration], major: u16, mut payload: Vec<u8>) -> Result<Vec<u8>, StateError> {
+    if major == 0 || major as usize > steps.len() + 1 {
+        return Err(StateError::UnsupportedVersion { major, minor: 0 });
+    }
+    for step in &steps[major as usize - 1..] {
+        payload = step(payload)?;
+    }
+    Ok(payload)
+}
+
+/// v1 stored a stack pointer and a fixed 16-entry stack; v2 stores the depth limit,
+/// the number of frames, and only the frames in use
+fn v1_to_v2(payload: Vec<u8>) -> Result<Vec<u8>, StateError> {
+    let registers = REGISTERS_COUNT + 4; // V0-VF, I, PC
+    let mut reader = Reader { bytes: &payload };
+    let head = reader.take(registers)?;
+    let sp = reader.u8()? as usize;
+    let stack = reader.take(STACK_SIZE * 2)?;
+    if sp > STACK_SIZE {
+        return Err(StateError::Corrupt("stack pointer out of range"));
+    }
+
+    let mut upgraded = Vec::with_capacity(payload.len());
+    upgraded.extend_from_slice(head);
+    upgraded.push(STACK_SIZE as u8);
+    upgraded.push(sp as u8);
+    upgraded.extend_from_slice(&stack[..sp * 2]);
+    upgraded.extend_from_slice(reader.bytes);
+    Ok(upgraded)
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    let bytes = &s.as_bytes()[..s.len().min(u8::MAX as usize)];
+    out.push(bytes.len() as u8);
+    out.extend_from_slice(bytes);
+}
+
+/// Cursor over the state bytes; running out of input is always a truncated file
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], StateError> {
+        if self.bytes.len() < n {
+            return Err(StateError::Truncated);
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, StateError> {
+        let len = self.u8()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| StateError::Corrupt("invalid UTF-8 in header"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> Header {
+        Header::new(rom_hash(b"rom"), "modern", 4096)
+    }
+
+    /// Overwrites the two bytes at offset with a big-endian u16
+    fn patch_u16(bytes: &mut [u8], offset: usize, value: u16) {
+        bytes[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let state = write(&header(), &[1, 2, 3]);
+        let (read_header, payload) = read(&state).unwrap();
+
+        assert_eq!(read_header, header());
+        assert_eq!(payload, vec![1, 2, 3]);
+        assert_eq!(check(&read_header, rom_hash(b"rom"), "modern", 4096), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_rejects_bad_magic() {
+        let mut state = write(&header(), &[]);
+        state[0] = b'X';
+        assert_eq!(read(&state), Err(StateError::BadMagic));
+        assert_eq!(read(b"CHIP"), Err(StateError::BadMagic));
+    }
+
+    #[test]
+    fn test_rejects_newer_major_version() {
+        let mut state = write(&header(), &[]);
+        patch_u16(&mut state, MAGIC.len(), FORMAT_MAJOR + 1);
+        patch_u16(&mut state, MAGIC.len() + 2, 3);
+
+        assert_eq!(read(&state), Err(StateError::UnsupportedVersion { major: FORMAT_MAJOR + 1, minor: 3 }));
+    }
+
+    #[test]
+    fn test_accepts_newer_minor_version() {
+        let mut state = write(&header(), &[7]);
+        patch_u16(&mut state, MAGIC.len() + 2, FORMAT_MINOR + 1);
+
+        let (read_header, payload) = read(&state).unwrap();
+        assert_eq!(read_header.minor, FORMAT_MINOR + 1);
+        assert_eq!(payload, vec![7]);
+    }
+
+    #[test]
+    fn test_older_states_name_the_modern_preset() {
+        let mut state = write(&header(), &[]);
+        patch_u16(&mut state, MAGIC.len() + 2, 1);
+        let (read_header, _) = read(&state).unwrap();
+        assert_eq!(read_header.quirks, "modern");
+    }
+
+    #[test]
+    fn test_rejects_memory_size_mismatch() {
+        let (read_header, _) = read(&write(&Header::new(rom_hash(b"rom"), "modern", 65536), &[])).unwrap();
+        assert_eq!(
+            check(&read_header, rom_hash(b"rom"), "modern", 4096),
+            Err(StateError::MemorySizeMismatch { saved: 65536, expected: 4096 })
+        );
+    }
+
+    #[test]
+    fn test_rejects_other_rom() {
+        assert_eq!(
+            check(&header(), rom_hash(b"other"), "modern", 4096),
+            Err(StateError::RomMismatch { saved: rom_hash(b"rom"), loaded: rom_hash(b"other") })
+        );
+    }
+
+    #[test]
+    fn test_rejects_truncated_header() {
+        let state = write(&header(), &[]);
+        for len in MAGIC.len()..state.len() {
+            assert_eq!(read(&state[..len]), Err(StateError::Truncated), "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_warns_on_version_and_quirk_differences() {
+        let mut old = header();
+        old.crate_version = "0.0.1".to_string();
+        old.quirks = "vip".to_string();
+
+        let (read_header, _) = read(&write(&old, &[])).unwrap();
+        assert_eq!(
+            check(&read_header, rom_hash(b"rom"), "modern", 4096),
+            Ok(vec![
+                StateWarning::CrateVersion { saved: "0.0.1".to_string() },
+                StateWarning::Quirks { saved: "vip".to_string(), current: "modern".to_string() },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_migrations_run_in_order_from_saved_version() {
+        fn v1_to_v2(payload: Vec<u8>) -> Result<Vec<u8>, StateError> {
+            Ok(payload)
+        }
+        fn v2_to_v3(mut payload: Vec<u8>) -> Result<Vec<u8>, StateError> {
+            payload.push(3);
+            Ok(payload)
+        }
+        let steps: &[Migration] = &[v1_to_v2, v2_to_v3];
+
+        assert_eq!(migrate_with(steps, 1, vec![1]), Ok(vec![1, 3]));
+        assert_eq!(migrate_with(steps, 2, vec![2]), Ok(vec![2, 3]));
+        assert_eq!(migrate_with(steps, 3, vec![3]), Ok(vec![3]));
+        assert!(migrate_with(steps, 0, vec![]).is_err());
+        assert_eq!(migrate(FORMAT_MAJOR, vec![9]), Ok(vec![9]));
+    }
+
+    #[test]
+    fn test_v1_stack_upgraded_to_v2() {
+        let mut v1 = vec![0xAA; REGISTERS_COUNT + 4];
+        v1.push(2); // Stack pointer
+        for frame in [0x0202u16, 0x0310].into_iter().chain(std::iter::repeat_n(0, STACK_SIZE - 2)) {
+            v1.extend_from_slice(&frame.to_be_bytes());
+        }
+        v1.extend_from_slice(&[1, 2, 3]);
+
+        let mut v2 = vec![0xAA; REGISTERS_COUNT + 4];
+        v2.extend_from_slice(&[STACK_SIZE as u8, 2, 0x02, 0x02, 0x03, 0x10, 1, 2, 3]);
+        assert_eq!(v1_to_v2(v1.clone()), Ok(v2.clone()));
+
+        // Through the envelope, as an old file would be read
+        let mut header = header();
+        header.major = 1;
+        assert_eq!(read(&write(&header, &v1)).map(|(_, payload)| payload), Ok(v2));
+
+        v1[REGISTERS_COUNT + 4] = STACK_SIZE as u8 + 1;
+        assert_eq!(v1_to_v2(v1), Err(StateError::Corrupt("stack pointer out of range")));
+    }
+
+    #[test]
+    fn test_rom_hash_is_stable() {
+        assert_eq!(rom_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(rom_hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_ne!(rom_hash(b"ab"), rom_hash(b"ba"));
+    }
+}
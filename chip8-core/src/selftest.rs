@@ -0,0 +1,517 @@
+//! `chip8_emulator selftest`: small generated programs, one or more per opcode, each run on a fresh
+//! CPU of every variant and checked against the registers, memory, timers and display it should
+//! leave behind. Cases steer clear of behavior the variants disagree on unless they are pinned to
+//! the variant whose behavior they check
+use std::fmt::Write;
+use crate::constants::STARTING_MEMORY_ADDRESS;
+use crate::cpu::CPU;
+use crate::expect::Mismatch;
+use crate::pattern::Pattern;
+use crate::quirks::Quirks;
+use crate::rom_builder::RomBuilder;
+use crate::run::{StopCondition, StopReason};
+
+/// Instructions a case may run before it counts as not finishing
+const MAX_INSTRUCTIONS: u64 = 1000;
+
+/// A preset of opcode behavior the cases run under, named as in save states
+pub struct Variant {
+    pub name: &'static str,
+    cpu: fn() -> CPU,
+}
+
+pub const VARIANTS: [Variant; 2] = [
+    Variant { name: "vip", cpu: || CPU::with_quirks(Quirks::vip()) },
+    Variant { name: "modern", cpu: || CPU::with_quirks(Quirks::modern()) },
+];
+
+/// Something a case's program should leave behind
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expect {
+    V(usize, u8),
+    I(u16),
+    Pc(u16),
+    Memory(u16, &'static [u8]),
+    DelayTimer(u8),
+    SoundTimer(u8),
+    StackDepth(usize),
+    /// SUPER-CHIP's flag registers from RPL0 on
+    Flags(&'static [u8]),
+    /// Whether the program is left in SUPER-CHIP's 128x64 mode
+    Hires(bool),
+    /// Pixels lit at the current resolution
+    LitPixels(usize),
+    /// A lit pixel at (x, y) at the current resolution
+    Pixel(usize, usize),
+    /// A display pattern (see `pattern`) with its top-left corner first found at (x, y)
+    Drawn(usize, usize, &'static str),
+}
+
+impl Expect {
+    fn check(&self, cpu: &CPU) -> Option<Mismatch> {
+        let (field, expected, actual) = match self {
+            Expect::V(register, value) => (format!("V{:X}", register), format!("{:#04X}", value), format!("{:#04X}", cpu.v[*register])),
+            Expect::I(i) => ("i".to_string(), format!("{:#05X}", i), format!("{:#05X}", cpu.i)),
+            Expect::Pc(pc) => ("pc".to_string(), format!("{:#05X}", pc), format!("{:#05X}", cpu.pc)),
+            Expect::Memory(addr, bytes) => {
+                let start = *addr as usize;
+                let actual = cpu.memory.get(start..start + bytes.len()).unwrap_or_default();
+                (format!("[{:#05X}]", addr), format!("{:02X?}", bytes), format!("{:02X?}", actual))
+            }
+            Expect::DelayTimer(value) => ("DT".to_string(), value.to_string(), cpu.delay_timer.to_string()),
+            Expect::SoundTimer(value) => ("ST".to_string(), value.to_string(), cpu.sound_timer.to_string()),
+            Expect::StackDepth(depth) => ("stack".to_string(), depth.to_string(), cpu.stack.len().to_string()),
+            Expect::Flags(bytes) => ("flags".to_string(), format!("{:02X?}", bytes), format!("{:02X?}", &cpu.rpl[..bytes.len()])),
+            Expect::Hires(hires) => ("hires".to_string(), hires.to_string(), cpu.hires().to_string()),
+            Expect::LitPixels(count) => {
+                let lit = cpu.screen().iter().filter(|pixel| **pixel != 0).count();
+                ("display".to_string(), format!("{} lit", count), format!("{} lit", lit))
+            }
+            Expect::Pixel(x, y) => {
+                let (width, _) = cpu.screen_size();
+                let lit = cpu.screen().get(y * width + x).is_some_and(|pixel| *pixel != 0);
+                (format!("({}, {})", x, y), "lit".to_string(), if lit { "lit" } else { "unlit" }.to_string())
+            }
+            Expect::Drawn(x, y, pattern) => {
+                let found = Pattern::parse(pattern).map(|pattern| pattern.find(&cpu.display));
+                let actual = match found {
+                    Ok(Some((x, y))) => format!("at ({}, {})", x, y),
+                    Ok(None) => "nowhere".to_string(),
+                    Err(e) => e.to_string(),
+                };
+                ("display".to_string(), format!("at ({}, {})", x, y), actual)
+            }
+        };
+        (expected != actual).then_some(Mismatch { field, expected, actual })
+    }
+}
+
+/// One program and what it should leave behind
+pub struct Case {
+    pub opcode: &'static str,
+    pub what: &'static str,
+    program: RomBuilder,
+    keys: u16, // Held down from the start
+    taps: u16, // Pressed and released before the first instruction
+    expect: Vec<Expect>,
+    only: Option<&'static str>, // The variant whose behavior this checks, for quirks
+    exits: bool, // Ends on 00FD rather than the spin
+}
+
+impl Case {
+    /// The program runs from 0x200 and finishes on a spin appended to it
+    fn new(opcode: &'static str, what: &'static str, program: RomBuilder) -> Self {
+        Self { opcode, what, program, keys: 0, taps: 0, expect: Vec::new(), only: None, exits: false }
+    }
+
+    fn expect(mut self, expect: Expect) -> Self {
+        self.expect.push(expect);
+        self
+    }
+
+    fn keys(mut self, keys: u16) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    fn taps(mut self, keys: u16) -> Self {
+        self.taps = keys;
+        self
+    }
+
+    /// The program finishes by exiting with 00FD, which halts it where it is
+    fn exits(mut self) -> Self {
+        self.exits = true;
+        self
+    }
+
+    fn only(mut self, variant: &'static str) -> Self {
+        self.only = Some(variant);
+        self
+    }
+
+    pub fn runs_on(&self, variant: &Variant) -> bool {
+        self.only.is_none_or(|only| only == variant.name)
+    }
+
+    /// Runs the case on a fresh CPU of variant, returning everything that came out differently.
+    /// A program that errors or doesn't reach its final spin (or 00FD) fails with just that
+    pub fn run(self, variant: &Variant) -> Vec<Mismatch> {
+        let rom = match self.program.spin().build() {
+            Ok(rom) => rom,
+            Err(e) => return vec![Mismatch { field: "program".to_string(), expected: "builds".to_string(), actual: e.to_string() }],
+        };
+        let mut cpu = (variant.cpu)();
+        cpu.set_seed(0);
+        cpu.memory[STARTING_MEMORY_ADDRESS..STARTING_MEMORY_ADDRESS + rom.len()].copy_from_slice(&rom);
+        for key in (0..16).filter(|key| self.keys & (1 << key) != 0) {
+            cpu.press_key(key);
+        }
+        for key in (0..16).filter(|key| self.taps & (1 << key) != 0) {
+            cpu.press_key(key);
+            cpu.release_key(key);
+        }
+
+        let end = STARTING_MEMORY_ADDRESS as u16 + rom.len() as u16 - 2;
+        let outcome = cpu.run_until(StopCondition::PcEquals(end), MAX_INSTRUCTIONS);
+        let finished = if self.exits { StopReason::Halted } else { StopReason::Condition(StopCondition::PcEquals(end)) };
+        if outcome.reason != finished {
+            return vec![Mismatch { field: "run".to_string(), expected: "finishes".to_string(), actual: format!("{:?}", outcome.reason) }];
+        }
+        self.expect.iter().filter_map(|expect| expect.check(&cpu)).collect()
+    }
+}
+
+/// Every case, in opcode order
+pub fn cases() -> Vec<Case> {
+    use Expect::*;
+    let rom = RomBuilder::new;
+    let sprite = [0b1100_0000, 0b1000_0000];
+    let big_sprite = [0xFF; 32];
+    // Draws sprite at (10, 4), for the scrolls to move
+    let drawn = || rom().ld(0, 10).ld(1, 4).ld_i("sprite").draw(0, 1, 2).jp("scroll").label("sprite").data(&sprite).label("scroll");
+
+    vec![
+        Case::new("00CN", "scrolls down N rows", drawn().op(0x00C3))
+            .expect(Drawn(10, 7, "##\n#.")).expect(LitPixels(3)),
+        Case::new("00CN", "scrolls N hires rows", rom().op(0x00FF).ld(0, 20).ld(1, 8).ld_i("sprite").draw(0, 1, 1).op(0x00C3).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(Pixel(20, 11)).expect(LitPixels(2)),
+        Case::new("00E0", "clears the display", rom().ld(0, 10).ld_i("sprite").draw(0, 0, 2).cls().jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(LitPixels(0)),
+        Case::new("00EE", "returns past the call", rom().call("sub").ld(2, 1).jp("end").label("sub").ld(1, 7).ret().label("end"))
+            .expect(V(1, 7)).expect(V(2, 1)).expect(StackDepth(0)),
+        Case::new("00FB", "scrolls right 4 pixels", drawn().op(0x00FB))
+            .expect(Drawn(14, 4, "##\n#.")).expect(LitPixels(3)),
+        Case::new("00FB", "doesn't wrap", rom().ld(0, 62).ld_i("sprite").draw(0, 1, 1).op(0x00FB).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(LitPixels(0)),
+        Case::new("00FC", "scrolls left 4 pixels", drawn().op(0x00FC))
+            .expect(Drawn(6, 4, "##\n#.")).expect(LitPixels(3)),
+        Case::new("00FD", "exits where it is", rom().ld(1, 1).op(0x00FD).ld(2, 2))
+            .exits().expect(Pc(0x202)).expect(V(1, 1)).expect(V(2, 0)),
+        Case::new("00FE", "leaves hires, clearing the screen", rom().op(0x00FF).ld(0, 100).ld_i("sprite").draw(0, 1, 2).op(0x00FE).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(Hires(false)).expect(LitPixels(0)),
+        Case::new("00FF", "enters hires, clearing the screen", drawn().op(0x00FF))
+            .expect(Hires(true)).expect(LitPixels(0)),
+        Case::new("00FF", "draws at 128x64", rom().op(0x00FF).ld(0, 100).ld(1, 40).ld_i("sprite").draw(0, 1, 2).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(Pixel(100, 40)).expect(Pixel(101, 40)).expect(Pixel(100, 41)).expect(LitPixels(3)),
+        Case::new("1NNN", "jumps", rom().jp("over").ld(1, 1).label("over").ld(2, 2))
+            .expect(V(1, 0)).expect(V(2, 2)),
+        Case::new("2NNN", "calls", rom().call("sub").label("sub").ld(1, 7))
+            .expect(V(1, 7)).expect(StackDepth(1)),
+        Case::new("3XNN", "skips when equal", rom().ld(1, 5).op(0x3105).ld(2, 1).op(0x3106).ld(3, 1))
+            .expect(V(2, 0)).expect(V(3, 1)),
+        Case::new("4XNN", "skips when not equal", rom().ld(1, 5).op(0x4106).ld(2, 1).op(0x4105).ld(3, 1))
+            .expect(V(2, 0)).expect(V(3, 1)),
+        Case::new("5XY0", "skips when registers equal", rom().ld(1, 5).ld(2, 5).ld(3, 6).op(0x5120).ld(4, 1).op(0x5130).ld(5, 1))
+            .expect(V(4, 0)).expect(V(5, 1)),
+        Case::new("6XNN", "loads", rom().ld(0xA, 0x42))
+            .expect(V(0xA, 0x42)),
+        Case::new("7XNN", "adds without carry", rom().ld(1, 0xFF).ld(0xF, 0x33).add(1, 2))
+            .expect(V(1, 0x01)).expect(V(0xF, 0x33)),
+        Case::new("8XY0", "copies", rom().ld(2, 0x42).op(0x8120))
+            .expect(V(1, 0x42)),
+        Case::new("8XY1", "ors", rom().ld(1, 0x0C).ld(2, 0x0A).op(0x8121))
+            .expect(V(1, 0x0E)),
+        Case::new("8XY1", "clears VF", rom().ld(0xF, 0x33).op(0x8121))
+            .expect(V(0xF, 0)).only("vip"),
+        Case::new("8XY1", "leaves VF alone", rom().ld(0xF, 0x33).op(0x8121))
+            .expect(V(0xF, 0x33)).only("modern"),
+        Case::new("8XY2", "ands", rom().ld(1, 0x0C).ld(2, 0x0A).op(0x8122))
+            .expect(V(1, 0x08)),
+        Case::new("8XY2", "clears VF", rom().ld(0xF, 0x33).op(0x8122))
+            .expect(V(0xF, 0)).only("vip"),
+        Case::new("8XY2", "leaves VF alone", rom().ld(0xF, 0x33).op(0x8122))
+            .expect(V(0xF, 0x33)).only("modern"),
+        Case::new("8XY3", "xors", rom().ld(1, 0x0C).ld(2, 0x0A).op(0x8123))
+            .expect(V(1, 0x06)),
+        Case::new("8XY3", "clears VF", rom().ld(0xF, 0x33).op(0x8123))
+            .expect(V(0xF, 0)).only("vip"),
+        Case::new("8XY3", "leaves VF alone", rom().ld(0xF, 0x33).op(0x8123))
+            .expect(V(0xF, 0x33)).only("modern"),
+        Case::new("8XY4", "adds with carry", rom().ld(1, 0xF0).ld(2, 0x20).op(0x8124).ld(3, 0x10).ld(4, 0x20).op(0x8344))
+            .expect(V(1, 0x10)).expect(V(3, 0x30)).expect(V(0xF, 0)),
+        Case::new("8XY4", "sets VF to the carry", rom().ld(1, 0xF0).ld(2, 0x20).op(0x8124))
+            .expect(V(0xF, 1)),
+        Case::new("8XY5", "subtracts with borrow", rom().ld(1, 0x10).ld(2, 0x20).op(0x8125).ld(3, 0x30).ld(4, 0x20).op(0x8345))
+            .expect(V(1, 0xF0)).expect(V(3, 0x10)).expect(V(0xF, 1)),
+        Case::new("8XY5", "clears VF on a borrow", rom().ld(1, 0x10).ld(2, 0x20).op(0x8125))
+            .expect(V(0xF, 0)),
+        // VX and VY hold the same value, so shifting either gives the same result
+        Case::new("8XY6", "shifts right", rom().ld(1, 0x05).ld(2, 0x05).op(0x8126))
+            .expect(V(1, 0x02)).expect(V(0xF, 1)),
+        Case::new("8XY6", "shifts VY into VX", rom().ld(1, 0x04).ld(2, 0x81).op(0x8126))
+            .expect(V(1, 0x40)).expect(V(0xF, 1)).only("vip"),
+        Case::new("8XY6", "shifts VX in place", rom().ld(1, 0x04).ld(2, 0x81).op(0x8126))
+            .expect(V(1, 0x02)).expect(V(0xF, 0)).only("modern"),
+        Case::new("8XY7", "subtracts backwards", rom().ld(1, 0x10).ld(2, 0x30).op(0x8127))
+            .expect(V(1, 0x20)).expect(V(0xF, 1)),
+        Case::new("8XY7", "clears VF on a borrow", rom().ld(1, 0x30).ld(2, 0x10).op(0x8127))
+            .expect(V(1, 0xE0)).expect(V(0xF, 0)),
+        Case::new("8XYE", "shifts left", rom().ld(1, 0x81).ld(2, 0x81).op(0x812E))
+            .expect(V(1, 0x02)).expect(V(0xF, 1)),
+        Case::new("8XYE", "shifts VY into VX", rom().ld(1, 0x01).ld(2, 0x80).op(0x812E))
+            .expect(V(1, 0x00)).expect(V(0xF, 1)).only("vip"),
+        Case::new("8XYE", "shifts VX in place", rom().ld(1, 0x01).ld(2, 0x80).op(0x812E))
+            .expect(V(1, 0x02)).expect(V(0xF, 0)).only("modern"),
+        Case::new("9XY0", "skips when registers differ", rom().ld(1, 5).ld(2, 6).ld(3, 5).op(0x9120).ld(4, 1).op(0x9130).ld(5, 1))
+            .expect(V(4, 0)).expect(V(5, 1)),
+        Case::new("ANNN", "loads I", rom().ld_i("here").label("here"))
+            .expect(I(0x202)),
+        // V0 and V2 are equal, so jumping from either lands in the same place
+        Case::new("BNNN", "jumps offset by V0", rom().ld(0, 2).ld(2, 2).op(0xB206).ld(1, 1).ld(3, 3))
+            .expect(V(1, 0)).expect(V(3, 3)),
+        Case::new("CXNN", "masks the random byte", rom().ld(1, 0xFF).op(0xC100))
+            .expect(V(1, 0)),
+        Case::new("DXY0", "draws a 16x16 sprite", rom().ld(0, 8).ld(1, 2).ld_i("sprite").draw(0, 1, 0).jp("end").label("sprite").data(&big_sprite).label("end"))
+            .expect(Pixel(8, 2)).expect(Pixel(23, 17)).expect(LitPixels(256)).expect(V(0xF, 0)),
+        Case::new("DXY0", "draws a 16x16 sprite in hires", rom().op(0x00FF).ld(0, 100).ld(1, 40).ld_i("sprite").draw(0, 1, 0).jp("end").label("sprite").data(&big_sprite).label("end"))
+            .expect(Pixel(100, 40)).expect(Pixel(115, 55)).expect(LitPixels(256)),
+        Case::new("DXYN", "draws a sprite", rom().ld(0, 10).ld(1, 4).ld_i("sprite").draw(0, 1, 2).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(Drawn(10, 4, "##\n#.")).expect(LitPixels(3)).expect(V(0xF, 0)),
+        Case::new("DXYN", "flags collisions", rom().ld(0, 10).ld(1, 4).ld_i("sprite").draw(0, 1, 2).draw(0, 1, 1).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(Drawn(10, 4, "..\n#.")).expect(LitPixels(1)).expect(V(0xF, 1)),
+        Case::new("DXYN", "clips at the right edge", rom().ld(0, 63).ld_i("sprite").draw(0, 1, 2).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(Drawn(63, 0, "#\n#")).expect(LitPixels(2)).only("vip"),
+        Case::new("DXYN", "wraps around the right edge", rom().ld(0, 63).ld_i("sprite").draw(0, 1, 2).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(Drawn(0, 0, "#")).expect(LitPixels(3)).only("modern"),
+        Case::new("DXYN", "counts colliding rows in VF in hires", rom().op(0x00FF).ld(0, 10).ld_i("sprite").draw(0, 1, 2).draw(0, 1, 2).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(LitPixels(0)).expect(V(0xF, 2)),
+        Case::new("DXYN", "counts rows clipped off the bottom in VF in hires", rom().op(0x00FF).ld(1, 63).ld_i("sprite").draw(0, 1, 2).jp("end").label("sprite").data(&sprite).label("end"))
+            .expect(LitPixels(2)).expect(V(0xF, 1)).only("vip"),
+        Case::new("EX9E", "skips when the key is down", rom().ld(1, 7).ld(2, 8).op(0xE19E).ld(3, 1).op(0xE29E).ld(4, 1))
+            .keys(1 << 7).expect(V(3, 0)).expect(V(4, 1)),
+        Case::new("EXA1", "skips when the key is up", rom().ld(1, 8).ld(2, 7).op(0xE1A1).ld(3, 1).op(0xE2A1).ld(4, 1))
+            .keys(1 << 7).expect(V(3, 0)).expect(V(4, 1)),
+        Case::new("FX07", "reads the delay timer", rom().ld(1, 42).op(0xF115).op(0xF207))
+            .expect(V(2, 42)),
+        Case::new("FX0A", "takes a key once released", rom().op(0xF10A))
+            .taps(1 << 0xB).expect(V(1, 0xB)),
+        Case::new("FX15", "sets the delay timer", rom().ld(1, 42).op(0xF115))
+            .expect(DelayTimer(42)),
+        Case::new("FX18", "sets the sound timer", rom().ld(1, 42).op(0xF118))
+            .expect(SoundTimer(42)),
+        Case::new("FX1E", "adds to I", rom().ld_i("here").label("here").ld(1, 0x10).op(0xF11E))
+            .expect(I(0x212)),
+        Case::new("FX29", "points I at a digit", rom().ld(1, 0xA).op(0xF129).op(0xF065))
+            .expect(V(0, 0xF0)),
+        Case::new("FX29", "draws the digit", rom().ld(1, 7).ld(2, 20).ld(3, 10).op(0xF129).draw(2, 3, 5))
+            .expect(Drawn(20, 10, "####\n...#\n..#.\n.#..\n.#..")),
+        Case::new("FX30", "points I at a big digit", rom().ld(1, 1).op(0xF130).op(0xF065))
+            .expect(V(0, 0x18)),
+        Case::new("FX33", "stores BCD", rom().ld_i("store").ld(1, 254).op(0xF133).jp("end").label("store").data(&[0; 3]).label("end"))
+            .expect(Memory(0x208, &[2, 5, 4])),
+        Case::new("FX55", "stores registers", rom().ld_i("store").ld(0, 1).ld(1, 2).ld(2, 3).op(0xF255).jp("end").label("store").data(&[0xEE; 4]).label("end"))
+            .expect(Memory(0x20C, &[1, 2, 3, 0xEE])),
+        Case::new("FX55", "moves I past the registers", rom().ld_i("store").op(0xF255).jp("end").label("store").data(&[0; 3]).label("end"))
+            .expect(I(0x209)).only("vip"),
+        Case::new("FX55", "leaves I alone", rom().ld_i("store").op(0xF255).jp("end").label("store").data(&[0; 3]).label("end"))
+            .expect(I(0x206)).only("modern"),
+        Case::new("FX65", "loads registers", rom().ld(3, 0x77).ld_i("load").op(0xF265).jp("end").label("load").data(&[4, 5, 6]).label("end"))
+            .expect(V(0, 4)).expect(V(1, 5)).expect(V(2, 6)).expect(V(3, 0x77)),
+        Case::new("FX65", "moves I past the registers", rom().ld_i("load").op(0xF265).jp("end").label("load").data(&[0; 3]).label("end"))
+            .expect(I(0x209)).only("vip"),
+        Case::new("FX65", "leaves I alone", rom().ld_i("load").op(0xF265).jp("end").label("load").data(&[0; 3]).label("end"))
+            .expect(I(0x206)).only("modern"),
+        Case::new("FX75", "stores registers in the flags", rom().ld(0, 1).ld(1, 2).ld(2, 3).op(0xF175))
+            .expect(Flags(&[1, 2, 0])),
+        Case::new("FX85", "loads registers from the flags", rom().ld(0, 1).ld(1, 2).op(0xF175).ld(0, 0).ld(1, 0).ld(2, 7).op(0xF185))
+            .expect(V(0, 1)).expect(V(1, 2)).expect(V(2, 7)),
+    ]
+}
+
+/// How one case went on one variant
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub opcode: &'static str,
+    pub what: &'static str,
+    pub variant: &'static str,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Every outcome of a self-test, and the pass/fail table they add up to
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Report {
+    pub variants: Vec<&'static str>,
+    pub outcomes: Vec<Outcome>,
+}
+
+/// A table cell: how an opcode's cases went on a variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Pass,
+    Fail,
+    /// No case for this opcode runs on the variant
+    Untested,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.outcomes.iter().all(Outcome::passed)
+    }
+
+    /// A row per opcode in the order first seen, a cell per variant. One failing case fails the cell
+    pub fn table(&self) -> Vec<(&'static str, Vec<Cell>)> {
+        let mut rows: Vec<(&'static str, Vec<Cell>)> = Vec::new();
+        for outcome in &self.outcomes {
+            let row = match rows.iter().position(|(opcode, _)| *opcode == outcome.opcode) {
+                Some(row) => row,
+                None => {
+                    rows.push((outcome.opcode, vec![Cell::Untested; self.variants.len()]));
+                    rows.len() - 1
+                }
+            };
+            let Some(column) = self.variants.iter().position(|variant| *variant == outcome.variant) else {
+                continue;
+            };
+            let cell = &mut rows[row].1[column];
+            *cell = match (*cell, outcome.passed()) {
+                (Cell::Fail, _) | (_, false) => Cell::Fail,
+                _ => Cell::Pass,
+            };
+        }
+        rows
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut out = format!("{:<8}", "Opcode");
+        for variant in &self.variants {
+            write!(out, "{:<8}", variant)?;
+        }
+        writeln!(f, "{}", out.trim_end())?;
+        for (opcode, cells) in self.table() {
+            let mut out = format!("{:<8}", opcode);
+            for cell in cells {
+                let cell = match cell {
+                    Cell::Pass => "pass",
+                    Cell::Fail => "FAIL",
+                    Cell::Untested => "-",
+                };
+                write!(out, "{:<8}", cell)?;
+            }
+            writeln!(f, "{}", out.trim_end())?;
+        }
+
+        for outcome in self.outcomes.iter().filter(|outcome| !outcome.passed()) {
+            writeln!(f, "\n{} {} ({}):", outcome.opcode, outcome.what, outcome.variant)?;
+            for mismatch in &outcome.mismatches {
+                writeln!(f, "  {}", mismatch)?;
+            }
+        }
+        let passed = self.outcomes.iter().filter(|outcome| outcome.passed()).count();
+        write!(f, "\n{} of {} checks passed", passed, self.outcomes.len())
+    }
+}
+
+/// Runs every case on every variant it applies to
+pub fn run() -> Report {
+    let mut report = Report { variants: VARIANTS.iter().map(|variant| variant.name).collect(), outcomes: Vec::new() };
+    for variant in &VARIANTS {
+        for case in cases().into_iter().filter(|case| case.runs_on(variant)) {
+            let (opcode, what) = (case.opcode, case.what);
+            report.outcomes.push(Outcome { opcode, what, variant: variant.name, mismatches: case.run(variant) });
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The opcode classes the interpreter decodes
+    const OPCODES: [&str; 44] = [
+        "00CN", "00E0", "00EE", "00FB", "00FC", "00FD", "00FE", "00FF", "1NNN", "2NNN", "3XNN", "4XNN", "5XY0", "6XNN", "7XNN",
+        "8XY0", "8XY1", "8XY2", "8XY3", "8XY4", "8XY5", "8XY6", "8XY7", "8XYE", "9XY0",
+        "ANNN", "BNNN", "CXNN", "DXY0", "DXYN", "EX9E", "EXA1",
+        "FX07", "FX0A", "FX15", "FX18", "FX1E", "FX29", "FX30", "FX33", "FX55", "FX65", "FX75", "FX85",
+    ];
+
+    #[test]
+    fn test_every_case_passes() {
+        let report = run();
+        assert!(report.passed(), "{}", report);
+    }
+
+    #[test]
+    fn test_every_opcode_is_covered_on_every_variant() {
+        let table = run().table();
+        let opcodes: Vec<&str> = table.iter().map(|(opcode, _)| *opcode).collect();
+        assert_eq!(opcodes, OPCODES);
+        assert!(table.iter().all(|(_, cells)| cells.iter().all(|cell| *cell == Cell::Pass)));
+    }
+
+    #[test]
+    fn test_wrong_expectation_fails() {
+        let variant = &VARIANTS[0];
+        let case = Case::new("6XNN", "loads", RomBuilder::new().ld(1, 5)).expect(Expect::V(1, 6)).expect(Expect::I(0));
+        assert_eq!(case.run(variant), [Mismatch { field: "V1".to_string(), expected: "0x06".to_string(), actual: "0x05".to_string() }]);
+
+        let drawn = Case::new("DXYN", "draws nothing", RomBuilder::new()).expect(Expect::Drawn(0, 0, "#"));
+        assert_eq!(drawn.run(variant)[0].actual, "nowhere");
+    }
+
+    #[test]
+    fn test_program_that_never_finishes_fails() {
+        let variant = &VARIANTS[0];
+        let waits = Case::new("FX0A", "waits forever", RomBuilder::new().op(0xF10A)).expect(Expect::V(1, 0));
+        let mismatches = waits.run(variant);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!((mismatches[0].field.as_str(), mismatches[0].actual.as_str()), ("run", "Halted"));
+
+        let broken = Case::new("0NNN", "unknown opcode", RomBuilder::new().op(0x0123));
+        assert_eq!(broken.run(variant)[0].field, "run");
+    }
+
+    #[test]
+    fn test_pinned_cases_run_on_their_variant_only() {
+        let other = Variant { name: "other", cpu: CPU::new };
+        let case = Case::new("8XY6", "shifts VX", RomBuilder::new()).only("modern");
+        assert!(case.runs_on(&VARIANTS[1]));
+        assert!(!case.runs_on(&VARIANTS[0]));
+        assert!(!case.runs_on(&other));
+    }
+
+    fn outcome(opcode: &'static str, variant: &'static str, passed: bool) -> Outcome {
+        let mismatches = if passed { Vec::new() } else { vec![Mismatch { field: "V1".to_string(), expected: "0x01".to_string(), actual: "0x02".to_string() }] };
+        Outcome { opcode, what: "does something", variant, mismatches }
+    }
+
+    #[test]
+    fn test_table_aggregates_cases_per_opcode_and_variant() {
+        let report = Report {
+            variants: vec!["a", "b"],
+            outcomes: vec![
+                outcome("6XNN", "a", true),
+                outcome("8XY6", "a", true),
+                outcome("8XY6", "a", false),
+                outcome("6XNN", "b", true),
+            ],
+        };
+        assert!(!report.passed());
+        assert_eq!(report.table(), [
+            ("6XNN", vec![Cell::Pass, Cell::Pass]),
+            ("8XY6", vec![Cell::Fail, Cell::Untested]),
+        ]);
+    }
+
+    #[test]
+    fn test_report_text() {
+        let report = Report {
+            variants: vec!["chip8"],
+            outcomes: vec![outcome("6XNN", "chip8", true), outcome("7XNN", "chip8", false)],
+        };
+        let text = report.to_string();
+        assert!(text.starts_with("Opcode  chip8\n6XNN    pass\n7XNN    FAIL\n"), "{}", text);
+        assert!(text.contains("\n7XNN does something (chip8):\n  V1 "), "{}", text);
+        assert!(text.ends_with("\n1 of 2 checks passed"), "{}", text);
+        assert_eq!(Report::default().to_string(), "Opcode\n\n0 of 0 checks passed");
+    }
+}
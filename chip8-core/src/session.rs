@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+use crate::cpu::CPU;
+use crate::savestate::StateWarning;
+
+/// Where auto-saves go, one file per ROM hash
+pub const AUTOSAVE_DIR: &str = "./saves/autosave";
+/// Where save states made on request are kept, one per ROM
+pub const STATE_DIR: &str = "./saves/state";
+
+/// The file in dir holding the save state for the ROM with this hash
+pub fn state_path(dir: &Path, rom_hash: u64) -> PathBuf {
+    dir.join(format!("{:016x}.state", rom_hash))
+}
+
+/// What the command line says to do with an existing auto-save
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResumePolicy {
+    Ask,
+    Always, // --resume
+    Never,  // --no-resume
+}
+
+/// Auto-save on disk for the current ROM
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SavedSession {
+    Missing,
+    Saved,
+    DeclinedOnce, // The user said no to resuming it once already
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitReason {
+    Quit,
+    Reset,
+    Error,
+}
+
+/// What to do at launch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Launch {
+    Fresh,
+    Prompt,
+    Resume,
+    Decline,
+}
+
+/// What to do with the auto-save file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileAction {
+    Keep,
+    Write,
+    MarkDeclined,
+    Delete,
+}
+
+/// Decides whether an existing auto-save is resumed, offered, or passed over
+pub fn on_launch(saved: SavedSession, policy: ResumePolicy) -> Launch {
+    match (saved, policy) {
+        (SavedSession::Missing, _) => Launch::Fresh,
+        (_, ResumePolicy::Always) => Launch::Resume,
+        (_, ResumePolicy::Never) => Launch::Decline,
+        (_, ResumePolicy::Ask) => Launch::Prompt,
+    }
+}
+
+/// A second refusal means the user doesn't want the save back
+pub fn on_decline(saved: SavedSession) -> FileAction {
+    match saved {
+        SavedSession::Missing => FileAction::Keep,
+        SavedSession::Saved => FileAction::MarkDeclined,
+        SavedSession::DeclinedOnce => FileAction::Delete,
+    }
+}
+
+/// Quitting saves (if enabled), resetting discards the save, and errors leave it alone
+pub fn on_exit(reason: ExitReason, enabled: bool) -> FileAction {
+    match reason {
+        ExitReason::Quit if enabled => FileAction::Write,
+        ExitReason::Quit | ExitReason::Error => FileAction::Keep,
+        ExitReason::Reset => FileAction::Delete,
+    }
+}
+
+/// The auto-save files for one ROM
+pub struct AutoSave {
+    state_path: PathBuf,
+    declined_path: PathBuf, // Marker file present after the first refusal
+    enabled: bool,
+}
+
+impl AutoSave {
+    pub fn new(dir: &Path, rom_hash: u64, enabled: bool) -> Self {
+        Self {
+            state_path: state_path(dir, rom_hash),
+            declined_path: dir.join(format!("{:016x}.declined", rom_hash)),
+            enabled,
+        }
+    }
+
+    pub fn status(&self) -> SavedSession {
+        match (self.state_path.is_file(), self.declined_path.is_file()) {
+            (false, _) => SavedSession::Missing,
+            (true, false) => SavedSession::Saved,
+            (true, true) => SavedSession::DeclinedOnce,
+        }
+    }
+
+    /// Restores the saved game into cpu, returning the warnings from loading its state
+    pub fn resume(&self, cpu: &mut CPU) -> Result<Vec<StateWarning>, Box<dyn std::error::Error>> {
+        let state = std::fs::read(&self.state_path)?;
+        let warnings = cpu.load_state(&state)?;
+        std::fs::remove_file(&self.declined_path).ok();
+        Ok(warnings)
+    }
+
+    pub fn decline(&self) -> Result<(), std::io::Error> {
+        self.apply(on_decline(self.status()), None)
+    }
+
+    pub fn exit(&self, reason: ExitReason, cpu: &CPU) -> Result<(), std::io::Error> {
+        self.apply(on_exit(reason, self.enabled), Some(cpu))
+    }
+
+    fn apply(&self, action: FileAction, cpu: Option<&CPU>) -> Result<(), std::io::Error> {
+        match (action, cpu) {
+            (FileAction::Write, Some(cpu)) => {
+                if let Some(dir) = self.state_path.parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(&self.state_path, cpu.save_state())?;
+                AutoSave::remove(&self.declined_path)
+            }
+            (FileAction::MarkDeclined, _) => std::fs::write(&self.declined_path, []),
+            (FileAction::Delete, _) => {
+                AutoSave::remove(&self.state_path)?;
+                AutoSave::remove(&self.declined_path)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn remove(path: &Path) -> Result<(), std::io::Error> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_launch_decisions() {
+        for policy in [ResumePolicy::Ask, ResumePolicy::Always, ResumePolicy::Never] {
+            assert_eq!(on_launch(SavedSession::Missing, policy), Launch::Fresh);
+        }
+        for saved in [SavedSession::Saved, SavedSession::DeclinedOnce] {
+            assert_eq!(on_launch(saved, ResumePolicy::Ask), Launch::Prompt);
+            assert_eq!(on_launch(saved, ResumePolicy::Always), Launch::Resume);
+            assert_eq!(on_launch(saved, ResumePolicy::Never), Launch::Decline);
+        }
+    }
+
+    #[test]
+    fn test_second_decline_deletes() {
+        assert_eq!(on_decline(SavedSession::Saved), FileAction::MarkDeclined);
+        assert_eq!(on_decline(SavedSession::DeclinedOnce), FileAction::Delete);
+        assert_eq!(on_decline(SavedSession::Missing), FileAction::Keep);
+    }
+
+    #[test]
+    fn test_exit_decisions() {
+        assert_eq!(on_exit(ExitReason::Quit, true), FileAction::Write);
+        assert_eq!(on_exit(ExitReason::Quit, false), FileAction::Keep);
+        assert_eq!(on_exit(ExitReason::Error, true), FileAction::Keep);
+        assert_eq!(on_exit(ExitReason::Reset, true), FileAction::Delete);
+        assert_eq!(on_exit(ExitReason::Reset, false), FileAction::Delete);
+    }
+
+    #[test]
+    fn test_auto_save_lifecycle() {
+        let dir = std::env::temp_dir().join(format!("chip8-autosave-{}", std::process::id()));
+        let save = AutoSave::new(&dir, 0x1234, true);
+        let mut cpu = CPU::new();
+        cpu.v[5] = 0x55;
+
+        assert_eq!(save.status(), SavedSession::Missing);
+        save.exit(ExitReason::Error, &cpu).unwrap();
+        assert_eq!(save.status(), SavedSession::Missing);
+
+        save.exit(ExitReason::Quit, &cpu).unwrap();
+        assert_eq!(save.status(), SavedSession::Saved);
+
+        save.decline().unwrap();
+        assert_eq!(save.status(), SavedSession::DeclinedOnce);
+
+        // Saving again starts the refusals over
+        save.exit(ExitReason::Quit, &cpu).unwrap();
+        assert_eq!(save.status(), SavedSession::Saved);
+        save.decline().unwrap();
+        save.decline().unwrap();
+        assert_eq!(save.status(), SavedSession::Missing);
+
+        save.exit(ExitReason::Quit, &cpu).unwrap();
+        let mut resumed = CPU::new();
+        assert!(save.resume(&mut resumed).unwrap().is_empty());
+        assert_eq!(resumed.v[5], 0x55);
+
+        save.exit(ExitReason::Reset, &cpu).unwrap();
+        assert_eq!(save.status(), SavedSession::Missing);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,93 @@
+//! The boundary between the interpreter and whatever shows its display: a window, a test, an LED
+//! matrix. Frontends implement FrameSink and hand it to Emulator::present once per host frame
+use crate::constants::{DISPLAY_WIDTH, HIRES_WIDTH, HIRES_HEIGHT};
+
+pub trait FrameSink {
+    /// Shows a whole frame: DISPLAY_HEIGHT rows of DISPLAY_WIDTH pixels, one byte each, nonzero lit.
+    /// In SUPER-CHIP's hires mode it is HIRES_HEIGHT rows of HIRES_WIDTH instead, which frame_width tells apart
+    fn draw(&mut self, buffer: &[u8]);
+
+    /// Shows a frame of which only the rows set in dirty_rows (bit N = row N) changed since the
+    /// last one. Sinks that can't redraw part of a frame draw all of it
+    fn draw_rows(&mut self, buffer: &[u8], dirty_rows: u64) {
+        let _ = dirty_rows;
+        self.draw(buffer);
+    }
+}
+
+/// Pixels per row of a frame handed to a FrameSink, from its length
+pub fn frame_width(buffer: &[u8]) -> usize {
+    if buffer.len() == HIRES_WIDTH * HIRES_HEIGHT { HIRES_WIDTH } else { DISPLAY_WIDTH }
+}
+
+/// Keeps the last frame drawn, for tests and for frontends that draw on their own schedule
+impl FrameSink for Vec<u8> {
+    fn draw(&mut self, buffer: &[u8]) {
+        self.clear();
+        self.extend_from_slice(buffer);
+    }
+
+    fn draw_rows(&mut self, buffer: &[u8], dirty_rows: u64) {
+        if self.len() != buffer.len() {
+            return self.draw(buffer);
+        }
+        let width = frame_width(buffer);
+        for (row, (shown, new)) in self.chunks_mut(width).zip(buffer.chunks(width)).enumerate() {
+            if dirty_rows & (1 << row) != 0 {
+                shown.copy_from_slice(new);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::DISPLAY_HEIGHT;
+
+    #[derive(Default)]
+    struct Counting {
+        draws: usize,
+    }
+
+    impl FrameSink for Counting {
+        fn draw(&mut self, _buffer: &[u8]) {
+            self.draws += 1;
+        }
+    }
+
+    #[test]
+    fn test_draw_rows_draws_everything_by_default() {
+        let mut sink = Counting::default();
+        sink.draw_rows(&[0; DISPLAY_WIDTH * DISPLAY_HEIGHT], 0b10);
+        assert_eq!(sink.draws, 1);
+    }
+
+    #[test]
+    fn test_vec_copies_only_dirty_rows() {
+        let mut shown = Vec::new();
+        let mut frame = vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        shown.draw_rows(&frame, 0); // Nothing shown yet, so it all is
+        assert_eq!(shown, frame);
+
+        frame[0] = 1;
+        frame[DISPLAY_WIDTH] = 1;
+        shown.draw_rows(&frame, 0b10);
+        assert_eq!((shown[0], shown[DISPLAY_WIDTH]), (0, 1));
+        shown.draw(&frame);
+        assert_eq!(shown, frame);
+    }
+
+    #[test]
+    fn test_hires_frames_are_split_into_their_rows() {
+        assert_eq!(frame_width(&[0; DISPLAY_WIDTH * DISPLAY_HEIGHT]), DISPLAY_WIDTH);
+        let mut frame = vec![0; HIRES_WIDTH * HIRES_HEIGHT];
+        assert_eq!(frame_width(&frame), HIRES_WIDTH);
+
+        let mut shown = Vec::new();
+        shown.draw(&frame);
+        frame[HIRES_WIDTH + 100] = 1;
+        shown.draw_rows(&frame, 0b10);
+        assert_eq!(shown, frame);
+    }
+}
@@ -0,0 +1,176 @@
+use embedded_hal::i2c::I2c;
+use crate::constants::DISPLAY_WIDTH;
+use crate::panel::push_frame;
+
+pub const PANEL_WIDTH: usize = 128;
+pub const PANEL_HEIGHT: usize = 64;
+const PAGES: usize = PANEL_HEIGHT / 8;
+/// The usual address; boards with the address pin pulled high use 0x3D
+pub const DEFAULT_ADDRESS: u8 = 0x3C;
+
+// The first byte of every I2C write says whether the rest are commands or pixel data
+const COMMANDS: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+/// Power-up settings for a 128x64 panel with the charge pump on and horizontal addressing
+const INIT: [u8; 25] = [
+    COMMANDS,
+    0xAE, // Display off
+    0xD5, 0x80, // Clock divide
+    0xA8, 0x3F, // Multiplex: 64 rows
+    0xD3, 0x00, // No display offset
+    0x40, // Start line 0
+    0x8D, 0x14, // Charge pump on
+    0x20, 0x00, // Horizontal addressing
+    0xA1, // Column 127 on the left, so x runs left to right
+    0xC8, // Scan from the bottom, so y runs top to bottom
+    0xDA, 0x12, // COM pins
+    0x81, 0xCF, // Contrast
+    0xD9, 0xF1, // Precharge
+    0xDB, 0x40, // VCOMH level
+    0xA4, // Show RAM
+    0xA6, // Not inverted
+];
+const DISPLAY_ON: [u8; 2] = [COMMANDS, 0xAF];
+/// Write the whole RAM, columns 0-127 and pages 0-7
+const FULL_WINDOW: [u8; 7] = [COMMANDS, 0x21, 0x00, 0x7F, 0x22, 0x00, 0x07];
+
+/// Packs the display, doubled to fill the panel, into SSD1306 RAM: one byte per column per page
+/// of 8 rows, with the top row of the page in bit 0. Byte `page * 128 + x` holds rows page*8..page*8+8
+pub fn pack_pages(display: &[u8]) -> [u8; PANEL_WIDTH * PAGES] {
+    let mut pages = [0; PANEL_WIDTH * PAGES];
+    push_frame(display, PANEL_WIDTH / DISPLAY_WIDTH, &mut |x, y, on| {
+        if on {
+            pages[(y / 8) * PANEL_WIDTH + x] |= 1 << (y % 8);
+        }
+    });
+    pages
+}
+
+/// A 128x64 SSD1306 panel on an I2C bus, showing the display at double size
+pub struct Ssd1306<I> {
+    i2c: I,
+    address: u8,
+}
+
+impl<I: I2c> Ssd1306<I> {
+    pub fn new(i2c: I, address: u8) -> Self {
+        Self { i2c, address }
+    }
+
+    /// Configures the panel and turns it on, blank
+    pub fn init(&mut self) -> Result<(), I::Error> {
+        self.i2c.write(self.address, &INIT)?;
+        self.push_pages(&[0; PANEL_WIDTH * PAGES])?;
+        self.i2c.write(self.address, &DISPLAY_ON)
+    }
+
+    /// Sends the whole frame, blocking until the bus is done
+    pub fn push_frame(&mut self, display: &[u8]) -> Result<(), I::Error> {
+        self.push_pages(&pack_pages(display))
+    }
+
+    /// Gives the bus back
+    pub fn release(self) -> I {
+        self.i2c
+    }
+
+    fn push_pages(&mut self, pages: &[u8; PANEL_WIDTH * PAGES]) -> Result<(), I::Error> {
+        self.i2c.write(self.address, &FULL_WINDOW)?;
+
+        // A page at a time keeps the buffer small on microcontrollers
+        let mut write = [DATA; PANEL_WIDTH + 1];
+        for page in pages.chunks(PANEL_WIDTH) {
+            write[1..].copy_from_slice(page);
+            self.i2c.write(self.address, &write)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::DISPLAY_HEIGHT;
+    use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+
+    fn blank() -> [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT] {
+        [0; DISPLAY_WIDTH * DISPLAY_HEIGHT]
+    }
+
+    #[test]
+    fn test_top_left_pixel() {
+        let mut display = blank();
+        display[0] = 1;
+
+        let pages = pack_pages(&display);
+        // Doubled: columns 0 and 1 of page 0, rows 0 and 1
+        assert_eq!(pages[0], 0b0000_0011);
+        assert_eq!(pages[1], 0b0000_0011);
+        assert!(pages[2..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_rows_fill_pages_bottom_bit_last() {
+        let mut display = blank();
+        // Display rows 0-3 are panel rows 0-7, all of page 0; row 4 starts page 1
+        for row in [0, 3, 4, 31] {
+            display[row * DISPLAY_WIDTH + 10] = 1;
+        }
+
+        let pages = pack_pages(&display);
+        assert_eq!(pages[20], 0b1100_0011);
+        assert_eq!(pages[21], 0b1100_0011);
+        assert_eq!(pages[PANEL_WIDTH + 20], 0b0000_0011);
+        assert_eq!(pages[7 * PANEL_WIDTH + 20], 0b1100_0000);
+        assert_eq!(pages.iter().filter(|byte| **byte != 0).count(), 6);
+    }
+
+    #[test]
+    fn test_last_pixel_and_full_screen() {
+        let mut display = blank();
+        display[DISPLAY_WIDTH * DISPLAY_HEIGHT - 1] = 1;
+        let pages = pack_pages(&display);
+        assert_eq!(pages[PANEL_WIDTH * PAGES - 2..], [0b1100_0000, 0b1100_0000]);
+
+        assert!(pack_pages(&[1; DISPLAY_WIDTH * DISPLAY_HEIGHT]).iter().all(|byte| *byte == 0xFF));
+    }
+
+    #[test]
+    fn test_checkerboard() {
+        let mut display = blank();
+        for (index, pixel) in display.iter_mut().enumerate() {
+            *pixel = ((index % DISPLAY_WIDTH + index / DISPLAY_WIDTH) % 2) as u8;
+        }
+
+        // Doubled 2x2 blocks alternate every two columns and every two bits
+        let pages = pack_pages(&display);
+        assert_eq!(pages[..4], [0b1100_1100, 0b1100_1100, 0b0011_0011, 0b0011_0011]);
+    }
+
+    fn frame_writes(pages: &[u8]) -> Vec<Transaction> {
+        let mut writes = vec![Transaction::write(DEFAULT_ADDRESS, FULL_WINDOW.to_vec())];
+        for page in pages.chunks(PANEL_WIDTH) {
+            let mut write = vec![DATA];
+            write.extend_from_slice(page);
+            writes.push(Transaction::write(DEFAULT_ADDRESS, write));
+        }
+        writes
+    }
+
+    #[test]
+    fn test_init_and_push_frame_bus_traffic() {
+        let mut display = blank();
+        display[0] = 1;
+
+        let mut expected = vec![Transaction::write(DEFAULT_ADDRESS, INIT.to_vec())];
+        expected.extend(frame_writes(&[0; PANEL_WIDTH * PAGES]));
+        expected.push(Transaction::write(DEFAULT_ADDRESS, DISPLAY_ON.to_vec()));
+        expected.extend(frame_writes(&pack_pages(&display)));
+
+        let mut panel = Ssd1306::new(Mock::new(&expected), DEFAULT_ADDRESS);
+        panel.init().unwrap();
+        panel.push_frame(&display).unwrap();
+        panel.release().done();
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::config::{Palette, Rgb};
+use crate::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT};
+
+/// Where thumbnails of exited ROMs are kept, one file per ROM hash
+pub const THUMBNAIL_DIR: &str = "./cache/thumbnails";
+/// Total size the cache may grow to before the least recently used thumbnails are removed
+pub const MAX_CACHE_BYTES: u64 = 512 * 1024;
+
+const PPM_HEADER: &str = "P6\n64 32\n255\n";
+
+/// A display snapshot colored with the palette it was played with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thumbnail {
+    pub pixels: Vec<Rgb>, // DISPLAY_WIDTH * DISPLAY_HEIGHT, row by row
+}
+
+impl Thumbnail {
+    pub fn from_display(display: &[u8], palette: &Palette) -> Self {
+        let pixels = display.iter()
+            .map(|pixel| if *pixel != 0 { palette.foreground } else { palette.background })
+            .collect();
+        Self { pixels }
+    }
+
+    /// Binary PPM, so thumbnails open in any image viewer
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(PPM_HEADER.len() + self.pixels.len() * 3);
+        out.extend_from_slice(PPM_HEADER.as_bytes());
+        for pixel in &self.pixels {
+            out.extend_from_slice(&[pixel.0, pixel.1, pixel.2]);
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let data = bytes.strip_prefix(PPM_HEADER.as_bytes())?;
+        if data.len() != DISPLAY_WIDTH * DISPLAY_HEIGHT * 3 {
+            return None;
+        }
+        let pixels = data.chunks_exact(3).map(|rgb| Rgb(rgb[0], rgb[1], rgb[2])).collect();
+        Some(Self { pixels })
+    }
+}
+
+/// Directory of thumbnails with a size cap and least-recently-used eviction.
+/// Reading a thumbnail counts as using it
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl ThumbnailCache {
+    pub fn new(dir: &Path, max_bytes: u64) -> Self {
+        Self { dir: dir.to_path_buf(), max_bytes }
+    }
+
+    pub fn store(&self, rom_hash: u64, thumbnail: &Thumbnail) -> Result<(), std::io::Error> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.path(rom_hash), thumbnail.encode())?;
+        self.evict()
+    }
+
+    /// The thumbnail for a ROM, if one has been saved
+    pub fn load(&self, rom_hash: u64) -> Option<Thumbnail> {
+        let path = self.path(rom_hash);
+        let thumbnail = Thumbnail::decode(&std::fs::read(&path).ok()?)?;
+        if let Ok(file) = std::fs::File::options().append(true).open(&path) {
+            file.set_modified(SystemTime::now()).ok();
+        }
+        Some(thumbnail)
+    }
+
+    /// Deletes the least recently used thumbnails until the cache fits, always keeping the newest
+    fn evict(&self) -> Result<(), std::io::Error> {
+        let mut files = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "ppm"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        for (_, len, path) in files.iter().take(files.len().saturating_sub(1)) {
+            if total <= self.max_bytes {
+                break;
+            }
+            std::fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    fn path(&self, rom_hash: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.ppm", rom_hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const PALETTE: Palette = Palette { foreground: Rgb(255, 255, 255), background: Rgb(0, 0, 10) };
+
+    fn temp_cache(name: &str, max_bytes: u64) -> (ThumbnailCache, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("chip8-thumbs-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        (ThumbnailCache::new(&dir, max_bytes), dir)
+    }
+
+    fn thumbnail(lit: usize) -> Thumbnail {
+        let mut display = [0u8; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        display[lit] = 1;
+        Thumbnail::from_display(&display, &PALETTE)
+    }
+
+    /// Backdates a cached thumbnail so eviction order doesn't depend on timing
+    fn age(cache: &ThumbnailCache, rom_hash: u64, secs: u64) {
+        let file = std::fs::File::options().append(true).open(cache.path(rom_hash)).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(secs)).unwrap();
+    }
+
+    #[test]
+    fn test_palette_mapping_and_round_trip() {
+        let thumb = thumbnail(65);
+        assert_eq!(thumb.pixels[65], PALETTE.foreground);
+        assert_eq!(thumb.pixels[0], PALETTE.background);
+        assert_eq!(Thumbnail::decode(&thumb.encode()), Some(thumb));
+    }
+
+    #[test]
+    fn test_decode_rejects_other_files() {
+        let encoded = thumbnail(0).encode();
+        assert_eq!(Thumbnail::decode(&encoded[..encoded.len() - 1]), None);
+        assert_eq!(Thumbnail::decode(b"P6\n128 64\n255\n"), None);
+        assert_eq!(Thumbnail::decode(b""), None);
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let (cache, dir) = temp_cache("load", MAX_CACHE_BYTES);
+        assert_eq!(cache.load(1), None);
+
+        cache.store(1, &thumbnail(3)).unwrap();
+        assert_eq!(cache.load(1), Some(thumbnail(3)));
+        assert_eq!(cache.load(2), None);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let size = thumbnail(0).encode().len() as u64;
+        let (cache, dir) = temp_cache("evict", size * 2);
+
+        cache.store(1, &thumbnail(1)).unwrap();
+        cache.store(2, &thumbnail(2)).unwrap();
+        age(&cache, 1, 300);
+        age(&cache, 2, 200);
+
+        // Using 1 makes 2 the oldest
+        assert!(cache.load(1).is_some());
+        cache.store(3, &thumbnail(3)).unwrap();
+
+        assert!(cache.load(1).is_some());
+        assert_eq!(cache.load(2), None);
+        assert!(cache.load(3).is_some());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_newest_kept_even_over_cap() {
+        let (cache, dir) = temp_cache("tiny", 1);
+        cache.store(1, &thumbnail(1)).unwrap();
+        age(&cache, 1, 100);
+        cache.store(2, &thumbnail(2)).unwrap();
+
+        assert_eq!(cache.load(1), None);
+        assert!(cache.load(2).is_some());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,263 @@
+use serde::{Deserialize, Serialize};
+use crate::cpu::CPU;
+
+/// COSMAC VIP machine cycles available per 60Hz frame (1.76MHz clock, 8 clocks per machine cycle)
+pub const VIP_CYCLES_PER_FRAME: u32 = 3668;
+
+/// How many instructions run per frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum TimingMode {
+    /// A flat number of instructions per frame
+    Simple,
+    /// Each instruction costs its COSMAC VIP machine-cycle count out of a per-frame budget
+    Cycles,
+}
+
+// Approximate VIP interpreter costs in machine cycles, by opcode class.
+// Every instruction pays the fetch/decode overhead on top of its own cost.
+const FETCH_DECODE: u32 = 40;
+const CLEAR: u32 = 3078;
+const RETURN: u32 = 10;
+const JUMP: u32 = 12;
+const CALL: u32 = 26;
+const SKIP_COMPARE_NN: u32 = 10;
+const SKIP_COMPARE_VY: u32 = 14;
+const SKIP_KEY: u32 = 14;
+const SKIP_TAKEN: u32 = 4;
+const LOAD_NN: u32 = 6;
+const ADD_NN: u32 = 10;
+const ALU: u32 = 44;
+const LOAD_I: u32 = 12;
+const JUMP_V0: u32 = 22;
+const RANDOM: u32 = 36;
+const DRAW_SETUP: u32 = 26;
+const DRAW_ROW: u32 = 46;
+const DRAW_ROW_UNALIGNED: u32 = 20; // Sprites not on a byte boundary straddle two display bytes
+const TIMER: u32 = 10;
+const ADD_I: u32 = 16;
+const FONT: u32 = 16;
+const BCD_SETUP: u32 = 16;
+const BCD_PER_DIGIT_STEP: u32 = 18; // BCD is computed by repeated subtraction
+const LOAD_STORE_SETUP: u32 = 14;
+const LOAD_STORE_PER_REGISTER: u32 = 14;
+
+/// Machine cycles the VIP interpreter would spend on opcode, given the CPU state before it runs
+pub fn vip_cycles(opcode: u16, cpu: &CPU) -> u32 {
+    let x = CPU::get_x(opcode);
+    let y = CPU::get_y(opcode);
+    let nn = CPU::get_nn(opcode);
+    let vx = cpu.v[x];
+    let vy = cpu.v[y];
+    let skip = |taken: bool| if taken { SKIP_TAKEN } else { 0 };
+
+    let cost = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => CLEAR,
+            0x00EE => RETURN,
+            _ => 0,
+        },
+        0x1000 => JUMP,
+        0x2000 => CALL,
+        0x3000 => SKIP_COMPARE_NN + skip(vx == nn),
+        0x4000 => SKIP_COMPARE_NN + skip(vx != nn),
+        0x5000 => SKIP_COMPARE_VY + skip(vx == vy),
+        0x6000 => LOAD_NN,
+        0x7000 => ADD_NN,
+        0x8000 => ALU,
+        0x9000 => SKIP_COMPARE_VY + skip(vx != vy),
+        0xA000 => LOAD_I,
+        0xB000 => JUMP_V0,
+        0xC000 => RANDOM,
+        0xD000 => {
+            let rows = CPU::get_n(opcode) as u32;
+            let row_cost = DRAW_ROW + if !vx.is_multiple_of(8) { DRAW_ROW_UNALIGNED } else { 0 };
+            DRAW_SETUP + rows * row_cost
+        }
+        0xE000 => {
+            let pressed = cpu.input[(vx & 0x0F) as usize];
+            match opcode & 0x00FF {
+                0x9E => SKIP_KEY + skip(pressed),
+                0xA1 => SKIP_KEY + skip(!pressed),
+                _ => 0,
+            }
+        }
+        _ => match opcode & 0x00FF {
+            0x07 | 0x0A | 0x15 | 0x18 => TIMER,
+            0x1E => ADD_I,
+            0x29 => FONT,
+            0x33 => {
+                let steps = (vx / 100 + (vx / 10) % 10 + vx % 10) as u32;
+                BCD_SETUP + steps * BCD_PER_DIGIT_STEP
+            }
+            0x55 | 0x65 => LOAD_STORE_SETUP + (x as u32 + 1) * LOAD_STORE_PER_REGISTER,
+            _ => 0,
+        },
+    };
+
+    FETCH_DECODE + cost
+}
+
+/// Decides how long the instruction loop keeps running within one frame
+pub struct FrameBudget {
+    mode: TimingMode,
+    instructions_per_frame: u32,
+    executed: u32,
+    cycles: i64, // Frames that overrun borrow from the next one, keeping the average exact
+}
+
+impl FrameBudget {
+    pub fn new(mode: TimingMode, instructions_per_frame: u32) -> Self {
+        Self { mode, instructions_per_frame, executed: 0, cycles: 0 }
+    }
+
+    pub fn mode(&self) -> TimingMode {
+        self.mode
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.executed = 0;
+        if self.mode == TimingMode::Cycles {
+            // Only overruns carry over: time left unused by a paused debugger is not saved up
+            self.cycles = self.cycles.min(0) + VIP_CYCLES_PER_FRAME as i64;
+        }
+    }
+
+    /// True while another instruction fits in this frame
+    pub fn has_time(&self) -> bool {
+        match self.mode {
+            TimingMode::Simple => self.executed < self.instructions_per_frame,
+            TimingMode::Cycles => self.cycles > 0,
+        }
+    }
+
+    /// Records an executed instruction and the cycles it cost
+    pub fn spend(&mut self, cycles: u32) {
+        self.executed += 1;
+        if self.mode == TimingMode::Cycles {
+            self.cycles -= cycles as i64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::RomBuilder;
+
+    #[test]
+    fn test_fixed_costs() {
+        let cpu = CPU::new();
+        assert_eq!(vip_cycles(0x00E0, &cpu), FETCH_DECODE + CLEAR);
+        assert_eq!(vip_cycles(0x00EE, &cpu), FETCH_DECODE + RETURN);
+        assert_eq!(vip_cycles(0x1234, &cpu), FETCH_DECODE + JUMP);
+        assert_eq!(vip_cycles(0x2234, &cpu), FETCH_DECODE + CALL);
+        assert_eq!(vip_cycles(0x6A12, &cpu), FETCH_DECODE + LOAD_NN);
+        assert_eq!(vip_cycles(0x7A12, &cpu), FETCH_DECODE + ADD_NN);
+        assert_eq!(vip_cycles(0x8AB4, &cpu), FETCH_DECODE + ALU);
+        assert_eq!(vip_cycles(0xA123, &cpu), FETCH_DECODE + LOAD_I);
+        assert_eq!(vip_cycles(0xB123, &cpu), FETCH_DECODE + JUMP_V0);
+        assert_eq!(vip_cycles(0xC1FF, &cpu), FETCH_DECODE + RANDOM);
+        assert_eq!(vip_cycles(0xF107, &cpu), FETCH_DECODE + TIMER);
+        assert_eq!(vip_cycles(0xF11E, &cpu), FETCH_DECODE + ADD_I);
+        assert_eq!(vip_cycles(0xF129, &cpu), FETCH_DECODE + FONT);
+    }
+
+    #[test]
+    fn test_skips_cost_more_when_taken() {
+        let mut cpu = CPU::new();
+        cpu.v[1] = 0x42;
+
+        assert_eq!(vip_cycles(0x3142, &cpu), FETCH_DECODE + SKIP_COMPARE_NN + SKIP_TAKEN);
+        assert_eq!(vip_cycles(0x3143, &cpu), FETCH_DECODE + SKIP_COMPARE_NN);
+        assert_eq!(vip_cycles(0x4143, &cpu), FETCH_DECODE + SKIP_COMPARE_NN + SKIP_TAKEN);
+        assert_eq!(vip_cycles(0x5120, &cpu), FETCH_DECODE + SKIP_COMPARE_VY);
+        assert_eq!(vip_cycles(0x9120, &cpu), FETCH_DECODE + SKIP_COMPARE_VY + SKIP_TAKEN);
+
+        cpu.v[2] = 0x3;
+        cpu.input[0x3] = true;
+        assert_eq!(vip_cycles(0xE29E, &cpu), FETCH_DECODE + SKIP_KEY + SKIP_TAKEN);
+        assert_eq!(vip_cycles(0xE2A1, &cpu), FETCH_DECODE + SKIP_KEY);
+    }
+
+    #[test]
+    fn test_draw_cost_depends_on_rows_and_alignment() {
+        let mut cpu = CPU::new();
+
+        cpu.v[0] = 8;
+        assert_eq!(vip_cycles(0xD015, &cpu), FETCH_DECODE + DRAW_SETUP + 5 * DRAW_ROW);
+        assert_eq!(vip_cycles(0xD01F, &cpu), FETCH_DECODE + DRAW_SETUP + 15 * DRAW_ROW);
+
+        cpu.v[0] = 9;
+        assert_eq!(vip_cycles(0xD015, &cpu), FETCH_DECODE + DRAW_SETUP + 5 * (DRAW_ROW + DRAW_ROW_UNALIGNED));
+    }
+
+    #[test]
+    fn test_register_dependent_costs() {
+        let mut cpu = CPU::new();
+
+        assert_eq!(vip_cycles(0xF055, &cpu), FETCH_DECODE + LOAD_STORE_SETUP + LOAD_STORE_PER_REGISTER);
+        assert_eq!(vip_cycles(0xFF65, &cpu), FETCH_DECODE + LOAD_STORE_SETUP + 16 * LOAD_STORE_PER_REGISTER);
+
+        cpu.v[4] = 0;
+        assert_eq!(vip_cycles(0xF433, &cpu), FETCH_DECODE + BCD_SETUP);
+        cpu.v[4] = 255; // 2 + 5 + 5 subtraction steps
+        assert_eq!(vip_cycles(0xF433, &cpu), FETCH_DECODE + BCD_SETUP + 12 * BCD_PER_DIGIT_STEP);
+    }
+
+    #[test]
+    fn test_cycle_frame_stops_within_one_instruction_of_budget() {
+        let mut cpu = RomBuilder::new().label("top").ld(0, 1).add(0, 1).jp("top").cpu().unwrap();
+
+        let mut budget = FrameBudget::new(TimingMode::Cycles, 10);
+        let mut carried = 0;
+
+        for _ in 0..5 {
+            budget.begin_frame();
+            let mut spent = 0;
+            let mut last = 0;
+            while budget.has_time() {
+                last = cpu.tick().unwrap();
+                budget.spend(last);
+                spent += last;
+            }
+
+            let available = VIP_CYCLES_PER_FRAME - carried;
+            assert!(spent >= available);
+            assert!(spent - last < available, "ran a whole instruction past the budget");
+            carried = spent - available;
+        }
+    }
+
+    #[test]
+    fn test_unused_cycles_are_not_saved_up() {
+        let mut budget = FrameBudget::new(TimingMode::Cycles, 10);
+        for _ in 0..3 {
+            budget.begin_frame();
+        }
+        budget.spend(VIP_CYCLES_PER_FRAME);
+        assert!(!budget.has_time());
+    }
+
+    #[test]
+    fn test_simple_frame_runs_fixed_instruction_count() {
+        let mut cpu = CPU::new();
+        cpu.memory[0x200..0x202].copy_from_slice(&[0x12, 0x00]);
+
+        let mut budget = FrameBudget::new(TimingMode::Simple, 10);
+        for _ in 0..3 {
+            budget.begin_frame();
+            let mut executed = 0;
+            while budget.has_time() {
+                budget.spend(cpu.tick().unwrap());
+                executed += 1;
+            }
+            assert_eq!(executed, 10);
+        }
+    }
+}
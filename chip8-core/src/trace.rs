@@ -0,0 +1,155 @@
+//! The last instructions the CPU ran, kept in a ring while tracing is on, so that a ROM that
+//! stops on an error can show how it got there
+use std::fmt::{self, Write};
+use crate::cpu::CPU;
+use crate::diagnostics;
+#[cfg(feature = "debugger")]
+use crate::disassembler::disassemble;
+
+/// Instructions kept; older ones are overwritten
+pub const TRACE_CAPACITY: usize = 256;
+
+/// One instruction as it was about to run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    pub i: u16,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#05X}  {:04X}  I {:#05X}", self.pc, self.opcode, self.i)?;
+        #[cfg(feature = "debugger")]
+        write!(f, "  {}", disassemble(self.opcode))?;
+        Ok(())
+    }
+}
+
+/// A fixed-size ring of TraceEntry, oldest first
+#[derive(Debug, Clone)]
+pub struct Trace {
+    entries: Vec<TraceEntry>, // Grows to TRACE_CAPACITY, then wraps
+    next: usize,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self { entries: Vec::with_capacity(TRACE_CAPACITY), next: 0 }
+    }
+
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() < TRACE_CAPACITY {
+            self.entries.push(entry);
+        } else {
+            self.entries[self.next] = entry;
+        }
+        self.next = (self.next + 1) % TRACE_CAPACITY;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries from oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &TraceEntry> {
+        // Until the ring fills, next is the end and everything is in the first half
+        let (newer, older) = self.entries.split_at(self.next);
+        older.iter().chain(newer)
+    }
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What to print under the error when a ROM stops on one: the last `entries` traced
+/// instructions, the failing one last, then the registers, stack and timers
+pub fn crash_report(cpu: &CPU, entries: usize) -> String {
+    let mut out = String::new();
+    let traced = cpu.trace().count();
+    if traced > 0 {
+        writeln!(out, "Last instructions, oldest first:").ok();
+        for entry in cpu.trace().skip(traced.saturating_sub(entries)) {
+            writeln!(out, "  {}", entry).ok();
+        }
+    }
+
+    out.push_str(&diagnostics::registers(cpu, ""));
+    let stack: Vec<String> = cpu.stack.iter().map(|addr| format!("{:#05X}", addr)).collect();
+    writeln!(out, "I {:#05X}  DT {}  ST {}  stack [{}]", cpu.i, cpu.delay_timer, cpu.sound_timer, stack.join(" ")).ok();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EmulatorError;
+    use crate::rom_builder::RomBuilder;
+
+    fn entry(pc: u16) -> TraceEntry {
+        TraceEntry { pc, opcode: 0x1200, i: 0 }
+    }
+
+    fn pcs(trace: &Trace) -> Vec<u16> {
+        trace.iter().map(|entry| entry.pc).collect()
+    }
+
+    #[test]
+    fn test_ring_keeps_order_until_full() {
+        let mut trace = Trace::new();
+        assert!(trace.is_empty());
+        trace.push(entry(1));
+        trace.push(entry(2));
+        assert_eq!(pcs(&trace), [1, 2]);
+    }
+
+    #[test]
+    fn test_wraparound_overwrites_the_oldest() {
+        let mut trace = Trace::new();
+        for pc in 0..TRACE_CAPACITY as u16 {
+            trace.push(entry(pc));
+        }
+        assert_eq!(pcs(&trace), (0..TRACE_CAPACITY as u16).collect::<Vec<_>>());
+
+        for pc in 0..(TRACE_CAPACITY as u16 + 3) {
+            trace.push(entry(1000 + pc));
+        }
+        assert_eq!(trace.len(), TRACE_CAPACITY);
+        let expected: Vec<u16> = (3..TRACE_CAPACITY as u16 + 3).map(|pc| 1000 + pc).collect();
+        assert_eq!(pcs(&trace), expected);
+        assert_eq!(trace.iter().next_back().map(|entry| entry.pc), Some(1000 + TRACE_CAPACITY as u16 + 2));
+    }
+
+    #[test]
+    fn test_cpu_traces_only_when_asked() {
+        let rom = RomBuilder::new().ld(0, 1).ld_i("data").ret().label("data").build().unwrap();
+        let mut cpu = CPU::new();
+        cpu.load_rom_bytes(&rom).unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.trace().count(), 0);
+
+        cpu.set_trace(true);
+        cpu.tick().unwrap();
+        assert_eq!(cpu.tick().unwrap_err(), EmulatorError::StackUnderflow { pc: 0x204 });
+        let traced: Vec<TraceEntry> = cpu.trace().copied().collect();
+        assert_eq!(traced, [
+            TraceEntry { pc: 0x202, opcode: 0xA206, i: 0 },
+            TraceEntry { pc: 0x204, opcode: 0x00EE, i: 0x206 },
+        ]);
+
+        let report = crash_report(&cpu, 1);
+        assert!(report.starts_with("Last instructions, oldest first:\n  0x204  00EE  I 0x206"), "{}", report);
+        assert!(report.contains("V0-V7  01 00 00 00 00 00 00 00\n"), "{}", report);
+        assert!(report.ends_with("I 0x206  DT 0  ST 0  stack []\n"), "{}", report);
+
+        cpu.set_trace(false);
+        assert_eq!(cpu.trace().count(), 0);
+    }
+}
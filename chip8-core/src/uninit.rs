@@ -0,0 +1,245 @@
+use crate::constants::{MEMORY_SIZE, REGISTERS_COUNT, STARTING_MEMORY_ADDRESS};
+use crate::cpu::CPU;
+use crate::quirks::Quirks;
+
+/// A read of something the ROM never set, found by --detect-uninit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UninitRead {
+    /// FX65 at pc loaded addr, which nothing had written
+    Memory { pc: u16, addr: u16 },
+    /// Arithmetic at pc used VX before anything had set it
+    Register { pc: u16, register: usize },
+}
+
+impl std::fmt::Display for UninitRead {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UninitRead::Memory { pc, addr } => write!(f, "{:03X}: read of uninitialized memory at {:03X}", pc, addr),
+            UninitRead::Register { pc, register } => write!(f, "{:03X}: V{:X} used before it was set", pc, register),
+        }
+    }
+}
+
+/// Which memory and registers have been written, decided from each opcode around its execution.
+/// Everything up to the end of the ROM counts as written
+pub struct UninitTracker {
+    written: Vec<bool>, // Per memory address
+    registers: u16, // Bit N set once VN has been written
+    reported_memory: Vec<bool>,
+    reported_registers: u16,
+    found: Vec<UninitRead>,
+}
+
+impl UninitTracker {
+    pub fn new(rom_size: usize) -> Self {
+        let mut written = vec![false; MEMORY_SIZE];
+        written[..(STARTING_MEMORY_ADDRESS + rom_size).min(MEMORY_SIZE)].fill(true);
+        Self {
+            written,
+            registers: 0,
+            reported_memory: vec![false; MEMORY_SIZE],
+            reported_registers: 0,
+            found: Vec::new(),
+        }
+    }
+
+    /// Starts over for a machine restored from a save state holding memory, keeping what has been
+    /// found. The state doesn't say what its session wrote, so every register and any memory not
+    /// left zero counts as written
+    pub fn restore(&mut self, rom_size: usize, memory: &[u8]) {
+        let mut written = Self::new(rom_size).written;
+        for (written, byte) in written.iter_mut().zip(memory) {
+            *written |= *byte != 0;
+        }
+        self.written = written;
+        self.registers = u16::MAX;
+    }
+
+    /// Every uninitialized read so far, each location once
+    pub fn found(&self) -> &[UninitRead] {
+        &self.found
+    }
+
+    /// Checks what opcode is about to read when run with quirks
+    pub fn before(&mut self, pc: u16, opcode: u16, i: u16, quirks: Quirks) {
+        let x = CPU::get_x(opcode);
+        let y = CPU::get_y(opcode);
+
+        let reads: u16 = match opcode & 0xF00F {
+            0x8000 => 1 << y,
+            0x8001..=0x8005 | 0x8007 => (1 << x) | (1 << y),
+            0x8006 | 0x800E if quirks.shift_uses_vy => 1 << y,
+            0x8006 | 0x800E => 1 << x,
+            _ if opcode & 0xF000 == 0x7000 || opcode & 0xF0FF == 0xF01E => 1 << x,
+            _ => 0,
+        };
+        let unset = reads & !self.registers & !self.reported_registers;
+        for register in (0..REGISTERS_COUNT).filter(|register| unset & (1 << register) != 0) {
+            self.reported_registers |= 1 << register;
+            self.report(UninitRead::Register { pc, register });
+        }
+
+        if opcode & 0xF0FF == 0xF065 {
+            for addr in memory_range(i, x) {
+                if !self.written[addr] && !self.reported_memory[addr] {
+                    self.reported_memory[addr] = true;
+                    self.report(UninitRead::Memory { pc, addr: addr as u16 });
+                }
+            }
+        }
+    }
+
+    /// Records what opcode wrote when run with quirks. i is I as it was before the opcode ran
+    pub fn after(&mut self, opcode: u16, i: u16, quirks: Quirks) {
+        let x = CPU::get_x(opcode);
+        let vf = REGISTERS_COUNT - 1;
+
+        match opcode & 0xF000 {
+            0x6000 | 0x7000 | 0xC000 => self.registers |= 1 << x,
+            0xD000 => self.registers |= 1 << vf,
+            _ => {}
+        }
+        match opcode & 0xF00F {
+            0x8000 => self.registers |= 1 << x,
+            0x8001..=0x8003 if quirks.vf_reset_on_logic => self.registers |= (1 << x) | (1 << vf),
+            0x8001..=0x8003 => self.registers |= 1 << x,
+            0x8004..=0x8007 | 0x800E => self.registers |= (1 << x) | (1 << vf),
+            _ => {}
+        }
+        match opcode & 0xF0FF {
+            0xF007 | 0xF00A => self.registers |= 1 << x,
+            0xF065 | 0xF085 => self.registers |= ((1u32 << (x + 1)) - 1) as u16,
+            0xF055 => memory_range(i, x).for_each(|addr| self.written[addr] = true),
+            0xF033 => memory_range(i, 2).for_each(|addr| self.written[addr] = true),
+            _ => {}
+        }
+    }
+
+    fn report(&mut self, read: UninitRead) {
+        self.found.push(read);
+    }
+}
+
+/// I..=I+x, cut off at the end of memory
+fn memory_range(i: u16, x: usize) -> impl Iterator<Item = usize> {
+    (i as usize..=i as usize + x).filter(|addr| *addr < MEMORY_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rom_builder::RomBuilder;
+    use crate::run::StopCondition;
+
+    fn run(rom: RomBuilder, instructions: u64) -> CPU {
+        let mut cpu = rom.cpu().unwrap();
+        cpu.set_detect_uninit(true);
+        cpu.run_until(StopCondition::InstructionCount(instructions), instructions);
+        cpu
+    }
+
+    #[test]
+    fn test_reports_each_location_once() {
+        let rom = RomBuilder::new()
+            .op(0xA800) // LD I, 0x800
+            .label("loop")
+            .op(0xF065) // LD V0, [I]
+            .add(1, 1)
+            .jp("loop");
+        let cpu = run(rom, 10);
+
+        assert_eq!(cpu.uninit_reads(), [
+            UninitRead::Memory { pc: 0x202, addr: 0x800 },
+            UninitRead::Register { pc: 0x204, register: 1 },
+        ]);
+    }
+
+    #[test]
+    fn test_writes_initialize() {
+        let rom = RomBuilder::new()
+            .ld(0, 7)
+            .op(0xA900) // LD I, 0x900
+            .op(0xF055) // LD [I], V0
+            .op(0xF165) // LD V1, [I]: 0x901 was never written
+            .add(0, 1)
+            .op(0x8014) // ADD V0, V1: loaded from memory, so set
+            .spin();
+        let cpu = run(rom, 7);
+
+        assert_eq!(cpu.uninit_reads(), [UninitRead::Memory { pc: 0x206, addr: 0x901 }]);
+    }
+
+    #[test]
+    fn test_rom_and_interpreter_memory_count_as_written() {
+        let mut tracker = UninitTracker::new(4);
+        tracker.before(0x200, 0xF365, 0x050, Quirks::default()); // Font
+        tracker.before(0x200, 0xF365, 0x200, Quirks::default()); // ROM
+        assert!(tracker.found().is_empty());
+
+        tracker.before(0x200, 0xF065, 0x204, Quirks::default());
+        assert_eq!(tracker.found().len(), 1);
+    }
+
+    #[test]
+    fn test_register_writes_follow_the_quirks() {
+        let mut tracker = UninitTracker::new(0);
+        tracker.before(0x200, 0x8010, 0, Quirks::default()); // LD V0, V1 reads V1
+        tracker.after(0x8010, 0, Quirks::default()); // and sets only V0
+        tracker.after(0x8231, 0, Quirks::modern()); // OR leaves VF alone
+        tracker.before(0x202, 0x8F04, 0, Quirks::default());
+        assert_eq!(tracker.found(), [
+            UninitRead::Register { pc: 0x200, register: 1 },
+            UninitRead::Register { pc: 0x202, register: 0xF },
+        ]);
+
+        let mut tracker = UninitTracker::new(0);
+        tracker.after(0x8231, 0, Quirks::vip()); // The VIP's OR clears VF
+        tracker.before(0x202, 0x8F24, 0, Quirks::vip());
+        assert!(tracker.found().is_empty());
+    }
+
+    #[test]
+    fn test_shifts_read_the_quirk_source() {
+        let mut tracker = UninitTracker::new(0);
+        tracker.before(0x200, 0x8126, 0, Quirks::vip());
+        tracker.before(0x202, 0x834E, 0, Quirks::modern());
+        assert_eq!(tracker.found(), [
+            UninitRead::Register { pc: 0x200, register: 2 },
+            UninitRead::Register { pc: 0x202, register: 3 },
+        ]);
+    }
+
+    #[test]
+    fn test_flag_registers_initialize() {
+        let rom = RomBuilder::new()
+            .op(0xF185) // LD V1, R
+            .op(0x8014) // ADD V0, V1
+            .spin();
+        assert!(run(rom, 3).uninit_reads().is_empty());
+    }
+
+    #[test]
+    fn test_loaded_state_counts_as_written() {
+        let rom = || RomBuilder::new()
+            .ld(0, 5)
+            .op(0xA800) // LD I, 0x800
+            .op(0xF055) // LD [I], V0
+            .op(0xF065) // LD V0, [I]
+            .add(1, 1)
+            .spin();
+        let saved = run(rom(), 3);
+
+        let mut cpu = rom().cpu().unwrap();
+        cpu.set_detect_uninit(true);
+        cpu.load_state(&saved.save_state()).unwrap();
+        cpu.run_until(StopCondition::InstructionCount(2), 2);
+        assert!(cpu.uninit_reads().is_empty());
+    }
+
+    #[test]
+    fn test_off_by_default() {
+        let mut cpu = CPU::new();
+        cpu.decode_and_execute(0x7001).unwrap();
+        assert!(cpu.uninit_reads().is_empty());
+    }
+}
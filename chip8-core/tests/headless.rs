@@ -0,0 +1,127 @@
+//! Whole ROMs run through the library alone, with no frontend. Run these against a core-only build with
+//! `cargo test -p chip8-core --no-default-features --features headless-tests`; the parts that need other features
+//! run when those are enabled too
+
+use chip8_core::cpu::CPU;
+use chip8_core::expect::Expectation;
+use chip8_core::headless::{self, Limits, RunEnd};
+use chip8_core::rom_builder::RomBuilder;
+use chip8_core::timing::{FrameBudget, TimingMode};
+
+/// Adds up 1 to 10 into V0, stores it at 0x300, then spins
+fn sum_rom() -> Vec<u8> {
+    RomBuilder::new()
+        .ld(1, 10)
+        .label("loop")
+        .op(0x8014) // ADD V0, V1
+        .op(0x71FF) // ADD V1, -1
+        .op(0x3100) // SE V1, 0
+        .jp("loop")
+        .ld_i("result")
+        .op(0xF055) // LD [I], V0
+        .spin()
+        .label("result")
+        .data(&[0])
+        .build()
+        .unwrap()
+}
+
+fn run(rom: &[u8], seed: u64) -> (CPU, RunEnd) {
+    let mut cpu = CPU::new();
+    cpu.set_seed(seed);
+    cpu.load_rom_bytes(rom).unwrap();
+    let limits = Limits { max_frames: Some(60), exit_on_halt: true, ..Default::default() };
+    let (end, _) = headless::run(&mut cpu, &mut FrameBudget::new(TimingMode::Simple, 100), &limits);
+    (cpu, end)
+}
+
+#[test]
+fn test_runs_rom_to_halt() {
+    let (cpu, end) = run(&sum_rom(), 0);
+    assert_eq!(end, RunEnd::Halted);
+
+    let expectation = Expectation::parse(r#"
+        v = { 0 = 55, 1 = 0 }
+        memory = { "0x210" = 55 }
+    "#).unwrap();
+    assert_eq!(expectation.check(&cpu), []);
+}
+
+#[test]
+fn test_seeded_runs_repeat() {
+    let rom = RomBuilder::new().op(0xC0FF).op(0xC1FF).spin().build().unwrap();
+    let (first, _) = run(&rom, 1234);
+    let (second, _) = run(&rom, 1234);
+    assert_eq!(first.v, second.v);
+}
+
+#[cfg(feature = "recording")]
+#[test]
+fn test_replay_verifies() {
+    use chip8_core::replay::{FrameInput, Recorder, Settings};
+
+    let rom = sum_rom();
+    let mut cpu = CPU::new();
+    cpu.load_rom_bytes(&rom).unwrap();
+    let settings = Settings {
+        quirks: cpu.quirks_name().to_string(),
+        timing: TimingMode::Simple,
+        instructions_per_frame: 10,
+        stack_depth: 16,
+        vip_display: false,
+        hz: None,
+    };
+    let mut recorder = Recorder::new(&cpu, settings);
+    let mut budget = FrameBudget::new(TimingMode::Simple, 10);
+    for _ in 0..5 {
+        headless::run_frame(&mut cpu, &mut budget).unwrap();
+        recorder.record(FrameInput { keys: 0, latched: 0, frames: 1, instructions: 0, timers: 1 });
+    }
+    assert_eq!(recorder.finish(&cpu).verify(&rom).unwrap(), []);
+}
+
+#[cfg(feature = "debugger")]
+#[test]
+fn test_debugger_stops_at_breakpoint() {
+    use chip8_core::debugger::Debugger;
+    use chip8_core::run::{StopCondition, StopReason};
+
+    let mut cpu = CPU::new();
+    cpu.load_rom_bytes(&sum_rom()).unwrap();
+    let mut debugger = Debugger::new();
+    debugger.toggle_breakpoint(0x208);
+    let outcome = debugger.run_until(&mut cpu, StopCondition::PcEquals(0xFFF), 1000);
+    assert_eq!(outcome.reason, StopReason::Breakpoint(0x208));
+}
+
+#[test]
+fn test_draws_score() {
+    // Shows 55 in font digits, via BCD, wherever they land
+    let rom = RomBuilder::new()
+        .ld(0, 55)
+        .ld_i("bcd")
+        .op(0xF033) // LD B, V0
+        .op(0xF265) // LD V0..V2, [I]
+        .ld(4, 20)
+        .ld(5, 12)
+        .op(0xF129) // LD F, V1
+        .draw(4, 5, 5)
+        .add(4, 5)
+        .op(0xF229) // LD F, V2
+        .draw(4, 5, 5)
+        .spin()
+        .label("bcd")
+        .data(&[0, 0, 0])
+        .build()
+        .unwrap();
+    let mut cpu = CPU::new();
+    cpu.load_rom_bytes(&rom).unwrap();
+
+    assert!(headless::run_until_display_matches(&mut cpu, "
+        ####?####
+        #...?#...
+        ####?####
+        ...#?...#
+        ####?####
+    ", 10));
+}
@@ -0,0 +1,594 @@
+use std::path::{Path, PathBuf};
+use iced::futures::channel::mpsc::UnboundedSender;
+use iced::keyboard::{self, key::Named, Key, Location};
+use iced::widget::{button, canvas, column, container, mouse_area, row, space, stack, text};
+use iced::{Element, Length, Padding, Subscription, Task};
+use chip8_core::async_runner::Frame;
+use chip8_core::config::{Config, Palette, QuirkOverrides, CONFIG_PATH};
+use chip8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_core::emulator::{EmulatorEvent, FrameBuffer, InputEvent};
+use chip8_core::quirks::{Preset, Quirks};
+use chip8_core::{rom, savestate, session};
+use chip8_sdl::platform::Audio;
+use chip8_sdl::recent::{RecentRoms, RECENT_PATH};
+use crate::emulation;
+use crate::keys::KeyMap;
+use crate::screen::Screen;
+
+/// Height of the menu bar, which the menus open just below
+pub const BAR_HEIGHT: f32 = 32.0;
+const BAR_BUTTON_WIDTH: f32 = 80.0;
+const MENU_WIDTH: f32 = 200.0;
+
+/// The menus that can be open. Recent and Quirks open beside the menu they belong to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Menu {
+    File,
+    Recent,
+    Machine,
+    Quirks,
+}
+
+impl Menu {
+    fn parent(self) -> Option<Menu> {
+        match self {
+            Menu::Recent => Some(Menu::File),
+            Menu::Quirks => Some(Menu::Machine),
+            Menu::File | Menu::Machine => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    /// The emulation task has started and takes input on this sender
+    Ready(UnboundedSender<InputEvent>),
+    Frame(Box<Frame>),
+    ToggleMenu(Menu),
+    CloseMenu,
+    OpenRom,
+    /// The file dialog closed, with the ROM picked if there was one
+    RomPicked(Option<PathBuf>),
+    OpenRecent(String),
+    Reset,
+    SaveState,
+    LoadState,
+    ToggleVipDisplay,
+    /// Runs the next reset or ROM with this preset
+    SetQuirks(Preset),
+    Keyboard(keyboard::Event),
+}
+
+impl Message {
+    /// Whether this is picked from a menu, which closes it
+    fn is_menu_item(&self) -> bool {
+        matches!(
+            self,
+            Message::CloseMenu | Message::OpenRom | Message::OpenRecent(_) | Message::Reset
+                | Message::SaveState | Message::LoadState | Message::ToggleVipDisplay | Message::SetQuirks(_)
+        )
+    }
+}
+
+/// The ROM being played
+struct Loaded {
+    name: String,
+    hash: u64,
+}
+
+pub struct App {
+    title: String,
+    palette: Palette,
+    min_beep_frames: u8,
+    keymap: KeyMap,
+    audio: Option<Audio>,
+    muted: bool,
+    recent: RecentRoms,
+    state_dir: PathBuf,
+    input: Option<UnboundedSender<InputEvent>>,
+    queued: Vec<InputEvent>, // Until the emulation task is ready for them
+    display: FrameBuffer,
+    sound: bool,
+    rom: Option<Loaded>,
+    menu: Option<Menu>,
+    vip_display: bool,
+    quirks: Preset,
+    overrides: QuirkOverrides, // The config file's, on top of whichever preset is picked
+    keys: u16,
+    status: String,
+}
+
+impl App {
+    pub fn new(config: &Config, recent: RecentRoms, audio: Option<Audio>, state_dir: &Path) -> Self {
+        Self {
+            title: config.display.title.clone(),
+            palette: config.display.palette,
+            min_beep_frames: config.audio.min_beep_frames,
+            keymap: KeyMap::new(&config.keys),
+            audio,
+            muted: config.audio.muted,
+            recent,
+            state_dir: state_dir.to_path_buf(),
+            input: None,
+            // The emulation task's machines start out on the default preset
+            queued: (config.emulation.resolve(None) != Quirks::default())
+                .then(|| InputEvent::SetQuirks(config.emulation.resolve(None)))
+                .into_iter()
+                .collect(),
+            display: [0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            sound: false,
+            rom: None,
+            menu: None,
+            vip_display: false,
+            quirks: config.emulation.quirks,
+            overrides: config.emulation.overrides,
+            keys: 0,
+            status: "Open a ROM from the File menu".to_string(),
+        }
+    }
+
+    /// The app as launched, with the config file's settings and the ROM named on the command line, if any
+    pub fn boot() -> (Self, Task<Message>) {
+        let config = Config::load(CONFIG_PATH).unwrap_or_else(|e| {
+            eprintln!("{}: {}", CONFIG_PATH, e);
+            Config::default()
+        });
+        let audio = sdl2::init()
+            .and_then(|sdl| Audio::new(&sdl, &config.audio))
+            .inspect_err(|e| eprintln!("No sound: {}", e))
+            .ok();
+
+        let mut app = App::new(&config, RecentRoms::load(Path::new(RECENT_PATH)), audio, Path::new(session::STATE_DIR));
+        if let Some(path) = std::env::args().nth(1) {
+            app.open(&path);
+        }
+        (app, Task::none())
+    }
+
+    pub fn title(&self) -> String {
+        match &self.rom {
+            Some(rom) => format!("{} - {}", self.title, rom.name),
+            None => self.title.clone(),
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<Message> {
+        Subscription::batch([
+            Subscription::run_with(self.min_beep_frames, |frames| emulation::worker(*frames)),
+            keyboard::listen().map(Message::Keyboard),
+        ])
+    }
+
+    pub fn update(&mut self, message: Message) -> Task<Message> {
+        if message.is_menu_item() {
+            self.menu = None;
+        }
+
+        match message {
+            Message::Ready(input) => {
+                for event in self.queued.drain(..) {
+                    input.unbounded_send(event).ok();
+                }
+                self.input = Some(input);
+            }
+            Message::Frame(frame) => self.frame(*frame),
+            Message::ToggleMenu(menu) => {
+                self.menu = if self.menu == Some(menu) { menu.parent() } else { Some(menu) };
+            }
+            Message::CloseMenu => {}
+            Message::OpenRom => return Task::perform(pick_rom(), Message::RomPicked),
+            Message::RomPicked(Some(path)) => self.open(&path.to_string_lossy()),
+            Message::RomPicked(None) => {}
+            Message::OpenRecent(path) => self.open(&path),
+            Message::Reset => {
+                if self.rom.is_some() {
+                    self.send(InputEvent::Reset);
+                }
+            }
+            Message::SaveState => match &self.rom {
+                Some(_) => self.send(InputEvent::SaveState),
+                None => self.status = "Open a ROM first".to_string(),
+            },
+            Message::LoadState => self.load_state(),
+            Message::ToggleVipDisplay => {
+                self.vip_display = !self.vip_display;
+                self.send(InputEvent::SetVipDisplay(self.vip_display));
+            }
+            Message::SetQuirks(preset) => {
+                self.quirks = preset;
+                self.send(InputEvent::SetQuirks(self.overrides.apply(preset.quirks())));
+                if self.rom.is_some() {
+                    self.status = format!("{} quirks from the next reset", preset.label());
+                }
+            }
+            Message::Keyboard(event) => self.keyboard(event),
+        }
+        Task::none()
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        let bar = row![
+            bar_button("File", Menu::File),
+            bar_button("Machine", Menu::Machine),
+        ]
+        .height(BAR_HEIGHT);
+        let screen = canvas(Screen { display: &self.display, palette: self.palette })
+            .width(Length::Fill)
+            .height(Length::Fill);
+        let body = column![bar, screen, text(&self.status).size(14)];
+
+        let Some(menu) = self.menu else {
+            return body.into();
+        };
+        let (top, submenu) = match menu.parent() {
+            Some(parent) => (parent, Some(menu)),
+            None => (menu, None),
+        };
+        let left = if top == Menu::File { 0.0 } else { BAR_BUTTON_WIDTH };
+        let mut menus = row![self.menu_view(top)];
+        if let Some(submenu) = submenu {
+            menus = menus.push(self.menu_view(submenu));
+        }
+
+        // Clicking anywhere outside the menus closes them
+        let outside = mouse_area(space().width(Length::Fill).height(Length::Fill)).on_press(Message::CloseMenu);
+        stack![body, outside, container(menus).padding(Padding::ZERO.top(BAR_HEIGHT).left(left))].into()
+    }
+
+    /// The labels in a menu, with what picking each sends; None is greyed out
+    pub fn menu_items(&self, menu: Menu) -> Vec<(String, Option<Message>)> {
+        let loaded = self.rom.is_some();
+        let item = |label: &str, message: Message, enabled: bool| (label.to_string(), enabled.then_some(message));
+        match menu {
+            Menu::File => vec![
+                item("Open ROM...", Message::OpenRom, true),
+                item("Recent >", Message::ToggleMenu(Menu::Recent), true),
+            ],
+            Menu::Recent if self.recent.entries().is_empty() => vec![("No recent ROMs".to_string(), None)],
+            Menu::Recent => self.recent.entries().iter()
+                .map(|path| item(file_name(path), Message::OpenRecent(path.clone()), true))
+                .collect(),
+            Menu::Machine => vec![
+                item("Reset", Message::Reset, loaded),
+                item("Save State", Message::SaveState, loaded),
+                item("Load State", Message::LoadState, loaded),
+                item("Quirks >", Message::ToggleMenu(Menu::Quirks), true),
+            ],
+            Menu::Quirks => Preset::ALL.iter()
+                .map(|preset| item(&check(preset.label(), self.quirks == *preset), Message::SetQuirks(*preset), true))
+                .chain([item(&check("VIP display (memory-mapped)", self.vip_display), Message::ToggleVipDisplay, true)])
+                .collect(),
+        }
+    }
+
+    fn menu_view(&self, menu: Menu) -> Element<'_, Message> {
+        let items = self.menu_items(menu).into_iter().map(|(label, message)| {
+            button(text(label)).width(Length::Fill).style(button::text).on_press_maybe(message).into()
+        });
+        container(column(items)).width(MENU_WIDTH).style(container::bordered_box).into()
+    }
+
+    fn send(&mut self, event: InputEvent) {
+        match &self.input {
+            Some(input) => {
+                input.unbounded_send(event).ok();
+            }
+            None => self.queued.push(event),
+        }
+    }
+
+    /// Starts the ROM at path, which may point into a .zip as the SDL window's do
+    fn open(&mut self, path: &str) {
+        match rom::read(path) {
+            Ok(bytes) => {
+                self.rom = Some(Loaded { name: file_name(path).to_string(), hash: savestate::rom_hash(&bytes) });
+                self.send(InputEvent::Load(bytes));
+                self.status = format!("Playing {}", path);
+                self.recent.add(path);
+                if let Err(e) = self.recent.save() {
+                    eprintln!("Could not save the recent ROMs list: {}", e);
+                }
+            }
+            Err(e) => self.status = format!("Could not open {}: {}", path, e),
+        }
+    }
+
+    fn state_path(&self) -> Option<PathBuf> {
+        self.rom.as_ref().map(|rom| session::state_path(&self.state_dir, rom.hash))
+    }
+
+    fn load_state(&mut self) {
+        let Some(path) = self.state_path() else {
+            return;
+        };
+        match std::fs::read(&path) {
+            Ok(state) => self.send(InputEvent::LoadState(state)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => self.status = "No saved state for this ROM".to_string(),
+            Err(e) => self.status = format!("Could not read {}: {}", path.display(), e),
+        }
+    }
+
+    fn save_state(&mut self, state: &[u8]) {
+        let Some(path) = self.state_path() else {
+            return;
+        };
+        let written = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(&path, state));
+        self.status = match written {
+            Ok(()) => "State saved".to_string(),
+            Err(e) => format!("Could not save the state to {}: {}", path.display(), e),
+        };
+    }
+
+    fn frame(&mut self, frame: Frame) {
+        self.display = frame.display;
+        self.sound = frame.sound;
+        if let Some(audio) = &mut self.audio {
+            audio.set_beeping(frame.sound && !self.muted);
+        }
+
+        for event in frame.events {
+            match event {
+                EmulatorEvent::Reset { error: Some(e) } => self.status = format!("Could not start the ROM: {}", e),
+                EmulatorEvent::Halted { pc, error } => self.status = format!("Halted at {:03X}: {}", pc, error),
+                EmulatorEvent::StateSaved(state) => self.save_state(&state),
+                EmulatorEvent::StateLoaded(Ok(warnings)) if warnings.is_empty() => self.status = "State loaded".to_string(),
+                EmulatorEvent::StateLoaded(Ok(warnings)) => self.status = format!("State loaded: {}", warnings.join("; ")),
+                EmulatorEvent::StateLoaded(Err(e)) => self.status = format!("Could not load the state: {}", e),
+                _ => {}
+            }
+        }
+    }
+
+    fn keyboard(&mut self, event: keyboard::Event) {
+        match event {
+            keyboard::Event::KeyPressed { key: Key::Named(Named::Escape), .. } if self.menu.is_some() => self.menu = None,
+            keyboard::Event::KeyPressed { key, location, repeat: false, .. } => self.set_key(&key, location, true),
+            keyboard::Event::KeyReleased { key, location, .. } => self.set_key(&key, location, false),
+            _ => {}
+        }
+    }
+
+    fn set_key(&mut self, key: &Key, location: Location, down: bool) {
+        let Some(chip8_key) = self.keymap.lookup(key, location) else {
+            return;
+        };
+        let keys = if down { self.keys | 1 << chip8_key } else { self.keys & !(1 << chip8_key) };
+        if keys != self.keys {
+            self.keys = keys;
+            self.send(InputEvent::Keys { down: keys, latched: 0 });
+        }
+    }
+}
+
+fn bar_button(label: &str, menu: Menu) -> Element<'_, Message> {
+    button(text(label)).width(BAR_BUTTON_WIDTH).height(Length::Fill).style(button::text)
+        .on_press(Message::ToggleMenu(menu))
+        .into()
+}
+
+fn check(label: &str, checked: bool) -> String {
+    format!("[{}] {}", if checked { "x" } else { " " }, label)
+}
+
+/// The last part of a ROM path, which for `pack.zip/GAME.ch8` is the ROM inside
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+async fn pick_rom() -> Option<PathBuf> {
+    let mut extensions = rom::ROM_EXTENSIONS.to_vec();
+    extensions.push("zip");
+    let file = rfd::AsyncFileDialog::new()
+        .set_title("Open a CHIP-8 ROM")
+        .add_filter("CHIP-8 ROMs", &extensions)
+        .pick_file()
+        .await?;
+    Some(file.path().to_path_buf())
+}
+
+/// Default window size: the display at ten times, with the menu bar and status line
+pub fn window_size() -> (f32, f32) {
+    let width = DISPLAY_WIDTH as f32 * 10.0;
+    (width, width / 2.0 + BAR_HEIGHT + 24.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced::futures::channel::mpsc::{self, UnboundedReceiver};
+    use chip8_core::EmulatorError;
+    use chip8_core::rom_builder::RomBuilder;
+
+    struct Fixture {
+        app: App,
+        inputs: UnboundedReceiver<InputEvent>,
+        dir: PathBuf,
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.dir).ok();
+        }
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("chip8-iced-{}-{}", name, std::process::id()));
+            std::fs::remove_dir_all(&dir).ok();
+            std::fs::create_dir_all(&dir).unwrap();
+            let recent = RecentRoms::load(&dir.join("recent.txt"));
+            let mut app = App::new(&Config::default(), recent, None, &dir.join("state"));
+            let (sender, inputs) = mpsc::unbounded();
+            let _ = app.update(Message::Ready(sender));
+            Self { app, inputs, dir }
+        }
+
+        fn update(&mut self, message: Message) {
+            let _ = self.app.update(message);
+        }
+
+        /// Everything sent to the emulator since the last call
+        fn sent(&mut self) -> Vec<InputEvent> {
+            std::iter::from_fn(|| self.inputs.try_recv().ok()).collect()
+        }
+
+        fn rom(&self) -> String {
+            let path = self.dir.join("SPIN.ch8");
+            std::fs::write(&path, RomBuilder::new().spin().build().unwrap()).unwrap();
+            path.to_string_lossy().into_owned()
+        }
+    }
+
+    fn frame(events: Vec<EmulatorEvent>) -> Message {
+        Message::Frame(Box::new(Frame { display: [0; DISPLAY_WIDTH * DISPLAY_HEIGHT], sound: false, events }))
+    }
+
+    fn key(c: &str, pressed: bool) -> Message {
+        let key = Key::Character(c.into());
+        let physical_key = keyboard::key::Physical::Unidentified(keyboard::key::NativeCode::Unidentified);
+        let (location, modifiers) = (Location::Standard, keyboard::Modifiers::empty());
+        Message::Keyboard(if pressed {
+            keyboard::Event::KeyPressed {
+                key: key.clone(), modified_key: key, physical_key, location, modifiers, text: None, repeat: false,
+            }
+        } else {
+            keyboard::Event::KeyReleased { key: key.clone(), modified_key: key, physical_key, location, modifiers }
+        })
+    }
+
+    #[test]
+    fn test_menus_open_close_and_nest() {
+        let mut fixture = Fixture::new("menus");
+        fixture.update(Message::ToggleMenu(Menu::Machine));
+        assert_eq!(fixture.app.menu, Some(Menu::Machine));
+        fixture.update(Message::ToggleMenu(Menu::Quirks));
+        assert_eq!(fixture.app.menu, Some(Menu::Quirks));
+        fixture.update(Message::ToggleMenu(Menu::Quirks)); // Back to the menu it's in
+        assert_eq!(fixture.app.menu, Some(Menu::Machine));
+        fixture.update(Message::ToggleMenu(Menu::File));
+        assert_eq!(fixture.app.menu, Some(Menu::File));
+        fixture.update(Message::CloseMenu);
+        assert_eq!(fixture.app.menu, None);
+    }
+
+    #[test]
+    fn test_input_waits_for_the_emulation_task() {
+        let dir = std::env::temp_dir().join(format!("chip8-iced-queue-{}", std::process::id()));
+        let mut app = App::new(&Config::default(), RecentRoms::load(&dir.join("recent.txt")), None, &dir);
+        let _ = app.update(Message::ToggleVipDisplay);
+
+        let (sender, mut inputs) = mpsc::unbounded();
+        let _ = app.update(Message::Ready(sender));
+        assert_eq!(inputs.try_recv().ok(), Some(InputEvent::SetVipDisplay(true)));
+    }
+
+    #[test]
+    fn test_open_a_rom() {
+        let mut fixture = Fixture::new("open");
+        // Reset and the states wait for a ROM
+        assert!(fixture.app.menu_items(Menu::Machine)[..3].iter().all(|(_, message)| message.is_none()));
+        let path = fixture.rom();
+        fixture.update(Message::ToggleMenu(Menu::File));
+        fixture.update(Message::RomPicked(Some(PathBuf::from(&path))));
+
+        assert!(matches!(fixture.sent()[..], [InputEvent::Load(_)]));
+        assert_eq!(fixture.app.title(), "CHIP-8 - SPIN.ch8");
+        assert_eq!(fixture.app.recent.entries(), [path.as_str()]);
+        let recent = fixture.app.menu_items(Menu::Recent);
+        assert_eq!(recent[0].0, "SPIN.ch8");
+        assert!(matches!(&recent[0].1, Some(Message::OpenRecent(p)) if *p == path));
+
+        fixture.update(Message::OpenRecent(fixture.dir.join("missing.ch8").to_string_lossy().into_owned()));
+        assert!(fixture.app.status.starts_with("Could not open"));
+        assert!(fixture.sent().is_empty());
+    }
+
+    #[test]
+    fn test_reset_and_quirks() {
+        let mut fixture = Fixture::new("quirks");
+        fixture.update(Message::Reset); // Nothing to reset yet
+        assert!(fixture.sent().is_empty());
+
+        let path = fixture.rom();
+        fixture.update(Message::OpenRecent(path));
+        fixture.update(Message::ToggleMenu(Menu::Quirks));
+        let labels = |app: &App| app.menu_items(Menu::Quirks).into_iter().map(|(label, _)| label).collect::<Vec<_>>();
+        assert_eq!(labels(&fixture.app), ["[ ] COSMAC VIP", "[x] Modern", "[ ] VIP display (memory-mapped)"]);
+        fixture.update(Message::ToggleVipDisplay);
+        assert_eq!(fixture.app.menu, None);
+        fixture.update(Message::SetQuirks(Preset::Vip));
+        assert_eq!(labels(&fixture.app), ["[x] COSMAC VIP", "[ ] Modern", "[x] VIP display (memory-mapped)"]);
+        assert_eq!(fixture.app.status, "COSMAC VIP quirks from the next reset");
+        fixture.update(Message::Reset);
+
+        let sent = fixture.sent();
+        assert_eq!(sent[1..], [InputEvent::SetVipDisplay(true), InputEvent::SetQuirks(Preset::Vip.quirks()), InputEvent::Reset]);
+    }
+
+    #[test]
+    fn test_quirks_from_the_config_file() {
+        let dir = std::env::temp_dir().join(format!("chip8-iced-config-quirks-{}", std::process::id()));
+        let mut config = Config::default();
+        config.emulation.quirks = Preset::Vip;
+        config.emulation.overrides.jump_with_vx = Some(true);
+        let mut app = App::new(&config, RecentRoms::load(&dir.join("recent.txt")), None, &dir);
+        assert_eq!(app.menu_items(Menu::Quirks)[0].0, "[x] COSMAC VIP");
+
+        let (sender, mut inputs) = mpsc::unbounded();
+        let _ = app.update(Message::Ready(sender));
+        let vip = Quirks { jump_with_vx: true, ..Quirks::vip() };
+        assert_eq!(inputs.try_recv().ok(), Some(InputEvent::SetQuirks(vip)));
+
+        // The override stays on top of a preset picked from the menu
+        let _ = app.update(Message::SetQuirks(Preset::Modern));
+        assert_eq!(inputs.try_recv().ok(), Some(InputEvent::SetQuirks(Quirks { jump_with_vx: true, ..Quirks::modern() })));
+    }
+
+    #[test]
+    fn test_keys_follow_the_bindings() {
+        let mut fixture = Fixture::new("keys");
+        fixture.update(key("w", true)); // 5
+        fixture.update(key("a", true)); // 7
+        fixture.update(key("w", true)); // Already down
+        fixture.update(key("p", true)); // Not bound
+        fixture.update(key("w", false));
+        assert_eq!(fixture.sent(), [
+            InputEvent::Keys { down: 1 << 5, latched: 0 },
+            InputEvent::Keys { down: 1 << 5 | 1 << 7, latched: 0 },
+            InputEvent::Keys { down: 1 << 7, latched: 0 },
+        ]);
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        let mut fixture = Fixture::new("state");
+        let path = fixture.rom();
+        fixture.update(Message::OpenRecent(path));
+        fixture.update(Message::LoadState);
+        assert_eq!(fixture.app.status, "No saved state for this ROM");
+
+        fixture.update(Message::SaveState);
+        fixture.update(frame(vec![EmulatorEvent::StateSaved(vec![1, 2, 3])]));
+        assert_eq!(fixture.app.status, "State saved");
+        fixture.update(Message::LoadState);
+        assert_eq!(fixture.sent()[1..], [InputEvent::SaveState, InputEvent::LoadState(vec![1, 2, 3])]);
+
+        fixture.update(frame(vec![EmulatorEvent::StateLoaded(Err("bad magic".to_string()))]));
+        assert_eq!(fixture.app.status, "Could not load the state: bad magic");
+    }
+
+    #[test]
+    fn test_frames_update_the_display_and_status() {
+        let mut fixture = Fixture::new("frames");
+        let mut display = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        display[3] = 1;
+        fixture.update(Message::Frame(Box::new(Frame {
+            display,
+            sound: true,
+            events: vec![EmulatorEvent::Halted { pc: 0x2A4, error: EmulatorError::UnknownOpcode { opcode: 0x0123, pc: 0x2A4 } }],
+        })));
+        assert_eq!(fixture.app.display, display);
+        assert!(fixture.app.sound);
+        assert_eq!(fixture.app.status, "Halted at 2A4: Unknown opcode 0123");
+    }
+}
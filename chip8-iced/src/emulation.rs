@@ -0,0 +1,52 @@
+use iced::futures::channel::mpsc::{self, UnboundedReceiver};
+use iced::futures::{future, SinkExt, Stream};
+use chip8_core::async_runner;
+use chip8_core::config::Config;
+use chip8_core::emulator::{Emulator, InputEvent, MachineSetup};
+use chip8_core::timing::TimingMode;
+use crate::app::Message;
+
+/// Instructions per frame when the timing mode is Simple, as in the SDL window
+pub const INSTRUCTIONS_PER_FRAME: u32 = 10;
+/// Frames the UI may fall behind by before the emulator waits for it to catch up
+const FRAME_QUEUE: usize = 4;
+
+/// The emulator's background task, run as a subscription. It first hands the app a sender for
+/// its input events, then sends a Message::Frame every frame for as long as the app runs.
+/// Beeps shorter than min_beep_frames are held on, as config.audio asks
+pub fn worker(min_beep_frames: u8) -> impl Stream<Item = Message> {
+    iced::stream::channel(FRAME_QUEUE, async move |mut output: mpsc::Sender<Message>| {
+        let (inputs, received) = mpsc::unbounded();
+        if output.send(Message::Ready(inputs)).await.is_err() {
+            return;
+        }
+
+        // Memory hooks needn't be Send, so neither is the emulator: it gets a thread and a
+        // runtime of its own instead of running on iced's executor
+        let thread = std::thread::Builder::new()
+            .name("emulation".to_string())
+            .spawn(move || run(output, received, min_beep_frames));
+        if let Err(e) = thread {
+            eprintln!("Could not start the emulator: {}", e);
+        }
+    })
+}
+
+/// Runs until the app stops listening for frames or drops its input sender
+fn run(output: mpsc::Sender<Message>, inputs: UnboundedReceiver<InputEvent>, min_beep_frames: u8) {
+    let runtime = match tokio::runtime::Builder::new_current_thread().enable_time().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Could not start the emulator: {}", e);
+            return;
+        }
+    };
+
+    // The beep length is all the emulator reads from its config; the app keeps the rest
+    let mut config = Config::default();
+    config.audio.min_beep_frames = min_beep_frames;
+    let mut emulator = Emulator::new(config, MachineSetup::default(), TimingMode::Simple, INSTRUCTIONS_PER_FRAME);
+    emulator.handle_event(InputEvent::Pause); // Nothing to run until a ROM is opened
+    let frames = output.with(|frame| future::ready(Ok::<_, mpsc::SendError>(Message::Frame(Box::new(frame)))));
+    runtime.block_on(async_runner::run(emulator, frames, inputs)).ok();
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use iced::keyboard::key::Named;
+use iced::keyboard::{Key, Location};
+use chip8_core::config::KeyBindings;
+
+/// The config's key bindings, looked up by iced key. Bindings are SDL key names, so the same
+/// chip8.toml drives both frontends
+pub struct KeyMap {
+    keys: HashMap<String, usize>, // Lowercase, as SDL ignores case in key names
+}
+
+impl KeyMap {
+    pub fn new(bindings: &KeyBindings) -> Self {
+        let lowercase = |(name, key): (&String, &usize)| (name.to_ascii_lowercase(), *key);
+        let mut keys: HashMap<String, usize> = bindings.player2.iter().map(lowercase).collect();
+        // Player 1 takes precedence, as in the SDL window
+        keys.extend(bindings.primary.iter().map(lowercase));
+        Self { keys }
+    }
+
+    /// The CHIP-8 key bound to an iced key, if any
+    pub fn lookup(&self, key: &Key, location: Location) -> Option<usize> {
+        self.keys.get(&sdl_name(key, location)?.to_ascii_lowercase()).copied()
+    }
+}
+
+/// SDL's name for an iced key. Only keys worth binding are named
+pub fn sdl_name(key: &Key, location: Location) -> Option<String> {
+    match key {
+        Key::Character(c) if location == Location::Numpad => Some(format!("Keypad {}", c.to_uppercase())),
+        Key::Character(c) => Some(c.to_uppercase()),
+        Key::Named(named) => named_key(*named, location).map(str::to_string),
+        Key::Unidentified => None,
+    }
+}
+
+fn named_key(named: Named, location: Location) -> Option<&'static str> {
+    let right = location == Location::Right;
+    Some(match named {
+        Named::ArrowUp => "Up",
+        Named::ArrowDown => "Down",
+        Named::ArrowLeft => "Left",
+        Named::ArrowRight => "Right",
+        Named::Space => "Space",
+        Named::Enter if location == Location::Numpad => "Keypad Enter",
+        Named::Enter => "Return",
+        Named::Tab => "Tab",
+        Named::Backspace => "Backspace",
+        Named::Delete => "Delete",
+        Named::Insert => "Insert",
+        Named::Home => "Home",
+        Named::End => "End",
+        Named::PageUp => "PageUp",
+        Named::PageDown => "PageDown",
+        Named::Shift => if right { "Right Shift" } else { "Left Shift" },
+        Named::Control => if right { "Right Ctrl" } else { "Left Ctrl" },
+        Named::Alt => if right { "Right Alt" } else { "Left Alt" },
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::config::Config;
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn test_sdl_names() {
+        assert_eq!(sdl_name(&character("w"), Location::Standard).as_deref(), Some("W"));
+        assert_eq!(sdl_name(&character("8"), Location::Numpad).as_deref(), Some("Keypad 8"));
+        assert_eq!(sdl_name(&Key::Named(Named::ArrowUp), Location::Standard).as_deref(), Some("Up"));
+        assert_eq!(sdl_name(&Key::Named(Named::Shift), Location::Right).as_deref(), Some("Right Shift"));
+        assert_eq!(sdl_name(&Key::Named(Named::F1), Location::Standard), None);
+        assert_eq!(sdl_name(&Key::Unidentified, Location::Standard), None);
+    }
+
+    #[test]
+    fn test_lookup_through_the_bindings() {
+        let config = Config::parse("[keys]\nW = 0x5\n[keys.player2]\nup = 0x5\n\"Keypad 8\" = 0x8\n").unwrap();
+        let keys = KeyMap::new(&config.keys);
+        assert_eq!(keys.lookup(&character("w"), Location::Standard), Some(0x5));
+        assert_eq!(keys.lookup(&Key::Named(Named::ArrowUp), Location::Standard), Some(0x5));
+        assert_eq!(keys.lookup(&character("8"), Location::Numpad), Some(0x8));
+        assert_eq!(keys.lookup(&character("8"), Location::Standard), None);
+        assert_eq!(keys.lookup(&character("q"), Location::Standard), None);
+    }
+}
@@ -0,0 +1,17 @@
+//! A desktop frontend for `chip8-core` built with iced: the game under a menu bar, with the emulator
+//! running in a background task. Keys, colours and sound come from the same chip8.toml as the SDL window.
+
+mod app;
+mod emulation;
+mod keys;
+mod screen;
+
+use app::App;
+
+fn main() -> iced::Result {
+    iced::application(App::boot, App::update, App::view)
+        .title(App::title)
+        .subscription(App::subscription)
+        .window_size(app::window_size())
+        .run()
+}
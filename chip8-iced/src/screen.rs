@@ -0,0 +1,59 @@
+use iced::widget::canvas::{self, Geometry, Program};
+use iced::{mouse, Color, Point, Rectangle, Renderer, Size, Theme};
+use chip8_core::config::{Palette, Rgb};
+use chip8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_core::emulator::FrameBuffer;
+use chip8_sdl::platform::display::row_runs;
+
+/// The game display, scaled to fit the widget with the background colour around it
+pub struct Screen<'a> {
+    pub display: &'a FrameBuffer,
+    pub palette: Palette,
+}
+
+/// Where the picture's top-left corner goes within bounds of this size, and how big a CHIP-8
+/// pixel is. Pixels are kept square
+pub fn fit(size: Size) -> (Point, f32) {
+    let pixel = (size.width / DISPLAY_WIDTH as f32).min(size.height / DISPLAY_HEIGHT as f32);
+    let origin = Point::new(
+        (size.width - pixel * DISPLAY_WIDTH as f32) / 2.0,
+        (size.height - pixel * DISPLAY_HEIGHT as f32) / 2.0,
+    );
+    (origin, pixel)
+}
+
+fn color(Rgb(r, g, b): Rgb) -> Color {
+    Color::from_rgb8(r, g, b)
+}
+
+impl<Message> Program<Message> for Screen<'_> {
+    type State = ();
+
+    fn draw(&self, _state: &(), renderer: &Renderer, _theme: &Theme, bounds: Rectangle, _cursor: mouse::Cursor) -> Vec<Geometry> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), color(self.palette.background));
+
+        let (origin, pixel) = fit(bounds.size());
+        let foreground = color(self.palette.foreground);
+        for (y, row) in self.display.chunks(DISPLAY_WIDTH).enumerate() {
+            // One rectangle per run of lit pixels, as in the SDL window
+            for (x, len) in row_runs(row) {
+                let top_left = Point::new(origin.x + x as f32 * pixel, origin.y + y as f32 * pixel);
+                frame.fill_rectangle(top_left, Size::new(len as f32 * pixel, pixel), foreground);
+            }
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fit_letterboxes() {
+        assert_eq!(fit(Size::new(640.0, 320.0)), (Point::ORIGIN, 10.0));
+        assert_eq!(fit(Size::new(640.0, 400.0)), (Point::new(0.0, 40.0), 10.0)); // Bars above and below
+        assert_eq!(fit(Size::new(800.0, 320.0)), (Point::new(80.0, 0.0), 10.0)); // And at the sides
+    }
+}
@@ -0,0 +1,351 @@
+use std::time::Duration;
+use clap::{Parser, Subcommand};
+use chip8_core::config::Rgb;
+use chip8_core::constants::{MAX_STACK_DEPTH, MEMORY_SIZE, STACK_SIZE};
+use chip8_core::headless::{Limits, EXIT_CODES_HELP};
+use chip8_core::pacing::VsyncMode;
+use chip8_core::quirks::Preset;
+use chip8_core::rom;
+use chip8_core::session::ResumePolicy;
+use chip8_core::timing::TimingMode;
+
+/// Instructions per frame with simple timing, unless --ipf or a playlist entry says otherwise
+pub const INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+/// A CHIP-8 emulator
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// ROM to play; without one, a menu of the ROMs in ./roms and recently played ones is offered
+    #[arg(value_name = "ROM", value_parser = parse_rom_path, conflicts_with = "playlist")]
+    pub rom: Option<String>,
+
+    /// Window pixels per CHIP-8 pixel [default: display.scale in chip8.toml]
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub scale: Option<u32>,
+
+    /// Instructions run per frame with simple timing
+    #[arg(long, value_name = "N", default_value_t = INSTRUCTIONS_PER_FRAME,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    pub ipf: u32,
+
+    /// Colour of lit pixels, as RRGGBB [default: display.palette.foreground in chip8.toml]
+    #[arg(long, value_name = "RRGGBB")]
+    pub fg: Option<Rgb>,
+
+    /// Colour of unlit pixels, as RRGGBB [default: display.palette.background in chip8.toml]
+    #[arg(long, value_name = "RRGGBB")]
+    pub bg: Option<Rgb>,
+
+    /// Host a two-player session, accepting a remote player's keypad on this port
+    #[arg(long, value_name = "PORT", conflicts_with = "connect")]
+    pub host: Option<u16>,
+
+    /// Join a session hosted at ADDR (e.g. 192.168.1.10:7800) as the remote player
+    #[arg(long, value_name = "ADDR")]
+    pub connect: Option<String>,
+
+    /// Serve the web debugger's WebSocket endpoint on this localhost port (see web-debugger/)
+    #[cfg(feature = "remote-debug")]
+    #[arg(long, value_name = "PORT", conflicts_with = "connect")]
+    pub remote_debug: Option<u16>,
+
+    /// Play the ROMs listed in a playlist file instead of picking one (Page Up/Page Down switch ROMs)
+    #[arg(long, value_name = "FILE", conflicts_with = "connect")]
+    pub playlist: Option<String>,
+
+    /// Resume this ROM's auto-saved game without asking (and auto-save it on quit)
+    #[arg(long, conflicts_with = "no_resume")]
+    pub resume: bool,
+
+    /// Start fresh without offering to resume an auto-saved game
+    #[arg(long)]
+    pub no_resume: bool,
+
+    /// Maximum nesting of subroutine calls, for homebrew that needs more than the original 16
+    #[arg(long, value_name = "N", default_value_t = STACK_SIZE as u8,
+          value_parser = clap::value_parser!(u8).range(1..=MAX_STACK_DEPTH as i64))]
+    pub stack_depth: u8,
+
+    /// Keep the display in memory at 0xF00-0xFFF like the COSMAC VIP, for ROMs that read or write it directly
+    #[arg(long)]
+    pub vip_display: bool,
+
+    /// How the opcodes that interpreters disagree on behave: the original COSMAC VIP's, or modern ones'.
+    /// Overrides emulation.quirks in chip8.toml [default: modern]
+    #[arg(long, value_enum)]
+    pub quirks: Option<Preset>,
+
+    /// Log unknown opcodes and carry on past them, rather than stopping the ROM
+    #[arg(long)]
+    pub skip_unknown_opcodes: bool,
+
+    /// Warn about FX65 reads of memory and arithmetic on registers the ROM never set (for ROM authors)
+    #[arg(long)]
+    pub detect_uninit: bool,
+
+    /// Keep the last 256 instructions and print them if the ROM stops on an error
+    #[arg(long)]
+    pub trace: bool,
+
+    /// Seed for CXNN's random numbers, in decimal or 0x hex, to repeat a run [default: random, printed at startup]
+    #[arg(long, value_name = "SEED", value_parser = parse_seed)]
+    pub seed: Option<u64>,
+
+    /// Pause when the instruction at this hex address is next (P pauses, Space steps, F8 continues); may repeat
+    #[cfg(feature = "debugger")]
+    #[arg(long, value_name = "ADDR", value_parser = parse_address)]
+    pub breakpoint: Vec<u16>,
+
+    /// Tapping a key latches it down until it is tapped again (Backspace releases all)
+    #[arg(long)]
+    pub sticky_keys: bool,
+
+    /// Keymap file of Name=key lines for player 1, in place of the [keys] in chip8.toml [default: keymap.cfg, if there is one]
+    #[arg(long, value_name = "FILE")]
+    pub keymap: Option<String>,
+
+    /// How frames are synchronized with the monitor [default: on]
+    #[arg(long, value_enum)]
+    pub vsync: Option<VsyncMode>,
+
+    /// Instruction timing: a flat 10 instructions per frame, or COSMAC VIP cycle costs
+    #[arg(long, value_enum, default_value = "simple")]
+    pub timing: TimingMode,
+
+    /// Run instructions at this clock rate by wall time, rather than a number per frame (700 suits most ROMs)
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..),
+          conflicts_with_all = ["ipf", "timing"])]
+    pub hz: Option<u32>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print the SHA-1, CRC32 and size of ROM files
+    Hash {
+        #[arg(required = true, value_name = "ROM")]
+        roms: Vec<String>,
+    },
+    /// Print a ROM's instructions, one per line with its address and opcode, without running it
+    #[cfg(feature = "debugger")]
+    Disassemble {
+        #[arg(value_name = "ROM")]
+        rom: String,
+    },
+    /// Run a ROM without a window or pacing until a limit is reached, for scripts and golden files
+    #[command(after_help = EXIT_CODES_HELP)]
+    Headless {
+        #[arg(value_name = "ROM")]
+        rom: String,
+
+        /// Stop after N frames, 60 per emulated second [default: 600 unless another limit is given]
+        #[arg(long, value_name = "N")]
+        max_frames: Option<u64>,
+
+        /// Stop once the ROM halts: jumps to itself or waits for a key, which never comes headless
+        #[arg(long)]
+        exit_on_halt: bool,
+
+        /// Stop after this much real time, as a safety net
+        #[arg(long, value_name = "S", value_parser = parse_timeout)]
+        timeout_secs: Option<Duration>,
+
+        /// Write the final display to PATH as a plain-text PBM image
+        #[arg(long, value_name = "PATH")]
+        dump_pbm: Option<String>,
+
+        /// Check the final registers and memory against a TOML file of expected values, failing on any mismatch
+        #[arg(long, value_name = "FILE")]
+        verify: Option<String>,
+    },
+    /// Run every opcode through generated test programs on each variant and print a pass/fail table,
+    /// exiting with 1 if any fail
+    Selftest,
+    /// Record a session to a .c8replay file, or check that one still plays out the same
+    #[cfg(feature = "recording")]
+    Replay {
+        #[command(subcommand)]
+        action: ReplayAction,
+    },
+}
+
+#[cfg(feature = "recording")]
+#[derive(Subcommand, Debug)]
+pub enum ReplayAction {
+    /// Play ROM in the window, saving the session to FILE on quit (F4 restarts the recording)
+    Record {
+        #[arg(value_name = "FILE")]
+        file: String,
+        #[arg(long, value_name = "ROM")]
+        rom: String,
+    },
+    /// Play FILE back against ROM without a window and report whether it ends as recorded
+    Verify {
+        #[arg(value_name = "FILE")]
+        file: String,
+        #[arg(long, value_name = "ROM")]
+        rom: String,
+    },
+}
+
+fn parse_seed(text: &str) -> Result<u64, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+        None => text.replace('_', "").parse(),
+    };
+    parsed.map_err(|e| format!("{} is not a seed: {}", text, e))
+}
+
+/// Seconds as a Duration, refusing negative, infinite and out-of-range values rather than
+/// panicking on them
+fn parse_timeout(text: &str) -> Result<Duration, String> {
+    let secs: f64 = text.parse().map_err(|e| format!("{} is not a number of seconds: {}", text, e))?;
+    Duration::try_from_secs_f64(secs).map_err(|e| format!("{} is not a timeout: {}", text, e))
+}
+
+/// A ROM given on the command line, which has to exist: a file, or a `pack.zip/GAME.ch8` entry
+/// in an archive that does
+fn parse_rom_path(text: &str) -> Result<String, String> {
+    if rom::exists(text) {
+        Ok(text.to_string())
+    } else {
+        Err(format!("no ROM file at {}", text))
+    }
+}
+
+/// A memory address in hex, with or without the 0x
+#[cfg_attr(not(feature = "debugger"), allow(dead_code))]
+fn parse_address(text: &str) -> Result<u16, String> {
+    let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+    match u16::from_str_radix(hex, 16) {
+        Ok(addr) if (addr as usize) < MEMORY_SIZE => Ok(addr),
+        Ok(_) => Err(format!("{} is outside memory", text)),
+        Err(e) => Err(format!("{} is not a hex address: {}", text, e)),
+    }
+}
+
+/// Frames a headless run stops after when nothing else would stop it
+const DEFAULT_MAX_FRAMES: u64 = 600;
+
+impl Command {
+    /// The limits a headless command asks for
+    pub fn limits(&self) -> Option<Limits> {
+        let Command::Headless { max_frames, exit_on_halt, timeout_secs, .. } = self else {
+            return None;
+        };
+        let max_frames = match (max_frames, exit_on_halt, timeout_secs) {
+            (None, false, None) => Some(DEFAULT_MAX_FRAMES),
+            _ => *max_frames,
+        };
+        Some(Limits { max_frames, exit_on_halt: *exit_on_halt, timeout: *timeout_secs })
+    }
+}
+
+impl Args {
+    pub fn resume_policy(&self) -> ResumePolicy {
+        if self.resume {
+            ResumePolicy::Always
+        } else if self.no_resume {
+            ResumePolicy::Never
+        } else {
+            ResumePolicy::Ask
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_seed() {
+        assert_eq!(parse_seed("0xDEADBEEF"), Ok(0xDEADBEEF));
+        assert_eq!(parse_seed("0xdead_beef"), Ok(0xDEADBEEF));
+        assert_eq!(parse_seed("1234"), Ok(1234));
+        assert!(parse_seed("0xZZ").is_err());
+        assert!(parse_seed("-1").is_err());
+    }
+
+    #[test]
+    fn test_rom_and_display_flags() {
+        let rom = std::env::temp_dir().join(format!("chip8_cli_test_{}.ch8", std::process::id()));
+        std::fs::write(&rom, [0x12, 0x00]).unwrap();
+        let rom = rom.to_string_lossy().into_owned();
+
+        let args = Args::try_parse_from(["chip8_emulator", &rom, "--scale", "8", "--ipf", "30", "--fg", "FFFFFF", "--bg", "#102030"]).unwrap();
+        assert_eq!(args.rom.as_deref(), Some(rom.as_str()));
+        assert_eq!((args.scale, args.ipf), (Some(8), 30));
+        assert_eq!((args.fg, args.bg), (Some(Rgb(255, 255, 255)), Some(Rgb(0x10, 0x20, 0x30))));
+
+        let args = Args::try_parse_from(["chip8_emulator"]).unwrap();
+        assert_eq!((args.rom, args.scale, args.ipf, args.hz), (None, None, INSTRUCTIONS_PER_FRAME, None));
+        assert_eq!(Args::try_parse_from(["chip8_emulator", "--hz", "1000"]).unwrap().hz, Some(1000));
+        assert!(matches!(Args::try_parse_from(["chip8_emulator", "hash", &rom]).unwrap().command, Some(Command::Hash { .. })));
+        std::fs::remove_file(&rom).ok();
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_zip_entries_are_rom_paths() {
+        // Only the archive has to exist here; reading the entry is left to loading the ROM
+        let pack = std::env::temp_dir().join(format!("chip8_cli_test_{}.zip", std::process::id()));
+        std::fs::write(&pack, []).unwrap();
+        let entry = format!("{}/GAME.ch8", pack.to_string_lossy());
+
+        let args = Args::try_parse_from(["chip8_emulator", &entry]).unwrap();
+        assert_eq!(args.rom.as_deref(), Some(entry.as_str()));
+        std::fs::remove_file(&pack).ok();
+        assert!(Args::try_parse_from(["chip8_emulator", &entry]).is_err());
+    }
+
+    #[test]
+    fn test_bad_arguments_are_rejected() {
+        let fails = |args: &[&str]| Args::try_parse_from(["chip8_emulator"].iter().chain(args)).is_err();
+        assert!(fails(&["no/such/rom.ch8"]));
+        assert!(fails(&[&std::env::temp_dir().to_string_lossy()]));
+        assert!(fails(&["--scale", "0"]));
+        assert!(fails(&["--scale", "big"]));
+        assert!(fails(&["--ipf", "0"]));
+        assert!(fails(&["--hz", "0"]));
+        assert!(fails(&["--hz", "700", "--ipf", "20"]));
+        assert!(fails(&["--fg", "12345"]));
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("0x2A4"), Ok(0x2A4));
+        assert_eq!(parse_address("2a4"), Ok(0x2A4));
+        assert!(parse_address("0x1000").is_err());
+        assert!(parse_address("PC").is_err());
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("0"), Ok(Duration::ZERO));
+        assert_eq!(parse_timeout("0.25"), Ok(Duration::from_millis(250)));
+        assert!(parse_timeout("-1").is_err());
+        assert!(parse_timeout("inf").is_err());
+        assert!(parse_timeout("NaN").is_err());
+        assert!(parse_timeout("1e20").is_err());
+        assert!(parse_timeout("soon").is_err());
+    }
+
+    #[test]
+    fn test_headless_limits() {
+        let limits = |args: &[&str]| {
+            let args = Args::try_parse_from(["chip8_emulator", "headless", "rom.ch8"].iter().chain(args)).unwrap();
+            args.command.unwrap().limits().unwrap()
+        };
+        assert_eq!(limits(&[]).max_frames, Some(DEFAULT_MAX_FRAMES));
+        assert_eq!(limits(&["--exit-on-halt"]).max_frames, None);
+        assert_eq!(limits(&["--timeout-secs", "1.5"]).timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(limits(&["--max-frames", "7", "--exit-on-halt"]), Limits {
+            max_frames: Some(7),
+            exit_on_halt: true,
+            timeout: None,
+        });
+    }
+}
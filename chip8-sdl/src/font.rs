@@ -0,0 +1,128 @@
+/// Glyphs are 3 pixels wide and 5 tall; each takes 4 columns with the gap after it
+pub const GLYPH_WIDTH: usize = 3;
+pub const GLYPH_HEIGHT: usize = 5;
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+/// Lines are a glyph tall with a row between them
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+/// Rows top to bottom, the leftmost pixel in bit 2. Letters are upper case only
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 54] = [
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b110, 0b001, 0b010, 0b100, 0b111]),
+    ('3', [0b110, 0b001, 0b010, 0b001, 0b110]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b110, 0b001, 0b110]),
+    ('6', [0b011, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b010, 0b010, 0b010]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b110]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('%', [0b101, 0b001, 0b010, 0b100, 0b101]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('(', [0b010, 0b100, 0b100, 0b100, 0b010]),
+    (')', [0b010, 0b001, 0b001, 0b001, 0b010]),
+    ('\'', [0b010, 0b010, 0b000, 0b000, 0b000]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    ('?', [0b110, 0b001, 0b010, 0b000, 0b010]),
+    ('<', [0b001, 0b010, 0b100, 0b010, 0b001]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100]),
+    ('[', [0b110, 0b100, 0b100, 0b100, 0b110]),
+    (']', [0b011, 0b001, 0b001, 0b001, 0b011]),
+];
+
+/// The glyph for c, with lower case drawn as upper case and anything unknown as ?
+pub fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter()
+        .find(|(glyph_char, _)| *glyph_char == c)
+        .or_else(|| GLYPHS.iter().find(|(glyph_char, _)| *glyph_char == '?'))
+        .map(|(_, rows)| *rows)
+        .unwrap_or_default()
+}
+
+/// Lit pixels of one line of text, as (x, y) in font pixels from its top left
+pub fn text_pixels(text: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    text.chars().enumerate().flat_map(|(index, c)| {
+        let rows = glyph(c);
+        (0..GLYPH_HEIGHT).flat_map(move |y| {
+            (0..GLYPH_WIDTH)
+                .filter(move |x| rows[y] & (1 << (GLYPH_WIDTH - 1 - x)) != 0)
+                .map(move |x| (index * ADVANCE + x, y))
+        })
+    })
+}
+
+/// The largest whole pixel size at which every line fits in width x height, at least 1
+pub fn fit_size(lines: &[String], width: u32, height: u32) -> u32 {
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0).max(1) * ADVANCE;
+    let rows = lines.len().max(1) * LINE_HEIGHT;
+    (width / columns as u32).min(height / rows as u32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glyph_lookup() {
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_eq!(glyph('~'), glyph('?'));
+        assert_eq!(glyph(' '), [0; GLYPH_HEIGHT]);
+        assert!(GLYPHS.iter().all(|(_, rows)| rows.iter().all(|row| *row < 1 << GLYPH_WIDTH)));
+    }
+
+    #[test]
+    fn test_text_pixels() {
+        // The bar of an L, then the top of a T one advance along
+        let pixels = text_pixels("LT").collect::<Vec<_>>();
+        assert!(pixels.contains(&(0, 0)));
+        assert!(pixels.contains(&(2, 4)));
+        assert!(!pixels.contains(&(1, 0)));
+        assert!(pixels.contains(&(ADVANCE, 0)) && pixels.contains(&(ADVANCE + 2, 0)));
+        assert!(pixels.iter().all(|(x, y)| *x < 2 * ADVANCE - 1 && *y < GLYPH_HEIGHT));
+        assert_eq!(pixels.len(), 7 + 7);
+    }
+
+    #[test]
+    fn test_fit_size() {
+        let lines = vec!["ABCDEFGHIJ".to_string(), "AB".to_string()];
+        // 10 characters are 40 font pixels wide, 2 lines 12 tall
+        assert_eq!(fit_size(&lines, 160, 480), 4);
+        assert_eq!(fit_size(&lines, 640, 36), 3);
+        assert_eq!(fit_size(&lines, 10, 10), 1);
+        assert_eq!(fit_size(&[], 64, 64), 10);
+    }
+}
@@ -0,0 +1,126 @@
+use egui::{Key, Modifiers, MouseWheelUnit, PointerButton, Pos2, TouchPhase, Vec2};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use crate::platform::hidpi;
+
+/// The egui modifiers held in an SDL key modifier state
+pub fn modifiers(keymod: Mod) -> Modifiers {
+    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    Modifiers {
+        alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        ctrl,
+        shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+        mac_cmd: false,
+        command: ctrl,
+    }
+}
+
+/// The egui event for an SDL event, if egui has a use for it. Mouse events carry no modifier
+/// state, so they are given the modifiers from the last key event, and their positions in
+/// pixels become points
+pub fn translate(event: &Event, held: Modifiers, pixels_per_point: f32) -> Option<egui::Event> {
+    let pos = |x: i32, y: i32| {
+        let (x, y) = hidpi::pixels_to_window(x as f32, y as f32, pixels_per_point);
+        Pos2::new(x, y)
+    };
+    match event {
+        Event::KeyDown { keycode: Some(kc), keymod, repeat, .. } => key(*kc, true, *repeat, modifiers(*keymod)),
+        Event::KeyUp { keycode: Some(kc), keymod, .. } => key(*kc, false, false, modifiers(*keymod)),
+        Event::TextInput { text, .. } => Some(egui::Event::Text(text.clone())),
+        Event::MouseMotion { x, y, .. } => Some(egui::Event::PointerMoved(pos(*x, *y))),
+        Event::MouseButtonDown { mouse_btn, x, y, .. } => pointer(*mouse_btn, pos(*x, *y), true, held),
+        Event::MouseButtonUp { mouse_btn, x, y, .. } => pointer(*mouse_btn, pos(*x, *y), false, held),
+        Event::MouseWheel { precise_x, precise_y, .. } => Some(egui::Event::MouseWheel {
+            unit: MouseWheelUnit::Line,
+            delta: Vec2::new(*precise_x, *precise_y),
+            phase: TouchPhase::Move,
+            modifiers: held,
+        }),
+        _ => None,
+    }
+}
+
+fn key(kc: Keycode, pressed: bool, repeat: bool, modifiers: Modifiers) -> Option<egui::Event> {
+    let key = egui_key(kc)?;
+    Some(egui::Event::Key { key, physical_key: None, pressed, repeat, modifiers })
+}
+
+fn pointer(button: MouseButton, pos: Pos2, pressed: bool, modifiers: Modifiers) -> Option<egui::Event> {
+    let button = match button {
+        MouseButton::Left => PointerButton::Primary,
+        MouseButton::Right => PointerButton::Secondary,
+        MouseButton::Middle => PointerButton::Middle,
+        _ => return None,
+    };
+    Some(egui::Event::PointerButton { pos, button, pressed, modifiers })
+}
+
+/// The keys the panel's widgets respond to; typed characters arrive as text instead
+fn egui_key(kc: Keycode) -> Option<Key> {
+    Some(match kc {
+        Keycode::Left => Key::ArrowLeft,
+        Keycode::Right => Key::ArrowRight,
+        Keycode::Up => Key::ArrowUp,
+        Keycode::Down => Key::ArrowDown,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Delete => Key::Delete,
+        Keycode::Return | Keycode::KpEnter => Key::Enter,
+        Keycode::Tab => Key::Tab,
+        Keycode::Escape => Key::Escape,
+        Keycode::Home => Key::Home,
+        Keycode::End => Key::End,
+        Keycode::PageUp => Key::PageUp,
+        Keycode::PageDown => Key::PageDown,
+        Keycode::A => Key::A,
+        Keycode::C => Key::C,
+        Keycode::V => Key::V,
+        Keycode::X => Key::X,
+        Keycode::Z => Key::Z,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_down(kc: Keycode, keymod: Mod) -> Event {
+        Event::KeyDown { timestamp: 0, window_id: 1, keycode: Some(kc), scancode: None, keymod, repeat: false }
+    }
+
+    #[test]
+    fn test_keys_carry_their_modifiers() {
+        let event = translate(&key_down(Keycode::A, Mod::LCTRLMOD), Modifiers::NONE, 1.0);
+        assert_eq!(event, Some(egui::Event::Key {
+            key: Key::A,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers: Modifiers::COMMAND | Modifiers::CTRL,
+        }));
+        assert_eq!(translate(&key_down(Keycode::F7, Mod::NOMOD), Modifiers::NONE, 1.0), None);
+    }
+
+    #[test]
+    fn test_mouse_events() {
+        let click = Event::MouseButtonDown {
+            timestamp: 0, window_id: 1, which: 0, mouse_btn: MouseButton::Left, clicks: 1, x: 12, y: 34,
+        };
+        assert_eq!(translate(&click, Modifiers::SHIFT, 1.0), Some(egui::Event::PointerButton {
+            pos: Pos2::new(12.0, 34.0),
+            button: PointerButton::Primary,
+            pressed: true,
+            modifiers: Modifiers::SHIFT,
+        }));
+
+        // At 2x the click lands on the point at half its pixel position
+        assert!(matches!(
+            translate(&click, Modifiers::NONE, 2.0),
+            Some(egui::Event::PointerButton { pos, .. }) if pos == Pos2::new(6.0, 17.0)
+        ));
+
+        let text = Event::TextInput { timestamp: 0, window_id: 1, text: "3f".to_string() };
+        assert_eq!(translate(&text, Modifiers::NONE, 1.0), Some(egui::Event::Text("3f".to_string())));
+    }
+}
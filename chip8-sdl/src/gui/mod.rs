@@ -0,0 +1,125 @@
+//! The in-window debug panel (F12), drawn with egui over the game.
+//!
+//! While hidden it takes no input and runs no UI code. While shown, it sees every SDL event
+//! first and keeps the keys typed into its fields from reaching the keypad.
+
+mod input;
+mod painter;
+mod panel;
+
+pub use panel::{apply, parse_hex, DebugPanel, PanelAction};
+
+use std::time::Instant;
+use egui::epaint::textures::TexturesDelta;
+use egui::epaint::ClippedPrimitive;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use chip8_core::emulator::Emulator;
+use crate::platform::hidpi;
+use painter::Painter;
+
+pub struct Gui {
+    ctx: egui::Context,
+    painter: Painter,
+    panel: DebugPanel,
+    visible: bool,
+    events: Vec<egui::Event>, // Since the last update
+    modifiers: egui::Modifiers,
+    pixels_per_point: f32, // The display's pixel ratio, so the panel keeps its size on HiDPI screens
+    started: Instant,
+    primitives: Vec<ClippedPrimitive>, // From the last update, for the next paint
+    textures: TexturesDelta,
+}
+
+impl Gui {
+    /// A hidden panel for a display with this many drawable pixels per window coordinate
+    pub fn new(pixels_per_point: f32) -> Self {
+        Self {
+            ctx: egui::Context::default(),
+            painter: Painter::new(),
+            panel: DebugPanel::new(),
+            visible: false,
+            events: Vec::new(),
+            modifiers: egui::Modifiers::NONE,
+            pixels_per_point,
+            started: Instant::now(),
+            primitives: Vec::new(),
+            textures: TexturesDelta::default(),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Greys out the panel's speed controls, e.g. while a replay is being recorded
+    pub fn lock_speed(&mut self, locked: bool) {
+        self.panel.lock_speed(locked);
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.events.clear();
+        self.primitives.clear();
+    }
+
+    /// Passes an SDL event to the panel, which F12 shows and hides. Returns true if the panel
+    /// took the event, so the game shouldn't see it
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        if let Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } = event {
+            self.toggle();
+            return true;
+        }
+        if !self.visible {
+            return false;
+        }
+        if let Event::KeyDown { keymod, .. } | Event::KeyUp { keymod, .. } = event {
+            self.modifiers = input::modifiers(*keymod);
+        }
+        let Some(translated) = input::translate(event, self.modifiers, self.pixels_per_point) else {
+            return false;
+        };
+        self.events.push(translated);
+
+        match event {
+            Event::KeyDown { .. } | Event::KeyUp { .. } | Event::TextInput { .. } => self.ctx.egui_wants_keyboard_input(),
+            _ => self.ctx.egui_wants_pointer_input(),
+        }
+    }
+
+    /// Lays out the panel for this frame over a screen of the given size in pixels, then applies
+    /// whatever was changed in it
+    pub fn update(&mut self, emulator: &mut Emulator, screen: (u32, u32)) {
+        if !self.visible {
+            return;
+        }
+
+        let size = hidpi::pixels_to_window(screen.0 as f32, screen.1 as f32, self.pixels_per_point);
+        let mut raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(size.0, size.1))),
+            time: Some(self.started.elapsed().as_secs_f64()),
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+        raw_input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(self.pixels_per_point);
+        let mut actions = Vec::new();
+        let output = self.ctx.run_ui(raw_input, |ui| actions = self.panel.show(ui.ctx(), emulator));
+
+        self.primitives = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        self.textures.append(output.textures_delta);
+        for action in actions {
+            if let Err(e) = apply(action, emulator) {
+                eprintln!("Debug panel: {}", e);
+            }
+        }
+    }
+
+    /// Draws the panel as laid out by the last update
+    pub fn paint(&mut self, canvas: &mut Canvas<Window>) {
+        if self.visible {
+            self.painter.paint(canvas, &self.primitives, std::mem::take(&mut self.textures), self.pixels_per_point);
+        }
+    }
+}
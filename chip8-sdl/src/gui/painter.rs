@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use egui::epaint::textures::TexturesDelta;
+use egui::epaint::{ClippedPrimitive, ImageDelta, Primitive};
+use egui::{Color32, TextureId};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{FPoint, Rect};
+use sdl2::render::{BlendMode, Canvas, Texture, Vertex, VertexIndices};
+use sdl2::video::Window;
+use crate::platform::hidpi;
+
+/// Draws egui's triangles with the SDL renderer, so the panel needs no OpenGL context of its own.
+/// egui lays out in points, which are scaled to the canvas' pixels
+#[derive(Default)]
+pub struct Painter {
+    textures: HashMap<TextureId, Texture>,
+    vertices: Vec<Vertex>, // Reused every mesh to avoid allocating
+}
+
+impl Painter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn paint(&mut self, canvas: &mut Canvas<Window>, primitives: &[ClippedPrimitive], mut textures: TexturesDelta, pixels_per_point: f32) {
+        for (id, deltas) in std::mem::take(&mut textures.set) {
+            for delta in deltas {
+                self.set_texture(canvas, id, &delta);
+            }
+        }
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        for ClippedPrimitive { clip_rect, primitive } in primitives {
+            let Primitive::Mesh(mesh) = primitive else {
+                continue; // Paint callbacks are for custom GPU rendering, which the panel doesn't use
+            };
+            let (x, y) = hidpi::window_to_pixels(clip_rect.min.x, clip_rect.min.y, pixels_per_point);
+            let (width, height) = hidpi::window_to_pixels(clip_rect.width().max(0.0), clip_rect.height().max(0.0), pixels_per_point);
+            let clip = Rect::new(x as i32, y as i32, width as u32, height as u32);
+            canvas.set_clip_rect(clip);
+
+            self.vertices.clear();
+            self.vertices.extend(mesh.vertices.iter().map(|vertex| {
+                let (x, y) = hidpi::window_to_pixels(vertex.pos.x, vertex.pos.y, pixels_per_point);
+                Vertex {
+                    position: FPoint::new(x, y),
+                    color: color(vertex.color),
+                    tex_coord: FPoint::new(vertex.uv.x, vertex.uv.y),
+                }
+            }));
+            let texture = self.textures.get(&mesh.texture_id);
+            canvas.render_geometry(&self.vertices, texture, VertexIndices::U32(&mesh.indices)).ok();
+        }
+        canvas.set_clip_rect(None);
+        canvas.set_blend_mode(BlendMode::None);
+
+        for id in std::mem::take(&mut textures.free) {
+            if let Some(texture) = self.textures.remove(&id) {
+                // SAFETY: the texture's renderer is the canvas, which is still alive
+                unsafe { texture.destroy() };
+            }
+        }
+    }
+
+    fn set_texture(&mut self, canvas: &mut Canvas<Window>, id: TextureId, delta: &ImageDelta) {
+        let egui::ImageData::Color(image) = &delta.image;
+        let [width, height] = image.size;
+        let pixels: Vec<u8> = image.pixels.iter().flat_map(|pixel| pixel.to_srgba_unmultiplied()).collect();
+
+        let (texture, rect) = match delta.pos {
+            Some([x, y]) => match self.textures.get_mut(&id) {
+                Some(texture) => (texture, Some(Rect::new(x as i32, y as i32, width as u32, height as u32))),
+                None => return,
+            },
+            None => {
+                let texture = match canvas.create_texture_static(PixelFormatEnum::RGBA32, width as u32, height as u32) {
+                    Ok(texture) => texture,
+                    Err(e) => {
+                        eprintln!("Debug panel: could not create a texture: {}", e);
+                        return;
+                    }
+                };
+                if let Some(old) = self.textures.insert(id, texture) {
+                    // SAFETY: as in paint
+                    unsafe { old.destroy() };
+                }
+                (self.textures.get_mut(&id).expect("just inserted"), None)
+            }
+        };
+
+        texture.set_blend_mode(BlendMode::Blend);
+        if let Err(e) = texture.update(rect, &pixels, width * 4) {
+            eprintln!("Debug panel: could not update a texture: {}", e);
+        }
+    }
+}
+
+/// SDL blends with straight alpha, while egui's colours are premultiplied
+fn color(color: Color32) -> Color {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    Color::RGBA(r, g, b, a)
+}
@@ -0,0 +1,267 @@
+use egui::{CollapsingHeader, ComboBox, DragValue, Grid, ScrollArea, TextEdit, Ui};
+use chip8_core::constants::{MEMORY_SIZE, REGISTERS_COUNT};
+use chip8_core::debugger::{self, Command, Register};
+use chip8_core::emulator::{Emulator, InputEvent};
+use chip8_core::quirks::Preset;
+use chip8_core::timing::TimingMode;
+
+/// Bytes per row in the memory viewer
+const MEMORY_ROW: usize = 16;
+
+/// Something the panel asks for, carried out once the frame's UI is done
+#[derive(Debug, Clone, PartialEq)]
+pub enum PanelAction {
+    Debug(Command),
+    Emulator(InputEvent),
+}
+
+/// Carries out a panel action: debugger commands go through debugger::apply, like the web
+/// debugger's, and speed and quirk changes through the emulator
+pub fn apply(action: PanelAction, emulator: &mut Emulator) -> Result<(), String> {
+    match action {
+        PanelAction::Debug(command) => debugger::apply(command, &mut emulator.cpu, &mut emulator.debugger),
+        PanelAction::Emulator(event) => {
+            emulator.handle_event(event);
+            Ok(())
+        }
+    }
+}
+
+/// Parses an address or value typed in hex, with or without 0x
+pub fn parse_hex(text: &str) -> Option<u16> {
+    let text = text.trim();
+    let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+/// The registers, breakpoints, memory and speed sections, with what has been typed into them
+#[derive(Debug, Default)]
+pub struct DebugPanel {
+    new_breakpoint: String,
+    goto: String,
+    scroll_to: Option<usize>, // Memory row to bring into view on the next frame
+    speed_locked: bool,
+}
+
+impl DebugPanel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lock_speed(&mut self, locked: bool) {
+        self.speed_locked = locked;
+    }
+
+    /// Lays the panel out over the whole context; nothing changes until the returned actions are applied
+    pub fn show(&mut self, ctx: &egui::Context, emulator: &Emulator) -> Vec<PanelAction> {
+        let mut actions = Vec::new();
+        egui::Window::new("Debugger")
+            .default_width(300.0)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                self.controls(ui, emulator, &mut actions);
+                CollapsingHeader::new("Registers").default_open(true).show(ui, |ui| registers(ui, emulator, &mut actions));
+                CollapsingHeader::new("Breakpoints").show(ui, |ui| self.breakpoints(ui, emulator, &mut actions));
+                CollapsingHeader::new("Memory").show(ui, |ui| self.memory(ui, emulator));
+                CollapsingHeader::new("Speed and quirks").show(ui, |ui| speed(ui, emulator, self.speed_locked, &mut actions));
+            });
+        actions
+    }
+
+    fn controls(&self, ui: &mut Ui, emulator: &Emulator, actions: &mut Vec<PanelAction>) {
+        ui.horizontal(|ui| {
+            if emulator.debugger.is_paused() {
+                if ui.button("Continue").clicked() {
+                    actions.push(PanelAction::Debug(Command::Continue));
+                }
+            } else if ui.button("Pause").clicked() {
+                actions.push(PanelAction::Debug(Command::Pause));
+            }
+            if ui.button("Step").clicked() {
+                actions.push(PanelAction::Debug(Command::Step(1)));
+            }
+            ui.label(if emulator.debugger.is_paused() { "Paused" } else { "Running" });
+        });
+    }
+
+    fn breakpoints(&mut self, ui: &mut Ui, emulator: &Emulator, actions: &mut Vec<PanelAction>) {
+        for addr in emulator.debugger.breakpoints() {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{:03X}", addr));
+                if ui.small_button("Remove").clicked() {
+                    actions.push(PanelAction::Debug(Command::SetBreakpoint { addr, set: false }));
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.new_breakpoint).hint_text("Address").desired_width(60.0));
+            let addr = parse_hex(&self.new_breakpoint).filter(|addr| (*addr as usize) < MEMORY_SIZE);
+            if ui.add_enabled(addr.is_some(), egui::Button::new("Add")).clicked() {
+                actions.extend(addr.map(|addr| PanelAction::Debug(Command::SetBreakpoint { addr, set: true })));
+                self.new_breakpoint.clear();
+            }
+        });
+    }
+
+    fn memory(&mut self, ui: &mut Ui, emulator: &Emulator) {
+        ui.horizontal(|ui| {
+            ui.add(TextEdit::singleline(&mut self.goto).hint_text("Address").desired_width(60.0));
+            if ui.button("Go to").clicked() {
+                self.scroll_to = parse_hex(&self.goto).map(|addr| addr as usize / MEMORY_ROW);
+            }
+        });
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let mut area = ScrollArea::vertical().id_salt("memory").max_height(row_height * 16.0);
+        if let Some(row) = self.scroll_to.take() {
+            area = area.vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+        }
+        area.show_rows(ui, row_height, MEMORY_SIZE / MEMORY_ROW, |ui, rows| {
+            for row in rows {
+                let start = row * MEMORY_ROW;
+                let bytes = &emulator.cpu.memory[start..start + MEMORY_ROW];
+                let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                ui.monospace(format!("{:03X}  {}", start, hex.join(" ")));
+            }
+        });
+    }
+}
+
+fn registers(ui: &mut Ui, emulator: &Emulator, actions: &mut Vec<PanelAction>) {
+    let cpu = &emulator.cpu;
+    let mut edit = |ui: &mut Ui, name: String, register: Register, value: u16, max: u16, digits: usize| {
+        ui.label(name);
+        let mut edited = value;
+        let response = ui.add(DragValue::new(&mut edited).range(0..=max).hexadecimal(digits, false, true));
+        if response.changed() && edited != value {
+            actions.push(PanelAction::Debug(Command::SetRegister { register, value: edited }));
+        }
+    };
+
+    Grid::new("registers").num_columns(8).show(ui, |ui| {
+        for x in 0..REGISTERS_COUNT {
+            edit(ui, format!("V{:X}", x), Register::V(x as u8), cpu.v[x] as u16, 0xFF, 2);
+            if x % 4 == 3 {
+                ui.end_row();
+            }
+        }
+        edit(ui, "PC".to_string(), Register::Pc, cpu.pc, MEMORY_SIZE as u16 - 1, 3);
+        edit(ui, "I".to_string(), Register::I, cpu.i, MEMORY_SIZE as u16 - 1, 3);
+        edit(ui, "DT".to_string(), Register::DelayTimer, cpu.delay_timer as u16, 0xFF, 2);
+        edit(ui, "ST".to_string(), Register::SoundTimer, cpu.sound_timer as u16, 0xFF, 2);
+        ui.end_row();
+    });
+}
+
+fn speed(ui: &mut Ui, emulator: &Emulator, locked: bool, actions: &mut Vec<PanelAction>) {
+    let (timing, instructions_per_frame) = emulator.speed();
+    let (mut new_timing, mut new_instructions) = (timing, instructions_per_frame);
+    ui.add_enabled_ui(!locked, |ui| {
+        ComboBox::from_label("Timing")
+            .selected_text(format!("{:?}", timing))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut new_timing, TimingMode::Simple, "Simple");
+                ui.selectable_value(&mut new_timing, TimingMode::Cycles, "Cycles");
+            });
+        ui.add_enabled(
+            timing == TimingMode::Simple,
+            DragValue::new(&mut new_instructions).range(1..=1000).suffix(" instructions per frame"),
+        );
+    }).response.on_disabled_hover_text("Fixed while recording a replay");
+    if (new_timing, new_instructions) != (timing, instructions_per_frame) {
+        actions.push(PanelAction::Emulator(InputEvent::SetSpeed {
+            timing: new_timing,
+            instructions_per_frame: new_instructions,
+        }));
+    }
+
+    // Like the settings menu's, a new preset waits for the next reset
+    let quirks = emulator.setup().quirks;
+    let mut preset = Preset::from_name(quirks.name());
+    ComboBox::from_label("Quirks (next reset)")
+        .selected_text(preset.map_or("Custom", Preset::label))
+        .show_ui(ui, |ui| {
+            for choice in Preset::ALL {
+                ui.selectable_value(&mut preset, Some(choice), choice.label());
+            }
+        });
+    if let Some(preset) = preset.filter(|preset| preset.quirks() != quirks) {
+        actions.push(PanelAction::Emulator(InputEvent::SetQuirks(preset.quirks())));
+    }
+
+    let mut vip_display = emulator.cpu.mapped_display();
+    if ui.checkbox(&mut vip_display, "VIP display (memory-mapped from 0xF00)").changed() {
+        actions.push(PanelAction::Emulator(InputEvent::SetVipDisplay(vip_display)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::config::Config;
+    use chip8_core::emulator::{MachineSetup, TIMER_INTERVAL};
+    use chip8_core::rom_builder::RomBuilder;
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("2A0"), Some(0x2A0));
+        assert_eq!(parse_hex(" 0x2a0 "), Some(0x2A0));
+        assert_eq!(parse_hex("0X10"), Some(0x10));
+        assert_eq!(parse_hex("zz"), None);
+        assert_eq!(parse_hex(""), None);
+    }
+
+    #[test]
+    fn test_pause_edit_and_resume() {
+        // V3 counts up while running
+        let rom = RomBuilder::new().label("loop").add(3, 1).jp("loop").build().unwrap();
+        let mut emulator = Emulator::new(Config::default(), MachineSetup::default(), TimingMode::Simple, 10);
+        emulator.load_bytes(rom).unwrap();
+        emulator.update(TIMER_INTERVAL);
+
+        apply(PanelAction::Debug(Command::Pause), &mut emulator).unwrap();
+        emulator.update(TIMER_INTERVAL);
+        let paused_at = emulator.cpu.v[3];
+        emulator.update(TIMER_INTERVAL);
+        assert_eq!(emulator.cpu.v[3], paused_at);
+
+        let edit = Command::SetRegister { register: Register::V(3), value: 0x80 };
+        apply(PanelAction::Debug(edit), &mut emulator).unwrap();
+        apply(PanelAction::Debug(Command::Continue), &mut emulator).unwrap();
+        emulator.update(TIMER_INTERVAL);
+        assert_eq!(emulator.cpu.v[3], 0x85); // Half of the frame's ten instructions are adds
+    }
+
+    #[test]
+    fn test_speed_and_quirk_changes_take_effect() {
+        let mut emulator = Emulator::new(Config::default(), MachineSetup::default(), TimingMode::Simple, 10);
+        let faster = InputEvent::SetSpeed { timing: TimingMode::Simple, instructions_per_frame: 30 };
+        apply(PanelAction::Emulator(faster), &mut emulator).unwrap();
+        apply(PanelAction::Emulator(InputEvent::SetVipDisplay(true)), &mut emulator).unwrap();
+        apply(PanelAction::Emulator(InputEvent::SetQuirks(Preset::Vip.quirks())), &mut emulator).unwrap();
+
+        assert_eq!(emulator.speed(), (TimingMode::Simple, 30));
+        assert!(emulator.cpu.mapped_display());
+        assert_eq!(emulator.setup().quirks, Preset::Vip.quirks());
+    }
+
+    #[test]
+    fn test_rejected_edits_are_errors() {
+        let mut emulator = Emulator::new(Config::default(), MachineSetup::default(), TimingMode::Simple, 10);
+        let edit = Command::SetRegister { register: Register::V(0), value: 0x100 };
+        assert!(apply(PanelAction::Debug(edit), &mut emulator).is_err());
+    }
+
+    #[test]
+    fn test_panel_lays_out_without_actions() {
+        let emulator = Emulator::new(Config::default(), MachineSetup::default(), TimingMode::Simple, 10);
+        let mut panel = DebugPanel::new();
+        let ctx = egui::Context::default();
+        let mut actions = Vec::new();
+        for _ in 0..2 {
+            let output = ctx.run_ui(egui::RawInput::default(), |ui| actions = panel.show(ui.ctx(), &emulator));
+            output.drop_without_applying_deltas();
+        }
+        assert!(actions.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use chip8_core::config::KeyBindings;
+use chip8_core::constants::KEYPAD_LAYOUT;
+
+/// Emulator hotkeys, as listed by the F1 overlay
+pub const HOTKEYS: &[(&str, &str)] = &[
+    ("F1", "Show or hide this help"),
+    ("F2", "Toggle the pixel grid"),
+    ("F3", "Print render stats"),
+    ("F4", "Reset the ROM"),
+    ("F5", "Save state"),
+    ("F6", "Mute or unmute the beeper"),
+    ("F9", "Load state"),
+    ("F10", "Settings (pauses the game)"),
+    ("Esc", "ROM library (pauses the game)"),
+    ("PgUp/PgDn", "Playlist back/forward"),
+    ("+/-", "Speed up or slow down (0.5x, 1x, 2x)"),
+    #[cfg(feature = "debugger")]
+    ("P", "Pause and print the registers"),
+    #[cfg(feature = "debugger")]
+    ("Space", "Step one instruction (while paused)"),
+    #[cfg(feature = "debugger")]
+    ("F8", "Continue (while paused)"),
+    ("Backspace", "Release sticky keys"),
+    #[cfg(feature = "dialog")]
+    ("Ctrl+O", "Open a ROM"),
+    #[cfg(feature = "gui")]
+    ("F12", "Show or hide the debug panel"),
+];
+
+/// The CHIP-8 keypad beside the physical keys playing each key, one grid row per line.
+/// A key with several bindings shows the shortest name; an unbound one shows `-`
+pub fn keypad_diagram(bindings: &KeyBindings) -> Vec<String> {
+    let mut lines = grid_lines("Keypad", "Keys", &bindings.primary);
+    if !bindings.player2.is_empty() {
+        let player2 = grid_lines("", "Player 2", &bindings.player2);
+        for (line, extra) in lines.iter_mut().zip(player2) {
+            line.push_str(&extra);
+        }
+    }
+    lines.iter().map(|line| line.trim_end().to_string()).collect()
+}
+
+/// The keypad diagram followed by the hotkeys, for the F1 overlay
+pub fn help_lines(bindings: &KeyBindings) -> Vec<String> {
+    let mut lines = keypad_diagram(bindings);
+    lines.push(String::new());
+
+    let width = HOTKEYS.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    lines.extend(HOTKEYS.iter().map(|(key, action)| format!("{:<width$}  {}", key, action)));
+    lines
+}
+
+/// A heading line then the keypad and bound names side by side, padded so further grids line up
+fn grid_lines(keypad_heading: &str, keys_heading: &str, names: &HashMap<String, usize>) -> Vec<String> {
+    let name_for = |key: usize| {
+        names.iter()
+            .filter(|(_, bound)| **bound == key)
+            .map(|(name, _)| name.as_str())
+            .min_by_key(|name| (name.len(), *name))
+            .unwrap_or("-")
+    };
+    let cell = KEYPAD_LAYOUT.iter().flatten().map(|key| name_for(*key).len()).max().unwrap_or(1);
+
+    let keypad_width = if keypad_heading.is_empty() { 0 } else { 4 * 2 - 1 + 4 };
+    let keys_width = (4 * (cell + 1) - 1).max(keys_heading.len()) + 4;
+
+    let mut lines = vec![format!("{:<keypad_width$}{:<keys_width$}", keypad_heading, keys_heading)];
+    for row in KEYPAD_LAYOUT {
+        let keypad = row.iter().map(|key| format!("{:X}", key)).collect::<Vec<_>>().join(" ");
+        let keys = row.iter().map(|key| format!("{:<cell$}", name_for(*key))).collect::<Vec<_>>().join(" ");
+        let keypad = if keypad_heading.is_empty() { String::new() } else { keypad };
+        lines.push(format!("{:<keypad_width$}{:<keys_width$}", keypad, keys));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_diagram() {
+        assert_eq!(keypad_diagram(&KeyBindings::default()), [
+            "Keypad     Keys",
+            "1 2 3 C    1 2 3 4",
+            "4 5 6 D    Q W E R",
+            "7 8 9 E    A S D F",
+            "A 0 B F    Z X C V",
+        ]);
+    }
+
+    #[test]
+    fn test_rebinds_are_shown() {
+        let mut bindings = KeyBindings::default();
+        bindings.primary.remove("X");
+        bindings.primary.insert("Space".to_string(), 0x0);
+        bindings.primary.insert("Up".to_string(), 0x5);
+        bindings.primary.remove("V");
+
+        let diagram = keypad_diagram(&bindings);
+        // W and Up both play 5: the shorter name wins. Nothing plays F any more
+        assert_eq!(diagram[2], "4 5 6 D    Q     W     E     R");
+        assert_eq!(diagram[4], "A 0 B F    Z     Space C     -");
+    }
+
+    #[test]
+    fn test_player2_grid() {
+        let mut bindings = KeyBindings::default();
+        bindings.player2.insert("Kp8".to_string(), 0x2);
+
+        let diagram = keypad_diagram(&bindings);
+        assert_eq!(diagram[0], "Keypad     Keys       Player 2");
+        assert_eq!(diagram[1], "1 2 3 C    1 2 3 4    -   Kp8 -   -");
+        assert_eq!(diagram[2], "4 5 6 D    Q W E R    -   -   -   -");
+    }
+
+    #[test]
+    fn test_help_lists_hotkeys() {
+        let lines = help_lines(&KeyBindings::default());
+        assert_eq!(lines.len(), 5 + 1 + HOTKEYS.len());
+        assert_eq!(lines[6], "F1         Show or hide this help");
+    }
+}
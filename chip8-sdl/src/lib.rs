@@ -0,0 +1,15 @@
+//! The SDL frontend for `chip8-core`: the window, keyboard, gamepad and beeper, the command line,
+//! the ROM picker and library. The `chip8_emulator` binary is built from these.
+
+pub mod cli;
+pub mod font;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod help;
+pub mod library;
+pub mod picker;
+pub mod platform;
+pub mod recent;
+pub mod resume_prompt;
+pub mod settings;
+pub mod signals;
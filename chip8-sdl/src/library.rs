@@ -0,0 +1,265 @@
+//! The ROM library: the picker's choices drawn in the window with the overlay font, for picking a
+//! ROM at startup and switching to another during a game. The selected ROM's size and checksum,
+//! and whether it has been played, show under the list, and the screen it was left on beside it
+use chip8_core::rom::{self, RomInfo};
+use chip8_core::savestate::rom_hash;
+use chip8_core::thumbnails::{Thumbnail, ThumbnailCache};
+use crate::picker::Choice;
+use crate::settings::MenuKey;
+
+/// Entries shown at once; the list scrolls to keep the selected one in view
+pub const VISIBLE_ENTRIES: usize = 8;
+/// Longer labels keep their end, so one long path doesn't shrink the whole menu
+pub const MAX_LABEL: usize = 28;
+
+/// What a key press in the library asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum LibraryAction {
+    None,
+    Open(String),
+    Browse,
+    Settings,
+    /// Back to the game, or quit if there is none
+    Close,
+}
+
+/// What shows beside the list for the selected entry
+#[derive(Debug, Clone, PartialEq)]
+pub enum Preview {
+    /// The screen exit_rom left the ROM on, in the palette it was played with
+    Thumbnail(Thumbnail),
+    /// An empty box: the ROM hasn't been played, can't be read, or the entry isn't a ROM
+    Placeholder,
+}
+
+pub struct Library {
+    choices: Vec<Choice>, // Never empty: picker::menu always ends with Settings…
+    roms_dir: String,
+    thumbnails: ThumbnailCache,
+    in_game: bool,
+    cursor: usize,
+    top: usize, // The first entry shown
+    details: String, // About the selected entry, read when it is selected
+    preview: Preview,
+}
+
+impl Library {
+    /// The library on choices, with last (the ROM played last) selected if it is listed.
+    /// in_game says whether Esc goes back to a game or quits
+    pub fn new(choices: Vec<Choice>, roms_dir: &str, thumbnails: ThumbnailCache, last: Option<&str>, in_game: bool) -> Self {
+        let cursor = last
+            .and_then(|last| choices.iter().position(|choice| matches!(choice, Choice::Recent(path) | Choice::Rom(path) if path == last)))
+            .unwrap_or(0);
+        let mut library = Self { choices, roms_dir: roms_dir.to_string(), thumbnails, in_game, cursor: 0, top: 0, details: String::new(), preview: Preview::Placeholder };
+        library.select(cursor);
+        library
+    }
+
+    pub fn selected(&self) -> &Choice {
+        &self.choices[self.cursor]
+    }
+
+    /// The picture drawn beside lines() for the selected entry
+    pub fn preview(&self) -> &Preview {
+        &self.preview
+    }
+
+    /// Up and Down move through the list, wrapping at either end, Enter takes the selection and Esc closes
+    pub fn press(&mut self, key: MenuKey) -> LibraryAction {
+        let entries = self.choices.len();
+        match key {
+            MenuKey::Up => self.select((self.cursor + entries - 1) % entries),
+            MenuKey::Down => self.select((self.cursor + 1) % entries),
+            MenuKey::Select => return match self.selected() {
+                Choice::Recent(path) | Choice::Rom(path) => LibraryAction::Open(path.clone()),
+                Choice::Browse => LibraryAction::Browse,
+                Choice::Settings => LibraryAction::Settings,
+            },
+            MenuKey::Close => return LibraryAction::Close,
+            MenuKey::Left | MenuKey::Right | MenuKey::NextCategory | MenuKey::PreviousCategory => {}
+        }
+        LibraryAction::None
+    }
+
+    /// The library as text: the entries in view with the selected one marked, its details, and the keys
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec!["ROMs".to_string(), String::new()];
+        for (index, choice) in self.choices.iter().enumerate().skip(self.top).take(VISIBLE_ENTRIES) {
+            let marker = if index == self.cursor { '>' } else { ' ' };
+            lines.push(format!("{} {}", marker, truncate(&choice.label(&self.roms_dir).replace('…', "..."))));
+        }
+        lines.push(String::new());
+        lines.push(self.details.clone());
+        lines.push(String::new());
+        lines.push("Up/Down: choose  Enter: open".to_string());
+        lines.push(if self.in_game { "Esc: back to the game" } else { "Esc: quit" }.to_string());
+        lines
+    }
+
+    fn select(&mut self, cursor: usize) {
+        self.cursor = cursor;
+        self.top = self.top.min(cursor).max((cursor + 1).saturating_sub(VISIBLE_ENTRIES));
+        (self.details, self.preview) = match self.selected() {
+            Choice::Recent(path) | Choice::Rom(path) => details(path, &self.thumbnails),
+            Choice::Browse => ("Open a ROM from anywhere".to_string(), Preview::Placeholder),
+            Choice::Settings => ("Change the settings".to_string(), Preview::Placeholder),
+        };
+    }
+}
+
+/// A ROM's size and CRC32, and whether it has been played: exit_rom leaves a thumbnail of every
+/// game, which becomes its preview
+fn details(path: &str, thumbnails: &ThumbnailCache) -> (String, Preview) {
+    match rom::read(path) {
+        Ok(bytes) => {
+            let info = RomInfo::new(&bytes);
+            let thumbnail = thumbnails.load(rom_hash(&bytes));
+            let played = if thumbnail.is_some() { "  played" } else { "" };
+            let preview = thumbnail.map_or(Preview::Placeholder, Preview::Thumbnail);
+            (format!("{} bytes  CRC32 {:08x}{}", info.size, info.crc32, played), preview)
+        }
+        Err(_) => ("Can't read this ROM".to_string(), Preview::Placeholder),
+    }
+}
+
+fn truncate(label: &str) -> String {
+    let chars = label.chars().count();
+    if chars <= MAX_LABEL {
+        return label.to_string();
+    }
+    let end = label.chars().skip(chars - (MAX_LABEL - 3)).collect::<String>();
+    format!("...{}", end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use chip8_core::config::Palette;
+    use chip8_core::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+    use chip8_core::config::Rgb;
+    use chip8_core::thumbnails::MAX_CACHE_BYTES;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip8-library-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn roms(count: usize) -> Vec<Choice> {
+        let mut choices = (0..count).map(|n| Choice::Rom(format!("./roms/GAME{}.ch8", n))).collect::<Vec<_>>();
+        choices.push(Choice::Settings);
+        choices
+    }
+
+    fn open(choices: Vec<Choice>, last: Option<&str>) -> Library {
+        Library::new(choices, "./roms", ThumbnailCache::new(Path::new("/nonexistent"), MAX_CACHE_BYTES), last, true)
+    }
+
+    fn shown(library: &Library) -> Vec<String> {
+        library.lines().into_iter().skip(2).take_while(|line| !line.is_empty()).collect()
+    }
+
+    #[test]
+    fn test_last_rom_is_preselected() {
+        let library = open(roms(3), Some("./roms/GAME2.ch8"));
+        assert_eq!(library.selected(), &Choice::Rom("./roms/GAME2.ch8".to_string()));
+
+        let library = open(roms(3), Some("./roms/DELETED.ch8"));
+        assert_eq!(library.selected(), &Choice::Rom("./roms/GAME0.ch8".to_string()));
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut library = open(roms(2), None);
+        assert_eq!(library.press(MenuKey::Up), LibraryAction::None);
+        assert_eq!(library.selected(), &Choice::Settings);
+        assert_eq!(library.press(MenuKey::Select), LibraryAction::Settings);
+
+        library.press(MenuKey::Down);
+        library.press(MenuKey::Right);
+        assert_eq!(library.press(MenuKey::Select), LibraryAction::Open("./roms/GAME0.ch8".to_string()));
+        assert_eq!(library.press(MenuKey::Close), LibraryAction::Close);
+    }
+
+    #[test]
+    fn test_list_scrolls_with_the_selection() {
+        let mut library = open(roms(20), None);
+        assert_eq!(shown(&library).len(), VISIBLE_ENTRIES);
+        assert_eq!(shown(&library)[0], "> GAME0.ch8");
+
+        for _ in 0..VISIBLE_ENTRIES {
+            library.press(MenuKey::Down);
+        }
+        let lines = shown(&library);
+        assert_eq!((lines[0].as_str(), lines[VISIBLE_ENTRIES - 1].as_str()), ("  GAME1.ch8", "> GAME8.ch8"));
+
+        // Going back up only scrolls once the selection reaches the top
+        library.press(MenuKey::Up);
+        assert_eq!(shown(&library)[0], "  GAME1.ch8");
+
+        // Wrapping to the end shows the last page
+        let mut library = open(roms(20), None);
+        library.press(MenuKey::Up);
+        assert_eq!(shown(&library).last().map(String::as_str), Some("> Settings..."));
+    }
+
+    #[test]
+    fn test_details_and_long_labels() {
+        let dir = temp_dir("details");
+        let rom = dir.join("A_VERY_LONG_NAME_FOR_A_GAME.ch8");
+        std::fs::write(&rom, [0x12, 0x00, 0x00]).unwrap();
+        let rom = rom.to_string_lossy().into_owned();
+        let thumbnails = ThumbnailCache::new(&dir.join("thumbnails"), MAX_CACHE_BYTES);
+
+        let choices = vec![Choice::Recent(rom.clone()), Choice::Rom("./roms/GONE.ch8".to_string()), Choice::Settings];
+        let mut library = Library::new(choices.clone(), "./roms", thumbnails, None, false);
+        let lines = library.lines();
+        assert!(lines[2].starts_with("> ...") && lines[2].ends_with("_GAME.ch8 (recent)"), "{}", lines[2]);
+        assert_eq!(lines[2].chars().count(), 2 + MAX_LABEL);
+        assert!(lines.contains(&format!("3 bytes  CRC32 {:08x}", RomInfo::new(&[0x12, 0x00, 0x00]).crc32)));
+        assert_eq!(lines.last().map(String::as_str), Some("Esc: quit"));
+
+        library.press(MenuKey::Down);
+        assert!(library.lines().contains(&"Can't read this ROM".to_string()));
+
+        // A thumbnail means the game has been played
+        let thumbnails = ThumbnailCache::new(&dir.join("thumbnails"), MAX_CACHE_BYTES);
+        thumbnails.store(rom_hash(&[0x12, 0x00, 0x00]), &Thumbnail::from_display(&[0; DISPLAY_WIDTH * DISPLAY_HEIGHT], &Palette::default())).unwrap();
+        let library = Library::new(choices, "./roms", thumbnails, None, false);
+        assert!(library.lines().iter().any(|line| line.ends_with("  played")));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_preview_follows_the_selection() {
+        let dir = temp_dir("preview");
+        let rom = dir.join("PLAYED.ch8");
+        std::fs::write(&rom, [0x00, 0xE0]).unwrap();
+        let rom = rom.to_string_lossy().into_owned();
+
+        // The thumbnail keeps the palette it was stored with
+        let mut display = [0; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        display[0] = 1;
+        let palette = Palette { foreground: Rgb(1, 2, 3), background: Rgb(4, 5, 6) };
+        let thumbnail = Thumbnail::from_display(&display, &palette);
+        let thumbnails = ThumbnailCache::new(&dir.join("thumbnails"), MAX_CACHE_BYTES);
+        thumbnails.store(rom_hash(&[0x00, 0xE0]), &thumbnail).unwrap();
+
+        let choices = vec![Choice::Rom(rom), Choice::Rom("./roms/GONE.ch8".to_string()), Choice::Settings];
+        let mut library = Library::new(choices, "./roms", thumbnails, None, true);
+        assert_eq!(library.preview(), &Preview::Thumbnail(thumbnail.clone()));
+        let Preview::Thumbnail(picture) = library.preview() else { unreachable!() };
+        assert_eq!((picture.pixels[0], picture.pixels[1]), (Rgb(1, 2, 3), Rgb(4, 5, 6)));
+
+        // Unreadable ROMs and the other entries get the empty box
+        library.press(MenuKey::Down);
+        assert_eq!(library.preview(), &Preview::Placeholder);
+        library.press(MenuKey::Down);
+        assert_eq!(library.preview(), &Preview::Placeholder);
+        library.press(MenuKey::Down);
+        assert_eq!(library.preview(), &Preview::Thumbnail(thumbnail));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
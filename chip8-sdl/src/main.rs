@@ -0,0 +1,1189 @@
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use clap::Parser;
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+
+use chip8_core::cpu::CPU;
+use chip8_core::emulator::{Emulator, EmulatorEvent, InputEvent, MachineSetup, RunState};
+use chip8_core::error::EmulatorError;
+use chip8_core::diagnostics::Requests;
+use chip8_core::expect::{Expectation, Mismatch};
+use chip8_core::headless::{self, Limits, Progress, RunEnd};
+use chip8_core::config::{Config, DisplayConfig, Palette, CONFIG_PATH};
+use chip8_core::config_reload::{self, Applies, ConfigWatcher};
+use chip8_core::config_save;
+#[cfg(feature = "debugger")]
+use chip8_core::{constants::STARTING_MEMORY_ADDRESS, disassembler};
+use chip8_core::net::{HostEvent, NetClient, NetHost};
+use chip8_core::pacing;
+use chip8_core::playlist::{Playlist, PlaylistEntry};
+use chip8_core::pnm;
+use chip8_core::quirks::Quirks;
+#[cfg(feature = "remote-debug")]
+use chip8_core::remote_debug::{self, RemoteDebugServer, Reply, Snapshot};
+#[cfg(feature = "recording")]
+use chip8_core::replay::{FrameInput, Recorder, Replay, Settings};
+use chip8_core::rom::{self, RomInfo};
+use chip8_core::session::{self, AutoSave, ExitReason, Launch, ResumePolicy, AUTOSAVE_DIR};
+use chip8_core::selftest;
+use chip8_core::thumbnails::{Thumbnail, ThumbnailCache, MAX_CACHE_BYTES, THUMBNAIL_DIR};
+use chip8_core::timing::FrameBudget;
+use chip8_core::trace;
+#[cfg(feature = "recording")]
+use chip8_sdl::cli::ReplayAction;
+use chip8_sdl::cli::{Args, Command};
+#[cfg(feature = "gui")]
+use chip8_sdl::gui::Gui;
+use chip8_sdl::help;
+use chip8_sdl::library::{Library, LibraryAction};
+use chip8_sdl::picker::{self, ROMS_DIR};
+use chip8_sdl::recent::{self, RecentRoms, LAST_ROM_PATH, RECENT_PATH};
+use chip8_sdl::resume_prompt::{ResumeAnswer, ResumePrompt};
+use chip8_sdl::settings::{Action, MenuKey, Setting, SettingsMenu};
+use chip8_sdl::signals;
+use chip8_sdl::platform::{Beeper, Display, Gamepad, Input};
+use chip8_sdl::platform::input::KEYMAP_PATH;
+
+/// How long the window title shows a playlist entry's name after switching to it
+const PLAYLIST_TITLE_DURATION: Duration = Duration::from_secs(2);
+/// How often a headless run paused by SIGUSR2 looks for the signal to resume
+const PAUSED_POLL: Duration = Duration::from_millis(50);
+/// Traced instructions printed when a ROM stops on an error, with --trace
+const CRASH_TRACE_ENTRIES: usize = 32;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    match &args.command {
+        Some(Command::Hash { roms }) => return hash_roms(roms),
+        #[cfg(feature = "debugger")]
+        Some(Command::Disassemble { rom }) => return print_disassembly(rom),
+        Some(Command::Selftest) => {
+            let report = selftest::run();
+            println!("{}", report);
+            std::process::exit(if report.passed() { 0 } else { 1 });
+        }
+        Some(command @ Command::Headless { rom, dump_pbm, verify, .. }) => {
+            let limits = command.limits().unwrap_or_default();
+            let code = run_headless(rom, &limits, dump_pbm.as_deref(), verify.as_deref(), &args)?;
+            std::process::exit(code.into());
+        }
+        #[cfg(feature = "recording")]
+        Some(Command::Replay { action: ReplayAction::Verify { file, rom } }) => return verify_replay(file, rom),
+        #[cfg(feature = "recording")]
+        Some(Command::Replay { action: ReplayAction::Record { .. } }) => {}
+        None => {}
+    }
+    #[cfg(feature = "recording")]
+    let record_to = match &args.command {
+        Some(Command::Replay { action: ReplayAction::Record { file, rom } }) => Some((file.clone(), rom.clone())),
+        _ => None,
+    };
+    #[cfg(not(feature = "recording"))]
+    let record_to: Option<(String, String)> = None;
+
+    let _timer_resolution = pacing::TimerResolution::acquire();
+    let config = Config::load(CONFIG_PATH)?;
+    let mut config_watcher = ConfigWatcher::new(CONFIG_PATH, Instant::now());
+
+    if let Some(addr) = &args.connect {
+        return run_client(addr, &args, &config);
+    }
+
+    let mut playlist = match record_to {
+        Some(_) => None,
+        None => args.playlist.as_deref().map(load_playlist).transpose()?,
+    };
+    let mut recent = RecentRoms::load(Path::new(RECENT_PATH));
+    let mut rom_path = match (&record_to, &playlist, &args.rom) {
+        (Some((_, rom)), _, _) => rom.clone(),
+        (None, Some(playlist), _) => playlist.current().path.to_string_lossy().into_owned(),
+        (None, None, Some(rom)) => rom.clone(),
+        // The library picks one once the window is open
+        (None, None, None) => String::new(),
+    };
+
+    let setup = machine_setup(&args, config.emulation.resolve(args.quirks));
+    let mut emulator = Emulator::new(config, setup, args.timing, args.ipf);
+    emulator.handle_event(InputEvent::SetClockRate(args.hz));
+    #[cfg(feature = "debugger")]
+    for addr in &args.breakpoint {
+        emulator.debugger.set_breakpoint(*addr, true);
+    }
+    if let Some(entry) = playlist.as_ref().map(Playlist::current) {
+        emulator.handle_event(set_speed(&args, Some(entry)));
+        emulator.handle_event(set_quirks(&args, &emulator.config, Some(entry)));
+    }
+    if !rom_path.is_empty() {
+        load_rom(&mut emulator, &rom_path);
+    }
+    let auto_save_enabled = emulator.config.session.auto_save || args.resume;
+    let mut autosave = AutoSave::new(Path::new(AUTOSAVE_DIR), emulator.cpu.rom_hash(), auto_save_enabled);
+    // A replay has to start from power-on
+    let resume_policy = if record_to.is_some() { ResumePolicy::Never } else { args.resume_policy() };
+    // With no terminal to answer on, or no answer from it, the window asks instead
+    let mut ask_in_window = false;
+    match session::on_launch(autosave.status(), resume_policy) {
+        Launch::Fresh => {}
+        Launch::Resume => resume(&autosave, &mut emulator.cpu),
+        Launch::Prompt => match ask_resume()? {
+            Some(true) => resume(&autosave, &mut emulator.cpu),
+            Some(false) => report(autosave.decline()),
+            None => ask_in_window = true,
+        },
+        Launch::Decline => report(autosave.decline()),
+    }
+
+    let sdl_ctx = sdl2::init()?;
+    let mut display = Display::new(&sdl_ctx, &display_config(&args, &emulator.config))?;
+    println!("VSync: {}", display.vsync());
+    let mut help_shown = false;
+    let mut base_title = display.config().title.clone();
+    let mut settings: Option<OpenSettings> = None;
+    let mut library = rom_path.is_empty().then(|| open_library(&mut emulator, &recent));
+    if let Some(open) = &library {
+        show_library(&mut display, &open.library);
+    }
+    let mut resume_prompt = ask_in_window.then(|| offer_resume(&mut emulator, &mut display));
+    let mut title_restore_at = None;
+    let mut beeper = Beeper::new(&sdl_ctx, &emulator.config.audio);
+    let mut input = new_input(&args, &emulator.config);
+    input.set_sticky(args.sticky_keys || emulator.config.input.sticky_keys);
+    let keypad = help::keypad_diagram(&input.shown_bindings(&emulator.config.keys));
+    println!("{}\nF1 shows this and the other hotkeys in the window", keypad.join("\n"));
+    let mut gamepad = Gamepad::new(emulator.config.gamepad.clone());
+    let mut event_pump = sdl_ctx.event_pump()?;
+
+    // Controllers are opened as SDL reports them (including those present at startup)
+    // and must stay alive to keep delivering events
+    let controller_subsystem = sdl_ctx.game_controller()
+        .map_err(|e| eprintln!("Game controllers unavailable: {}", e))
+        .ok();
+    let mut controllers = Vec::new();
+
+    let mut host = match args.host {
+        Some(port) => {
+            let host = NetHost::bind(port)?;
+            println!("Waiting for a remote player on {}", host.local_addr());
+            Some(host)
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "remote-debug")]
+    let remote_debug = match args.remote_debug {
+        Some(port) => {
+            let server = RemoteDebugServer::bind(port)?;
+            println!("Remote debugger listening on ws://{}", server.local_addr());
+            Some(server)
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "recording")]
+    let mut recording = record_to.map(|(file, _)| {
+        println!("Recording a replay to {}", file);
+        (file, Recorder::new(&emulator.cpu, replay_settings(&emulator.cpu, &args)))
+    });
+    // A replay runs at the speed it was started with, so neither menu can change it while recording
+    #[cfg(feature = "recording")]
+    let speed_locked = recording.is_some();
+    #[cfg(not(feature = "recording"))]
+    let speed_locked = false;
+
+    #[cfg(feature = "gui")]
+    let mut gui = Gui::new(display.pixel_ratio());
+    #[cfg(feature = "gui")]
+    gui.lock_speed(speed_locked);
+    let requests = install_signals();
+    let started = Instant::now();
+    let mut frames = 0;
+    let mut last_update = Instant::now();
+    let mut uninit_printed = 0;
+    #[cfg(feature = "debugger")]
+    let (mut was_paused, mut stepped) = (false, false);
+
+    loop {
+        let frame_start = Instant::now();
+        frames += 1;
+
+        input.begin_frame();
+        for event in event_pump.poll_iter() {
+            #[cfg(feature = "gui")]
+            if gui.handle_event(&event) {
+                continue;
+            }
+            // The resume prompt takes every key until it is answered
+            if let Some(prompt) = resume_prompt.as_mut() {
+                match &event {
+                    Event::KeyDown { keycode: Some(kc), keymod, .. } => {
+                        match menu_key(*kc, *keymod).and_then(|key| prompt.press(key)) {
+                            Some(answer) => {
+                                answer_resume(answer, &autosave, &mut emulator);
+                                resume_prompt = None;
+                                display.set_help_overlay(None);
+                                last_update = Instant::now();
+                            }
+                            None => display.set_help_overlay(Some(prompt.lines())),
+                        }
+                        continue;
+                    }
+                    Event::KeyUp { .. } => continue,
+                    _ => {}
+                }
+            }
+            // Keys work the menu while it is open, and reach neither the hotkeys nor the keypad
+            if let Some(open) = settings.as_mut() {
+                match &event {
+                    Event::KeyDown { keycode: Some(kc), keymod, .. } => {
+                        match menu_key(*kc, *keymod).map(|key| open.menu.press(key)) {
+                            Some(Action::Changed(Setting::Speed | Setting::Timing)) => {
+                                if let Some((timing, instructions_per_frame)) = open.menu.speed() {
+                                    emulator.handle_event(InputEvent::SetSpeed { timing, instructions_per_frame });
+                                }
+                            }
+                            Some(Action::Changed(_)) => {
+                                let config = config_reload::in_effect(&emulator.config, open.menu.config());
+                                apply_config(config, &mut emulator, &args, &mut display, &mut input, &mut gamepad, &mut beeper);
+                            }
+                            Some(Action::Close) => {
+                                save_settings(open.menu.config());
+                                if open.resume {
+                                    emulator.handle_event(InputEvent::Resume);
+                                }
+                                settings = None;
+                                match &library {
+                                    Some(open) => show_library(&mut display, &open.library),
+                                    None => display.set_help_overlay(None),
+                                }
+                                continue;
+                            }
+                            Some(Action::None) | None => {}
+                        }
+                        display.set_help_overlay(Some(open.menu.lines()));
+                        continue;
+                    }
+                    Event::KeyUp { .. } => continue,
+                    _ => {}
+                }
+            }
+            // Likewise the library, under the settings if it opened them
+            if settings.is_none() && let Some(open) = library.as_mut() {
+                match &event {
+                    Event::KeyDown { keycode: Some(kc), keymod, .. } => {
+                        // F10 opens the settings from here as from the game, rather than closing
+                        let action = match *kc {
+                            Keycode::F10 => Some(LibraryAction::Settings),
+                            _ => menu_key(*kc, *keymod).map(|key| open.library.press(key)),
+                        };
+                        let picked = match action {
+                            Some(LibraryAction::Open(path)) => Some(path),
+                            Some(LibraryAction::Browse) => browse(&mut recent),
+                            Some(LibraryAction::Settings) => {
+                                let open = open_settings(&mut emulator, speed_locked);
+                                display.set_help_overlay(Some(open.menu.lines()));
+                                settings = Some(open);
+                                continue;
+                            }
+                            Some(LibraryAction::Close) if rom_path.is_empty() => return Ok(()),
+                            Some(LibraryAction::Close) => {
+                                if open.resume {
+                                    emulator.handle_event(InputEvent::Resume);
+                                }
+                                library = None;
+                                display.set_help_overlay(None);
+                                continue;
+                            }
+                            Some(LibraryAction::None) | None => None,
+                        };
+                        if let Some(path) = picked {
+                            #[cfg(feature = "recording")]
+                            if recording.is_some() {
+                                eprintln!("A replay records one ROM; quit to save it before opening another");
+                                continue;
+                            }
+                            exit_rom(&autosave, ExitReason::Quit, &emulator.cpu, &display.config().palette);
+                            rom_path = path;
+                            emulator.handle_event(set_speed(&args, None));
+                            emulator.handle_event(set_quirks(&args, &emulator.config, None));
+                            library = None;
+                            display.set_help_overlay(None);
+                            (autosave, resume_prompt) = switch_rom(&mut emulator, &mut display, &rom_path, &args, auto_save_enabled);
+                            last_update = Instant::now();
+                            continue;
+                        }
+                        show_library(&mut display, &open.library);
+                        continue;
+                    }
+                    Event::KeyUp { .. } => continue,
+                    _ => {}
+                }
+            }
+            match event {
+                Event::Quit { .. } => {
+                    // Still unanswered, the save is left for next time rather than overwritten
+                    if resume_prompt.is_none() {
+                        exit_rom(&autosave, ExitReason::Quit, &emulator.cpu, &display.config().palette);
+                    }
+                    #[cfg(feature = "recording")]
+                    save_replay(recording, &emulator.cpu);
+                    return Ok(());
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F4), repeat: false, .. } => {
+                    exit_rom(&autosave, ExitReason::Reset, &emulator.cpu, &display.config().palette);
+                    emulator.handle_event(InputEvent::Reset);
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Backspace), repeat: false, .. } if input.is_sticky() => {
+                    input.release_latched();
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => {
+                    emulator.handle_event(InputEvent::SaveState);
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F6), repeat: false, .. } => {
+                    let mut config = emulator.config.clone();
+                    config.audio.muted = !config.audio.muted;
+                    println!("Sound {}", if config.audio.muted { "muted" } else { "on" });
+                    save_muted(config.audio.muted);
+                    apply_config(config, &mut emulator, &args, &mut display, &mut input, &mut gamepad, &mut beeper);
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F9), repeat: false, .. } => {
+                    #[cfg(feature = "recording")]
+                    if recording.is_some() {
+                        eprintln!("A replay can't jump to a save state; quit to save it first");
+                        continue;
+                    }
+                    load_state_file(&mut emulator);
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F1), repeat: false, .. } => {
+                    help_shown = !help_shown;
+                    display.set_help_overlay(help_shown.then(|| help::help_lines(&input.shown_bindings(&emulator.config.keys))));
+                }
+
+                // Esc stays with the keypad too, for anyone who bound it
+                Event::KeyDown { keycode: Some(Keycode::Escape), repeat: false, .. } if input.map_sdl_keycode(Keycode::Escape).is_none() => {
+                    let open = open_library(&mut emulator, &recent);
+                    show_library(&mut display, &open.library);
+                    help_shown = false;
+                    library = Some(open);
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F10), repeat: false, .. } => {
+                    let open = open_settings(&mut emulator, speed_locked);
+                    display.set_help_overlay(Some(open.menu.lines()));
+                    help_shown = false;
+                    settings = Some(open);
+                }
+
+                // P and Space stay with the keypad for anyone who bound them to it
+                #[cfg(feature = "debugger")]
+                Event::KeyDown { keycode: Some(Keycode::P), repeat: false, .. } if input.map_sdl_keycode(Keycode::P).is_none() => {
+                    emulator.debugger.pause();
+                }
+
+                #[cfg(feature = "debugger")]
+                Event::KeyDown { keycode: Some(Keycode::Space), .. }
+                    if emulator.debugger.is_paused() && input.map_sdl_keycode(Keycode::Space).is_none() =>
+                {
+                    emulator.debugger.step(1, emulator.cpu.pc);
+                    stepped = true;
+                }
+
+                #[cfg(feature = "debugger")]
+                Event::KeyDown { keycode: Some(Keycode::F8), repeat: false, .. } if emulator.debugger.is_paused() => {
+                    emulator.debugger.resume(emulator.cpu.pc);
+                    println!("Continuing");
+                }
+
+                Event::KeyDown { keycode: Some(kc @ (Keycode::Equals | Keycode::KpPlus | Keycode::Minus | Keycode::KpMinus)), repeat: false, .. }
+                    if input.map_sdl_keycode(kc).is_none() =>
+                {
+                    let speed = emulator.clock().speed();
+                    let speed = if matches!(kc, Keycode::Minus | Keycode::KpMinus) { speed.slower() } else { speed.faster() };
+                    emulator.handle_event(InputEvent::SetClockSpeed(speed));
+                    println!("Speed {}", speed);
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F2), repeat: false, .. } => {
+                    let mut display_config = display.config().clone();
+                    display_config.grid = !display_config.grid;
+                    display.apply(&display_config);
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F3), repeat: false, .. } => {
+                    println!("Render: {}", display.stats());
+                }
+
+                Event::KeyDown { keycode: Some(kc @ (Keycode::PageUp | Keycode::PageDown)), repeat: false, .. }
+                    if playlist.is_some() =>
+                {
+                    if let Some(playlist) = playlist.as_mut() {
+                        exit_rom(&autosave, ExitReason::Quit, &emulator.cpu, &display.config().palette);
+
+                        let entry = if kc == Keycode::PageDown { playlist.advance() } else { playlist.go_back() };
+                        rom_path = entry.path.to_string_lossy().into_owned();
+                        emulator.handle_event(set_speed(&args, Some(entry)));
+                        emulator.handle_event(set_quirks(&args, &emulator.config, Some(entry)));
+                        (autosave, resume_prompt) = switch_rom(&mut emulator, &mut display, &rom_path, &args, auto_save_enabled);
+
+                        let mut display_config = display.config().clone();
+                        display_config.title = format!("{} - {}", base_title, entry.name);
+                        display.apply(&display_config);
+                        title_restore_at = Some(Instant::now() + PLAYLIST_TITLE_DURATION);
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::O), keymod, repeat: false, .. }
+                    if cfg!(feature = "dialog") && keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) =>
+                {
+                    #[cfg(feature = "recording")]
+                    if recording.is_some() {
+                        eprintln!("A replay records one ROM; quit to save it before opening another");
+                        continue;
+                    }
+                    // The dialog blocks this loop, so nothing runs (or beeps) until it closes
+                    beeper.set_beeping(false, &mut display);
+                    if let Some(path) = browse(&mut recent) {
+                        exit_rom(&autosave, ExitReason::Quit, &emulator.cpu, &display.config().palette);
+                        rom_path = path;
+                        emulator.handle_event(set_speed(&args, None));
+                        emulator.handle_event(set_quirks(&args, &emulator.config, None));
+                        (autosave, resume_prompt) = switch_rom(&mut emulator, &mut display, &rom_path, &args, auto_save_enabled);
+                    }
+                    beeper.set_beeping(emulator.sound(), &mut display);
+                    last_update = Instant::now();
+                }
+
+                Event::RenderTargetsReset { .. } | Event::RenderDeviceReset { .. } => {
+                    display.invalidate();
+                }
+
+                Event::KeyDown { keycode: Some(kc), repeat: false, .. } => {
+                    input.set_keycode(kc, true);
+                }
+
+                Event::KeyUp { keycode: Some(kc), .. } => {
+                    input.set_keycode(kc, false);
+                }
+
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Some(subsystem) = &controller_subsystem {
+                        match subsystem.open(which) {
+                            Ok(controller) => {
+                                println!("Controller connected: {}", controller.name());
+                                controllers.push(controller);
+                            }
+                            Err(e) => eprintln!("Could not open controller {}: {}", which, e),
+                        }
+                    }
+                }
+
+                Event::ControllerButtonDown { button, .. } => {
+                    gamepad.set_button(button, true);
+                    input.set_gamepad_keys(gamepad.keys());
+                }
+
+                Event::ControllerButtonUp { button, .. } => {
+                    gamepad.set_button(button, false);
+                    input.set_gamepad_keys(gamepad.keys());
+                }
+
+                Event::ControllerAxisMotion { axis, value, .. } => {
+                    gamepad.set_axis(axis, value);
+                    input.set_gamepad_keys(gamepad.keys());
+                }
+
+                _ => {}
+            }
+        }
+
+        if let Some(reloaded) = config_watcher.poll(frame_start) {
+            match reloaded {
+                Ok(config) => {
+                    reload_config(config, &mut emulator, &args, &mut display, &mut input, &mut gamepad, &mut beeper);
+                    base_title = display.config().title.clone();
+                }
+                Err(e) => eprintln!("Keeping the settings in use, {} has an error: {}", CONFIG_PATH, e),
+            }
+        }
+
+        if let Some(host) = host.as_mut() {
+            for event in host.poll() {
+                match event {
+                    HostEvent::Connected(addr) => println!("Remote player connected from {}", addr),
+                    HostEvent::Key { key, pressed } => input.set_remote_key(key, pressed),
+                    HostEvent::Disconnected => {
+                        println!("Remote player disconnected");
+                        input.clear_remote_keys();
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "remote-debug")]
+        if let Some(server) = &remote_debug {
+            for command in server.poll() {
+                match remote_debug::apply(command, &mut emulator.cpu, &mut emulator.debugger) {
+                    Ok(Some(reply)) => server.publish(&reply),
+                    Ok(None) => {}
+                    Err(message) => server.publish(&Reply::Error { message }),
+                }
+            }
+        }
+
+        if let Some(dump) = requests.take_dump(&emulator.cpu, frames, started.elapsed()) {
+            eprint!("{}", dump);
+        }
+        if requests.take_toggle_pause() {
+            emulator.handle_event(InputEvent::TogglePause);
+        }
+
+        emulator.handle_event(InputEvent::Keys { down: input.key_mask(), latched: input.latched_mask() });
+        let now = Instant::now();
+        let events = emulator.update(now - last_update);
+        last_update = now;
+
+        for event in events {
+            match event {
+                #[cfg(feature = "recording")]
+                EmulatorEvent::Frame { keys, latched, frames, instructions, timers } => {
+                    if let Some((_, recorder)) = recording.as_mut() {
+                        recorder.record(FrameInput { keys, latched, frames, instructions, timers });
+                    }
+                }
+                EmulatorEvent::LatchConsumed(key) => input.consume_latch(key),
+                EmulatorEvent::Reset { error } => {
+                    println!("Seed: {:#x}", emulator.cpu.seed());
+                    uninit_printed = 0;
+                    if let Some(e) = error {
+                        eprintln!("Could not load {}: {}", rom_path, e);
+                    }
+                    #[cfg(feature = "recording")]
+                    if let Some((_, recorder)) = recording.as_mut() {
+                        recorder.restart(&emulator.cpu);
+                    }
+                }
+                EmulatorEvent::Halted { pc, error: EmulatorError::UnknownOpcode { opcode, .. } } if args.skip_unknown_opcodes => {
+                    eprintln!("Skipped unknown opcode {:04X} at PC {:#05X}", opcode, pc);
+                    emulator.handle_event(InputEvent::SkipInstruction);
+                }
+                EmulatorEvent::Halted { pc, error } => {
+                    eprintln!("Emulation error at PC {:#05X} (seed {:#x}): {}", pc, emulator.cpu.seed(), error);
+                    eprint!("{}", trace::crash_report(&emulator.cpu, CRASH_TRACE_ENTRIES));
+                    exit_rom(&autosave, ExitReason::Error, &emulator.cpu, &display.config().palette);
+                    #[cfg(feature = "recording")]
+                    save_replay(recording, &emulator.cpu);
+                    return Ok(());
+                }
+                EmulatorEvent::StateSaved(state) => save_state_file(&emulator.cpu, &state),
+                EmulatorEvent::StateLoaded(Ok(warnings)) => {
+                    println!("State loaded");
+                    for warning in warnings {
+                        eprintln!("Warning: {}", warning);
+                    }
+                }
+                EmulatorEvent::StateLoaded(Err(e)) => eprintln!("Could not load the state: {}", e),
+                EmulatorEvent::Sound(on) => beeper.set_beeping(on, &mut display),
+                _ => {}
+            }
+        }
+        print_uninit_reads(&emulator.cpu, &mut uninit_printed);
+
+        // Pausing, a breakpoint or a step prints where the machine stopped
+        #[cfg(feature = "debugger")]
+        {
+            let paused = emulator.debugger.is_paused();
+            if paused && (!was_paused || std::mem::take(&mut stepped)) {
+                print!("{}", emulator.debugger.dump(&emulator.cpu));
+            }
+            was_paused = paused;
+        }
+
+        #[cfg(feature = "gui")]
+        gui.update(&mut emulator, display.logical_size());
+
+        #[cfg(feature = "remote-debug")]
+        if let Some(server) = remote_debug.as_ref().filter(|server| server.has_clients()) {
+            server.publish(&Reply::State(Snapshot::capture(&emulator.cpu, &emulator.debugger)));
+        }
+
+        if let Some(host) = host.as_mut() {
+            host.send_frame(emulator.frame(), emulator.sound());
+        }
+
+        if title_restore_at.is_some_and(|at| Instant::now() >= at) {
+            let mut display_config = display.config().clone();
+            display_config.title = base_title.clone();
+            display.apply(&display_config);
+            title_restore_at = None;
+        }
+
+        if input.is_sticky() {
+            display.set_keypad_overlay(Some(input.latched()));
+        }
+
+        #[cfg(feature = "gui")]
+        {
+            let dirty_rows = emulator.cpu.take_dirty_rows();
+            display.render_rows_with(emulator.cpu.screen(), dirty_rows, |canvas| gui.paint(canvas));
+        }
+        #[cfg(not(feature = "gui"))]
+        emulator.present(&mut display);
+        pacing::precise_sleep(pacing::frame_sleep(display.vsync(), frame_start.elapsed()));
+    }
+}
+
+/// `chip8_emulator hash`: prints the checksums of each ROM, carrying on past unreadable files
+fn hash_roms(roms: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut failed = 0;
+    for path in roms {
+        match rom::read(path) {
+            Ok(rom) => println!("{}", RomInfo::new(&rom).summary(path)),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} ROMs could not be read", failed, roms.len()).into());
+    }
+    Ok(())
+}
+
+/// `chip8_emulator disassemble`: the ROM's listing as it would load at 0x200
+#[cfg(feature = "debugger")]
+fn print_disassembly(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let rom = rom::read(path)?;
+    for (addr, opcode, mnemonic) in disassembler::disassemble_rom(&rom, STARTING_MEMORY_ADDRESS as u16) {
+        println!("{:#05X}  {:04X}  {}", addr, opcode, mnemonic);
+    }
+    Ok(())
+}
+
+/// `chip8_emulator headless`: runs the ROM unpaced with no window or input, then reports how it ended.
+/// Returns the exit code, as listed in headless::EXIT_CODES_HELP
+fn run_headless(
+    path: &str,
+    limits: &Limits,
+    dump_pbm: Option<&str>,
+    verify: Option<&str>,
+    args: &Args,
+) -> Result<u8, Box<dyn std::error::Error>> {
+    let expectation = verify.map(Expectation::load).transpose()?;
+    let mut cpu = new_cpu(args);
+    cpu.load_rom(path)?;
+
+    let requests = install_signals();
+    let mut budget = FrameBudget::new(args.timing, args.ipf);
+    let (end, frames) = headless::run_with(&mut cpu, &mut budget, limits, |cpu, progress| answer_signals(&requests, cpu, progress));
+    if let Some(dump_path) = dump_pbm {
+        std::fs::write(dump_path, pnm::to_pbm(&cpu.display))?;
+    }
+    print_uninit_reads(&cpu, &mut 0);
+    println!("Ran {} frames, PC {:#05X}, seed {:#x}: {}", frames, cpu.pc, cpu.seed(), end);
+    if let RunEnd::Error(_) = end {
+        eprint!("{}", trace::crash_report(&cpu, CRASH_TRACE_ENTRIES));
+    }
+    if end.exit_code() != 0 {
+        return Ok(end.exit_code());
+    }
+
+    if let Some(expectation) = expectation {
+        let mismatches = expectation.check(&cpu);
+        if !mismatches.is_empty() {
+            println!("FAIL: final state differs from {}:", verify.unwrap_or_default());
+            print_mismatches(&mismatches);
+            return Err(format!("{} expected values differ", mismatches.len()).into());
+        }
+        println!("PASS: final state matches {}", verify.unwrap_or_default());
+    }
+    Ok(0)
+}
+
+/// Requests from SIGUSR1 and SIGUSR2, where there are such signals
+fn install_signals() -> Requests {
+    let requests = Requests::default();
+    if let Err(e) = signals::install(&requests) {
+        eprintln!("Could not listen for SIGUSR1/SIGUSR2: {}", e);
+    }
+    requests
+}
+
+/// Between headless frames: prints a dump if one was asked for, and on a pause waits here,
+/// still answering dumps, until told to resume
+fn answer_signals(requests: &Requests, cpu: &CPU, progress: &Progress) {
+    let print_dump = || {
+        if let Some(dump) = requests.take_dump(cpu, progress.frames, progress.elapsed) {
+            eprint!("{}", dump);
+        }
+    };
+    print_dump();
+    if requests.take_toggle_pause() {
+        eprintln!("Paused after {} frames; send SIGUSR2 again to resume", progress.frames);
+        while !requests.take_toggle_pause() {
+            std::thread::sleep(PAUSED_POLL);
+            print_dump();
+        }
+        eprintln!("Resumed");
+    }
+}
+
+/// Prints the --detect-uninit reads found since printed were, starting over if the ROM restarted
+fn print_uninit_reads(cpu: &CPU, printed: &mut usize) {
+    let reads = cpu.uninit_reads();
+    for read in reads.get(*printed..).unwrap_or(reads) {
+        eprintln!("Uninitialized read: {}", read);
+    }
+    *printed = reads.len();
+}
+
+fn print_mismatches(mismatches: &[Mismatch]) {
+    println!("  {:<8} {:<18} actual", "field", "expected");
+    for mismatch in mismatches {
+        println!("  {:<8} {:<18} {}", mismatch.field, mismatch.expected, mismatch.actual);
+    }
+}
+
+/// `chip8_emulator replay verify`: plays a replay back and prints what differs from the recording
+#[cfg(feature = "recording")]
+fn verify_replay(path: &str, rom_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let replay = Replay::decode(&std::fs::read(path)?)?;
+    let mismatches = replay.verify(&rom::read(rom_path)?)?;
+    if mismatches.is_empty() {
+        println!("PASS: {} frames ended as recorded", replay.frames);
+        return Ok(());
+    }
+
+    println!("FAIL: {} frames played, final state differs:", replay.frames);
+    print_mismatches(&mismatches);
+    Err(format!("{} fields differ from the recording", mismatches.len()).into())
+}
+
+/// Settings a replay needs to set the machine up the same way again
+#[cfg(feature = "recording")]
+fn replay_settings(cpu: &CPU, args: &Args) -> Settings {
+    Settings {
+        quirks: cpu.quirks_name().to_string(),
+        timing: args.timing,
+        instructions_per_frame: args.ipf,
+        stack_depth: args.stack_depth,
+        vip_display: args.vip_display,
+        hz: args.hz,
+    }
+}
+
+/// Writes out the session being recorded, if there is one
+#[cfg(feature = "recording")]
+fn save_replay(recording: Option<(String, Recorder)>, cpu: &CPU) {
+    if let Some((path, recorder)) = recording {
+        let replay = recorder.finish(cpu);
+        match std::fs::write(&path, replay.encode()) {
+            Ok(()) => println!("Saved {} frames to {}", replay.frames, path),
+            Err(e) => eprintln!("Could not save replay to {}: {}", path, e),
+        }
+    }
+}
+
+/// The settings menu over a game, and whether to resume the game when it closes
+struct OpenSettings {
+    menu: SettingsMenu,
+    resume: bool,
+}
+
+/// Pauses the game and opens the menu on the settings in the config file, or on those in use if
+/// it can't be read. Settings waiting for the next launch stay as the file has them, and a
+/// locked speed isn't offered
+fn open_settings(emulator: &mut Emulator, speed_locked: bool) -> OpenSettings {
+    let resume = *emulator.state() == RunState::Running;
+    emulator.handle_event(InputEvent::Pause);
+    let config = Config::load(CONFIG_PATH).unwrap_or_else(|_| emulator.config.clone());
+    // With no ROM loaded yet there is no speed to set, so no Speed or Timing rows
+    let speed = (emulator.cpu.rom_info().is_some() && !speed_locked).then(|| emulator.speed());
+    OpenSettings { menu: SettingsMenu::new(config, speed), resume }
+}
+
+/// The ROM library over the window, and whether to resume the game under it when it closes
+struct OpenLibrary {
+    library: Library,
+    resume: bool,
+}
+
+/// Pauses the game, if there is one, and opens the library on ./roms and the recent ROMs with the
+/// last one played selected. Esc goes back to the game, or quits when there is none
+fn open_library(emulator: &mut Emulator, recent: &RecentRoms) -> OpenLibrary {
+    let resume = *emulator.state() == RunState::Running;
+    emulator.handle_event(InputEvent::Pause);
+    let (roms, warnings) = picker::roms_in(ROMS_DIR);
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+    let choices = picker::menu(recent, roms, cfg!(feature = "dialog"));
+    let thumbnails = ThumbnailCache::new(Path::new(THUMBNAIL_DIR), MAX_CACHE_BYTES);
+    let last = recent::load_last_rom(Path::new(LAST_ROM_PATH));
+    let in_game = emulator.cpu.rom_info().is_some();
+    OpenLibrary { library: Library::new(choices, ROMS_DIR, thumbnails, last.as_deref(), in_game), resume }
+}
+
+/// Covers the window with the library, the selected ROM's thumbnail beside the list
+fn show_library(display: &mut Display, library: &Library) {
+    display.set_help_overlay(Some(library.lines()));
+    display.set_preview(Some(library.preview()));
+}
+
+/// Writes the menu's settings to the config file, saying which were saved
+fn save_settings(config: &Config) {
+    match config_save::save(CONFIG_PATH, config) {
+        Ok(saved) if saved.is_empty() => {}
+        Ok(saved) => println!("Saved to {}: {}", CONFIG_PATH, saved.join(", ")),
+        Err(e) => eprintln!("Could not save settings to {}: {}", CONFIG_PATH, e),
+    }
+}
+
+/// F6 flips the Mute setting, so it is saved as the menu would save it. Only that is written: the
+/// running config may hold edits to the file that wait for the next launch
+fn save_muted(muted: bool) {
+    match Config::load(CONFIG_PATH) {
+        Ok(mut config) => {
+            config.audio.muted = muted;
+            save_settings(&config);
+        }
+        Err(e) => eprintln!("Could not save settings to {}: {}", CONFIG_PATH, e),
+    }
+}
+
+/// The menus' keys: arrows, Tab and Shift+Tab between sections, Enter to pick, and Esc (or F10 again) to close
+fn menu_key(keycode: Keycode, keymod: Mod) -> Option<MenuKey> {
+    match keycode {
+        Keycode::Up => Some(MenuKey::Up),
+        Keycode::Down => Some(MenuKey::Down),
+        Keycode::Left => Some(MenuKey::Left),
+        Keycode::Right => Some(MenuKey::Right),
+        Keycode::Tab if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => Some(MenuKey::PreviousCategory),
+        Keycode::Tab => Some(MenuKey::NextCategory),
+        Keycode::Return | Keycode::KpEnter => Some(MenuKey::Select),
+        Keycode::Escape | Keycode::F10 => Some(MenuKey::Close),
+        _ => None,
+    }
+}
+
+/// Picks a ROM with the native file dialog, remembering it as recent
+#[cfg(feature = "dialog")]
+fn browse(recent: &mut RecentRoms) -> Option<String> {
+    picker::browse(&mut picker::NativeDialog, recent)
+}
+
+#[cfg(not(feature = "dialog"))]
+fn browse(_recent: &mut RecentRoms) -> Option<String> {
+    None
+}
+
+/// Loads another ROM mid-session, along with its auto-save. A save that would be asked about at
+/// launch is offered in the window, with the game paused under the returned prompt
+fn switch_rom(emulator: &mut Emulator, display: &mut Display, path: &str, args: &Args, auto_save_enabled: bool) -> (AutoSave, Option<ResumePrompt>) {
+    load_rom(emulator, path);
+    let autosave = AutoSave::new(Path::new(AUTOSAVE_DIR), emulator.cpu.rom_hash(), auto_save_enabled);
+    let prompt = match session::on_launch(autosave.status(), args.resume_policy()) {
+        Launch::Resume => {
+            resume(&autosave, &mut emulator.cpu);
+            None
+        }
+        Launch::Prompt => Some(offer_resume(emulator, display)),
+        Launch::Fresh | Launch::Decline => None,
+    };
+    (autosave, prompt)
+}
+
+/// Pauses the game under a ResumePrompt
+fn offer_resume(emulator: &mut Emulator, display: &mut Display) -> ResumePrompt {
+    emulator.handle_event(InputEvent::Pause);
+    let prompt = ResumePrompt::new();
+    display.set_help_overlay(Some(prompt.lines()));
+    prompt
+}
+
+/// Carries out the answer to a ResumePrompt and lets the game run
+fn answer_resume(answer: ResumeAnswer, autosave: &AutoSave, emulator: &mut Emulator) {
+    match answer {
+        ResumeAnswer::Resume => resume(autosave, &mut emulator.cpu),
+        ResumeAnswer::StartOver => report(autosave.decline()),
+        ResumeAnswer::Dismissed => {}
+    }
+    emulator.handle_event(InputEvent::Resume);
+}
+
+/// Starts the ROM at path on a fresh machine, reporting what was loaded
+fn load_rom(emulator: &mut Emulator, path: &str) {
+    let loaded = emulator.load(path);
+    println!("Seed: {:#x}", emulator.cpu.seed());
+    match loaded {
+        Ok(()) => {
+            if let Some(info) = emulator.cpu.rom_info() {
+                println!("ROM {}: {}", path, info);
+            }
+            if let Err(e) = recent::save_last_rom(Path::new(LAST_ROM_PATH), path) {
+                eprintln!("Could not save {}: {}", LAST_ROM_PATH, e);
+            }
+        }
+        Err(e) => eprintln!("Could not load {}: {}", path, e),
+    }
+}
+
+/// A machine set up as the command line asks, with nothing loaded
+fn new_cpu(args: &Args) -> CPU {
+    let cpu = machine_setup(args, args.quirks.unwrap_or_default().quirks()).machine();
+    println!("Seed: {:#x}", cpu.seed());
+    cpu
+}
+
+fn machine_setup(args: &Args, quirks: Quirks) -> MachineSetup {
+    MachineSetup {
+        stack_depth: args.stack_depth as usize,
+        vip_display: args.vip_display,
+        detect_uninit: args.detect_uninit,
+        trace: args.trace,
+        quirks,
+        seed: args.seed,
+    }
+}
+
+/// Asks on stdin whether to pick up the auto-saved game. None if there is no answer: stdin isn't
+/// a terminal (say, launched from a file manager) or ends without one
+fn ask_resume() -> Result<Option<bool>, std::io::Error> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(None);
+    }
+    print!("Resume where you left off? [y/n] ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().eq_ignore_ascii_case("y")))
+}
+
+fn resume(autosave: &AutoSave, cpu: &mut CPU) {
+    match autosave.resume(cpu) {
+        Ok(warnings) => {
+            for warning in warnings {
+                eprintln!("Resume: {}", warning);
+            }
+            println!("Resumed auto-saved game");
+        }
+        Err(e) => eprintln!("Could not resume: {}", e),
+    }
+}
+
+/// Loads the --playlist file, printing what it skipped
+fn load_playlist(path: &str) -> Result<Playlist, std::io::Error> {
+    let (playlist, warnings) = Playlist::load(path)?;
+    for warning in warnings {
+        eprintln!("Playlist: {}", warning);
+    }
+    Ok(playlist)
+}
+
+/// Leaves the running ROM: updates its auto-save and keeps a thumbnail of the screen for the picker
+fn exit_rom(autosave: &AutoSave, reason: ExitReason, cpu: &CPU, palette: &Palette) {
+    // Nothing was played while the library was up at startup
+    if cpu.rom_info().is_none() {
+        return;
+    }
+    report(autosave.exit(reason, cpu));
+
+    let thumbnails = ThumbnailCache::new(Path::new(THUMBNAIL_DIR), MAX_CACHE_BYTES);
+    if let Err(e) = thumbnails.store(cpu.rom_hash(), &Thumbnail::from_display(&cpu.display, palette)) {
+        eprintln!("Could not save thumbnail: {}", e);
+    }
+}
+
+/// Writes the ROM's F5 save state, kept with the desktop app's in one slot per ROM
+fn save_state_file(cpu: &CPU, state: &[u8]) {
+    let path = session::state_path(Path::new(session::STATE_DIR), cpu.rom_hash());
+    let written = path.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|_| std::fs::write(&path, state));
+    match written {
+        Ok(()) => println!("State saved to {}", path.display()),
+        Err(e) => eprintln!("Could not save the state to {}: {}", path.display(), e),
+    }
+}
+
+/// Asks the emulator to restore the ROM's save state, if it has one, for F9
+fn load_state_file(emulator: &mut Emulator) {
+    let path = session::state_path(Path::new(session::STATE_DIR), emulator.cpu.rom_hash());
+    match std::fs::read(&path) {
+        Ok(state) => emulator.handle_event(InputEvent::LoadState(state)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => eprintln!("No saved state for this ROM"),
+        Err(e) => eprintln!("Could not read {}: {}", path.display(), e),
+    }
+}
+
+/// Auto-save problems are reported but never stop the emulator
+fn report(result: Result<(), std::io::Error>) {
+    if let Err(e) = result {
+        eprintln!("Auto-save: {}", e);
+    }
+}
+
+/// The speed the command line asks for, with a playlist entry's overrides applied
+fn set_speed(args: &Args, entry: Option<&PlaylistEntry>) -> InputEvent {
+    InputEvent::SetSpeed {
+        timing: entry.and_then(|entry| entry.timing).unwrap_or(args.timing),
+        instructions_per_frame: entry.and_then(|entry| entry.speed).unwrap_or(args.ipf),
+    }
+}
+
+/// The quirks for the next machine: a playlist entry's preset, then the command line's, then the
+/// config file's, with the file's overrides on top
+fn set_quirks(args: &Args, config: &Config, entry: Option<&PlaylistEntry>) -> InputEvent {
+    let preset = entry.and_then(|entry| entry.quirks).or(args.quirks);
+    InputEvent::SetQuirks(config.emulation.resolve(preset))
+}
+
+/// Runs as the remote player of a hosted session: forwards keypad changes to the host
+/// and renders the display it streams back
+fn run_client(addr: &str, args: &Args, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = NetClient::connect(addr)?;
+    println!("Connected to {}", addr);
+
+    let sdl_ctx = sdl2::init()?;
+    let mut display = Display::new(&sdl_ctx, &display_config(args, config))?;
+    let mut beeper = Beeper::new(&sdl_ctx, &config.audio);
+    let mut input = new_input(args, config);
+    let mut event_pump = sdl_ctx.event_pump()?;
+
+    loop {
+        let frame_start = Instant::now();
+
+        for event in event_pump.poll_iter() {
+            let (kc, pressed) = match event {
+                Event::Quit { .. } => return Ok(()),
+                Event::KeyDown { keycode: Some(kc), repeat: false, .. } => (kc, true),
+                Event::KeyUp { keycode: Some(kc), .. } => (kc, false),
+                _ => continue,
+            };
+
+            let before = input.keys;
+            input.set_keycode(kc, pressed);
+            for (key, (was, is)) in before.iter().zip(input.keys).enumerate() {
+                if *was != is {
+                    client.send_key(key, is);
+                }
+            }
+        }
+
+        client.poll();
+        if !client.connected {
+            println!("Host closed the session");
+            return Ok(());
+        }
+
+        beeper.set_beeping(client.buzzer, &mut display);
+
+        display.render(&client.display);
+        pacing::precise_sleep(pacing::frame_sleep(display.vsync(), frame_start.elapsed()));
+    }
+}
+
+/// The keyboard as configured, with the keymap file in place of the primary keys if there is one.
+/// Lines the keymap skipped are reported; one that can't be read is too, and the configured keys are kept
+fn new_input(args: &Args, config: &Config) -> Input {
+    let mut input = Input::from_bindings(&config.keys);
+    let path = args.keymap.as_deref().or_else(|| Path::new(KEYMAP_PATH).exists().then_some(KEYMAP_PATH));
+    if let Some(path) = path {
+        match input.load_keymap(path) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    eprintln!("{}: {}", path, warning);
+                }
+                println!("Keys from {}", path);
+            }
+            Err(e) => eprintln!("Could not load the keymap, keeping the configured keys: {}", e),
+        }
+    }
+    input
+}
+
+/// Takes up an edit to the config file: applies what the running window can, and says what waits
+/// for the next launch
+fn reload_config(
+    new: Config,
+    emulator: &mut Emulator,
+    args: &Args,
+    display: &mut Display,
+    input: &mut Input,
+    gamepad: &mut Gamepad,
+    beeper: &mut Beeper,
+) {
+    let changes = config_reload::changes(&emulator.config, &new);
+    if changes.is_empty() {
+        return;
+    }
+    let named = |applies| changes.iter()
+        .filter(|change| change.applies == applies)
+        .map(|change| change.setting)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (now, reset, later) = (named(Applies::Now), named(Applies::OnReset), named(Applies::OnRestart));
+    if !now.is_empty() {
+        println!("Reloaded {}: {}", CONFIG_PATH, now);
+    }
+    if !reset.is_empty() {
+        println!("Changed in {}, takes effect on the next reset or ROM: {}", CONFIG_PATH, reset);
+    }
+    if !later.is_empty() {
+        println!("Changed in {}, takes effect on the next launch: {}", CONFIG_PATH, later);
+    }
+
+    apply_config(config_reload::in_effect(&emulator.config, &new), emulator, args, display, input, gamepad, beeper);
+}
+
+/// Switches the window over to config: its display, keys, controller and audio settings
+fn apply_config(
+    config: Config,
+    emulator: &mut Emulator,
+    args: &Args,
+    display: &mut Display,
+    input: &mut Input,
+    gamepad: &mut Gamepad,
+    beeper: &mut Beeper,
+) {
+    display.apply(&display_config(args, &config));
+    input.set_bindings(&config.keys);
+    input.set_sticky(args.sticky_keys || config.input.sticky_keys);
+    if !input.is_sticky() {
+        display.set_keypad_overlay(None);
+    }
+    gamepad.set_bindings(config.gamepad.clone());
+    input.set_gamepad_keys(gamepad.keys());
+    beeper.set_config(&config.audio, display);
+    if config.emulation != emulator.config.emulation {
+        emulator.handle_event(set_quirks(args, &config, None));
+    }
+    emulator.set_config(config);
+}
+
+/// The config file's display settings with command-line overrides applied
+fn display_config(args: &Args, config: &Config) -> DisplayConfig {
+    let mut display = config.display.clone();
+    if let Some(vsync) = args.vsync {
+        display.vsync = vsync;
+    }
+    if let Some(scale) = args.scale {
+        display.scale = scale;
+    }
+    if let Some(foreground) = args.fg {
+        display.palette.foreground = foreground;
+    }
+    if let Some(background) = args.bg {
+        display.palette.background = background;
+    }
+    display
+}
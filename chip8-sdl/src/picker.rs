@@ -0,0 +1,191 @@
+use std::path::PathBuf;
+use chip8_core::rom::{self, ROM_EXTENSIONS};
+use crate::recent::RecentRoms;
+
+/// Where the picker looks for ROMs
+pub const ROMS_DIR: &str = "./roms";
+
+/// Asks for a ROM file anywhere on disk; None if the user cancels.
+/// Dialogs block, so this is only called from the thread handling window events
+pub trait FileDialog {
+    fn pick_rom(&mut self) -> Option<PathBuf>;
+}
+
+/// The platform's own file-open dialog, filtered to ROM extensions
+#[cfg(feature = "dialog")]
+#[derive(Debug, Default)]
+pub struct NativeDialog;
+
+#[cfg(feature = "dialog")]
+impl FileDialog for NativeDialog {
+    fn pick_rom(&mut self) -> Option<PathBuf> {
+        let mut extensions = ROM_EXTENSIONS.to_vec();
+        if cfg!(feature = "zip") {
+            extensions.push("zip");
+        }
+        rfd::FileDialog::new()
+            .set_title("Open a CHIP-8 ROM")
+            .add_filter("CHIP-8 ROMs", &extensions)
+            .pick_file()
+    }
+}
+
+/// One line of the ROM picker
+#[derive(Debug, Clone, PartialEq)]
+pub enum Choice {
+    Recent(String),
+    Rom(String),
+    Browse,
+    Settings,
+}
+
+impl Choice {
+    /// What the picker shows, with the roms directory left off its own ROMs
+    pub fn label(&self, roms_dir: &str) -> String {
+        match self {
+            Choice::Recent(path) => format!("{} (recent)", path),
+            Choice::Rom(path) => path.strip_prefix(roms_dir).unwrap_or(path).trim_start_matches('/').to_string(),
+            Choice::Browse => "Browse…".to_string(),
+            Choice::Settings => "Settings…".to_string(),
+        }
+    }
+}
+
+/// The ROMs in dir by name, each as rom::picker_entries lists it: files with a ROM extension or
+/// none at all (as in the classic packs, and ./roms), and .zip packs with the zip feature.
+/// Subdirectories and other files are skipped, and a directory that can't be read has none. Also
+/// returns a warning for each pack that couldn't be listed
+pub fn roms_in(dir: &str) -> (Vec<String>, Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return (Vec::new(), Vec::new());
+    };
+    let mut names = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| is_rom_name(name))
+        .collect::<Vec<_>>();
+    names.sort();
+    let (mut roms, mut warnings) = (Vec::new(), Vec::new());
+    for name in names {
+        let (entries, unlisted) = rom::picker_entries(&format!("{}/{}", dir, name));
+        roms.extend(entries);
+        warnings.extend(unlisted);
+    }
+    (roms, warnings)
+}
+
+fn is_rom_name(name: &str) -> bool {
+    let Some((_, extension)) = name.rsplit_once('.') else {
+        return true;
+    };
+    let extension = extension.to_ascii_lowercase();
+    ROM_EXTENSIONS.contains(&extension.as_str()) || (cfg!(feature = "zip") && extension == "zip")
+}
+
+/// Recent ROMs that still exist, then those in the roms directory, then Browse… if there is a dialog,
+/// then Settings…
+pub fn menu(recent: &RecentRoms, roms: Vec<String>, can_browse: bool) -> Vec<Choice> {
+    let recent = recent.entries().iter()
+        .filter(|path| chip8_core::rom::exists(path))
+        .map(|path| Choice::Recent(path.clone()));
+    let mut choices = recent.chain(roms.into_iter().map(Choice::Rom)).collect::<Vec<_>>();
+    if can_browse {
+        choices.push(Choice::Browse);
+    }
+    choices.push(Choice::Settings);
+    choices
+}
+
+/// Opens the dialog and, if a file was picked, puts it at the top of the recent list and returns it.
+/// Cancelling leaves everything as it was
+pub fn browse(dialog: &mut impl FileDialog, recent: &mut RecentRoms) -> Option<String> {
+    let path = dialog.pick_rom()?.to_string_lossy().into_owned();
+    recent.add(&path);
+    if let Err(e) = recent.save() {
+        eprintln!("Could not save the recent ROMs list: {}", e);
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Answers each dialog with the next of its picks, None being a cancel
+    struct StubDialog {
+        picks: Vec<Option<PathBuf>>,
+        opened: usize,
+    }
+
+    impl FileDialog for StubDialog {
+        fn pick_rom(&mut self) -> Option<PathBuf> {
+            self.opened += 1;
+            self.picks.remove(0)
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip8-picker-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_browse_adds_to_recent() {
+        let dir = temp_dir("browse");
+        let mut recent = RecentRoms::load(&dir.join("recent.txt"));
+        let mut dialog = StubDialog { picks: vec![Some(PathBuf::from("/far/away/PONG.ch8")), None], opened: 0 };
+
+        assert_eq!(browse(&mut dialog, &mut recent), Some("/far/away/PONG.ch8".to_string()));
+        assert_eq!(RecentRoms::load(&dir.join("recent.txt")).entries(), ["/far/away/PONG.ch8"]);
+
+        // Cancelling picks nothing and leaves the list alone
+        assert_eq!(browse(&mut dialog, &mut recent), None);
+        assert_eq!(recent.entries(), ["/far/away/PONG.ch8"]);
+        assert_eq!(dialog.opened, 2);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_menu() {
+        let dir = temp_dir("menu");
+        let rom = dir.join("FAR.ch8");
+        std::fs::write(&rom, [0x12, 0x00]).unwrap();
+        let rom = rom.to_string_lossy().into_owned();
+
+        let mut recent = RecentRoms::load(&dir.join("recent.txt"));
+        recent.add(&rom);
+        recent.add("/deleted/GONE.ch8");
+
+        let choices = menu(&recent, vec!["./roms/PONG".to_string()], true);
+        assert_eq!(choices, [Choice::Recent(rom.clone()), Choice::Rom("./roms/PONG".to_string()), Choice::Browse, Choice::Settings]);
+        assert_eq!(choices[1].label("./roms"), "PONG");
+        assert_eq!(choices[0].label("./roms"), format!("{} (recent)", rom));
+
+        assert_eq!(menu(&RecentRoms::load(Path::new("")), Vec::new(), false), [Choice::Settings]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_roms_in_skips_directories_and_other_files() {
+        let dir = temp_dir("roms");
+        for name in ["PONG.ch8", "BRIX.CH8", "README.txt", "TETRIS", "CAR.sc8", "cover.png", ".hidden"] {
+            std::fs::write(dir.join(name), [0x12, 0x00]).unwrap();
+        }
+        std::fs::create_dir(dir.join("more.ch8")).unwrap();
+        let dir_name = dir.to_string_lossy().into_owned();
+
+        let (roms, warnings) = roms_in(&dir_name);
+        assert!(warnings.is_empty());
+        let names = roms.iter()
+            .map(|path| path.strip_prefix(&format!("{}/", dir_name)).unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["BRIX.CH8", "CAR.sc8", "PONG.ch8", "TETRIS"]);
+
+        assert!(roms_in(&dir.join("missing").to_string_lossy()).0.is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
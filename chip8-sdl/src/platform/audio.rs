@@ -0,0 +1,242 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use chip8_core::config::AudioConfig;
+
+const SAMPLE_RATE: i32 = 44_100;
+
+/// Linear attack/release envelope over samples.
+/// Switching the gate never jumps the level: it ramps from wherever it currently is,
+/// so a beep that restarts mid-release re-attacks from the partially faded level
+pub struct Envelope {
+    level: f32,
+    attack_step: f32,
+    release_step: f32,
+    gate: bool,
+}
+
+impl Envelope {
+    pub fn new(sample_rate: i32, attack_ms: f32, release_ms: f32) -> Self {
+        Self {
+            level: 0.0,
+            attack_step: Envelope::step(sample_rate, attack_ms),
+            release_step: Envelope::step(sample_rate, release_ms),
+            gate: false,
+        }
+    }
+
+    /// Changes the ramps from the next sample on, without moving the current level
+    pub fn set_ramps(&mut self, sample_rate: i32, attack_ms: f32, release_ms: f32) {
+        self.attack_step = Envelope::step(sample_rate, attack_ms);
+        self.release_step = Envelope::step(sample_rate, release_ms);
+    }
+
+    pub fn set_gate(&mut self, on: bool) {
+        self.gate = on;
+    }
+
+    /// Advances one sample and returns the new level (0.0 to 1.0)
+    pub fn next_level(&mut self) -> f32 {
+        if self.gate {
+            self.level = (self.level + self.attack_step).min(1.0);
+        } else {
+            self.level = (self.level - self.release_step).max(0.0);
+        }
+        self.level
+    }
+
+    /// Level change per sample needed to ramp between silence and full volume in ms
+    fn step(sample_rate: i32, ms: f32) -> f32 {
+        1.0 / (sample_rate as f32 * ms / 1000.0).max(1.0)
+    }
+}
+
+/// Square wave generator shaped by the envelope
+pub struct SquareWave {
+    phase: f32,
+    phase_inc: f32,
+    volume: f32,
+    envelope: Envelope,
+}
+
+impl SquareWave {
+    pub fn new(sample_rate: i32, config: &AudioConfig) -> Self {
+        Self {
+            phase: 0.0,
+            phase_inc: config.tone_hz / sample_rate as f32,
+            volume: config.volume,
+            envelope: Envelope::new(sample_rate, config.attack_ms, config.release_ms),
+        }
+    }
+
+    /// Takes the tone, volume and ramps from config from the next sample on, keeping the phase
+    /// and level so the wave doesn't click
+    pub fn set_config(&mut self, sample_rate: i32, config: &AudioConfig) {
+        self.phase_inc = config.tone_hz / sample_rate as f32;
+        self.volume = config.volume;
+        self.envelope.set_ramps(sample_rate, config.attack_ms, config.release_ms);
+    }
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            let level = self.envelope.next_level();
+            let wave = if self.phase < 0.5 { self.volume } else { -self.volume };
+            *sample = wave * level;
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+/// Beeper driven by the sound timer.
+/// The device plays continuously (silence while the gate is closed) so releases can finish
+pub struct Audio {
+    device: AudioDevice<SquareWave>,
+    sample_rate: i32, // What the device opened with, which may not be SAMPLE_RATE
+    beeping: bool,
+}
+
+impl Audio {
+    pub fn new(sdl_ctx: &sdl2::Sdl, config: &AudioConfig) -> Result<Self, String> {
+        let audio = sdl_ctx.audio()?;
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device = audio.open_playback(None, &desired, |spec| SquareWave::new(spec.freq, config))?;
+        device.resume();
+        let sample_rate = device.spec().freq;
+
+        Ok(Self { device, sample_rate, beeping: false })
+    }
+
+    /// Takes the tone, volume and envelope ramps from an edited config
+    pub fn set_config(&mut self, config: &AudioConfig) {
+        self.device.lock().set_config(self.sample_rate, config);
+    }
+
+    pub fn set_beeping(&mut self, on: bool) {
+        if on != self.beeping {
+            self.device.lock().envelope.set_gate(on);
+            self.beeping = on;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: i32 = 48_000;
+
+    /// Runs the envelope for n samples, asserting no step exceeds the allowed slope
+    fn run(envelope: &mut Envelope, samples: usize, max_step: f32, prev: &mut f32) {
+        for _ in 0..samples {
+            let level = envelope.next_level();
+            assert!((level - *prev).abs() <= max_step + f32::EPSILON, "jump from {} to {}", prev, level);
+            *prev = level;
+        }
+    }
+
+    #[test]
+    fn test_attack_and_release_ramp() {
+        // 2ms at 48kHz = 96 samples per ramp
+        let mut envelope = Envelope::new(RATE, 2.0, 2.0);
+        let step = 1.0 / 96.0;
+        let mut prev = 0.0;
+
+        envelope.set_gate(true);
+        run(&mut envelope, 95, step, &mut prev);
+        assert!(prev < 1.0);
+        run(&mut envelope, 1, step, &mut prev);
+        assert!((prev - 1.0).abs() < 1e-4);
+
+        envelope.set_gate(false);
+        run(&mut envelope, 97, step, &mut prev);
+        assert_eq!(prev, 0.0);
+    }
+
+    #[test]
+    fn test_retrigger_during_release_continues_from_current_level() {
+        let mut envelope = Envelope::new(RATE, 1.0, 5.0);
+        let max_step = Envelope::step(RATE, 1.0);
+        let mut prev = 0.0;
+
+        envelope.set_gate(true);
+        run(&mut envelope, 100, max_step, &mut prev);
+        envelope.set_gate(false);
+        run(&mut envelope, 60, max_step, &mut prev);
+        let mid_release = prev;
+        assert!(mid_release > 0.0 && mid_release < 1.0);
+
+        envelope.set_gate(true);
+        let level = envelope.next_level();
+        assert!(level > mid_release, "re-attack restarted from zero");
+        prev = level;
+        run(&mut envelope, 100, max_step, &mut prev);
+        assert_eq!(prev, 1.0);
+    }
+
+    #[test]
+    fn test_new_ramps_continue_from_current_level() {
+        let mut envelope = Envelope::new(RATE, 1.0, 1.0);
+        let mut prev = 0.0;
+        envelope.set_gate(true);
+        run(&mut envelope, 20, Envelope::step(RATE, 1.0), &mut prev);
+
+        // 5ms now, so each step is a fifth of the old ones
+        envelope.set_ramps(RATE, 5.0, 5.0);
+        let max_step = Envelope::step(RATE, 5.0);
+        run(&mut envelope, 1, max_step, &mut prev);
+        envelope.set_gate(false);
+        run(&mut envelope, 240, max_step, &mut prev);
+        assert_eq!(prev, 0.0);
+    }
+
+    #[test]
+    fn test_rapid_toggling_stays_continuous() {
+        let mut envelope = Envelope::new(RATE, 3.0, 3.0);
+        let max_step = Envelope::step(RATE, 3.0);
+        let mut prev = 0.0;
+
+        for i in 0..200 {
+            envelope.set_gate(i % 2 == 0);
+            run(&mut envelope, 7, max_step, &mut prev);
+        }
+    }
+
+    #[test]
+    fn test_square_wave_shaped_by_envelope() {
+        let config = AudioConfig { attack_ms: 1.0, release_ms: 1.0, ..Default::default() };
+        let volume = config.volume;
+        let mut wave = SquareWave::new(RATE, &config);
+        let mut out = vec![1.0; 256];
+
+        wave.callback(&mut out);
+        assert!(out.iter().all(|s| *s == 0.0));
+
+        wave.envelope.set_gate(true);
+        wave.callback(&mut out);
+        assert!(out[0].abs() < volume / 10.0);
+        assert!(out.iter().all(|s| s.abs() <= volume));
+        assert!(out.iter().any(|s| s.abs() == volume));
+    }
+
+    #[test]
+    fn test_tone_and_volume_follow_the_config() {
+        let mut wave = SquareWave::new(RATE, &AudioConfig { attack_ms: 1.0, ..Default::default() });
+        wave.envelope.set_gate(true);
+        let mut out = vec![0.0; RATE as usize / 10];
+        wave.callback(&mut out);
+
+        // 375 Hz is a half-cycle every 64 samples at 48 kHz
+        wave.set_config(RATE, &AudioConfig { volume: 0.5, tone_hz: 375.0, ..Default::default() });
+        wave.phase = 0.0;
+        wave.callback(&mut out);
+        assert!(out[..64].iter().all(|s| *s == 0.5));
+        assert!(out[64..128].iter().all(|s| *s == -0.5));
+    }
+}
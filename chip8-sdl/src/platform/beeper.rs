@@ -0,0 +1,55 @@
+#[cfg(feature = "audio")]
+use super::Audio;
+use super::Display;
+use chip8_core::config::AudioConfig;
+
+/// Sounds the emulator's beeper on the audio device, or flashes the window's border when there
+/// is no device (or the build has no `audio` feature), so beeps are never lost silently
+pub struct Beeper {
+    #[cfg(feature = "audio")]
+    audio: Option<Audio>,
+    on: bool, // What the emulator last asked for, muted or not
+    muted: bool,
+}
+
+impl Beeper {
+    pub fn new(sdl_ctx: &sdl2::Sdl, config: &AudioConfig) -> Self {
+        #[cfg(feature = "audio")]
+        {
+            let audio = Audio::new(sdl_ctx, config)
+                .map_err(|e| eprintln!("Audio disabled, flashing the border for beeps: {}", e))
+                .ok();
+            Self { audio, on: false, muted: config.muted }
+        }
+        #[cfg(not(feature = "audio"))]
+        {
+            let _ = sdl_ctx;
+            Self { on: false, muted: config.muted }
+        }
+    }
+
+    /// Takes mute, the tone, volume and envelope ramps from an edited config; the border flash
+    /// only minds mute. A beep going on when unmuted carries on
+    pub fn set_config(&mut self, config: &AudioConfig, display: &mut Display) {
+        #[cfg(feature = "audio")]
+        if let Some(audio) = self.audio.as_mut() {
+            audio.set_config(config);
+        }
+        if config.muted != self.muted {
+            self.muted = config.muted;
+            self.set_beeping(self.on, display);
+        }
+    }
+
+    /// Follows Emulator::sound, or whatever else says the beeper is on
+    pub fn set_beeping(&mut self, on: bool, display: &mut Display) {
+        self.on = on;
+        let on = on && !self.muted;
+        #[cfg(feature = "audio")]
+        if let Some(audio) = self.audio.as_mut() {
+            audio.set_beeping(on);
+            return;
+        }
+        display.set_beep_flash(on);
+    }
+}
@@ -0,0 +1,547 @@
+use std::time::{Duration, Instant};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::{SwapInterval, Window};
+use chip8_core::blend::{self, FrameBlend, MAX_BLEND_FRAMES};
+use chip8_core::config::{DisplayConfig, Rgb};
+use chip8_core::constants::{DISPLAY_WIDTH, DISPLAY_HEIGHT, INPUTS_COUNT, KEYPAD_LAYOUT};
+use crate::font;
+use crate::library::Preview;
+use crate::platform::hidpi;
+use chip8_core::pacing::VsyncMode;
+use chip8_core::sink::{self, FrameSink};
+
+pub struct Display {
+    canvas: Canvas<Window>,
+    config: DisplayConfig,
+    scale: u32, // Drawable pixels per lo-res CHIP-8 pixel, config.scale on an ordinary screen; hires pixels are half
+    pixel_ratio: f32,
+    vsync: VsyncMode,
+    keypad_overlay: Option<[bool; INPUTS_COUNT]>,
+    help_overlay: Option<Vec<String>>,
+    preview: Option<Preview>, // Drawn beside the help overlay's text
+    beep_flash: bool,
+    blend: FrameBlend, // The last frames presented, a single one unless blending
+    levels: Vec<u8>,
+    backbuffer: Option<Texture>, // Previously presented picture, so only changed rows need drawing
+    invalidated: bool,
+    dirty_rows: u64, // Rows the next draw() redraws on top of the backbuffer
+    rects: [Vec<Rect>; MAX_BLEND_FRAMES as usize], // Lit runs by blend level, reused every frame to avoid allocating
+    row_bands: Vec<Rect>,
+    stats: RenderStats,
+}
+
+/// Debug counters for the last rendered frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderStats {
+    pub rows_drawn: u32,
+    pub lit_pixels: usize,
+    pub rects: usize,
+    pub draw_calls: usize,
+    pub frame_time: Duration,
+}
+
+impl std::fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} rows redrawn, {} lit pixels drawn as {} rects in {} draw call(s), {:?}",
+            self.rows_drawn, self.lit_pixels, self.rects, self.draw_calls, self.frame_time
+        )
+    }
+}
+
+/// Splits a row of colored pixels into runs of one color, as (start, length, color)
+pub fn color_runs(row: &[Rgb]) -> impl Iterator<Item = (usize, usize, Rgb)> + '_ {
+    let mut x = 0;
+    std::iter::from_fn(move || {
+        let start = x;
+        let color = *row.get(start)?;
+        while x < row.len() && row[x] == color {
+            x += 1;
+        }
+        Some((start, x - start, color))
+    })
+}
+
+/// Where a preview goes on an overlay `width` wide: at the top right, about two fifths of the
+/// width, in whole pixels of the 64x32 thumbnail
+pub fn preview_rect(width: u32, margin: u32) -> Rect {
+    let pixel = (width * 2 / 5 / DISPLAY_WIDTH as u32).max(1);
+    let (w, h) = (DISPLAY_WIDTH as u32 * pixel, DISPLAY_HEIGHT as u32 * pixel);
+    Rect::new(width.saturating_sub(margin + w) as i32, margin as i32, w, h)
+}
+
+/// Splits a row of pixels into runs of consecutive lit pixels, as (start, length) pairs
+pub fn row_runs(row: &[u8]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut x = 0;
+    std::iter::from_fn(move || {
+        while x < row.len() && row[x] == 0 {
+            x += 1;
+        }
+        if x == row.len() {
+            return None;
+        }
+
+        let start = x;
+        while x < row.len() && row[x] != 0 {
+            x += 1;
+        }
+        Some((start, x - start))
+    })
+}
+
+impl Display {
+    pub fn new(sdl_ctx: &sdl2::Sdl, config: &DisplayConfig) -> Result<Self, String> {
+        let video = sdl_ctx.video()?;
+        let width = DISPLAY_WIDTH as u32 * config.scale;
+        let height = DISPLAY_HEIGHT as u32 * config.scale;
+
+        let mut window = video.window(&config.title, width, height);
+        window.position_centered().allow_highdpi();
+        if config.fullscreen {
+            window.fullscreen_desktop();
+        }
+        if config.resizable {
+            window.resizable();
+        }
+        let window = window.build().map_err(|e| e.to_string())?;
+
+        let mut builder = window.into_canvas();
+        if config.vsync != VsyncMode::Off {
+            builder = builder.present_vsync();
+        }
+        let mut canvas = builder.build().map_err(|e| e.to_string())?;
+
+        // A HiDPI drawable has more pixels than the window has coordinates. Drawing at its
+        // resolution keeps CHIP-8 pixels sharp rather than stretched
+        let pixel_ratio = hidpi::pixel_ratio(canvas.window().size(), canvas.output_size()?);
+        let scale = hidpi::to_pixels(config.scale, pixel_ratio);
+        let (width, height) = (DISPLAY_WIDTH as u32 * scale, DISPLAY_HEIGHT as u32 * scale);
+
+        // Draw in CHIP-8 scaled coordinates and let SDL stretch/letterbox into
+        // fullscreen or resized windows
+        canvas.set_logical_size(width, height).map_err(|e| e.to_string())?;
+
+        // Without render target support every frame is drawn from scratch
+        let backbuffer = canvas.texture_creator()
+            .create_texture_target(None, width, height)
+            .map_err(|e| eprintln!("No backbuffer texture ({}), redrawing full frames", e))
+            .ok();
+
+        // Late swap tearing is only available on some (OpenGL) renderers
+        let vsync = match config.vsync {
+            VsyncMode::Adaptive => match video.gl_set_swap_interval(SwapInterval::LateSwapTearing) {
+                Ok(()) => VsyncMode::Adaptive,
+                Err(e) => {
+                    eprintln!("Adaptive vsync unavailable ({}), falling back to vsync on", e);
+                    VsyncMode::On
+                }
+            },
+            mode => mode,
+        };
+
+        let mut display = Self {
+            canvas,
+            config: config.clone(),
+            scale,
+            pixel_ratio,
+            vsync,
+            keypad_overlay: None,
+            help_overlay: None,
+            preview: None,
+            beep_flash: false,
+            blend: FrameBlend::new(config.blend_frames),
+            levels: Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT),
+            backbuffer,
+            invalidated: true,
+            dirty_rows: u64::MAX,
+            rects: std::array::from_fn(|_| Vec::with_capacity(DISPLAY_WIDTH * DISPLAY_HEIGHT)),
+            row_bands: Vec::with_capacity(DISPLAY_HEIGHT),
+            stats: RenderStats::default(),
+        };
+        display.update_title();
+
+        Ok(display)
+    }
+
+    /// Applies the settings that can change while the window is open (palette, grid, blending and
+    /// title), redrawing the last frame if the picture changed
+    pub fn apply(&mut self, config: &DisplayConfig) {
+        let changes = self.config.runtime_changes(config);
+
+        self.config.palette = config.palette;
+        self.config.grid = config.grid;
+        self.config.blend_frames = config.blend_frames;
+        self.config.title = config.title.clone();
+
+        if changes.title {
+            self.update_title();
+        }
+        if changes.blend_frames {
+            self.blend.set_window(config.blend_frames);
+        }
+        if changes.needs_redraw() {
+            self.invalidated = true;
+            self.draw(|_| {});
+        }
+    }
+
+    pub fn config(&self) -> &DisplayConfig {
+        &self.config
+    }
+
+    /// The size the picture is drawn at in drawable pixels, before SDL scales it to the window
+    pub fn logical_size(&self) -> (u32, u32) {
+        (DISPLAY_WIDTH as u32 * self.scale, DISPLAY_HEIGHT as u32 * self.scale)
+    }
+
+    /// Drawable pixels per window coordinate: 2 on a typical HiDPI screen
+    pub fn pixel_ratio(&self) -> f32 {
+        self.pixel_ratio
+    }
+
+    /// The vsync mode actually in effect, after any fallback
+    pub fn vsync(&self) -> VsyncMode {
+        self.vsync
+    }
+
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Forgets the backbuffer contents (e.g. after the renderer lost its targets),
+    /// so the next frame is drawn in full
+    pub fn invalidate(&mut self) {
+        self.invalidated = true;
+    }
+
+    /// Redraws the whole frame
+    pub fn render(&mut self, buffer: &[u8]) {
+        self.render_rows(buffer, u64::MAX);
+    }
+
+    /// Redraws only the rows set in dirty_rows (bit N = row N) on top of the previous frame
+    pub fn render_rows(&mut self, buffer: &[u8], dirty_rows: u64) {
+        self.render_rows_with(buffer, dirty_rows, |_| {});
+    }
+
+    /// Like render_rows, with overlay drawing on top of everything else just before the frame
+    /// is presented, in the same logical coordinates as the picture
+    pub fn render_rows_with(&mut self, buffer: &[u8], dirty_rows: u64, overlay: impl FnOnce(&mut Canvas<Window>)) {
+        self.dirty_rows = self.blend.push(buffer, dirty_rows);
+        self.draw(overlay);
+    }
+
+    /// Draws the blended frames, redrawing the rows in self.dirty_rows, and presents them
+    fn draw(&mut self, overlay: impl FnOnce(&mut Canvas<Window>)) {
+        let start = Instant::now();
+        let palette = self.config.palette;
+        let width = DISPLAY_WIDTH as u32 * self.scale;
+        let window = self.blend.window();
+
+        let dirty_rows = if self.invalidated || self.backbuffer.is_none() { u64::MAX } else { self.dirty_rows };
+        self.invalidated = false;
+
+        // Horizontal runs of equally lit pixels become one rect each, submitted in one call per
+        // shade: a single call when not blending. An unchanged frame skips all of it and only
+        // shows the backbuffer again
+        self.rects.iter_mut().for_each(Vec::clear);
+        self.row_bands.clear();
+        if dirty_rows != 0 {
+            self.blend.levels(&mut self.levels);
+        }
+        let columns = sink::frame_width(&self.levels);
+        let at = self.pixel_edges(columns);
+        for (y, row) in self.levels.chunks_exact(columns).enumerate() {
+            if dirty_rows & (1 << y) == 0 {
+                continue;
+            }
+
+            let (top, bottom) = (at(y), at(y + 1));
+            self.row_bands.push(Rect::new(0, top, width, (bottom - top) as u32));
+            for (x, len, level) in blend::level_runs(row) {
+                self.rects[level as usize - 1].push(Rect::new(
+                    at(x),
+                    top,
+                    (at(x + len) - at(x)) as u32,
+                    (bottom - top) as u32,
+                ));
+            }
+        }
+
+        self.canvas.set_draw_color(Display::color(palette.background));
+        self.canvas.clear();
+
+        let (row_bands, rects) = (&self.row_bands, &self.rects);
+        let draw_rows = |target: &mut Canvas<Window>| {
+            target.set_draw_color(Display::color(palette.background));
+            target.fill_rects(row_bands).ok();
+            for (level, rects) in (1..=window).zip(rects).filter(|(_, rects)| !rects.is_empty()) {
+                target.set_draw_color(Display::color(blend::shade(palette.background, palette.foreground, level, window)));
+                target.fill_rects(rects).ok();
+            }
+        };
+
+        match self.backbuffer.as_mut() {
+            Some(backbuffer) => {
+                if dirty_rows != 0 {
+                    self.canvas.with_texture_canvas(backbuffer, draw_rows).ok();
+                }
+                self.canvas.copy(backbuffer, None, None).ok();
+            }
+            None => draw_rows(&mut self.canvas),
+        }
+
+        if self.config.grid {
+            self.draw_grid();
+        }
+
+        if self.beep_flash {
+            self.draw_border();
+        }
+
+        if let Some(keys) = self.keypad_overlay {
+            self.draw_keypad(&keys);
+        }
+
+        if let Some(lines) = self.help_overlay.take() {
+            let preview = self.preview.take();
+            self.draw_text(&lines, preview.as_ref());
+            (self.help_overlay, self.preview) = (Some(lines), preview);
+        }
+
+        overlay(&mut self.canvas);
+        self.canvas.present();
+
+        self.stats = RenderStats {
+            rows_drawn: self.row_bands.len() as u32,
+            lit_pixels: self.levels.iter().filter(|level| **level != 0).count(),
+            rects: self.rects.iter().map(Vec::len).sum(),
+            draw_calls: self.rects.iter().filter(|rects| !rects.is_empty()).count(),
+            frame_time: start.elapsed(),
+        };
+    }
+
+    /// Shows a small keypad in the top-right corner with the given keys highlighted,
+    /// or hides it when None
+    pub fn set_keypad_overlay(&mut self, keys: Option<&[bool; INPUTS_COUNT]>) {
+        self.keypad_overlay = keys.copied();
+    }
+
+    /// Frames the picture in the foreground colour while on, standing in for the beeper
+    pub fn set_beep_flash(&mut self, on: bool) {
+        self.beep_flash = on;
+    }
+
+    /// Covers the picture with the given lines of text, or uncovers it when None. Any preview
+    /// went with the previous text
+    pub fn set_help_overlay(&mut self, lines: Option<Vec<String>>) {
+        self.help_overlay = lines;
+        self.preview = None;
+    }
+
+    /// Shows a preview to the right of the help overlay's text, narrowing the text to make room
+    pub fn set_preview(&mut self, preview: Option<&Preview>) {
+        self.preview = preview.cloned();
+    }
+
+    /// Where the drawable edge of each pixel lies, for frames `columns` pixels wide: every
+    /// `scale` pixels in lo-res, every half of that in hires
+    fn pixel_edges(&self, columns: usize) -> impl Fn(usize) -> i32 + use<> {
+        let width = DISPLAY_WIDTH as u32 * self.scale;
+        move |pixel| (pixel as u32 * width / columns as u32) as i32
+    }
+
+    fn draw_grid(&mut self) {
+        let palette = self.config.palette;
+        let columns = sink::frame_width(&self.levels);
+        let rows = self.levels.len() / columns;
+        let at = self.pixel_edges(columns);
+        let (width, height) = (at(columns), at(rows));
+
+        // A faint line, a fifth of the way from the background to the foreground
+        let mix = |bg: u8, fg: u8| (bg as i32 + (fg as i32 - bg as i32) / 5) as u8;
+        self.canvas.set_draw_color(Color::RGB(
+            mix(palette.background.0, palette.foreground.0),
+            mix(palette.background.1, palette.foreground.1),
+            mix(palette.background.2, palette.foreground.2),
+        ));
+
+        for x in 1..columns {
+            self.canvas.draw_line((at(x), 0), (at(x), height)).ok();
+        }
+        for y in 1..rows {
+            self.canvas.draw_line((0, at(y)), (width, at(y))).ok();
+        }
+    }
+
+    fn draw_border(&mut self) {
+        let (width, height) = self.logical_size();
+        let thickness = (self.scale / 2).max(2);
+        self.canvas.set_draw_color(Display::color(self.config.palette.foreground));
+        self.canvas.fill_rects(&[
+            Rect::new(0, 0, width, thickness),
+            Rect::new(0, (height - thickness) as i32, width, thickness),
+            Rect::new(0, 0, thickness, height),
+            Rect::new((width - thickness) as i32, 0, thickness, height),
+        ]).ok();
+    }
+
+    fn update_title(&mut self) {
+        let title = format!("{} (vsync {})", self.config.title, self.vsync);
+        self.canvas.window_mut().set_title(&title).ok();
+    }
+
+    fn color(rgb: Rgb) -> Color {
+        Color::RGB(rgb.0, rgb.1, rgb.2)
+    }
+
+    /// Lines of text on a dark backdrop, as large as fits the window, with the preview if any
+    /// beside them
+    fn draw_text(&mut self, lines: &[String], preview: Option<&Preview>) {
+        let (width, height) = self.logical_size();
+        let margin = self.scale;
+        let picture = preview.map(|_| preview_rect(width, margin));
+        let text_width = picture.map_or(width, |picture| picture.x() as u32).saturating_sub(2 * margin);
+        let size = font::fit_size(lines, text_width, height - 2 * margin);
+
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 220));
+        self.canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+        self.canvas.fill_rect(Rect::new(0, 0, width, height)).ok();
+        self.canvas.set_blend_mode(sdl2::render::BlendMode::None);
+
+        let rects = lines.iter().enumerate().flat_map(|(line, text)| {
+            font::text_pixels(text).map(move |(x, y)| Rect::new(
+                (margin + x as u32 * size) as i32,
+                (margin + (line * font::LINE_HEIGHT + y) as u32 * size) as i32,
+                size,
+                size,
+            ))
+        }).collect::<Vec<_>>();
+        self.canvas.set_draw_color(Color::RGB(230, 230, 230));
+        self.canvas.fill_rects(&rects).ok();
+
+        if let (Some(preview), Some(mut picture)) = (preview, picture) {
+            // Level with the list, below the title and the blank line after it
+            let top = margin + 2 * font::LINE_HEIGHT as u32 * size;
+            picture.set_y(top.min(height.saturating_sub(margin + picture.height())) as i32);
+            self.draw_preview(preview, picture);
+        }
+    }
+
+    /// A thumbnail in its own colors filling area, or an empty box outlining it
+    fn draw_preview(&mut self, preview: &Preview, area: Rect) {
+        match preview {
+            Preview::Thumbnail(thumbnail) => {
+                let pixel = area.width() / DISPLAY_WIDTH as u32;
+                for (y, row) in thumbnail.pixels.chunks_exact(DISPLAY_WIDTH).enumerate() {
+                    for (x, len, color) in color_runs(row) {
+                        self.canvas.set_draw_color(Display::color(color));
+                        self.canvas.fill_rect(Rect::new(
+                            area.x() + (x as u32 * pixel) as i32,
+                            area.y() + (y as u32 * pixel) as i32,
+                            len as u32 * pixel,
+                            pixel,
+                        )).ok();
+                    }
+                }
+            }
+            Preview::Placeholder => {
+                self.canvas.set_draw_color(Color::RGB(90, 90, 90));
+                self.canvas.draw_rect(area).ok();
+            }
+        }
+    }
+
+    fn draw_keypad(&mut self, keys: &[bool; INPUTS_COUNT]) {
+        let scale = self.scale;
+        let cell = (scale / 2).max(4);
+        let origin_x = (DISPLAY_WIDTH as u32 * scale) - (cell * 4) - cell;
+        let origin_y = cell;
+
+        for (row, row_keys) in KEYPAD_LAYOUT.iter().enumerate() {
+            for (col, key) in row_keys.iter().enumerate() {
+                let rect = Rect::new(
+                    (origin_x + col as u32 * cell) as i32,
+                    (origin_y + row as u32 * cell) as i32,
+                    cell - 1,
+                    cell - 1,
+                );
+
+                if keys[*key] {
+                    self.canvas.set_draw_color(Color::RGB(255, 200, 0));
+                    self.canvas.fill_rect(rect).ok();
+                } else {
+                    self.canvas.set_draw_color(Color::RGB(90, 90, 90));
+                    self.canvas.draw_rect(rect).ok();
+                }
+            }
+        }
+    }
+}
+
+impl FrameSink for Display {
+    fn draw(&mut self, buffer: &[u8]) {
+        self.render(buffer);
+    }
+
+    fn draw_rows(&mut self, buffer: &[u8], dirty_rows: u64) {
+        self.render_rows(buffer, dirty_rows);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs(row: &[u8]) -> Vec<(usize, usize)> {
+        row_runs(row).collect()
+    }
+
+    #[test]
+    fn test_row_runs() {
+        assert_eq!(runs(&[0, 0, 0, 0]), vec![]);
+        assert_eq!(runs(&[1, 1, 1, 1]), vec![(0, 4)]);
+        assert_eq!(runs(&[1, 0, 1, 1, 0, 0, 1]), vec![(0, 1), (2, 2), (6, 1)]);
+        assert_eq!(runs(&[0, 1, 1, 0]), vec![(1, 2)]);
+        assert_eq!(runs(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_row_runs_cover_every_lit_pixel() {
+        let mut row = [0u8; DISPLAY_WIDTH];
+        for x in (0..DISPLAY_WIDTH).filter(|x| x % 3 != 0 || x % 7 == 0) {
+            row[x] = 1;
+        }
+
+        let mut rebuilt = [0u8; DISPLAY_WIDTH];
+        for (start, len) in row_runs(&row) {
+            assert!(len > 0);
+            rebuilt[start..start + len].fill(1);
+        }
+        assert_eq!(rebuilt, row);
+    }
+
+    #[test]
+    fn test_color_runs() {
+        let (a, b) = (Rgb(0, 0, 0), Rgb(255, 255, 255));
+        let runs = |row: &[Rgb]| color_runs(row).collect::<Vec<_>>();
+        assert_eq!(runs(&[a, a, b, a]), vec![(0, 2, a), (2, 1, b), (3, 1, a)]);
+        assert_eq!(runs(&[b; 4]), vec![(0, 4, b)]);
+        assert_eq!(runs(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_preview_rect() {
+        // Whole thumbnail pixels at the top right, inside the margin
+        let rect = preview_rect(640, 10);
+        assert_eq!((rect.width(), rect.height()), (256, 128));
+        assert_eq!((rect.right(), rect.y()), (630, 10));
+
+        // Never smaller than one window pixel per thumbnail pixel
+        let rect = preview_rect(64, 1);
+        assert_eq!((rect.x(), rect.width(), rect.height()), (0, 64, 32));
+    }
+}
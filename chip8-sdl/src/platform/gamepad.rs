@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+use sdl2::controller::{Axis, Button};
+use chip8_core::config::GamepadBindings;
+use chip8_core::constants::INPUTS_COUNT;
+
+/// Stick deflection (as a fraction of full travel) needed to press a direction
+const PRESS_THRESHOLD: f32 = 0.5;
+/// Deflection below which a pressed direction is released.
+/// The gap between the two thresholds stops the key from chattering near the edge
+const RELEASE_THRESHOLD: f32 = 0.3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Deflection {
+    Neutral,
+    Negative,
+    Positive,
+}
+
+/// Turns the left stick into up to two held directions (one per axis) with hysteresis
+pub struct StickMapper {
+    x: Deflection,
+    y: Deflection,
+}
+
+impl Default for StickMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StickMapper {
+    pub fn new() -> Self {
+        Self { x: Deflection::Neutral, y: Deflection::Neutral }
+    }
+
+    /// Feeds an axis-motion event. Axes other than the left stick are ignored
+    pub fn update(&mut self, axis: Axis, value: i16) {
+        let value = value as f32 / i16::MAX as f32;
+        match axis {
+            Axis::LeftX => self.x = StickMapper::next(self.x, value),
+            Axis::LeftY => self.y = StickMapper::next(self.y, value),
+            _ => {}
+        }
+    }
+
+    pub fn left(&self) -> bool {
+        self.x == Deflection::Negative
+    }
+
+    pub fn right(&self) -> bool {
+        self.x == Deflection::Positive
+    }
+
+    /// SDL reports up as negative Y
+    pub fn up(&self) -> bool {
+        self.y == Deflection::Negative
+    }
+
+    pub fn down(&self) -> bool {
+        self.y == Deflection::Positive
+    }
+
+    fn next(current: Deflection, value: f32) -> Deflection {
+        if value > PRESS_THRESHOLD {
+            return Deflection::Positive;
+        }
+        if value < -PRESS_THRESHOLD {
+            return Deflection::Negative;
+        }
+
+        match current {
+            Deflection::Positive if value < RELEASE_THRESHOLD => Deflection::Neutral,
+            Deflection::Negative if value > -RELEASE_THRESHOLD => Deflection::Neutral,
+            _ => current,
+        }
+    }
+}
+
+/// Game controller state mapped onto the CHIP-8 keypad
+pub struct Gamepad {
+    bindings: GamepadBindings,
+    buttons: HashSet<Button>,
+    stick: StickMapper,
+}
+
+impl Gamepad {
+    pub fn new(bindings: GamepadBindings) -> Self {
+        Self { bindings, buttons: HashSet::new(), stick: StickMapper::new() }
+    }
+
+    /// Remaps the controller; buttons and the stick stay as they are held
+    pub fn set_bindings(&mut self, bindings: GamepadBindings) {
+        self.bindings = bindings;
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if pressed {
+            self.buttons.insert(button);
+        } else {
+            self.buttons.remove(&button);
+        }
+    }
+
+    pub fn set_axis(&mut self, axis: Axis, value: i16) {
+        self.stick.update(axis, value);
+    }
+
+    /// CHIP-8 keys currently held by the D-pad, face buttons, or stick
+    pub fn keys(&self) -> [bool; INPUTS_COUNT] {
+        let mut keys = [false; INPUTS_COUNT];
+        let held = |button| self.buttons.contains(&button);
+
+        keys[self.bindings.up] |= held(Button::DPadUp) || self.stick.up();
+        keys[self.bindings.down] |= held(Button::DPadDown) || self.stick.down();
+        keys[self.bindings.left] |= held(Button::DPadLeft) || self.stick.left();
+        keys[self.bindings.right] |= held(Button::DPadRight) || self.stick.right();
+
+        if let Some(key) = self.bindings.a {
+            keys[key] |= held(Button::A);
+        }
+        if let Some(key) = self.bindings.b {
+            keys[key] |= held(Button::B);
+        }
+
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converts a fraction of full deflection into a raw axis value
+    fn raw(fraction: f32) -> i16 {
+        (fraction * i16::MAX as f32) as i16
+    }
+
+    #[test]
+    fn test_press_and_release_thresholds() {
+        let mut stick = StickMapper::new();
+
+        stick.update(Axis::LeftX, raw(0.45));
+        assert!(!stick.right());
+        stick.update(Axis::LeftX, raw(0.55));
+        assert!(stick.right());
+
+        // Between the thresholds the key stays down
+        stick.update(Axis::LeftX, raw(0.4));
+        assert!(stick.right());
+        stick.update(Axis::LeftX, raw(0.31));
+        assert!(stick.right());
+
+        stick.update(Axis::LeftX, raw(0.29));
+        assert!(!stick.right());
+
+        // And coming back up it stays released until the press threshold
+        stick.update(Axis::LeftX, raw(0.45));
+        assert!(!stick.right());
+    }
+
+    #[test]
+    fn test_no_chatter_near_threshold() {
+        let mut stick = StickMapper::new();
+        let mut transitions = 0;
+        let mut was_down = false;
+
+        for value in [0.51, 0.49, 0.52, 0.48, 0.5, 0.53, 0.35, 0.51] {
+            stick.update(Axis::LeftY, raw(-value));
+            if stick.up() != was_down {
+                transitions += 1;
+                was_down = stick.up();
+            }
+        }
+
+        assert_eq!(transitions, 1);
+        assert!(stick.up());
+    }
+
+    #[test]
+    fn test_flick_to_opposite_direction() {
+        let mut stick = StickMapper::new();
+
+        stick.update(Axis::LeftX, raw(-0.9));
+        assert!(stick.left());
+        stick.update(Axis::LeftX, raw(0.9));
+        assert!(!stick.left());
+        assert!(stick.right());
+    }
+
+    #[test]
+    fn test_diagonal_holds_two_keys() {
+        let mut pad = Gamepad::new(GamepadBindings::default());
+
+        pad.set_axis(Axis::LeftX, raw(0.7));
+        pad.set_axis(Axis::LeftY, raw(0.7));
+        let keys = pad.keys();
+        assert!(keys[0x6] && keys[0x8]);
+        assert_eq!(keys.iter().filter(|k| **k).count(), 2);
+
+        pad.set_axis(Axis::LeftX, 0);
+        let keys = pad.keys();
+        assert!(!keys[0x6] && keys[0x8]);
+    }
+
+    #[test]
+    fn test_other_axes_ignored() {
+        let mut pad = Gamepad::new(GamepadBindings::default());
+        pad.set_axis(Axis::RightX, i16::MAX);
+        pad.set_axis(Axis::TriggerLeft, i16::MAX);
+        assert_eq!(pad.keys(), [false; INPUTS_COUNT]);
+    }
+
+    #[test]
+    fn test_dpad_and_stick_share_keys() {
+        let mut pad = Gamepad::new(GamepadBindings::default());
+
+        pad.set_button(Button::DPadUp, true);
+        pad.set_axis(Axis::LeftY, i16::MIN);
+        pad.set_button(Button::DPadUp, false);
+        assert!(pad.keys()[0x2]);
+
+        pad.set_axis(Axis::LeftY, 0);
+        assert!(!pad.keys()[0x2]);
+
+        pad.set_button(Button::A, true);
+        assert!(pad.keys()[0x5]);
+    }
+}
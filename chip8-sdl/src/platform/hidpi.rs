@@ -0,0 +1,58 @@
+//! Conversions between window coordinates and drawable pixels. With allow-high-dpi, a window
+//! asked for at 1280x640 can get a 2560x1280 drawable (Retina screens, scaled desktops): window
+//! sizes and egui's points are in window coordinates, what the renderer draws is in pixels.
+
+/// Drawable pixels per window coordinate, going by the width: 1 on ordinary screens
+pub fn pixel_ratio(window: (u32, u32), drawable: (u32, u32)) -> f32 {
+    if window.0 == 0 {
+        return 1.0;
+    }
+    drawable.0 as f32 / window.0 as f32
+}
+
+/// A length in window coordinates as whole drawable pixels, never less than one
+pub fn to_pixels(length: u32, ratio: f32) -> u32 {
+    ((length as f32 * ratio).round() as u32).max(1)
+}
+
+/// A point in drawable pixels as window coordinates
+pub fn pixels_to_window(x: f32, y: f32, ratio: f32) -> (f32, f32) {
+    (x / ratio, y / ratio)
+}
+
+/// A point in window coordinates as drawable pixels
+pub fn window_to_pixels(x: f32, y: f32, ratio: f32) -> (f32, f32) {
+    (x * ratio, y * ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_ratio() {
+        assert_eq!(pixel_ratio((1280, 640), (1280, 640)), 1.0);
+        assert_eq!(pixel_ratio((1280, 640), (1920, 960)), 1.5);
+        assert_eq!(pixel_ratio((1280, 640), (2560, 1280)), 2.0);
+        assert_eq!(pixel_ratio((0, 0), (0, 0)), 1.0); // Minimized
+    }
+
+    #[test]
+    fn test_to_pixels() {
+        assert_eq!(to_pixels(20, 1.0), 20);
+        assert_eq!(to_pixels(20, 1.5), 30);
+        assert_eq!(to_pixels(5, 1.5), 8); // 7.5 rounds up
+        assert_eq!(to_pixels(20, 2.0), 40);
+        assert_eq!(to_pixels(1, 0.25), 1);
+    }
+
+    #[test]
+    fn test_points_round_trip() {
+        for ratio in [1.0, 1.5, 2.0] {
+            let (x, y) = window_to_pixels(100.0, 30.0, ratio);
+            assert_eq!((x, y), (100.0 * ratio, 30.0 * ratio));
+            assert_eq!(pixels_to_window(x, y, ratio), (100.0, 30.0));
+        }
+        assert_eq!(pixels_to_window(300.0, 15.0, 1.5), (200.0, 10.0));
+    }
+}
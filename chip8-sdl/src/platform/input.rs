@@ -0,0 +1,526 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use chip8_core::config::KeyBindings;
+use chip8_core::constants::{INPUTS_COUNT};
+use sdl2::keyboard::Keycode;
+
+/// The keymap file read at startup when it exists and --keymap doesn't name another
+pub const KEYMAP_PATH: &str = "keymap.cfg";
+
+pub struct Input {
+    pub keys: [bool; INPUTS_COUNT],
+    primary: HashMap<Keycode, usize>,
+    keymap: Option<HashMap<Keycode, usize>>, // From load_keymap, in place of the configured primary keys
+    player2: HashMap<Keycode, usize>,
+    held: HashSet<Keycode>,
+    remote: [bool; INPUTS_COUNT],
+    gamepad: [bool; INPUTS_COUNT],
+    sticky: bool,
+    latched: [bool; INPUTS_COUNT],
+    pressed_edges: u16, // Bit N set if key N went down since begin_frame
+    released_edges: u16, // Bit N set if key N went up since begin_frame
+}
+
+impl Input {
+    /// Builds the input from configured bindings, skipping key names SDL doesn't know
+    pub fn from_bindings(bindings: &KeyBindings) -> Self {
+        Self::with_keymaps(Input::resolve(&bindings.primary), Input::resolve(&bindings.player2))
+    }
+
+    pub fn with_keymaps(primary: HashMap<Keycode, usize>, player2: HashMap<Keycode, usize>) -> Self {
+        Self {
+            keys: [false; INPUTS_COUNT],
+            primary,
+            keymap: None,
+            player2,
+            held: HashSet::new(),
+            remote: [false; INPUTS_COUNT],
+            gamepad: [false; INPUTS_COUNT],
+            sticky: false,
+            latched: [false; INPUTS_COUNT],
+            pressed_edges: 0,
+            released_edges: 0,
+        }
+    }
+
+    /// Replaces the key bindings. Keys still held count under the new bindings, and a loaded
+    /// keymap still stands in for the primary ones
+    pub fn set_bindings(&mut self, bindings: &KeyBindings) {
+        self.set_keymaps(Input::resolve(&bindings.primary), Input::resolve(&bindings.player2));
+    }
+
+    pub fn set_keymaps(&mut self, primary: HashMap<Keycode, usize>, player2: HashMap<Keycode, usize>) {
+        self.primary = self.keymap.clone().unwrap_or(primary);
+        self.player2 = player2;
+        for key in 0..INPUTS_COUNT {
+            self.refresh(key);
+        }
+    }
+
+    /// Replaces the primary keys with those in a keymap file (see parse_keymap) for as long as
+    /// the input lasts, returning the warnings about lines it skipped. On an error the keys are
+    /// left as they were
+    pub fn load_keymap(&mut self, path: &str) -> Result<Vec<String>, Error> {
+        let text = std::fs::read_to_string(path)?;
+        let (keymap, warnings) = parse_keymap(&text).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+        self.keymap = Some(keymap.clone());
+        let player2 = std::mem::take(&mut self.player2);
+        self.set_keymaps(keymap, player2);
+        Ok(warnings)
+    }
+
+    /// The bindings to show in the keypad diagram: these with a loaded keymap in place of the primary keys
+    pub fn shown_bindings(&self, bindings: &KeyBindings) -> KeyBindings {
+        let primary = match &self.keymap {
+            Some(keymap) => keymap.iter().map(|(keycode, key)| (keycode.name(), *key)).collect(),
+            None => bindings.primary.clone(),
+        };
+        KeyBindings { primary, player2: bindings.player2.clone() }
+    }
+
+    /// Starts a new frame of edges: was_pressed and was_released report changes from here on
+    pub fn begin_frame(&mut self) {
+        self.pressed_edges = 0;
+        self.released_edges = 0;
+    }
+
+    /// True if CHIP-8 key went down since begin_frame, even if it has come back up since
+    pub fn was_pressed(&self, key: usize) -> bool {
+        key < INPUTS_COUNT && self.pressed_edges & (1 << key) != 0
+    }
+
+    /// True if CHIP-8 key went up since begin_frame, even if it has gone down again since
+    pub fn was_released(&self, key: usize) -> bool {
+        key < INPUTS_COUNT && self.released_edges & (1 << key) != 0
+    }
+
+    /// In sticky mode a tap on a key latches it down until it is tapped again,
+    /// for players who can't hold keys continuously
+    pub fn set_sticky(&mut self, sticky: bool) {
+        self.sticky = sticky;
+        if !sticky {
+            self.release_latched();
+        }
+    }
+
+    pub fn is_sticky(&self) -> bool {
+        self.sticky
+    }
+
+    pub fn latched(&self) -> &[bool; INPUTS_COUNT] {
+        &self.latched
+    }
+
+    /// Releases every latched key
+    pub fn release_latched(&mut self) {
+        self.latched = [false; INPUTS_COUNT];
+        for key in 0..INPUTS_COUNT {
+            self.refresh(key);
+        }
+    }
+
+    /// Called when FX0A is waiting on a key to be released. A latched key only satisfies one wait,
+    /// otherwise a ROM looping on FX0A would see it pressed forever
+    pub fn consume_latch(&mut self, key: usize) {
+        if key < INPUTS_COUNT && self.latched[key] {
+            self.latched[key] = false;
+            self.refresh(key);
+        }
+    }
+
+    /// Updates the state of a physical key.
+    /// A CHIP-8 key stays down as long as any physical key bound to it (from either player) is held
+    pub fn set_keycode(&mut self, keycode: Keycode, pressed: bool) {
+        let Some(key) = self.map_sdl_keycode(keycode) else {
+            return;
+        };
+
+        if self.sticky {
+            if pressed {
+                self.latched[key] = !self.latched[key];
+                self.refresh(key);
+            }
+            return;
+        }
+
+        if pressed {
+            self.held.insert(keycode);
+        } else {
+            self.held.remove(&keycode);
+        }
+
+        self.refresh(key);
+    }
+
+    /// Sets the state of a key pressed by a remote player, merged with the local keys
+    pub fn set_remote_key(&mut self, key: usize, pressed: bool) {
+        if key < INPUTS_COUNT {
+            self.remote[key] = pressed;
+            self.refresh(key);
+        }
+    }
+
+    /// Releases every key held by the remote player (e.g. after a disconnect)
+    pub fn clear_remote_keys(&mut self) {
+        self.remote = [false; INPUTS_COUNT];
+        for key in 0..INPUTS_COUNT {
+            self.refresh(key);
+        }
+    }
+
+    /// Replaces the keys held on the game controller, merged with the keyboard like the remote keys
+    pub fn set_gamepad_keys(&mut self, keys: [bool; INPUTS_COUNT]) {
+        self.gamepad = keys;
+        for key in 0..INPUTS_COUNT {
+            self.refresh(key);
+        }
+    }
+
+    /// The merged keypad as a bit mask (bit N = key N), for CPU::set_keys
+    pub fn key_mask(&self) -> u16 {
+        Self::mask(&self.keys)
+    }
+
+    /// The sticky-latched keys as a bit mask, for the latched half of InputEvent::Keys
+    pub fn latched_mask(&self) -> u16 {
+        Self::mask(&self.latched)
+    }
+
+    fn mask(keys: &[bool; INPUTS_COUNT]) -> u16 {
+        keys.iter().enumerate().fold(0, |mask, (key, pressed)| mask | (u16::from(*pressed) << key))
+    }
+
+    /// Map SDL2 keycodes to CHIP-8 hex keypad values, checking the primary bindings first
+    pub fn map_sdl_keycode(&self, keycode: Keycode) -> Option<usize> {
+        self.primary.get(&keycode).or_else(|| self.player2.get(&keycode)).copied()
+    }
+
+    /// Recomputes a CHIP-8 key from every source that can hold it down
+    fn refresh(&mut self, key: usize) {
+        let local = self.held.iter().any(|held| self.map_sdl_keycode(*held) == Some(key));
+        let down = local || self.latched[key] || self.remote[key] || self.gamepad[key];
+        match (self.keys[key], down) {
+            (false, true) => self.pressed_edges |= 1 << key,
+            (true, false) => self.released_edges |= 1 << key,
+            _ => {}
+        }
+        self.keys[key] = down;
+    }
+
+    fn resolve(names: &HashMap<String, usize>) -> HashMap<Keycode, usize> {
+        names.iter()
+            .filter_map(|(name, key)| match Keycode::from_name(name) {
+                Some(keycode) => Some((keycode, *key)),
+                None => {
+                    eprintln!("Unknown key name '{}' in config, ignoring it", name);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Reads a keymap: one `Name=key` line per binding, where Name is an SDL key name and key a
+/// CHIP-8 key from 0 to F, with or without 0x. Blank lines and lines starting with # are skipped,
+/// and so are names SDL doesn't know, each with a warning returned beside the keymap
+pub fn parse_keymap(text: &str) -> Result<(HashMap<Keycode, usize>, Vec<String>), String> {
+    let mut keymap = HashMap::new();
+    let mut warnings = Vec::new();
+    let mut lines_of = HashMap::new();
+    for (number, line) in text.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, key)) = line.split_once('=') else {
+            return Err(format!("line {}: expected Name=key, got '{}'", number, line));
+        };
+        let (name, key) = (name.trim(), key.trim());
+        let digits = key.strip_prefix("0x").or_else(|| key.strip_prefix("0X")).unwrap_or(key);
+        let key = match usize::from_str_radix(digits, 16) {
+            Ok(key) if key < INPUTS_COUNT => key,
+            _ => return Err(format!("line {}: '{}' is not a CHIP-8 key (0x0-0xF)", number, key)),
+        };
+
+        let Some(keycode) = Keycode::from_name(name) else {
+            warnings.push(format!("line {}: unknown key name '{}', ignoring it", number, name));
+            continue;
+        };
+        if let Some(first) = lines_of.insert(keycode, number) {
+            return Err(format!("line {}: '{}' is already bound on line {}", number, name, first));
+        }
+        keymap.insert(keycode, key);
+    }
+    Ok((keymap, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::rom_builder::RomBuilder;
+
+    fn two_player_input() -> Input {
+        Input::with_keymaps(
+            HashMap::from([(Keycode::W, 0x5), (Keycode::S, 0x8)]),
+            HashMap::from([(Keycode::Up, 0x5), (Keycode::Down, 0x8)]),
+        )
+    }
+
+    #[test]
+    fn test_either_binding_presses_key() {
+        let mut input = two_player_input();
+
+        input.set_keycode(Keycode::W, true);
+        assert!(input.keys[0x5]);
+        input.set_keycode(Keycode::W, false);
+        assert!(!input.keys[0x5]);
+
+        input.set_keycode(Keycode::Down, true);
+        assert!(input.keys[0x8]);
+        input.set_keycode(Keycode::Down, false);
+        assert!(!input.keys[0x8]);
+    }
+
+    #[test]
+    fn test_key_held_until_both_bindings_released() {
+        let mut input = two_player_input();
+
+        input.set_keycode(Keycode::W, true);
+        input.set_keycode(Keycode::Up, true);
+        input.set_keycode(Keycode::W, false);
+        assert!(input.keys[0x5]);
+
+        input.set_keycode(Keycode::Up, false);
+        assert!(!input.keys[0x5]);
+    }
+
+    #[test]
+    fn test_unbound_keycode_ignored() {
+        let mut input = two_player_input();
+        input.set_keycode(Keycode::P, true);
+        assert_eq!(input.keys, [false; INPUTS_COUNT]);
+        assert_eq!(input.map_sdl_keycode(Keycode::P), None);
+    }
+
+    #[test]
+    fn test_remote_keys_merged_with_local() {
+        let mut input = two_player_input();
+
+        input.set_remote_key(0x5, true);
+        input.set_keycode(Keycode::W, true);
+        input.set_keycode(Keycode::W, false);
+        assert!(input.keys[0x5]);
+
+        input.set_remote_key(0x5, false);
+        assert!(!input.keys[0x5]);
+
+        // Out of range remote keys are ignored
+        input.set_remote_key(INPUTS_COUNT, true);
+        assert_eq!(input.keys, [false; INPUTS_COUNT]);
+    }
+
+    #[test]
+    fn test_clear_remote_keys_keeps_local() {
+        let mut input = two_player_input();
+
+        input.set_keycode(Keycode::S, true);
+        input.set_remote_key(0x8, true);
+        input.set_remote_key(0x2, true);
+        input.clear_remote_keys();
+
+        assert!(input.keys[0x8]);
+        assert!(!input.keys[0x2]);
+    }
+
+    #[test]
+    fn test_gamepad_keys_merged_with_keyboard() {
+        let mut input = two_player_input();
+        let mut pad = [false; INPUTS_COUNT];
+        pad[0x5] = true;
+
+        input.set_gamepad_keys(pad);
+        input.set_keycode(Keycode::W, true);
+        input.set_keycode(Keycode::W, false);
+        assert!(input.keys[0x5]);
+
+        input.set_gamepad_keys([false; INPUTS_COUNT]);
+        assert!(!input.keys[0x5]);
+    }
+
+    #[test]
+    fn test_sticky_tap_toggles_key() {
+        let mut input = two_player_input();
+        input.set_sticky(true);
+
+        input.set_keycode(Keycode::W, true);
+        input.set_keycode(Keycode::W, false);
+        assert!(input.keys[0x5]);
+        assert!(input.latched()[0x5]);
+        assert_eq!(input.latched_mask(), 1 << 0x5);
+
+        input.set_keycode(Keycode::W, true);
+        input.set_keycode(Keycode::W, false);
+        assert!(!input.keys[0x5]);
+
+        // Either player's binding toggles the same latch
+        input.set_keycode(Keycode::Up, true);
+        assert!(input.keys[0x5]);
+        input.set_keycode(Keycode::W, true);
+        assert!(!input.keys[0x5]);
+    }
+
+    #[test]
+    fn test_sticky_release_all() {
+        let mut input = two_player_input();
+        input.set_sticky(true);
+
+        input.set_keycode(Keycode::W, true);
+        input.set_keycode(Keycode::S, true);
+        input.release_latched();
+
+        assert_eq!(input.keys, [false; INPUTS_COUNT]);
+        assert_eq!(input.latched(), &[false; INPUTS_COUNT]);
+    }
+
+    #[test]
+    fn test_disabling_sticky_releases_latches() {
+        let mut input = two_player_input();
+        input.set_sticky(true);
+        input.set_keycode(Keycode::S, true);
+
+        input.set_sticky(false);
+        assert!(!input.keys[0x8]);
+    }
+
+    #[test]
+    fn test_latched_key_satisfies_one_key_wait() {
+        let mut cpu = RomBuilder::new().label("wait").op(0xF30A).jp("wait").cpu().unwrap(); // LD V3, K
+
+        let mut input = two_player_input();
+        input.set_sticky(true);
+        input.set_keycode(Keycode::S, true);
+        cpu.set_keys(input.key_mask());
+
+        // Seen down, then accepted once the latch lets it up
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x200);
+        let key = cpu.key_wait().unwrap();
+        input.consume_latch(key);
+        cpu.set_keys(input.key_mask());
+        assert!(!input.keys[0x8]);
+
+        cpu.tick().unwrap();
+        assert_eq!(cpu.v[3], 0x8);
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.take_awaited_key(), Some(0x8));
+
+        // Back on FX0A, the consumed latch no longer satisfies it
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!(cpu.key_wait(), None);
+    }
+
+    #[test]
+    fn test_consume_ignores_physically_held_keys() {
+        let mut input = two_player_input();
+        input.set_keycode(Keycode::S, true);
+        input.consume_latch(0x8);
+        assert!(input.keys[0x8]);
+    }
+
+    #[test]
+    fn test_new_keymaps_remap_held_keys() {
+        let mut input = two_player_input();
+        input.set_keycode(Keycode::W, true);
+        assert!(input.keys[0x5]);
+
+        input.set_keymaps(HashMap::from([(Keycode::W, 0x2)]), HashMap::new());
+        assert!(!input.keys[0x5]);
+        assert!(input.keys[0x2]);
+        assert_eq!(input.map_sdl_keycode(Keycode::Up), None);
+
+        input.set_keycode(Keycode::W, false);
+        assert!(!input.keys[0x2]);
+    }
+
+    #[test]
+    fn test_parse_keymap() {
+        let (keymap, warnings) = parse_keymap("# Arrows for the paddles\nUp=5\n  Down = 0x8\n\nLeft=a\nRight=0XF\n").unwrap();
+        assert_eq!(keymap, HashMap::from([(Keycode::Up, 0x5), (Keycode::Down, 0x8), (Keycode::Left, 0xA), (Keycode::Right, 0xF)]));
+        assert!(warnings.is_empty());
+
+        // Names SDL doesn't know are skipped with a warning rather than failing the file
+        let (keymap, warnings) = parse_keymap("NoSuchKey=1\nQ=4").unwrap();
+        assert_eq!(keymap, HashMap::from([(Keycode::Q, 0x4)]));
+        assert_eq!(warnings, ["line 1: unknown key name 'NoSuchKey', ignoring it"]);
+        assert_eq!(parse_keymap("").unwrap(), (HashMap::new(), Vec::new()));
+    }
+
+    #[test]
+    fn test_keymap_validation() {
+        let error = |text: &str| parse_keymap(text).unwrap_err();
+        assert_eq!(error("Up=10"), "line 1: '10' is not a CHIP-8 key (0x0-0xF)");
+        assert_eq!(error("Up=G"), "line 1: 'G' is not a CHIP-8 key (0x0-0xF)");
+        assert_eq!(error("Up=-1"), "line 1: '-1' is not a CHIP-8 key (0x0-0xF)");
+        assert_eq!(error("Up\n"), "line 1: expected Name=key, got 'Up'");
+        assert_eq!(error("Up=5\n# Again\nup=6"), "line 3: 'up' is already bound on line 1");
+    }
+
+    #[test]
+    fn test_loaded_keymap_outlasts_new_bindings() {
+        let path = std::env::temp_dir().join(format!("chip8-keymap-{}.cfg", std::process::id()));
+        std::fs::write(&path, "Up=2\nUpp=3\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut input = Input::from_bindings(&KeyBindings::default());
+        assert_eq!(input.map_sdl_keycode(Keycode::Num1), Some(0x1));
+        assert_eq!(input.load_keymap(path).unwrap(), ["line 2: unknown key name 'Upp', ignoring it"]);
+        assert_eq!((input.map_sdl_keycode(Keycode::Up), input.map_sdl_keycode(Keycode::Num1)), (Some(0x2), None));
+        input.set_bindings(&KeyBindings::default());
+        assert_eq!(input.map_sdl_keycode(Keycode::Up), Some(0x2));
+        assert_eq!(input.shown_bindings(&KeyBindings::default()).primary, HashMap::from([("Up".to_string(), 0x2)]));
+
+        // A bad file keeps the keys there were
+        std::fs::write(path, "Up=2\nDown=16\n").unwrap();
+        let mut input = Input::from_bindings(&KeyBindings::default());
+        assert!(input.load_keymap(path).unwrap_err().to_string().contains("line 2"));
+        assert_eq!(input.map_sdl_keycode(Keycode::Num1), Some(0x1));
+        assert_eq!(input.shown_bindings(&KeyBindings::default()), KeyBindings::default());
+        std::fs::remove_file(path).unwrap();
+        assert!(input.load_keymap(path).is_err());
+    }
+
+    #[test]
+    fn test_edges_last_one_frame() {
+        let mut input = two_player_input();
+        input.begin_frame();
+        input.set_keycode(Keycode::W, true);
+        assert!(input.was_pressed(0x5) && !input.was_released(0x5));
+
+        // Held into the next frame: no new edge
+        input.begin_frame();
+        assert!(!input.was_pressed(0x5));
+
+        // A tap within one frame is both
+        input.set_keycode(Keycode::W, false);
+        input.set_keycode(Keycode::W, true);
+        assert!(input.was_pressed(0x5) && input.was_released(0x5));
+
+        input.begin_frame();
+        input.set_keycode(Keycode::W, false);
+        assert!(input.was_released(0x5) && !input.was_pressed(0x5));
+        assert!(!input.was_released(0x8) && !input.was_released(INPUTS_COUNT));
+
+        // A second binding for the same key going up while the first is held isn't an edge
+        input.begin_frame();
+        input.set_keycode(Keycode::W, true);
+        input.set_keycode(Keycode::Up, true);
+        input.begin_frame();
+        input.set_keycode(Keycode::Up, false);
+        assert!(!input.was_released(0x5));
+
+        // Other sources count too
+        input.set_remote_key(0x2, true);
+        assert!(input.was_pressed(0x2));
+    }
+}
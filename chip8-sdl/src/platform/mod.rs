@@ -0,0 +1,14 @@
+pub mod beeper;
+pub mod display;
+pub mod hidpi;
+pub mod input;
+pub mod gamepad;
+#[cfg(feature = "audio")]
+pub mod audio;
+
+pub use beeper::Beeper;
+pub use display::Display;
+pub use input:: Input;
+pub use gamepad::Gamepad;
+#[cfg(feature = "audio")]
+pub use audio::Audio;
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+
+pub const RECENT_PATH: &str = "./saves/recent.txt";
+/// How many ROMs the list remembers before dropping the oldest
+pub const MAX_RECENT: usize = 10;
+/// The ROM played last, which the library selects at the next launch
+pub const LAST_ROM_PATH: &str = "./saves/last_rom.txt";
+
+/// ROMs opened from outside the roms directory, newest first, kept one path per line
+pub struct RecentRoms {
+    path: PathBuf,
+    entries: Vec<String>,
+}
+
+impl RecentRoms {
+    /// The list saved at path; a missing or unreadable file is an empty list
+    pub fn load(path: &Path) -> Self {
+        let entries = std::fs::read_to_string(path)
+            .map(|text| text.lines().filter(|line| !line.is_empty()).map(str::to_string).take(MAX_RECENT).collect())
+            .unwrap_or_default();
+        Self { path: path.to_path_buf(), entries }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Puts rom at the front, moving it there if it was already listed
+    pub fn add(&mut self, rom: &str) {
+        self.entries.retain(|entry| entry != rom);
+        self.entries.insert(0, rom.to_string());
+        self.entries.truncate(MAX_RECENT);
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut text = self.entries.join("\n");
+        text.push('\n');
+        std::fs::write(&self.path, text)
+    }
+}
+
+/// The ROM save_last_rom wrote to path, if there is one
+pub fn load_last_rom(path: &Path) -> Option<String> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let rom = text.trim_end_matches(['\r', '\n']);
+    (!rom.is_empty()).then(|| rom.to_string())
+}
+
+pub fn save_last_rom(path: &Path, rom: &str) -> Result<(), std::io::Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, format!("{}\n", rom))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip8-recent-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        dir.join("recent.txt")
+    }
+
+    #[test]
+    fn test_add_moves_to_front() {
+        let mut recent = RecentRoms::load(&temp_path("add"));
+        assert!(recent.entries().is_empty());
+
+        recent.add("a.ch8");
+        recent.add("b.ch8");
+        recent.add("a.ch8");
+        assert_eq!(recent.entries(), ["a.ch8", "b.ch8"]);
+
+        for n in 0..MAX_RECENT {
+            recent.add(&format!("{}.ch8", n));
+        }
+        assert_eq!(recent.entries().len(), MAX_RECENT);
+        assert_eq!(recent.entries()[0], format!("{}.ch8", MAX_RECENT - 1));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = temp_path("save");
+        let mut recent = RecentRoms::load(&path);
+        recent.add("/home/me/roms/PONG.ch8");
+        recent.add("games.zip/BRIX.ch8");
+        recent.save().unwrap();
+
+        assert_eq!(RecentRoms::load(&path).entries(), ["games.zip/BRIX.ch8", "/home/me/roms/PONG.ch8"]);
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_last_rom() {
+        let path = temp_path("last").with_file_name("last_rom.txt");
+        assert_eq!(load_last_rom(&path), None);
+
+        save_last_rom(&path, "./roms/PONG.ch8").unwrap();
+        save_last_rom(&path, "./roms/games.zip/BRIX.ch8").unwrap();
+        assert_eq!(load_last_rom(&path).as_deref(), Some("./roms/games.zip/BRIX.ch8"));
+
+        std::fs::write(&path, "\n").unwrap();
+        assert_eq!(load_last_rom(&path), None);
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
@@ -0,0 +1,72 @@
+//! The offer to pick up a ROM's auto-save, drawn in the window with the overlay font: for ROMs
+//! opened from the library, a playlist or the file dialog, and at launch when there is no terminal
+//! to ask on. The game waits paused under it until it is answered
+use crate::settings::MenuKey;
+
+/// What the player made of the offer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResumeAnswer {
+    Resume,
+    /// Play from power-on, which counts as declining the save
+    StartOver,
+    /// Esc: play from power-on but leave the save as it was, as if nothing had been asked
+    Dismissed,
+}
+
+const ENTRIES: [(&str, ResumeAnswer); 2] = [
+    ("Resume where you left off", ResumeAnswer::Resume),
+    ("Start over", ResumeAnswer::StartOver),
+];
+
+#[derive(Debug, Default)]
+pub struct ResumePrompt {
+    cursor: usize,
+}
+
+impl ResumePrompt {
+    /// The prompt with Resume selected
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Up and Down move between the entries, Enter takes the selected one and Esc dismisses
+    pub fn press(&mut self, key: MenuKey) -> Option<ResumeAnswer> {
+        match key {
+            MenuKey::Up | MenuKey::Down => self.cursor = (self.cursor + 1) % ENTRIES.len(),
+            MenuKey::Select => return Some(ENTRIES[self.cursor].1),
+            MenuKey::Close => return Some(ResumeAnswer::Dismissed),
+            MenuKey::Left | MenuKey::Right | MenuKey::NextCategory | MenuKey::PreviousCategory => {}
+        }
+        None
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec!["Auto-saved game".to_string(), String::new()];
+        for (index, (label, _)) in ENTRIES.iter().enumerate() {
+            let marker = if index == self.cursor { '>' } else { ' ' };
+            lines.push(format!("{} {}", marker, label));
+        }
+        lines.push(String::new());
+        lines.push("Up/Down: choose  Enter: OK".to_string());
+        lines.push("Esc: start over, keeping the save".to_string());
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        let mut prompt = ResumePrompt::new();
+        assert_eq!(prompt.lines()[2], "> Resume where you left off");
+        assert_eq!(prompt.press(MenuKey::Down), None);
+        assert_eq!(prompt.lines()[3], "> Start over");
+        assert_eq!(prompt.press(MenuKey::Select), Some(ResumeAnswer::StartOver));
+
+        prompt.press(MenuKey::Up);
+        assert_eq!(prompt.press(MenuKey::Select), Some(ResumeAnswer::Resume));
+        assert_eq!(prompt.press(MenuKey::Close), Some(ResumeAnswer::Dismissed));
+    }
+}
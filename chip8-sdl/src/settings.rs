@@ -0,0 +1,573 @@
+//! The settings menu (F10 in the window, or Settings in the ROM picker). This is the menu's state
+//! alone: categories of settings, a cursor, and Left/Right changing the value under it. The window
+//! draws lines() with the text overlay, applies each change press() reports, and saves config()
+//! to the config file when the menu closes
+use chip8_core::blend::MAX_BLEND_FRAMES;
+use chip8_core::config::{AudioConfig, Config, Palette, Rgb};
+use chip8_core::quirks::{Preset, Quirk};
+use chip8_core::timing::TimingMode;
+
+/// Palettes Left/Right step through, the default first
+pub const PALETTES: [(&str, Palette); 4] = [
+    ("Green", Palette { foreground: Rgb(57, 255, 20), background: Rgb(0, 26, 0) }),
+    ("Amber", Palette { foreground: Rgb(255, 176, 0), background: Rgb(26, 16, 0) }),
+    ("White", Palette { foreground: Rgb(230, 230, 230), background: Rgb(16, 16, 16) }),
+    ("Blue", Palette { foreground: Rgb(90, 200, 250), background: Rgb(0, 16, 32) }),
+];
+
+/// Instructions per frame the Speed setting allows
+const SPEED_RANGE: (u32, u32) = (1, 100);
+const SCALE_RANGE: (u32, u32) = (1, 40);
+const BEEP_FRAMES_RANGE: (u8, u8) = (0, 10);
+/// Envelope ramps, as the config file accepts them, in half-millisecond steps
+const RAMP_RANGE: (f32, f32) = (1.0, 5.0);
+const RAMP_STEP: f32 = 0.5;
+const VOLUME_STEP: f32 = 0.05;
+const TONE_STEP: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Video,
+    Audio,
+    Emulation,
+    Input,
+}
+
+impl Category {
+    fn label(self) -> &'static str {
+        match self {
+            Category::Video => "Video",
+            Category::Audio => "Audio",
+            Category::Emulation => "Emulation",
+            Category::Input => "Input",
+        }
+    }
+
+    fn settings(self) -> &'static [Setting] {
+        match self {
+            Category::Video => &[Setting::Palette, Setting::Scale, Setting::Grid, Setting::Blend, Setting::Fullscreen],
+            Category::Audio => &[Setting::Volume, Setting::Tone, Setting::Mute, Setting::AttackMs, Setting::ReleaseMs, Setting::MinBeepFrames],
+            Category::Emulation => &[
+                Setting::Speed,
+                Setting::Timing,
+                Setting::Quirks,
+                Setting::Quirk(Quirk::ShiftUsesVy),
+                Setting::Quirk(Quirk::LoadStoreIncrementsI),
+                Setting::Quirk(Quirk::JumpWithVx),
+                Setting::Quirk(Quirk::VfResetOnLogic),
+                Setting::Quirk(Quirk::WrapSprites),
+            ],
+            Category::Input => &[Setting::StickyKeys],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Palette,
+    Scale,
+    Grid,
+    Blend,
+    Fullscreen,
+    Volume,
+    Tone,
+    Mute,
+    AttackMs,
+    ReleaseMs,
+    MinBeepFrames,
+    Speed,
+    Timing,
+    Quirks,
+    /// One quirk, set apart from the preset when it differs
+    Quirk(Quirk),
+    StickyKeys,
+}
+
+impl Setting {
+    fn label(self) -> &'static str {
+        match self {
+            Setting::Palette => "Palette",
+            Setting::Scale => "Scale",
+            Setting::Grid => "Grid",
+            Setting::Blend => "Blend",
+            Setting::Fullscreen => "Fullscreen",
+            Setting::Volume => "Volume",
+            Setting::Tone => "Tone",
+            Setting::Mute => "Mute",
+            Setting::AttackMs => "Fade in",
+            Setting::ReleaseMs => "Fade out",
+            Setting::MinBeepFrames => "Shortest beep",
+            Setting::Speed => "Speed",
+            Setting::Timing => "Timing",
+            Setting::Quirks => "Quirks",
+            Setting::Quirk(quirk) => quirk.label(),
+            Setting::StickyKeys => "Sticky keys",
+        }
+    }
+
+    /// The window is built with these, so they are saved but only take effect at the next launch
+    pub fn needs_restart(self) -> bool {
+        matches!(self, Setting::Scale | Setting::Fullscreen)
+    }
+
+    /// Each fresh machine is set up with these, so they take effect on the next reset or ROM
+    pub fn needs_reset(self) -> bool {
+        matches!(self, Setting::Quirks | Setting::Quirk(_))
+    }
+
+    /// The speed belongs to the game running, not the config file, so it isn't saved
+    pub fn is_saved(self) -> bool {
+        !matches!(self, Setting::Speed | Setting::Timing)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    NextCategory,
+    PreviousCategory,
+    /// Enter: opens the library's selection, and does nothing in the settings
+    Select,
+    Close,
+}
+
+/// What the window should do after a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    None,
+    Changed(Setting),
+    /// Save config() and go back to the game or the picker
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsMenu {
+    config: Config,
+    speed: Option<(TimingMode, u32)>, // None with no game running, which leaves out Speed and Timing
+    category: usize,
+    cursor: usize,
+}
+
+impl SettingsMenu {
+    pub fn new(config: Config, speed: Option<(TimingMode, u32)>) -> Self {
+        Self { config, speed, category: 0, cursor: 0 }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The timing mode and instructions per frame chosen, if a game is running
+    pub fn speed(&self) -> Option<(TimingMode, u32)> {
+        self.speed
+    }
+
+    pub fn categories(&self) -> Vec<Category> {
+        vec![Category::Video, Category::Audio, Category::Emulation, Category::Input]
+    }
+
+    pub fn category(&self) -> Category {
+        self.categories()[self.category]
+    }
+
+    /// The current category's settings, less the game's speed when there is no game
+    pub fn settings(&self) -> Vec<Setting> {
+        self.category().settings().iter()
+            .copied()
+            .filter(|setting| setting.is_saved() || self.speed.is_some())
+            .collect()
+    }
+
+    /// The setting under the cursor
+    pub fn selected(&self) -> Setting {
+        self.settings()[self.cursor]
+    }
+
+    pub fn press(&mut self, key: MenuKey) -> Action {
+        let (categories, settings) = (self.categories().len(), self.settings().len());
+        match key {
+            MenuKey::Up => self.cursor = (self.cursor + settings - 1) % settings,
+            MenuKey::Down => self.cursor = (self.cursor + 1) % settings,
+            MenuKey::NextCategory => self.switch_category((self.category + 1) % categories),
+            MenuKey::PreviousCategory => self.switch_category((self.category + categories - 1) % categories),
+            MenuKey::Left => return self.adjust(-1),
+            MenuKey::Right => return self.adjust(1),
+            MenuKey::Select => {}
+            MenuKey::Close => return Action::Close,
+        }
+        Action::None
+    }
+
+    /// The menu as text: the categories with the current one in brackets, its settings with the
+    /// cursor's marked, and the keys
+    pub fn lines(&self) -> Vec<String> {
+        let bar = self.categories().iter()
+            .map(|category| if *category == self.category() {
+                format!("[{}]", category.label())
+            } else {
+                category.label().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut lines = vec!["Settings".to_string(), bar, String::new()];
+
+        let settings = self.settings();
+        let width = settings.iter().map(|setting| setting.label().len()).max().unwrap_or(0);
+        for setting in settings {
+            let marker = if setting == self.selected() { '>' } else { ' ' };
+            let mut line = format!("{} {:<width$}  < {} >", marker, setting.label(), self.value(setting));
+            if setting.needs_restart() {
+                line.push_str(" (next launch)");
+            } else if setting.needs_reset() {
+                line.push_str(" (next reset)");
+            } else if !setting.is_saved() {
+                line.push_str(" (this game)");
+            }
+            lines.push(line);
+        }
+
+        lines.push(String::new());
+        lines.push("Up/Down: choose  Left/Right: change".to_string());
+        lines.push("Tab: next section  Esc: save and close".to_string());
+        lines
+    }
+
+    /// How a setting's value reads in the menu
+    pub fn value(&self, setting: Setting) -> String {
+        let on_off = |on: bool| if on { "On" } else { "Off" }.to_string();
+        let (display, audio) = (&self.config.display, &self.config.audio);
+        match setting {
+            Setting::Palette => PALETTES.iter()
+                .find(|(_, palette)| *palette == display.palette)
+                .map_or("Custom", |(name, _)| name)
+                .to_string(),
+            Setting::Scale => display.scale.to_string(),
+            Setting::Grid => on_off(display.grid),
+            Setting::Blend if display.blend_frames > 1 => format!("{} frames", display.blend_frames),
+            Setting::Blend => on_off(false),
+            Setting::Fullscreen => on_off(display.fullscreen),
+            Setting::Volume => format!("{:.0}%", audio.volume * 100.0),
+            Setting::Tone => format!("{:.0} Hz", audio.tone_hz),
+            Setting::Mute => on_off(audio.muted),
+            Setting::AttackMs => format!("{:.1} ms", audio.attack_ms),
+            Setting::ReleaseMs => format!("{:.1} ms", audio.release_ms),
+            Setting::MinBeepFrames => format!("{} frames", audio.min_beep_frames),
+            Setting::Speed => format!("{} per frame", self.speed.map_or(0, |(_, speed)| speed)),
+            Setting::Timing => match self.speed.map(|(timing, _)| timing) {
+                Some(TimingMode::Cycles) => "VIP cycles".to_string(),
+                _ => "Simple".to_string(),
+            },
+            Setting::Quirks => self.config.emulation.quirks.label().to_string(),
+            Setting::Quirk(quirk) => on_off(self.config.emulation.resolve(None).get(quirk)),
+            Setting::StickyKeys => on_off(self.config.input.sticky_keys),
+        }
+    }
+
+    fn switch_category(&mut self, category: usize) {
+        self.category = category;
+        self.cursor = 0;
+    }
+
+    /// Steps the selected setting one way or the other. Numbers stop at their ends; the palette
+    /// and the quirk presets wrap around and a switch flips either way
+    fn adjust(&mut self, direction: i32) -> Action {
+        let setting = self.selected();
+        let (display, audio) = (&mut self.config.display, &mut self.config.audio);
+        let before = (display.clone(), audio.clone(), self.config.input.clone(), self.config.emulation.clone(), self.speed);
+        match setting {
+            Setting::Palette => {
+                let count = PALETTES.len() as i32;
+                let next = match PALETTES.iter().position(|(_, palette)| *palette == display.palette) {
+                    Some(index) => (index as i32 + direction).rem_euclid(count),
+                    None if direction > 0 => 0,
+                    None => count - 1,
+                };
+                display.palette = PALETTES[next as usize].1;
+            }
+            Setting::Scale => display.scale = step(display.scale, direction, SCALE_RANGE),
+            Setting::Grid => display.grid = !display.grid,
+            Setting::Blend => {
+                let frames = step(u32::from(display.blend_frames), direction, (1, MAX_BLEND_FRAMES.into()));
+                display.blend_frames = frames as u8;
+            }
+            Setting::Fullscreen => display.fullscreen = !display.fullscreen,
+            Setting::Volume => audio.volume = step_snapped(audio.volume, direction, VOLUME_STEP, AudioConfig::VOLUME_RANGE),
+            Setting::Tone => audio.tone_hz = step_snapped(audio.tone_hz, direction, TONE_STEP, AudioConfig::TONE_RANGE),
+            Setting::Mute => audio.muted = !audio.muted,
+            Setting::AttackMs => audio.attack_ms = step_snapped(audio.attack_ms, direction, RAMP_STEP, RAMP_RANGE),
+            Setting::ReleaseMs => audio.release_ms = step_snapped(audio.release_ms, direction, RAMP_STEP, RAMP_RANGE),
+            Setting::MinBeepFrames => {
+                let frames = step(u32::from(audio.min_beep_frames), direction, (BEEP_FRAMES_RANGE.0.into(), BEEP_FRAMES_RANGE.1.into()));
+                audio.min_beep_frames = frames as u8;
+            }
+            Setting::Speed => if let Some((_, speed)) = self.speed.as_mut() {
+                *speed = step(*speed, direction, SPEED_RANGE);
+            },
+            Setting::Timing => if let Some((timing, _)) = self.speed.as_mut() {
+                *timing = match timing {
+                    TimingMode::Simple => TimingMode::Cycles,
+                    TimingMode::Cycles => TimingMode::Simple,
+                };
+            },
+            Setting::Quirks => {
+                let quirks = &mut self.config.emulation.quirks;
+                let count = Preset::ALL.len() as i32;
+                let index = Preset::ALL.iter().position(|preset| preset == quirks).unwrap_or(0) as i32;
+                *quirks = Preset::ALL[(index + direction).rem_euclid(count) as usize];
+            }
+            // Only kept as an override while it differs from the preset, so a later preset change
+            // isn't held back by overrides that agreed with the old one
+            Setting::Quirk(quirk) => {
+                let emulation = &mut self.config.emulation;
+                let on = !emulation.resolve(None).get(quirk);
+                let preset = emulation.quirks.quirks().get(quirk);
+                emulation.overrides.set(quirk, (on != preset).then_some(on));
+            }
+            Setting::StickyKeys => self.config.input.sticky_keys = !self.config.input.sticky_keys,
+        }
+
+        let config = &self.config;
+        let after = (config.display.clone(), config.audio.clone(), config.input.clone(), config.emulation.clone(), self.speed);
+        if after == before { Action::None } else { Action::Changed(setting) }
+    }
+}
+
+fn step(value: u32, direction: i32, (min, max): (u32, u32)) -> u32 {
+    value.saturating_add_signed(direction).clamp(min, max)
+}
+
+fn step_snapped(value: f32, direction: i32, step: f32, (min, max): (f32, f32)) -> f32 {
+    // Snapped to the step, so a value typed into the file rejoins the menu's steps
+    let snapped = (value / step).round() * step;
+    (snapped + direction as f32 * step).clamp(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::quirks::Quirks;
+
+    fn in_game() -> SettingsMenu {
+        SettingsMenu::new(Config::default(), Some((TimingMode::Simple, 10)))
+    }
+
+    fn press(menu: &mut SettingsMenu, keys: &[MenuKey]) -> Vec<Action> {
+        keys.iter().map(|key| menu.press(*key)).collect()
+    }
+
+    #[test]
+    fn test_cursor_wraps_within_a_category() {
+        let mut menu = in_game();
+        assert_eq!(menu.selected(), Setting::Palette);
+        press(&mut menu, &[MenuKey::Down, MenuKey::Down]);
+        assert_eq!(menu.selected(), Setting::Grid);
+        press(&mut menu, &[MenuKey::Down, MenuKey::Down, MenuKey::Down]);
+        assert_eq!(menu.selected(), Setting::Palette);
+        menu.press(MenuKey::Up);
+        assert_eq!(menu.selected(), Setting::Fullscreen);
+    }
+
+    #[test]
+    fn test_categories_switch_and_reset_the_cursor() {
+        let mut menu = in_game();
+        menu.press(MenuKey::Down);
+        menu.press(MenuKey::NextCategory);
+        assert_eq!((menu.category(), menu.selected()), (Category::Audio, Setting::Volume));
+        press(&mut menu, &[MenuKey::NextCategory, MenuKey::NextCategory]);
+        assert_eq!(menu.category(), Category::Input);
+        menu.press(MenuKey::NextCategory);
+        assert_eq!(menu.category(), Category::Video);
+        menu.press(MenuKey::PreviousCategory);
+        assert_eq!(menu.category(), Category::Input);
+    }
+
+    #[test]
+    fn test_only_quirks_without_a_game() {
+        let mut menu = SettingsMenu::new(Config::default(), None);
+        press(&mut menu, &[MenuKey::NextCategory, MenuKey::NextCategory]);
+        let mut expected = vec![Setting::Quirks];
+        expected.extend(Quirk::ALL.map(Setting::Quirk));
+        assert_eq!((menu.category(), menu.settings()), (Category::Emulation, expected));
+        assert_eq!(menu.speed(), None);
+    }
+
+    #[test]
+    fn test_quirk_presets_cycle() {
+        let mut menu = SettingsMenu::new(Config::default(), None);
+        press(&mut menu, &[MenuKey::NextCategory, MenuKey::NextCategory]);
+        assert_eq!(menu.value(Setting::Quirks), "Modern");
+        assert_eq!(menu.press(MenuKey::Right), Action::Changed(Setting::Quirks));
+        assert_eq!((menu.config().emulation.quirks, menu.value(Setting::Quirks).as_str()), (Preset::Vip, "COSMAC VIP"));
+        menu.press(MenuKey::Left);
+        assert_eq!(menu.config().emulation.quirks, Preset::Modern);
+        assert!(menu.lines().iter().any(|line| line == "> Quirks             < Modern > (next reset)"), "{:?}", menu.lines());
+    }
+
+    #[test]
+    fn test_quirks_toggle_apart_from_the_preset() {
+        let mut menu = SettingsMenu::new(Config::default(), None);
+        press(&mut menu, &[MenuKey::NextCategory, MenuKey::NextCategory, MenuKey::Up]);
+        assert_eq!((menu.selected(), menu.value(Setting::Quirk(Quirk::WrapSprites)).as_str()), (Setting::Quirk(Quirk::WrapSprites), "On"));
+
+        assert_eq!(menu.press(MenuKey::Right), Action::Changed(Setting::Quirk(Quirk::WrapSprites)));
+        let emulation = &menu.config().emulation;
+        assert_eq!((emulation.quirks, emulation.overrides.wrap_sprites), (Preset::Modern, Some(false)));
+        assert_eq!(emulation.resolve(None), Quirks { wrap_sprites: false, ..Quirks::modern() });
+
+        // Back to what the preset says, so no override is kept
+        menu.press(MenuKey::Left);
+        assert_eq!(menu.config().emulation.overrides, Default::default());
+        assert!(menu.lines().iter().any(|line| line == "> Wrap sprites       < On > (next reset)"), "{:?}", menu.lines());
+    }
+
+    #[test]
+    fn test_switches_flip_either_way() {
+        let mut menu = in_game();
+        press(&mut menu, &[MenuKey::Down, MenuKey::Down]);
+        assert_eq!(menu.press(MenuKey::Right), Action::Changed(Setting::Grid));
+        assert!(menu.config().display.grid);
+        assert_eq!(menu.press(MenuKey::Left), Action::Changed(Setting::Grid));
+        assert!(!menu.config().display.grid);
+    }
+
+    #[test]
+    fn test_numbers_stop_at_their_ends() {
+        let mut menu = in_game();
+        menu.press(MenuKey::Down);
+        assert_eq!(menu.press(MenuKey::Left), Action::Changed(Setting::Scale));
+        assert_eq!(menu.config().display.scale, 19);
+
+        let mut config = Config::default();
+        config.display.scale = SCALE_RANGE.1;
+        let mut menu = SettingsMenu::new(config, None);
+        menu.press(MenuKey::Down);
+        assert_eq!(menu.press(MenuKey::Right), Action::None);
+        assert_eq!(menu.config().display.scale, SCALE_RANGE.1);
+
+        // The shortest beep goes down to 0, and no further
+        press(&mut menu, &[MenuKey::NextCategory, MenuKey::Up]);
+        assert_eq!(press(&mut menu, &[MenuKey::Left, MenuKey::Left, MenuKey::Left]), [
+            Action::Changed(Setting::MinBeepFrames),
+            Action::Changed(Setting::MinBeepFrames),
+            Action::None,
+        ]);
+        assert_eq!(menu.config().audio.min_beep_frames, 0);
+    }
+
+    #[test]
+    fn test_blend_steps_from_off_to_three_frames() {
+        let mut menu = in_game();
+        press(&mut menu, &[MenuKey::Down, MenuKey::Down, MenuKey::Down]);
+        assert_eq!(menu.selected(), Setting::Blend);
+        assert_eq!(menu.press(MenuKey::Left), Action::None);
+        assert_eq!(press(&mut menu, &[MenuKey::Right, MenuKey::Right, MenuKey::Right]), [
+            Action::Changed(Setting::Blend),
+            Action::Changed(Setting::Blend),
+            Action::None,
+        ]);
+        assert_eq!(menu.value(Setting::Blend), "3 frames");
+    }
+
+    #[test]
+    fn test_ramps_step_by_half_milliseconds() {
+        let mut config = Config::default();
+        config.audio.attack_ms = 1.3;
+        let mut menu = SettingsMenu::new(config, None);
+        press(&mut menu, &[MenuKey::NextCategory, MenuKey::Down, MenuKey::Down, MenuKey::Down]);
+
+        menu.press(MenuKey::Right);
+        assert_eq!(menu.config().audio.attack_ms, 2.0);
+        press(&mut menu, &[MenuKey::Left, MenuKey::Left, MenuKey::Left]);
+        assert_eq!(menu.config().audio.attack_ms, RAMP_RANGE.0);
+        assert_eq!(menu.value(Setting::AttackMs), "1.0 ms");
+    }
+
+    #[test]
+    fn test_volume_tone_and_mute() {
+        let mut menu = SettingsMenu::new(Config::default(), None);
+        menu.press(MenuKey::NextCategory);
+        assert_eq!(menu.value(Setting::Volume), "25%");
+        assert_eq!(menu.press(MenuKey::Right), Action::Changed(Setting::Volume));
+        assert_eq!(menu.value(Setting::Volume), "30%");
+        for _ in 0..10 {
+            menu.press(MenuKey::Left);
+        }
+        assert_eq!(menu.config().audio.volume, 0.0);
+
+        menu.press(MenuKey::Down);
+        assert_eq!(menu.press(MenuKey::Left), Action::Changed(Setting::Tone));
+        assert_eq!(menu.value(Setting::Tone), "430 Hz");
+
+        menu.press(MenuKey::Down);
+        assert_eq!(menu.press(MenuKey::Right), Action::Changed(Setting::Mute));
+        assert!(menu.config().audio.muted);
+        assert_eq!(menu.value(Setting::Mute), "On");
+    }
+
+    #[test]
+    fn test_palette_cycles_through_presets() {
+        let mut menu = in_game();
+        assert_eq!(menu.value(Setting::Palette), "Green");
+        assert_eq!(menu.press(MenuKey::Right), Action::Changed(Setting::Palette));
+        assert_eq!(menu.value(Setting::Palette), "Amber");
+        press(&mut menu, &[MenuKey::Left, MenuKey::Left]);
+        assert_eq!(menu.config().display.palette, PALETTES[PALETTES.len() - 1].1);
+
+        // A palette from the file that isn't a preset joins at either end
+        let mut config = Config::default();
+        config.display.palette.foreground = Rgb(1, 2, 3);
+        let mut menu = SettingsMenu::new(config, None);
+        assert_eq!(menu.value(Setting::Palette), "Custom");
+        menu.press(MenuKey::Left);
+        assert_eq!(menu.value(Setting::Palette), "Blue");
+    }
+
+    #[test]
+    fn test_speed_and_timing() {
+        let mut menu = in_game();
+        press(&mut menu, &[MenuKey::PreviousCategory, MenuKey::PreviousCategory]);
+        assert_eq!(menu.category(), Category::Emulation);
+        menu.press(MenuKey::Right);
+        menu.press(MenuKey::Down);
+        assert_eq!(menu.press(MenuKey::Left), Action::Changed(Setting::Timing));
+        assert_eq!(menu.speed(), Some((TimingMode::Cycles, 11)));
+        assert_eq!(menu.config(), &Config::default()); // The speed isn't part of the file
+
+        let mut config_menu = SettingsMenu::new(Config::default(), Some((TimingMode::Simple, SPEED_RANGE.0)));
+        press(&mut config_menu, &[MenuKey::NextCategory, MenuKey::NextCategory]);
+        assert_eq!(config_menu.press(MenuKey::Left), Action::None);
+    }
+
+    #[test]
+    fn test_close() {
+        let mut menu = in_game();
+        assert_eq!(menu.press(MenuKey::Close), Action::Close);
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut menu = in_game();
+        menu.press(MenuKey::Down);
+        let lines = menu.lines();
+        assert_eq!(lines[..8], [
+            "Settings",
+            "[Video] Audio Emulation Input",
+            "",
+            "  Palette     < Green >",
+            "> Scale       < 20 > (next launch)",
+            "  Grid        < Off >",
+            "  Blend       < Off >",
+            "  Fullscreen  < Off > (next launch)",
+        ]);
+
+        // Only glyphs the overlay font has
+        press(&mut menu, &[MenuKey::PreviousCategory, MenuKey::PreviousCategory]);
+        let text = menu.lines().concat();
+        assert!(text.contains("10 per frame > (this game)"), "{}", text);
+        let known = |c: char| crate::font::glyph(c) != crate::font::glyph('?') || c == '?';
+        assert!(text.chars().all(known), "{}", text);
+    }
+}
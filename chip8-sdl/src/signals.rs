@@ -0,0 +1,19 @@
+//! Poking a run from outside on Unix: SIGUSR1 asks for a state dump on stderr and SIGUSR2 pauses
+//! or resumes. Elsewhere nothing is installed and the requests never come
+use chip8_core::diagnostics::Requests;
+
+/// Points SIGUSR1 and SIGUSR2 at the requests' flags
+#[cfg(unix)]
+pub fn install(requests: &Requests) -> Result<(), std::io::Error> {
+    use signal_hook::consts::{SIGUSR1, SIGUSR2};
+    use std::sync::Arc;
+
+    signal_hook::flag::register(SIGUSR1, Arc::clone(&requests.dump))?;
+    signal_hook::flag::register(SIGUSR2, Arc::clone(&requests.toggle_pause))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install(_requests: &Requests) -> Result<(), std::io::Error> {
+    Ok(())
+}
@@ -0,0 +1,54 @@
+//! Runs the emulator binary headless with --verify against an assembled test ROM
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use chip8_core::rom_builder::RomBuilder;
+
+/// Writes a file for this test run and returns its path
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("chip8-verify-{}-{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// A test ROM that leaves its result in V0 and memory, then spins
+fn test_rom() -> PathBuf {
+    let rom = RomBuilder::new()
+        .ld(0, 0x20)
+        .add(0, 0x0A)
+        .ld_i("result")
+        .op(0xF055) // LD [I], V0
+        .label("done")
+        .jp("done")
+        .label("result")
+        .data(&[0])
+        .build()
+        .unwrap();
+    temp_file("rom.ch8", &rom)
+}
+
+fn run_verify(rom: &PathBuf, expect: &str, name: &str) -> Output {
+    let expect = temp_file(name, expect.as_bytes());
+    Command::new(env!("CARGO_BIN_EXE_chip8_emulator"))
+        .args(["headless", "--max-frames", "5", "--verify"])
+        .arg(&expect)
+        .arg(rom)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_verify_pass_and_fail() {
+    let rom = test_rom();
+
+    let pass = run_verify(&rom, "pc = 0x208\nv = { 0 = 0x2A }\nmemory = { \"0x20A\" = 0x2A }\n", "pass.toml");
+    assert!(pass.status.success(), "{}", String::from_utf8_lossy(&pass.stdout));
+    assert!(String::from_utf8_lossy(&pass.stdout).contains("PASS"));
+
+    let fail = run_verify(&rom, "v = { 0 = 0x2B, 1 = 0 }\n", "fail.toml");
+    assert!(!fail.status.success());
+    let stdout = String::from_utf8_lossy(&fail.stdout);
+    assert!(stdout.contains("FAIL"));
+    assert!(stdout.contains("V0       0x2B               0x2A"), "{}", stdout);
+    assert!(!stdout.contains("V1"));
+}
@@ -1,5 +0,0 @@
-pub mod display;
-pub mod input;
-
-pub use display::Display;
-pub use input:: Input;
\ No newline at end of file